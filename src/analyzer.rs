@@ -1,11 +1,12 @@
-use crate::context::{Context, UserFunctionDef};
+use crate::context::{ClosureDef, Context, UserFunctionDef};
+use crate::error::{error_message, WarpError};
 use crate::extensions::numbers::Number;
 use crate::function::{Function, FunctionRegistry, Signature};
 use crate::local::Local;
-use crate::node::{Bracket, Node};
+use crate::node::{Bracket, Node, Separator};
 use crate::normalize::hints as norm;
 use crate::operators::{is_function_keyword, Op};
-use crate::type_kinds::Kind;
+use crate::type_kinds::{declared_name, generic_base, type_args, written_type, Kind, TypeDef, TypeRegistry};
 use std::collections::HashMap;
 
 /// Check if a node is pure data (not a statement/function call)
@@ -23,6 +24,14 @@ fn is_data_node(node: &Node) -> bool {
 /// Returns Int, Float, Text, etc. based on the expression's result type
 pub fn infer_type(node: &Node, scope: &Scope) -> Kind {
 	let node = node.drop_meta();
+	// Text functions: split(s, ","), s.trim()
+	if let Some((_, kind, _)) = text_function(node, scope) {
+		return *kind;
+	}
+	// List functions: map(xs, f), xs.any(it > 2)
+	if let Some(((_, kind, _), _)) = list_function(node, scope) {
+		return *kind;
+	}
 	match node {
 		// Float literals and derived types
 		Node::Number(Number::Float(_)) => Kind::Float,
@@ -43,6 +52,12 @@ pub fn infer_type(node: &Node, scope: &Scope) -> Kind {
 		}
 		// List handling: distinguish data lists from statement sequences and function calls
 		Node::List(items, bracket, _) if !items.is_empty() => {
+			// Closures return any node
+			if let Node::Symbol(s) = items[0].drop_meta() {
+				if scope.lookup(s).is_some_and(|local| local.kind == Kind::Closure) {
+					return Kind::Data;
+				}
+			}
 			// Check for function calls: (funcname args...) where first item is a symbol
 			if items.len() >= 2 {
 				if let Node::Symbol(s) = items[0].drop_meta() {
@@ -98,11 +113,13 @@ pub fn infer_type(node: &Node, scope: &Scope) -> Kind {
 				Kind::Empty
 			}
 		}
-		// Arithmetic: upgrade to Float if either operand is Float
+		// Arithmetic: upgrade to Float if either operand is Float, + with Text joins text
 		Node::Key(left, op, right) if op.is_arithmetic() => {
 			let left_kind = infer_type(left, scope);
 			let right_kind = infer_type(right, scope);
-			if left_kind == Kind::Float || right_kind == Kind::Float {
+			if *op == Op::Add && (left_kind == Kind::Text || right_kind == Kind::Text) {
+				Kind::Text
+			} else if left_kind == Kind::Float || right_kind == Kind::Float {
 				Kind::Float
 			} else {
 				Kind::Int
@@ -116,7 +133,9 @@ pub fn infer_type(node: &Node, scope: &Scope) -> Kind {
 		Node::Key(left, op, right) if op.is_compound_assign() => {
 			let left_kind = infer_type(left, scope);
 			let right_kind = infer_type(right, scope);
-			if left_kind == Kind::Float || right_kind == Kind::Float {
+			if *op == Op::AddAssign && left_kind == Kind::Text {
+				Kind::Text
+			} else if left_kind == Kind::Float || right_kind == Kind::Float {
 				Kind::Float
 			} else {
 				Kind::Int
@@ -134,6 +153,18 @@ pub fn infer_type(node: &Node, scope: &Scope) -> Kind {
 		}
 		// Comparison operators return Int (boolean as 0/1)
 		Node::Key(_, op, _) if op.is_comparison() => Kind::Int,
+		Node::Key(_, Op::FatArrow, _) => Kind::Closure,
+		// tree?.left may be null, tree?.value ?? 0 is what the fallback is when it is
+		Node::Key(_, Op::SafeDot, _) => Kind::Data,
+		Node::Key(_, Op::Coalesce, fallback) => infer_type(fallback, scope),
+		// match: the kind all arms agree on, if it is an Int or Text, else any node
+		Node::Key(_, Op::Match, arms) => {
+			let kinds: Vec<Kind> = match_arms(arms).filter_map(match_arm).map(|(_, _, value)| infer_type(value, scope)).collect();
+			match kinds.first() {
+				Some(&kind) if matches!(kind, Kind::Int | Kind::Text) && kinds.iter().all(|&k| k == kind) => kind,
+				_ => Kind::Data,
+			}
+		}
 		// Prefix operators (neg, abs, sqrt): inherit type from operand
 		Node::Key(left, op, right) if op.is_prefix() && matches!(left.drop_meta(), Node::Empty) => {
 			infer_type(right, scope)
@@ -231,10 +262,59 @@ fn collect_variables_inner(node: &Node, scope: &mut Scope, skip_first_assign: bo
 		Node::Key(left, op, right) if op.is_compound_assign() => {
 			collect_variables_inner(left, scope, false, in_structure) + collect_variables_inner(right, scope, false, in_structure)
 		}
+		// For loop: its body may hold loops of its own, one level deeper
+		Node::Key(left, Op::Do, right) if matches!(left.drop_meta(), Node::Key(_, Op::For, _)) => {
+			let temps = 1 + collect_variables_inner(left, scope, false, in_structure);
+			scope.loop_depth += 1;
+			let temps = temps + collect_variables_inner(right, scope, false, in_structure);
+			scope.loop_depth -= 1;
+			temps
+		}
 		Node::Key(left, Op::Do, right) => {
 			// While loop needs a temp local for result
 			1 + collect_variables_inner(left, scope, false, in_structure) + collect_variables_inner(right, scope, false, in_structure)
 		}
+		// for x in items: the loop variables and the hidden locals walking the items
+		Node::Key(vars, Op::In, items) => {
+			for (name, kind) in loop_variables(vars, items, scope) {
+				if scope.lookup(&name).is_none() {
+					scope.define(name, None, kind);
+				}
+			}
+			scope.loop_depth += 1;
+			let temps = collect_variables_inner(items, scope, false, in_structure);
+			scope.loop_depth -= 1;
+			temps
+		}
+		// match: the subject, the names its patterns bind and a temp for a branch table
+		Node::Key(subject, Op::Match, arms) => {
+			let subject_local = match_local(subject);
+			if scope.lookup(&subject_local).is_none() {
+				scope.define(subject_local, None, Kind::Data);
+			}
+			let mut temps = 1 + collect_variables_inner(subject, scope, false, in_structure);
+			for arm in match_arms(arms) {
+				let Some((pattern, guard, value)) = match_arm(arm) else { continue };
+				for name in pattern_bindings(pattern) {
+					if scope.lookup(&name).is_none() {
+						scope.define(name, None, Kind::Data);
+					}
+				}
+				temps += guard.map_or(0, |guard| collect_variables_inner(guard, scope, false, in_structure));
+				temps += collect_variables_inner(value, scope, false, in_structure);
+			}
+			temps
+		}
+		// ?. and ?? keep the value they test in a hidden local
+		Node::Key(left, Op::SafeDot | Op::Coalesce, right) => {
+			let local = optional_local(left);
+			if scope.lookup(&local).is_none() {
+				scope.define(local, None, Kind::Data);
+			}
+			collect_variables_inner(left, scope, false, in_structure) + collect_variables_inner(right, scope, false, in_structure)
+		}
+		// Closure bodies get their own function and locals
+		Node::Key(_, Op::FatArrow, _) => 0,
 		Node::Key(left, Op::Abs, right) if matches!(left.drop_meta(), Node::Empty) => {
 			// Integer abs needs a temp local for the if-then-else pattern
 			1 + collect_variables_inner(right, scope, false, in_structure)
@@ -249,12 +329,485 @@ fn collect_variables_inner(node: &Node, scope: &mut Scope, skip_first_assign: bo
 	}
 }
 
+/// Runtime functions walking the items of a for loop that is not over a range
+pub const LOOP_RUNTIME: [&str; 4] = ["node_count", "utf8_offset", "text_codepoints", "loop_items"];
+
+/// Whether a for loop counts through a range instead of walking nodes
+pub fn is_range_loop(items: &Node) -> bool {
+	matches!(items.drop_meta(), Node::Key(_, Op::Range | Op::To, _))
+}
+
+/// Names bound by a for loop: `x` or `key, value`
+pub fn loop_names(vars: &Node) -> Vec<String> {
+	match vars.drop_meta() {
+		Node::List(names, _, _) => names.iter().map(|name| name.drop_meta().to_string()).collect(),
+		name => vec![name.to_string()],
+	}
+}
+
+/// Hidden local of a for loop, keyed by its nesting depth so inner loops keep their own: for0#end, for1#items
+pub fn loop_local(depth: usize, role: &str) -> String {
+	format!("for{}#{}", depth, role)
+}
+
+/// Locals of a for loop: its variables, then the hidden ones
+/// Ranges count in an Int, text yields Codepoints, literal lists of ints or texts their kind,
+/// anything else any node (Data); of two names the first is the index or the key of Key items
+pub fn loop_variables(vars: &Node, items: &Node, scope: &Scope) -> Vec<(String, Kind)> {
+	let names = loop_names(vars);
+	let (index_kind, value_kind) = if is_range_loop(items) {
+		(Kind::Int, Kind::Int)
+	} else if infer_type(items, scope) == Kind::Text {
+		(Kind::Int, Kind::Codepoint)
+	} else {
+		let value_kind = match items.drop_meta() {
+			Node::List(elements, Bracket::Square, _) if !elements.is_empty() => {
+				let kind = infer_type(&elements[0], scope);
+				let same = elements.iter().all(|element| infer_type(element, scope) == kind);
+				if same && matches!(kind, Kind::Int | Kind::Text) { kind } else { Kind::Data }
+			}
+			_ => Kind::Data,
+		};
+		(Kind::Data, value_kind)
+	};
+	let mut locals = match names.as_slice() {
+		[value] => vec![(value.clone(), value_kind)],
+		[index, value, ..] => vec![(index.clone(), index_kind), (value.clone(), value_kind)],
+		[] => vec![],
+	};
+	if is_range_loop(items) {
+		locals.push((loop_local(scope.loop_depth, "end"), Kind::Int));
+	} else {
+		locals.push((loop_local(scope.loop_depth, "items"), Kind::List));
+		locals.push((loop_local(scope.loop_depth, "item"), Kind::Data));
+		locals.push((loop_local(scope.loop_depth, "count"), Kind::Int));
+		locals.push((loop_local(scope.loop_depth, "index"), Kind::Int));
+	}
+	locals
+}
+
+/// Runtime functions testing the patterns of a match
+pub const MATCH_RUNTIME: [&str; 3] = ["node_count", "node_compare", "node_field"];
+
+/// Hidden local holding the value ?. and ?? test for null: x#optional
+pub fn optional_local(value: &Node) -> String {
+	format!("{}#optional", value.serialize())
+}
+
+/// Hidden local holding the subject of a match: x#match
+pub fn match_local(subject: &Node) -> String {
+	format!("{}#match", subject.serialize())
+}
+
+/// The arms in the block of a match
+pub fn match_arms(arms: &Node) -> impl Iterator<Item = &Node> {
+	let arms = match arms.drop_meta() {
+		Node::List(items, _, _) => items.as_slice(),
+		_ => &[],
+	};
+	arms.iter()
+}
+
+/// Pattern, guard and value of the match arm `pattern if guard => value`
+pub fn match_arm(arm: &Node) -> Option<(&Node, Option<&Node>, &Node)> {
+	let Node::Key(pattern, Op::FatArrow, value) = arm.drop_meta() else { return None };
+	match pattern.drop_meta() {
+		Node::Key(pattern, Op::If, guard) if !matches!(pattern.drop_meta(), Node::Empty) => Some((pattern, Some(guard), value)),
+		_ => Some((pattern, None, value)),
+	}
+}
+
+/// The Kind a pattern like `Int` or `text` tests for
+pub fn pattern_kind(name: &str) -> Option<Kind> {
+	match name.to_lowercase().as_str() {
+		"int" | "integer" | "i64" => Some(Kind::Int),
+		"float" | "real" | "f64" => Some(Kind::Float),
+		"text" | "string" | "str" => Some(Kind::Text),
+		"char" | "codepoint" => Some(Kind::Codepoint),
+		"symbol" => Some(Kind::Symbol),
+		"key" => Some(Kind::Key),
+		"list" => Some(Kind::List),
+		"empty" => Some(Kind::Empty),
+		"error" => Some(Kind::Error),
+		"closure" => Some(Kind::Closure),
+		_ => None,
+	}
+}
+
+/// Capitalized names in patterns test for user types: Person or Person{name, age}
+pub fn is_type_pattern(name: &str) -> bool {
+	name.starts_with(|c: char| c.is_uppercase()) && pattern_kind(name).is_none()
+}
+
+/// Names a match pattern binds: x in `x if x > 0`, a and b in `[a, b]`, name in `Person{name}`
+pub fn pattern_bindings(pattern: &Node) -> Vec<String> {
+	match pattern.drop_meta() {
+		Node::Symbol(name) if name == "_" || pattern_kind(name).is_some() || is_type_pattern(name) => vec![],
+		Node::Symbol(name) => vec![name.clone()],
+		// Person{name, age: a} binds name and a, not age
+		Node::Key(name, Op::Colon, fields) if is_struct_pattern(name, fields) => match fields.drop_meta() {
+			Node::List(fields, _, _) => fields.iter().flat_map(|field| match field.drop_meta() {
+				Node::Key(_, Op::Colon, pattern) => pattern_bindings(pattern),
+				field => pattern_bindings(field),
+			}).collect(),
+			_ => vec![],
+		},
+		Node::Key(key, Op::Colon, value) => [pattern_bindings(key), pattern_bindings(value)].concat(),
+		Node::List(items, Bracket::Square, _) => items.iter().flat_map(pattern_bindings).collect(),
+		_ => vec![],
+	}
+}
+
+/// Person{…} as a pattern destructures a user type instance
+pub fn is_struct_pattern(name: &Node, fields: &Node) -> bool {
+	matches!(name.drop_meta(), Node::Symbol(name) if is_type_pattern(name))
+		&& matches!(fields.drop_meta(), Node::List(_, Bracket::Curly, _))
+}
+
+/// Variants construct instances: Circle(2) and Circle(r: 2) read as Circle{r: 2}, a unit variant Dot as Dot{}
+/// Match patterns take the same rewrite, so Circle(x) destructures like Circle{r: x}
+pub fn construct_variants(types: &TypeRegistry, node: &Node) -> Node {
+	let variant = |node: &Node| match node.drop_meta() {
+		Node::Symbol(name) => types.variant(name),
+		_ => None,
+	};
+	match node {
+		Node::Meta { node: inner, data } => Node::Meta {
+			node: Box::new(construct_variants(types, inner)),
+			data: data.clone(),
+		},
+		Node::Type { .. } => node.clone(),
+		Node::Symbol(_) => match variant(node) {
+			Some(type_def) if type_def.fields.is_empty() => variant_instance(type_def, vec![]),
+			_ => node.clone(),
+		},
+		// a wrong number of fields is left as a call for check_types to report
+		Node::List(items, Bracket::Round, Separator::None)
+			if items.first().and_then(variant).is_some_and(|type_def| type_def.fields.len() == items.len() - 1) =>
+		{
+			let args = items[1..].iter().map(|arg| construct_variants(types, arg)).collect();
+			variant_instance(variant(&items[0]).unwrap(), args)
+		}
+		Node::List(items, bracket, separator) => Node::List(
+			items.iter().map(|item| construct_variants(types, item)).collect(),
+			bracket.clone(),
+			separator.clone(),
+		),
+		// Circle{r: 2} is an instance already
+		Node::Key(left, Op::Colon, right) if variant(left).is_some() => {
+			Node::Key(left.clone(), Op::Colon, Box::new(construct_variants(types, right)))
+		}
+		Node::Key(left, op, right) => Node::Key(
+			Box::new(construct_variants(types, left)),
+			*op,
+			Box::new(construct_variants(types, right)),
+		),
+		_ => node.clone(),
+	}
+}
+
+/// Instance of a variant with positional or named field values, one per field
+fn variant_instance(type_def: &TypeDef, args: Vec<Node>) -> Node {
+	let fields = type_def.fields.iter().zip(args).map(|(field, arg)| match arg.drop_meta() {
+		Node::Key(_, Op::Colon, _) => arg,
+		_ => Node::Key(Box::new(Node::Symbol(field.name.clone())), Op::Colon, Box::new(arg)),
+	}).collect();
+	Node::Key(
+		Box::new(Node::Symbol(type_def.name.clone())),
+		Op::Colon,
+		Box::new(Node::List(fields, Bracket::Curly, Separator::None)),
+	)
+}
+
+/// Definitions naming only the types of their parameters, like `combine float with float = float#1 + float#2`,
+/// read as combine($0: float, $1: float) = $0 + $1: the body takes the parameters as $0 and $1,
+/// or by their type as float#1 and float#2, counting each type from 1
+pub fn with_definitions(node: &Node) -> Node {
+	match node {
+		Node::Meta { node: inner, data } => Node::Meta {
+			node: Box::new(with_definitions(inner)),
+			data: data.clone(),
+		},
+		Node::List(items, Bracket::None, Separator::Space) if with_signature(items).is_some() => {
+			let (name, types, body) = with_signature(items).unwrap();
+			let params = types.iter().enumerate().map(|(i, type_name)| {
+				Node::Key(Box::new(Node::Symbol(format!("${}", i))), Op::Colon, Box::new(Node::Symbol(type_name.clone())))
+			});
+			let signature = Node::List(std::iter::once(name.clone()).chain(params).collect(), Bracket::Round, Separator::None);
+			Node::Key(Box::new(signature), Op::Assign, Box::new(typed_param_refs(body, &types)))
+		}
+		Node::List(items, bracket, separator) => Node::List(items.iter().map(with_definitions).collect(), bracket.clone(), separator.clone()),
+		_ => node.clone(),
+	}
+}
+
+/// Name, parameter types and body of `name T with T … = body`
+fn with_signature(items: &[Node]) -> Option<(&Node, Vec<String>, &Node)> {
+	let (last, head) = items.split_last()?;
+	let Node::Key(last_type, Op::Assign, body) = last.drop_meta() else { return None };
+	let (name, words) = head.split_first()?;
+	if words.len() < 2 || !matches!(name.drop_meta(), Node::Symbol(_)) {
+		return None;
+	}
+	let mut types = vec![];
+	for (i, word) in words.iter().chain(std::iter::once(last_type.as_ref())).enumerate() {
+		match word.drop_meta() {
+			Node::Symbol(type_name) if i % 2 == 0 => types.push(type_name.clone()),
+			Node::Symbol(with) if with == "with" => {}
+			_ => return None,
+		}
+	}
+	Some((name, types, body))
+}
+
+/// float#2 in the body of a with definition is its second float parameter, $1 in combine float with float
+fn typed_param_refs(node: &Node, types: &[String]) -> Node {
+	match node {
+		Node::Meta { node: inner, data } => Node::Meta {
+			node: Box::new(typed_param_refs(inner, types)),
+			data: data.clone(),
+		},
+		Node::Key(left, Op::Hash, right) => {
+			if let (Node::Symbol(type_name), Node::Number(Number::Int(n))) = (left.drop_meta(), right.drop_meta()) {
+				let nth = (*n as usize).wrapping_sub(1);
+				if let Some((i, _)) = types.iter().enumerate().filter(|(_, t)| *t == type_name).nth(nth) {
+					return Node::Symbol(format!("${}", i));
+				}
+			}
+			Node::Key(Box::new(typed_param_refs(left, types)), Op::Hash, Box::new(typed_param_refs(right, types)))
+		}
+		Node::Key(left, op, right) => Node::Key(Box::new(typed_param_refs(left, types)), *op, Box::new(typed_param_refs(right, types))),
+		Node::List(items, bracket, separator) => Node::List(
+			items.iter().map(|item| typed_param_refs(item, types)).collect(),
+			bracket.clone(),
+			separator.clone(),
+		),
+		_ => node.clone(),
+	}
+}
+
+/// A match over enum variants has to cover each of them, unless an arm catches all
+fn check_exhaustive(node: &Node, types: &TypeRegistry) -> Option<Node> {
+	match node.drop_meta() {
+		Node::Key(subject, Op::Match, arms) => check_exhaustive(subject, types)
+			.or_else(|| missing_variants(arms, types))
+			.or_else(|| match_arms(arms).filter_map(match_arm).find_map(|(_, _, value)| check_exhaustive(value, types))),
+		Node::Key(left, _, right) => check_exhaustive(left, types).or_else(|| check_exhaustive(right, types)),
+		Node::List(items, _, _) => items.iter().find_map(|item| check_exhaustive(item, types)),
+		_ => None,
+	}
+}
+
+/// Error naming the variants no arm covers
+fn missing_variants(arms: &Node, types: &TypeRegistry) -> Option<Node> {
+	let mut enum_name = None;
+	let mut covered = vec![];
+	for (pattern, guard, _) in match_arms(arms).filter_map(match_arm) {
+		let Some((name, fields)) = variant_pattern(pattern) else {
+			if guard.is_none() && is_irrefutable(pattern) {
+				return None;
+			}
+			continue;
+		};
+		let Some(type_def) = types.get_by_name(name) else { continue };
+		let complete = guard.is_none() && fields.iter().all(|field| is_irrefutable(field));
+		if !type_def.variants.is_empty() {
+			// the enum itself
+			if complete {
+				return None;
+			}
+			enum_name = Some(type_def.name.clone());
+		} else if let Some(owner) = types.variant(name).and_then(|variant| variant.supertype.as_ref()) {
+			enum_name = Some(owner.clone());
+			if complete {
+				covered.push(type_def.name.clone());
+			}
+		}
+	}
+	let enum_name = enum_name?;
+	let missing: Vec<&str> = types.variants_of(&enum_name).iter()
+		.filter(|variant| !covered.contains(variant))
+		.map(String::as_str)
+		.collect();
+	if missing.is_empty() {
+		return None;
+	}
+	Some(Node::Error(Box::new(Node::Text(format!(
+		"match on {} misses {}",
+		enum_name,
+		missing.join(", ")
+	)))))
+}
+
+/// Type name and field patterns of Dot, Circle{r: x} or Circle(x)
+fn variant_pattern(pattern: &Node) -> Option<(&str, Vec<&Node>)> {
+	match pattern.drop_meta() {
+		Node::Symbol(name) if is_type_pattern(name) => Some((name.as_str(), vec![])),
+		Node::Key(name, Op::Colon, fields) if is_struct_pattern(name, fields) => {
+			let Node::Symbol(name) = name.drop_meta() else { return None };
+			let Node::List(fields, _, _) = fields.drop_meta() else { return None };
+			Some((name.as_str(), fields.iter().map(field_pattern).collect()))
+		}
+		Node::List(items, Bracket::Round, Separator::None) => match items.first().map(Node::drop_meta) {
+			Some(Node::Symbol(name)) if is_type_pattern(name) => Some((name.as_str(), items[1..].iter().map(field_pattern).collect())),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Pattern of a field: x in r: x
+fn field_pattern(field: &Node) -> &Node {
+	match field.drop_meta() {
+		Node::Key(_, Op::Colon, pattern) => pattern,
+		field => field,
+	}
+}
+
+/// Patterns every value matches: _ and plain bindings
+fn is_irrefutable(pattern: &Node) -> bool {
+	matches!(pattern.drop_meta(), Node::Symbol(name) if name == "_" || (pattern_kind(name).is_none() && !is_type_pattern(name)))
+}
+
+/// Null where the wasm would trap on it: reading through an optional field without ?. or a check first,
+/// or null for a field that is not optional. Checked holds what a surrounding condition ruled out as null
+fn check_null_safety(node: &Node, types: &TypeRegistry, checked: &[String]) -> Option<Node> {
+	let check = |node: &Node| check_null_safety(node, types, checked);
+	let check_guarded = |cond: &Node, node: &Node| {
+		let mut checked = checked.to_vec();
+		non_null(cond, &mut checked);
+		check_null_safety(node, types, &checked)
+	};
+	match node.drop_meta() {
+		Node::Key(if_part, Op::Then, then) => match if_part.drop_meta() {
+			Node::Key(_, Op::If, cond) => check(cond).or_else(|| check_guarded(cond, then)),
+			_ => check(if_part).or_else(|| check(then)),
+		},
+		Node::Key(cond, Op::Question, branches) => match branches.drop_meta() {
+			Node::Key(then, Op::Colon, otherwise) => check(cond).or_else(|| check_guarded(cond, then)).or_else(|| check(otherwise)),
+			_ => check(cond).or_else(|| check(branches)),
+		},
+		Node::Key(left, Op::And, right) => check(left).or_else(|| check_guarded(left, right)),
+		Node::Key(receiver, Op::Dot, member) => match receiver.drop_meta() {
+			Node::Key(_, Op::Dot | Op::SafeDot, field) if is_optional_field(field, types) && !checked.contains(&receiver.serialize()) => {
+				Some(Node::Error(Box::new(Node::Text(format!(
+					"{} may be null, use ?. or check it first",
+					receiver.serialize()
+				)))))
+			}
+			_ => check(receiver).or_else(|| check(member)),
+		},
+		Node::Key(name, Op::Colon, fields) => match (name.drop_meta(), fields.drop_meta()) {
+			(Node::Symbol(name), Node::List(fields, Bracket::Curly, _)) if types.get_by_name(name).is_some() => {
+				let type_def = types.get_by_name(name)?;
+				null_field(type_def, fields).or_else(|| mistyped_field(type_def, fields, types)).or_else(|| fields.iter().find_map(check))
+			}
+			_ => check(name).or_else(|| check(fields)),
+		},
+		Node::Key(left, _, right) => check(left).or_else(|| check(right)),
+		Node::List(items, _, _) => items.iter().find_map(check),
+		_ => None,
+	}
+}
+
+fn is_optional_field(field: &Node, types: &TypeRegistry) -> bool {
+	matches!(field.drop_meta(), Node::Symbol(name) if types.is_optional_field(name))
+}
+
+/// What a true condition rules out as null: tree.left, tree.left != null, a and b
+fn non_null(cond: &Node, checked: &mut Vec<String>) {
+	match cond.drop_meta() {
+		Node::Key(left, Op::And, right) => {
+			non_null(left, checked);
+			non_null(right, checked);
+		}
+		Node::Key(value, Op::Ne, null) | Node::Key(null, Op::Ne, value) if matches!(null.drop_meta(), Node::Empty) => {
+			checked.push(value.serialize());
+		}
+		other => checked.push(other.serialize()),
+	}
+}
+
+/// Error for null given to a field that is not optional: Tree{value: null}
+fn null_field(type_def: &TypeDef, fields: &[Node]) -> Option<Node> {
+	fields.iter().find_map(|field| {
+		let Node::Key(name, Op::Colon, value) = field.drop_meta() else { return None };
+		if !matches!(value.drop_meta(), Node::Empty) {
+			return None;
+		}
+		let name = name.drop_meta().to_string();
+		let field_def = type_def.fields.iter().find(|f| f.name == name && !f.is_optional())?;
+		Some(Node::Error(Box::new(Node::Text(format!(
+			"{}.{} is not optional, declare it {}: {}?",
+			type_def.name, name, name, field_def.type_name
+		)))))
+	})
+}
+
+/// An instance given to a field of a user type has to be one, or of a class extending it:
+/// a Person field takes an Employee
+fn mistyped_field(type_def: &TypeDef, fields: &[Node], types: &TypeRegistry) -> Option<Node> {
+	fields.iter().find_map(|field| {
+		let Node::Key(name, Op::Colon, value) = field.drop_meta() else { return None };
+		let Node::Key(instance_type, Op::Colon, _) = value.drop_meta() else { return None };
+		let instance_type = instance_type.drop_meta().to_string();
+		let name = name.drop_meta().to_string();
+		let field_def = type_def.fields.iter().find(|f| f.name == name)?;
+		let field_type = field_def.base_type();
+		if types.get_by_name(&instance_type).is_none() || types.get_by_name(field_type).is_none() || types.is_subtype(&instance_type, field_type) {
+			return None;
+		}
+		Some(Node::Error(Box::new(Node::Text(format!(
+			"{}.{} takes a {}, got a {}",
+			type_def.name, name, field_type, instance_type
+		)))))
+	})
+}
+
+/// Variants are constructed with one value per field: Circle(2), not Circle(1, 2)
+fn check_variant_arity(node: &Node, types: &TypeRegistry) -> Option<Node> {
+	match node.drop_meta() {
+		Node::List(items, bracket, separator) => {
+			if let (Bracket::Round, Separator::None, Some(Node::Symbol(name))) = (bracket, separator, items.first().map(Node::drop_meta)) {
+				if let Some(type_def) = types.variant(name) {
+					if type_def.fields.len() != items.len() - 1 {
+						return Some(Node::Error(Box::new(Node::Text(format!(
+							"{} takes {} fields, got {}",
+							type_def.name,
+							type_def.fields.len(),
+							items.len() - 1
+						)))));
+					}
+				}
+			}
+			items.iter().find_map(|item| check_variant_arity(item, types))
+		}
+		Node::Key(left, _, right) => check_variant_arity(left, types).or_else(|| check_variant_arity(right, types)),
+		_ => None,
+	}
+}
+
+/// A class extends a class declared before it
+fn check_supertype(node: &Node, types: &TypeRegistry) -> Option<Node> {
+	let Node::Type { name, .. } = node.drop_meta() else { return None };
+	let (type_name, Some(supertype)) = declared_name(name)? else { return None };
+	if types.get_by_name(type_name)?.supertype.is_some() {
+		return None;
+	}
+	Some(Node::Error(Box::new(Node::Text(format!(
+		"{} extends {}, which is no class declared before it",
+		type_name, supertype
+	)))))
+}
+
 /// Scope for tracking variable bindings
 #[derive(Clone, Debug, Default)]
 pub struct Scope {
 	pub locals: HashMap<String, Local>,
 	pub types: HashMap<String, Node>,  // User-defined types
 	pub parent: Option<Box<Scope>>,
+	/// How many for loops enclose the code being looked at, for their hidden locals
+	pub loop_depth: usize,
 }
 
 impl Scope {
@@ -267,6 +820,7 @@ impl Scope {
 			locals: HashMap::new(),
 			types: HashMap::new(),
 			parent: Some(Box::new(self.clone())),
+			loop_depth: self.loop_depth,
 		}
 	}
 
@@ -341,6 +895,36 @@ pub fn analyze(raw: Node) -> Node {
 	raw
 }
 
+/// Type-check a program, reporting the first mismatch with the position of its statement
+pub fn check_types(node: &Node) -> Result<(), WarpError> {
+	let node = &with_definitions(node);
+	let mut scope = Scope::new();
+	let mut ctx = Context::new();
+	collect_all_types(&mut ctx.type_registry, node);
+	extract_user_functions(&mut ctx, node);
+	let types = &ctx.type_registry;
+	let statements: Vec<&Node> = match node.drop_meta() {
+		Node::List(items, _, _) => items.iter().collect(),
+		_ => vec![node],
+	};
+	for statement in statements {
+		if let Some(err) = check_type_errors(statement, &mut scope)
+			.or_else(|| check_exhaustive(statement, types))
+			.or_else(|| check_null_safety(statement, types, &[]))
+			.or_else(|| check_supertype(statement, types))
+			.or_else(|| check_variant_arity(statement, types))
+			.or_else(|| check_overloads(statement, &ctx, &scope))
+			.or_else(|| check_generics(statement, &ctx, &scope))
+		{
+			return Err(WarpError::Type {
+				message: error_message(&err),
+				position: statement.get_lineinfo(),
+			});
+		}
+	}
+	Ok(())
+}
+
 /// Check for type errors in the AST, returns Some(Node::Error) if found
 fn check_type_errors(node: &Node, scope: &mut Scope) -> Option<Node> {
 	check_type_errors_inner(node, scope, false)
@@ -393,6 +977,18 @@ fn check_type_errors_inner(node: &Node, scope: &mut Scope, in_structure: bool) -
 			}
 			check_type_errors_inner(right, scope, in_structure)
 		}
+		// Closure bodies have their own scope
+		Node::Key(_, Op::FatArrow, _) => None,
+		// match arms are no closures, their guards and values are checked in place
+		Node::Key(subject, Op::Match, arms) => {
+			if let Some(err) = check_type_errors_inner(subject, scope, in_structure) {
+				return Some(err);
+			}
+			match_arms(arms).filter_map(match_arm).find_map(|(_, guard, value)| {
+				guard.and_then(|guard| check_type_errors_inner(guard, scope, in_structure))
+					.or_else(|| check_type_errors_inner(value, scope, in_structure))
+			})
+		}
 		Node::Key(left, _, right) => {
 			if let Some(err) = check_type_errors_inner(left, scope, in_structure) {
 				return Some(err);
@@ -573,13 +1169,129 @@ fn type_name_to_kind(name: &str) -> Kind {
 
 /// Extract user-defined functions from the AST into context
 /// Infer return type of a function body given its parameters
-fn infer_function_return_kind(params: &[(String, Option<Node>)], body: &Node) -> Kind {
+fn infer_function_return_kind(user_fn: &UserFunctionDef) -> Kind {
 	let mut scope = Scope::new();
-	// Add parameters to scope (all assumed Int for now)
-	for (name, _default) in params {
-		scope.define(name.clone(), None, Kind::Int);
+	define_params(&mut scope, user_fn);
+	infer_type(&user_fn.body, &scope)
+}
+
+/// A user function returning the kind its body infers from its parameters
+fn user_function(name: String, params: Vec<(String, Option<Node>)>, param_types: Vec<Option<String>>, body: Box<Node>) -> UserFunctionDef {
+	generic_function(name, vec![], params, param_types, None, body)
+}
+
+/// A user function with the type parameters and the return type it declares: first<T>(xs: List<T>) -> T,
+/// values of a type parameter are passed and returned as nodes
+fn generic_function(
+	name: String,
+	type_params: Vec<String>,
+	params: Vec<(String, Option<Node>)>,
+	param_types: Vec<Option<String>>,
+	return_type: Option<String>,
+	body: Box<Node>,
+) -> UserFunctionDef {
+	let mut user_fn = UserFunctionDef {
+		name,
+		params,
+		param_types,
+		type_params,
+		return_type,
+		body,
+		return_kind: Kind::Int,
+		func_index: None,
+	};
+	user_fn.return_kind = match &user_fn.return_type {
+		Some(type_name) => type_kind(&user_fn, type_name),
+		None => infer_function_return_kind(&user_fn),
+	};
+	user_fn
+}
+
+/// Kind of a user function parameter: closures the body calls and instances it reads members of,
+/// like self in methods, are passed as nodes, the rest as Int
+pub fn param_kind(name: &str, body: &Node) -> Kind {
+	if calls(name, body) {
+		Kind::Closure
+	} else if name == "self" || reads_member(name, body) {
+		Kind::Data
+	} else {
+		Kind::Int
+	}
+}
+
+/// Kinds of the parameters of a user function: the types they declare, else what the body does with them
+pub fn param_kinds(user_fn: &UserFunctionDef) -> Vec<Kind> {
+	user_fn.params.iter().enumerate().map(|(i, (name, _default))| match declared_type(user_fn, i) {
+		Some(type_name) => type_kind(user_fn, type_name),
+		None => param_kind(name, &user_fn.body),
+	}).collect()
+}
+
+/// Kind of a type a user function declares, a node for its type parameters
+fn type_kind(user_fn: &UserFunctionDef, type_name: &str) -> Kind {
+	if user_fn.type_params.iter().any(|param| param == type_name) {
+		Kind::Data
+	} else {
+		declared_kind(type_name)
+	}
+}
+
+/// The type parameter i of a user function declares
+fn declared_type(user_fn: &UserFunctionDef, i: usize) -> Option<&str> {
+	user_fn.param_types.get(i).and_then(Option::as_deref)
+}
+
+/// Kind of a parameter declaring its type: float x, x: text, p: Person, xs: List<int> or x: any, which takes any node
+fn declared_kind(type_name: &str) -> Kind {
+	let type_name = generic_base(type_name);
+	if type_name == "any" || is_type_pattern(type_name) {
+		Kind::Data
+	} else {
+		pattern_kind(type_name).unwrap_or_else(|| type_name_to_kind(type_name))
+	}
+}
+
+/// Define the parameters of a user function in its scope
+pub fn define_params(scope: &mut Scope, user_fn: &UserFunctionDef) {
+	for (i, ((name, _default), kind)) in user_fn.params.iter().zip(param_kinds(user_fn)).enumerate() {
+		scope.define(name.clone(), param_type_node(user_fn, i), kind);
+	}
+}
+
+/// Type parameter i declares as a node, the class of self in methods
+fn param_type_node(user_fn: &UserFunctionDef, i: usize) -> Option<Box<Node>> {
+	let type_name = match declared_type(user_fn, i) {
+		Some(type_name) => type_name,
+		None if user_fn.params[i].0 == "self" => user_fn.name.split_once('.').map(|(owner, _)| owner).filter(|owner| !owner.is_empty())?,
+		None => return None,
+	};
+	Some(Box::new(Node::Symbol(type_name.to_string())))
+}
+
+/// Whether body calls name with arguments: name(x) or name x
+fn calls(name: &str, body: &Node) -> bool {
+	match body.drop_meta() {
+		Node::List(items, _, separator) => {
+			let is_call = items.len() >= 2
+				&& matches!(separator, Separator::None | Separator::Space)
+				&& matches!(items[0].drop_meta(), Node::Symbol(s) if s == name);
+			is_call || items.iter().any(|item| calls(name, item))
+		}
+		Node::Key(left, _, right) => calls(name, left) || calls(name, right),
+		_ => false,
+	}
+}
+
+/// Whether body reads a member of name: name.w or name?.w
+fn reads_member(name: &str, body: &Node) -> bool {
+	match body.drop_meta() {
+		Node::Key(left, Op::Dot | Op::SafeDot, right) => {
+			matches!(left.drop_meta(), Node::Symbol(s) if s == name) || reads_member(name, left) || reads_member(name, right)
+		}
+		Node::Key(left, _, right) => reads_member(name, left) || reads_member(name, right),
+		Node::List(items, _, _) => items.iter().any(|item| reads_member(name, item)),
+		_ => false,
 	}
-	infer_type(body, &scope)
 }
 
 /// Recognizes patterns:
@@ -587,108 +1299,736 @@ fn infer_function_return_kind(params: &[(String, Option<Node>)], body: &Node) ->
 /// - `name := body` → Key(Symbol(name), Define, body) (uses implicit `it`)
 pub fn extract_user_functions(ctx: &mut Context, node: &Node) {
 	extract_user_functions_inner(ctx, node);
+	add_method_dispatchers(ctx);
+	add_overload_dispatchers(ctx);
+	// nodes like the arguments and results of dispatchers and generic results are unboxed for floats
+	let is_generic_result = |user_fn: &UserFunctionDef| user_fn.return_type.as_ref().is_some_and(|t| user_fn.type_params.contains(t));
+	if ctx.user_functions.values().any(|user_fn| user_fn.return_kind.is_float() || param_kinds(user_fn).contains(&Kind::Float) || is_generic_result(user_fn)) {
+		ctx.required_functions.insert("get_float_value");
+	}
 }
 
 fn extract_user_functions_inner(ctx: &mut Context, node: &Node) {
-	let node = node.drop_meta();
-	match node {
-		// Pattern: name(param1, param2, ...) = body
-		Node::Key(left, Op::Assign, body) => {
-			if let Node::List(items, _, _) = left.drop_meta() {
-				if !items.is_empty() {
-					if let Node::Symbol(name) = items[0].drop_meta() {
-						let params: Vec<(String, Option<Node>)> =
-							items.iter().skip(1).filter_map(extract_param).collect();
-						let return_kind = infer_function_return_kind(&params, body);
-						let func_def = UserFunctionDef {
-							name: name.clone(),
-							params,
-							body: body.clone(),
-							return_kind,
-							func_index: None,
-						};
-						ctx.user_functions.insert(name.clone(), func_def);
-						return;
-					}
-				}
+	if let Some(func_def) = function_definition(node) {
+		add_user_function(ctx, func_def);
+		return;
+	}
+	match node.drop_meta() {
+		Node::Type { name, body } => {
+			if let Some((type_name, _)) = declared_name(name) {
+				extract_methods(ctx, type_name, body);
+			}
+		}
+		Node::List(items, _, _) => {
+			for item in items {
+				extract_user_functions_inner(ctx, item);
 			}
+		}
+		Node::Key(left, _, right) => {
 			extract_user_functions_inner(ctx, left);
-			extract_user_functions_inner(ctx, body);
+			extract_user_functions_inner(ctx, right);
+		}
+		_ => {}
+	}
+}
+
+/// The function node defines, if it is a definition
+fn function_definition(node: &Node) -> Option<UserFunctionDef> {
+	match node.drop_meta() {
+		// Pattern: name(param1, param2, ...) = body, or name(params) -> type = body
+		Node::Key(left, Op::Assign, body) => {
+			let (left, return_type) = split_return_type(left);
+			let Node::List(items, _, _) = left.drop_meta() else { return None };
+			declared_function(items, return_type, body)
 		}
 		// Pattern: name x := body (with explicit parameter x using $0 or `it`)
 		Node::Key(left, Op::Define, body) => {
 			if let Node::List(items, _, _) = left.drop_meta() {
-				if !items.is_empty() {
-					if let Node::Symbol(name) = items[0].drop_meta() {
-						let params: Vec<(String, Option<Node>)> =
-							items.iter().skip(1).filter_map(extract_param).collect();
-						if !params.is_empty() || uses_dollar_param(body) || uses_it(body) {
-							let actual_params = if params.is_empty() {
-								vec![("it".to_string(), None)]
-							} else {
-								params
-							};
-							let return_kind = infer_function_return_kind(&actual_params, body);
-							let func_def = UserFunctionDef {
-								name: name.clone(),
-								params: actual_params,
-								body: body.clone(),
-								return_kind,
-								func_index: None,
-							};
-							ctx.user_functions.insert(name.clone(), func_def);
-							return;
-						}
+				if let Some(Node::Symbol(name)) = items.first().map(Node::drop_meta) {
+					let (params, param_types) = extract_params(&items[1..]);
+					if !params.is_empty() || uses_dollar_param(body) || uses_it(body) {
+						let (params, param_types) = if params.is_empty() {
+							(vec![("it".to_string(), None)], vec![None])
+						} else {
+							(params, param_types)
+						};
+						return Some(user_function(name.clone(), params, param_types, body.clone()));
 					}
 				}
 			}
 			// Pattern: name := body (uses implicit `it` parameter)
-			if let Node::Symbol(name) = left.drop_meta() {
-				if uses_it(body) || uses_dollar_param(body) {
-					let params = vec![("it".to_string(), None)];
-					let return_kind = infer_function_return_kind(&params, body);
-					let func_def = UserFunctionDef {
-						name: name.clone(),
-						params,
-						body: body.clone(),
-						return_kind,
-						func_index: None,
-					};
-					ctx.user_functions.insert(name.clone(), func_def);
-					return;
-				}
+			let Node::Symbol(name) = left.drop_meta() else { return None };
+			if !(uses_it(body) || uses_dollar_param(body)) {
+				return None;
 			}
-			extract_user_functions_inner(ctx, left);
-			extract_user_functions_inner(ctx, body);
+			Some(user_function(name.clone(), vec![("it".to_string(), None)], vec![None], body.clone()))
 		}
 		// Check for def/fun/fn syntax
+		Node::List(items, _, _) if items.len() >= 2 => match items[0].drop_meta() {
+			Node::Symbol(s) if is_function_keyword(s) => extract_def_function(&items[1..]),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// A function defined again with other parameter types is overloaded: its definitions become variants
+/// like combine(float,float) and combine(int,int), a definition with the same types replaces the earlier one
+fn add_user_function(ctx: &mut Context, func_def: UserFunctionDef) {
+	let overloaded = is_overloaded(ctx, &func_def.name);
+	match ctx.user_functions.remove(&func_def.name) {
+		Some(first) if signature(&first) != signature(&func_def) => {
+			add_variant(ctx, first);
+			add_variant(ctx, func_def);
+		}
+		_ if overloaded => add_variant(ctx, func_def),
+		_ => {
+			ctx.user_functions.insert(func_def.name.clone(), func_def);
+		}
+	}
+}
+
+fn add_variant(ctx: &mut Context, mut func_def: UserFunctionDef) {
+	func_def.name = format!("{}({})", func_def.name, signature(&func_def).join(","));
+	ctx.user_functions.insert(func_def.name.clone(), func_def);
+}
+
+/// The parameter types of a user function as variant names write them: int, float, text, Person or _ for any
+fn signature(user_fn: &UserFunctionDef) -> Vec<String> {
+	(0..user_fn.params.len()).map(|i| match declared_type(user_fn, i) {
+		Some(type_name) if user_fn.type_params.iter().any(|param| param == type_name) => "_".to_string(),
+		Some(type_name) if is_type_pattern(generic_base(type_name)) => generic_base(type_name).to_string(),
+		Some(type_name) => kind_type(declared_kind(type_name)).to_string(),
+		None => "_".to_string(),
+	}).collect()
+}
+
+/// Name of a kind in signatures and dispatch patterns, _ for any node
+fn kind_type(kind: Kind) -> &'static str {
+	match kind {
+		Kind::Int => "int",
+		Kind::Float => "float",
+		Kind::Text => "text",
+		Kind::Codepoint => "char",
+		Kind::Symbol => "symbol",
+		Kind::List => "list",
+		Kind::Closure => "closure",
+		_ => "_",
+	}
+}
+
+/// Whether name is defined several times with different parameter types
+pub fn is_overloaded(ctx: &Context, name: &str) -> bool {
+	!overload_variants(ctx, name).is_empty()
+}
+
+/// The variants of the overloaded function name: combine(float,float) and combine(int,int) for combine
+fn overload_variants<'c>(ctx: &'c Context, name: &str) -> Vec<&'c UserFunctionDef> {
+	let mut variants: Vec<&UserFunctionDef> = ctx.user_functions.values()
+		.filter(|user_fn| overloaded_name(&user_fn.name) == Some(name))
+		.collect();
+	variants.sort_by(|a, b| a.name.cmp(&b.name));
+	variants
+}
+
+/// combine for the variant combine(int,int)
+fn overloaded_name(variant: &str) -> Option<&str> {
+	variant.strip_suffix(')')?.split_once('(').map(|(name, _)| name)
+}
+
+/// Type of a call argument for choosing a variant: the class of an instance like Sq{s:3},
+/// else its kind, _ if only known at runtime
+pub fn arg_type(arg: &Node, kind: Kind, types: &TypeRegistry) -> String {
+	if let Node::Key(name, Op::Colon, fields) = arg.drop_meta() {
+		if let (Node::Symbol(name), Node::List(_, Bracket::Curly, _)) = (name.drop_meta(), fields.drop_meta()) {
+			if types.get_by_name(name).is_some() {
+				return name.clone();
+			}
+		}
+	}
+	match kind {
+		// a name of unknown kind may hold anything
+		Kind::Symbol => "_".to_string(),
+		kind => kind_type(kind).to_string(),
+	}
+}
+
+/// Whether an argument of type arg may be passed for a parameter of type param:
+/// the same type, an int for a float or a subclass for its class; _ takes anything
+fn fits(arg: &str, param: &str, types: &TypeRegistry) -> bool {
+	param == "_" || arg == param || (arg == "int" && param == "float") || types.is_subtype(arg, param)
+}
+
+/// Whether the first n parameters of a are at least as specific as those of b
+fn narrower(a: &[String], b: &[String], n: usize, types: &TypeRegistry) -> bool {
+	a.iter().zip(b).take(n).all(|(a, b)| fits(a, b, types))
+}
+
+/// The variant of the overloaded function name a call with arguments of these types runs, the most specific
+/// one taking them, or None to call the dispatcher name choosing at runtime
+pub fn resolve_overload(ctx: &Context, name: &str, arg_types: &[String]) -> Result<Option<String>, String> {
+	let types = &ctx.type_registry;
+	let n = arg_types.len();
+	let candidates: Vec<(&UserFunctionDef, Vec<String>)> = overload_variants(ctx, name)
+		.into_iter()
+		.filter(|variant| {
+			let required = variant.params.iter().filter(|(_, default)| default.is_none()).count();
+			(required..=variant.params.len()).contains(&n)
+		})
+		.map(|variant| (variant, signature(variant)))
+		.filter(|(_, params)| arg_types.iter().zip(params).all(|(arg, param)| arg == "_" || fits(arg, param, types)))
+		.collect();
+	if candidates.is_empty() {
+		return Err(format!("no {} takes ({})", name, arg_types.join(", ")));
+	}
+	// the candidates no other one is strictly narrower than
+	let best: Vec<&(&UserFunctionDef, Vec<String>)> = candidates.iter()
+		.filter(|(_, params)| !candidates.iter().any(|(_, other)| narrower(other, params, n, types) && !narrower(params, other, n, types)))
+		.collect();
+	if !arg_types.iter().any(|arg| arg == "_") {
+		return match best.as_slice() {
+			[(variant, _)] => Ok(Some(variant.name.clone())),
+			_ => Err(ambiguity(name, best[0].0, best[1].0, arg_types)),
+		};
+	}
+	// at runtime any two candidates might both match, unless one is narrower or their types exclude each other
+	for (i, (a, a_params)) in candidates.iter().enumerate() {
+		for (b, b_params) in &candidates[i + 1..] {
+			let overlap = a_params.iter().zip(b_params).take(n).all(|(a, b)| fits(a, b, types) || fits(b, a, types));
+			if overlap && !narrower(a_params, b_params, n, types) && !narrower(b_params, a_params, n, types) {
+				return Err(ambiguity(name, a, b, arg_types));
+			}
+		}
+	}
+	match candidates.as_slice() {
+		[(variant, _)] => Ok(Some(variant.name.clone())),
+		_ if ctx.user_functions.get(name).is_some_and(|dispatcher| dispatcher.params.len() == n) => Ok(None),
+		_ => Err(format!("{} takes {} arguments whose types are only known at runtime", name, n)),
+	}
+}
+
+fn ambiguity(name: &str, a: &UserFunctionDef, b: &UserFunctionDef, arg_types: &[String]) -> String {
+	format!("call of {} is ambiguous: {} and {} both take ({})", name, a.name, b.name, arg_types.join(", "))
+}
+
+/// Report calls of overloaded functions no variant or more than one variant takes equally well
+fn check_overloads(node: &Node, ctx: &Context, scope: &Scope) -> Option<Node> {
+	if function_definition(node).is_some() {
+		return None; // its parameters are not in scope
+	}
+	match node.drop_meta() {
 		Node::List(items, _, _) => {
-			if items.len() >= 2 {
-				if let Node::Symbol(s) = items[0].drop_meta() {
-					if is_function_keyword(s) {
-						if let Some(func_def) = extract_def_function(&items[1..]) {
-							ctx.user_functions.insert(func_def.name.clone(), func_def);
-							return;
-						}
+			if let Some(Node::Symbol(name)) = items.first().map(Node::drop_meta) {
+				if is_overloaded(ctx, name) {
+					let arg_types: Vec<String> = items[1..].iter()
+						.map(|arg| arg_type(arg, infer_type(arg, scope), &ctx.type_registry))
+						.collect();
+					if let Err(message) = resolve_overload(ctx, name, &arg_types) {
+						return Some(Node::Error(Box::new(Node::Text(message))));
 					}
 				}
 			}
-			for item in items {
-				extract_user_functions_inner(ctx, item);
+			items.iter().find_map(|item| check_overloads(item, ctx, scope))
+		}
+		Node::Key(_, Op::FatArrow, _) | Node::Type { .. } => None,
+		Node::Key(left, _, right) => check_overloads(left, ctx, scope).or_else(|| check_overloads(right, ctx, scope)),
+		_ => None,
+	}
+}
+
+/// Generic types take as many type arguments as they declare parameters, and a type parameter one type
+/// throughout a call of a generic function or an instance of a generic class: T is int in first([7, 8])
+fn check_generics(node: &Node, ctx: &Context, scope: &Scope) -> Option<Node> {
+	let types = &ctx.type_registry;
+	let error = |message: String| Some(Node::Error(Box::new(Node::Text(message))));
+	if let Some(user_fn) = function_definition(node) {
+		// its parameters are not in scope
+		let mut declared = user_fn.param_types.iter().flatten().chain(&user_fn.return_type);
+		return declared.find_map(|type_name| type_args_error(type_name, types)).and_then(error);
+	}
+	match node.drop_meta() {
+		Node::Type { name, .. } => {
+			let (type_name, _) = declared_name(name)?;
+			let fields = &types.get_by_name(type_name)?.fields;
+			fields.iter().find_map(|field| type_args_error(&field.type_name, types)).and_then(error)
+		}
+		Node::List(items, _, _) => {
+			if let Some(Node::Symbol(name)) = items.first().map(Node::drop_meta) {
+				if let Some(user_fn) = ctx.user_functions.get(name).filter(|user_fn| !user_fn.type_params.is_empty()) {
+					let kind_of = |arg: &Node| infer_type(arg, scope);
+					if let Err(message) = bind_type_params(user_fn, &items[1..], &kind_of, types) {
+						return error(format!("{}<{}> takes {}", name, user_fn.type_params.join(", "), message));
+					}
+				}
 			}
+			items.iter().find_map(|item| check_generics(item, ctx, scope))
 		}
-		Node::Key(left, _, right) => {
-			extract_user_functions_inner(ctx, left);
-			extract_user_functions_inner(ctx, right);
+		Node::Key(name, Op::Colon, fields) => match (name.drop_meta(), fields.drop_meta()) {
+			(Node::Symbol(type_name), Node::List(field_values, Bracket::Curly, _)) if types.get_by_name(type_name).is_some_and(|t| !t.type_params.is_empty()) => {
+				let type_def = types.get_by_name(type_name)?;
+				let kind_of = |value: &Node| infer_type(value, scope);
+				if let Err(message) = bind_field_types(type_def, &type_def.type_params, field_values, &kind_of, types, &mut HashMap::new()) {
+					return error(format!("{}<{}> takes {}", type_name, type_def.type_params.join(", "), message));
+				}
+				field_values.iter().find_map(|value| check_generics(value, ctx, scope))
+			}
+			_ => check_generics(name, ctx, scope).or_else(|| check_generics(fields, ctx, scope)),
+		},
+		Node::Key(_, Op::FatArrow, _) => None,
+		Node::Key(left, _, right) => check_generics(left, ctx, scope).or_else(|| check_generics(right, ctx, scope)),
+		_ => None,
+	}
+}
+
+/// Error for a generic type given the wrong number of type arguments: Pair<int> for Pair<A, B>
+fn type_args_error(type_name: &str, types: &TypeRegistry) -> Option<String> {
+	let args = type_args(type_name);
+	if let Some(type_def) = types.get_by_name(generic_base(type_name)) {
+		if !args.is_empty() && args.len() != type_def.type_params.len() {
+			return Some(format!(
+				"{} takes {} type parameters, got {}",
+				type_def.name, type_def.type_params.len(), args.len()
+			));
+		}
+	}
+	args.iter().find_map(|arg| type_args_error(arg, types))
+}
+
+/// The types the type parameters of a generic function take in a call, from the arguments:
+/// T is int in first([7, 8]) for first<T>(xs: List<T>)
+pub fn bind_type_params(user_fn: &UserFunctionDef, args: &[Node], kind_of: &dyn Fn(&Node) -> Kind, types: &TypeRegistry) -> Result<HashMap<String, String>, String> {
+	let mut bindings = HashMap::new();
+	for (i, arg) in args.iter().enumerate() {
+		if let Some(param_type) = declared_type(user_fn, i) {
+			bind_type(&user_fn.type_params, param_type, arg, kind_of, types, &mut bindings)?;
+		}
+	}
+	Ok(bindings)
+}
+
+/// Bind the type parameters in param_type to the types value has: T to int for 1 as T, for [1, 2] as List<T>
+/// and for Box{item: 1} as Box<T>; values of types only known at runtime bind nothing
+fn bind_type(
+	type_params: &[String],
+	param_type: &str,
+	value: &Node,
+	kind_of: &dyn Fn(&Node) -> Kind,
+	types: &TypeRegistry,
+	bindings: &mut HashMap<String, String>,
+) -> Result<(), String> {
+	if type_params.iter().any(|param| param == param_type) {
+		return bind_type_param(param_type, arg_type(value, kind_of(value), types), bindings);
+	}
+	let args = type_args(param_type);
+	match value.drop_meta() {
+		Node::List(items, Bracket::Square, _) if generic_base(param_type).eq_ignore_ascii_case("list") && args.len() == 1 => {
+			items.iter().try_for_each(|item| bind_type(type_params, &args[0], item, kind_of, types, bindings))
+		}
+		Node::Key(name, Op::Colon, fields) => match (name.drop_meta(), fields.drop_meta()) {
+			(Node::Symbol(name), Node::List(fields, Bracket::Curly, _)) if *name == generic_base(param_type) => {
+				let Some(type_def) = types.get_by_name(name) else { return Ok(()) };
+				if args.len() != type_def.type_params.len() {
+					return Ok(());
+				}
+				// the field item: A of Box<A> takes a T for Box<T>
+				let mut field_bindings = HashMap::new();
+				bind_field_types(type_def, &type_def.type_params, fields, kind_of, types, &mut field_bindings)?;
+				for (param, arg) in type_def.type_params.iter().zip(&args) {
+					if let Some(bound) = field_bindings.remove(param).filter(|_| type_params.contains(arg)) {
+						bind_type_param(arg, bound, bindings)?;
+					}
+				}
+				Ok(())
+			}
+			_ => Ok(()),
+		},
+		_ => Ok(()),
+	}
+}
+
+/// Bind the type parameter to value_type unless it took another type already, _ binds nothing
+fn bind_type_param(type_param: &str, value_type: String, bindings: &mut HashMap<String, String>) -> Result<(), String> {
+	match bindings.get(type_param) {
+		_ if value_type == "_" => Ok(()),
+		Some(bound) if *bound != value_type => Err(format!("one type for {}, got {} and {}", type_param, bound, value_type)),
+		_ => {
+			bindings.insert(type_param.to_string(), value_type);
+			Ok(())
+		}
+	}
+}
+
+/// Bind the type parameters of type_def to the types of the field values of its instance: Pair{first: 1, second: 'a'}
+fn bind_field_types(
+	type_def: &TypeDef,
+	type_params: &[String],
+	fields: &[Node],
+	kind_of: &dyn Fn(&Node) -> Kind,
+	types: &TypeRegistry,
+	bindings: &mut HashMap<String, String>,
+) -> Result<(), String> {
+	for field in fields {
+		let Node::Key(name, Op::Colon, value) = field.drop_meta() else { continue };
+		let name = name.drop_meta().to_string();
+		if let Some(field_def) = type_def.fields.iter().find(|f| f.name == name) {
+			bind_type(type_params, field_def.base_type(), value, kind_of, types, bindings)?;
+		}
+	}
+	Ok(())
+}
+
+/// Functions in a class body are methods: area() in class Rect is Rect.area(self), reading w as self.w
+/// An init is the constructor: init(w, h) in class Rect is Rect(w, h)
+fn extract_methods(ctx: &mut Context, type_name: &str, body: &Node) {
+	let fields: Vec<String> = match ctx.type_registry.get_by_name(type_name) {
+		Some(type_def) => type_def.fields.iter().map(|field| field.name.clone()).collect(),
+		None => return,
+	};
+	for member in class_members(body) {
+		let Some(func_def) = function_definition(member) else { continue };
+		let func_def = if func_def.name == "init" {
+			constructor(type_name, &fields, func_def)
+		} else {
+			method(type_name, &fields, func_def)
+		};
+		ctx.user_functions.insert(func_def.name.clone(), func_def);
+	}
+}
+
+/// Fields and functions of a class body, whichever lines they share
+fn class_members(body: &Node) -> Vec<&Node> {
+	match body.drop_meta() {
+		Node::List(items, _, _) if !defines_function(body) => items.iter().flat_map(class_members).collect(),
+		Node::Empty => vec![],
+		member => vec![member],
+	}
+}
+
+/// Whether node is a def/fun/fn list
+fn defines_function(node: &Node) -> bool {
+	matches!(node.drop_meta(), Node::List(items, _, _) if matches!(items.first().map(Node::drop_meta), Some(Node::Symbol(s)) if is_function_keyword(s)))
+}
+
+/// The method T.name taking the instance as its first parameter self
+fn method(type_name: &str, fields: &[String], func_def: UserFunctionDef) -> UserFunctionDef {
+	let param_names: Vec<String> = func_def.params.iter().map(|(name, _)| name.clone()).collect();
+	let body = bind_fields(&func_def.body, fields, &param_names);
+	let mut params = vec![("self".to_string(), None)];
+	params.extend(func_def.params);
+	let mut param_types = vec![None];
+	param_types.extend(func_def.param_types);
+	user_function(format!("{}.{}", type_name, func_def.name), params, param_types, Box::new(body))
+}
+
+/// Field names a method body reads without self. are the fields of self, unless parameters shadow them
+fn bind_fields(node: &Node, fields: &[String], params: &[String]) -> Node {
+	let bind = |node: &Node| Box::new(bind_fields(node, fields, params));
+	match node {
+		Node::Meta { node, data } => Node::Meta { node: bind(node), data: data.clone() },
+		Node::Symbol(name) if fields.contains(name) && !params.contains(name) => {
+			Node::Key(Box::new(Node::Symbol("self".to_string())), Op::Dot, Box::new(node.clone()))
+		}
+		Node::List(items, bracket, separator) => Node::List(
+			items.iter().map(|item| bind_fields(item, fields, params)).collect(),
+			bracket.clone(),
+			separator.clone(),
+		),
+		// the member in other.w and the names in Rect{w: 1} or w = 1 stay
+		Node::Key(left, op @ (Op::Dot | Op::SafeDot), right) => Node::Key(bind(left), *op, right.clone()),
+		Node::Key(left, op @ (Op::Colon | Op::Assign | Op::Define), right) if matches!(left.drop_meta(), Node::Symbol(_)) => {
+			Node::Key(left.clone(), *op, bind(right))
+		}
+		Node::Key(left, op, right) => Node::Key(bind(left), *op, bind(right)),
+		_ => node.clone(),
+	}
+}
+
+/// The constructor T(params) runs the body of init, validating or adjusting its parameters,
+/// then makes the instance from the parameters and locals named like the fields
+fn constructor(type_name: &str, fields: &[String], init: UserFunctionDef) -> UserFunctionDef {
+	let field_values = fields.iter().map(|field| {
+		Node::Key(Box::new(Node::Symbol(field.clone())), Op::Colon, Box::new(Node::Symbol(field.clone())))
+	}).collect();
+	let instance = Node::Key(
+		Box::new(Node::Symbol(type_name.to_string())),
+		Op::Colon,
+		Box::new(Node::List(field_values, Bracket::Curly, Separator::Space)),
+	);
+	let mut statements = match init.body.drop_meta() {
+		Node::List(items, Bracket::Curly, _) => items.clone(),
+		Node::Empty => vec![],
+		body => vec![body.clone()],
+	};
+	let this = Node::Symbol("self".to_string());
+	statements.push(Node::Key(Box::new(this.clone()), Op::Assign, Box::new(instance)));
+	statements.push(this);
+	let body = Node::List(statements, Bracket::Curly, Separator::Newline);
+	user_function(type_name.to_string(), init.params, init.param_types, Box::new(body))
+}
+
+/// A method several types declare dispatches on the type of self: .area(self) runs Rect.area or Circle.area
+fn add_method_dispatchers(ctx: &mut Context) {
+	let mut methods: HashMap<String, Vec<UserFunctionDef>> = HashMap::new();
+	for user_fn in ctx.user_functions.values() {
+		if let Some((owner, method)) = user_fn.name.split_once('.') {
+			if !owner.is_empty() {
+				methods.entry(method.to_string()).or_default().push(user_fn.clone());
+			}
+		}
+	}
+	for (method, mut owners) in methods {
+		if owners.len() < 2 {
+			continue;
+		}
+		// subclasses before the classes they extend, whose arms would match them too
+		let depth = |owner: &UserFunctionDef| {
+			let type_name = owner.name.split_once('.').map_or("", |(type_name, _)| type_name);
+			ctx.type_registry.types().iter().filter(|t| ctx.type_registry.is_subtype(type_name, &t.name)).count()
+		};
+		owners.sort_by(|a, b| depth(b).cmp(&depth(a)).then_with(|| a.name.cmp(&b.name)));
+		let params = owners[0].params.clone();
+		let arms = owners.iter().map(|owner| {
+			let type_name = owner.name.split_once('.').map_or("", |(type_name, _)| type_name);
+			let mut call = vec![Node::Symbol(owner.name.clone())];
+			call.extend(params.iter().map(|(name, _)| Node::Symbol(name.clone())));
+			Node::Key(
+				Box::new(Node::Symbol(type_name.to_string())),
+				Op::FatArrow,
+				Box::new(Node::List(call, Bracket::Round, Separator::None)),
+			)
+		}).collect();
+		let body = Node::Key(
+			Box::new(Node::Symbol("self".to_string())),
+			Op::Match,
+			Box::new(Node::List(arms, Bracket::Curly, Separator::Newline)),
+		);
+		let return_kind = match owners[0].return_kind {
+			kind if owners.iter().all(|owner| owner.return_kind == kind) => kind,
+			_ => Kind::Data,
+		};
+		let name = format!(".{}", method);
+		ctx.user_functions.insert(name.clone(), UserFunctionDef {
+			name,
+			param_types: vec![None; params.len()],
+			type_params: vec![],
+			return_type: None,
+			params,
+			body: Box::new(body),
+			return_kind,
+			func_index: None,
+		});
+	}
+}
+
+/// An overloaded function called with arguments of types only known at runtime dispatches on them:
+/// combine(a, b) matches [a, b] against [float, float] and [int, int], more specific variants first
+fn add_overload_dispatchers(ctx: &mut Context) {
+	let mut overloads: HashMap<String, Vec<UserFunctionDef>> = HashMap::new();
+	for user_fn in ctx.user_functions.values() {
+		if let Some(name) = overloaded_name(&user_fn.name) {
+			overloads.entry(name.to_string()).or_default().push(user_fn.clone());
+		}
+	}
+	for (name, variants) in overloads {
+		let arity = variants.iter().map(|variant| variant.params.len()).max().unwrap_or(0);
+		let mut variants: Vec<(UserFunctionDef, Vec<String>)> = variants.into_iter()
+			.filter(|variant| variant.params.len() == arity)
+			.map(|variant| {
+				let params = signature(&variant);
+				(variant, params)
+			})
+			.collect();
+		variants.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+		let types = &ctx.type_registry;
+		// a variant narrower than more others is more specific
+		let specificity = |params: &[String]| variants.iter().filter(|(_, other)| narrower(params, other, arity, types)).count();
+		let mut order: Vec<(usize, usize)> = variants.iter().enumerate().map(|(i, (_, params))| (specificity(params), i)).collect();
+		order.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+		let params = variants[0].0.params.clone();
+		let args: Vec<Node> = params.iter().map(|(name, _)| Node::Symbol(name.clone())).collect();
+		let arms = order.iter().map(|&(_, i)| {
+			let (variant, param_types) = &variants[i];
+			let patterns = param_types.iter().map(|t| Node::Symbol(t.clone())).collect();
+			let mut call = vec![Node::Symbol(variant.name.clone())];
+			call.extend(args.iter().cloned());
+			Node::Key(
+				Box::new(Node::List(patterns, Bracket::Square, Separator::Colon)),
+				Op::FatArrow,
+				Box::new(Node::List(call, Bracket::Round, Separator::None)),
+			)
+		}).collect();
+		let body = Node::Key(
+			Box::new(Node::List(args, Bracket::Square, Separator::Colon)),
+			Op::Match,
+			Box::new(Node::List(arms, Bracket::Curly, Separator::Newline)),
+		);
+		let return_kind = match variants[0].0.return_kind {
+			kind if variants.iter().all(|(variant, _)| variant.return_kind == kind) => kind,
+			_ => Kind::Data,
+		};
+		ctx.user_functions.insert(name.clone(), UserFunctionDef {
+			name,
+			param_types: vec![Some("any".to_string()); params.len()],
+			type_params: vec![],
+			return_type: None,
+			params,
+			body: Box::new(body),
+			return_kind,
+			func_index: None,
+		});
+	}
+}
+
+/// The user function type_name.name() runs: the method the class or its nearest superclass declares,
+/// or the dispatcher .name when subclasses override it; None if the class has no such method
+pub fn method_function(ctx: &Context, type_name: &str, method: &str) -> Option<String> {
+	let types = &ctx.type_registry;
+	let mut owner = Some(type_name.to_string());
+	let declared = loop {
+		let name = owner?;
+		let function = format!("{}.{}", name, method);
+		if ctx.user_functions.contains_key(&function) {
+			break function;
+		}
+		owner = types.get_by_name(&name).and_then(|t| t.supertype.clone());
+	};
+	let dispatcher = format!(".{}", method);
+	let overridden = types
+		.instance_types(type_name)
+		.iter()
+		.any(|subtype| subtype != type_name && ctx.user_functions.contains_key(&format!("{}.{}", subtype, method)));
+	if overridden && ctx.user_functions.contains_key(&dispatcher) {
+		Some(dispatcher)
+	} else {
+		Some(declared)
+	}
+}
+
+/// Class a value is known to be an instance of before running: Rect for Rect{w: 1} or Rect(2),
+/// variables and parameters holding one, fields declaring one and methods returning one
+pub fn instance_type(ctx: &Context, node: &Node, scope: &Scope) -> Option<String> {
+	let known = |name: &str| ctx.type_registry.get_by_name(generic_base(name)).map(|t| t.name.clone());
+	match node.drop_meta() {
+		Node::Key(name, Op::Colon, fields) if matches!(fields.drop_meta(), Node::List(_, Bracket::Curly, _)) => match name.drop_meta() {
+			Node::Symbol(name) => known(name),
+			_ => None,
+		},
+		Node::Symbol(name) => known(&written_type(scope.lookup(name)?.type_node.as_ref()?)?),
+		Node::List(items, Bracket::Round, _) | Node::List(items, _, Separator::None | Separator::Space) => match items.first()?.drop_meta() {
+			Node::Symbol(name) if ctx.user_functions.contains_key(name) => known(name),
+			_ => None,
+		},
+		Node::Key(receiver, Op::Dot | Op::SafeDot, member) => {
+			let owner = instance_type(ctx, receiver, scope)?;
+			let type_def = ctx.type_registry.get_by_name(&owner)?;
+			let name = match member.drop_meta() {
+				Node::List(items, _, _) => items.first()?.drop_meta(),
+				member => member,
+			};
+			let Node::Symbol(name) = name else { return None };
+			if let Some(field) = type_def.fields.iter().find(|field| &field.name == name) {
+				return known(&field.type_name);
+			}
+			returned_type(ctx, ctx.user_functions.get(&method_function(ctx, &owner, name)?)?)
+		}
+		_ => None,
+	}
+}
+
+/// Class a user function returns instances of: the type it declares, else the one its last expression constructs
+fn returned_type(ctx: &Context, user_fn: &UserFunctionDef) -> Option<String> {
+	if let Some(type_def) = user_fn.return_type.as_deref().and_then(|t| ctx.type_registry.get_by_name(generic_base(t))) {
+		return Some(type_def.name.clone());
+	}
+	let last = match user_fn.body.drop_meta() {
+		Node::List(items, Bracket::Curly, _) => items.last()?,
+		body => body,
+	};
+	// methods calling methods are not followed, they may recurse
+	match last.drop_meta() {
+		Node::Key(_, Op::Dot | Op::SafeDot, _) => None,
+		last => instance_type(ctx, last, &Scope::new()),
+	}
+}
+
+/// Remember the class of variables assigned an instance: r in r = Rect{w: 1} calls the methods of Rect
+pub fn collect_instance_types(ctx: &Context, node: &Node, scope: &mut Scope) {
+	match node.drop_meta() {
+		Node::Key(left, op, right) => {
+			collect_instance_types(ctx, left, scope);
+			collect_instance_types(ctx, right, scope);
+			let Node::Symbol(name) = left.drop_meta() else { return };
+			if !matches!(op, Op::Assign | Op::Define) {
+				return;
+			}
+			if let Some(type_name) = instance_type(ctx, right, scope) {
+				if let Some(local) = scope.locals.get_mut(name).filter(|local| local.type_node.is_none()) {
+					local.type_node = Some(Box::new(Node::Symbol(type_name)));
+				}
+			}
+		}
+		Node::List(items, _, _) => {
+			for item in items {
+				collect_instance_types(ctx, item, scope);
+			}
 		}
 		_ => {}
 	}
 }
 
+/// The user function of a signature like (name params...) or (first<T> xs), returning return_type if given
+fn declared_function(sig_items: &[Node], return_type: Option<String>, body: &Node) -> Option<UserFunctionDef> {
+	let (name, type_params) = function_head(sig_items.first()?)?;
+	let (params, param_types) = extract_params(&sig_items[1..]);
+	Some(generic_function(name, type_params, params, param_types, return_type, Box::new(body.clone())))
+}
+
+/// Name and type parameters of a function: first and T of first<T>
+pub fn function_head(head: &Node) -> Option<(String, Vec<String>)> {
+	match head.drop_meta() {
+		Node::Symbol(name) => Some((name.clone(), vec![])),
+		Node::Key(name, Op::Colon, type_params) => match (name.drop_meta(), type_params.drop_meta()) {
+			(Node::Symbol(name), Node::List(type_params, Bracket::Less, _)) => {
+				Some((name.clone(), type_params.iter().filter_map(written_type).collect()))
+			}
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// The signature and the type it returns: f(x) and int of f(x) -> int
+pub fn split_return_type(signature: &Node) -> (&Node, Option<String>) {
+	match signature.drop_meta() {
+		Node::Key(signature, Op::Arrow, return_type) => (signature.as_ref(), written_type(return_type)),
+		_ => (signature, None),
+	}
+}
+
+/// Parameters with their defaults and the types they declare
+fn extract_params(items: &[Node]) -> (Vec<(String, Option<Node>)>, Vec<Option<String>>) {
+	items.iter().filter_map(|item| Some((extract_param(item)?, param_type(item)))).unzip()
+}
+
+/// The type a parameter declares: float in `float a` or `a: float`, List<T> in `xs: List<T>`
+fn param_type(item: &Node) -> Option<String> {
+	match item.drop_meta() {
+		Node::Key(_, Op::Colon, type_name) if !matches!(type_name.drop_meta(), Node::Text(_)) => written_type(type_name),
+		Node::List(items, _, Separator::Space) if is_typed_param(items) => Some(items[0].name()),
+		_ => None,
+	}
+}
+
+/// Whether items are the type and the name of a parameter: float a
+fn is_typed_param(items: &[Node]) -> bool {
+	matches!(items, [type_name, name] if matches!((type_name.drop_meta(), name.drop_meta()), (Node::Symbol(_), Node::Symbol(_))))
+}
+
 /// Extract parameter name and optional default value from a parameter node
 fn extract_param(item: &Node) -> Option<(String, Option<Node>)> {
 	match item.drop_meta() {
 		Node::Symbol(s) => Some((s.clone(), None)),
+		Node::List(items, _, Separator::Space) if is_typed_param(items) => Some((items[1].name(), None)),
 		Node::Key(n, Op::Colon, _) => {
 			if let Node::Symbol(s) = n.drop_meta() {
 				Some((s.clone(), None))
@@ -718,6 +2058,11 @@ fn uses_it(node: &Node) -> bool {
 	}
 }
 
+/// An argument like `it * 2` that stands for the closure `it => it * 2`
+pub fn is_it_lambda(arg: &Node) -> bool {
+	uses_it(arg) && !matches!(arg.drop_meta(), Node::Key(_, Op::FatArrow, _))
+}
+
 /// Check if a node uses $n parameter references (e.g., $0, $1)
 fn uses_dollar_param(node: &Node) -> bool {
 	let node = node.drop_meta();
@@ -739,20 +2084,14 @@ fn extract_def_function(items: &[Node]) -> Option<UserFunctionDef> {
 	// Pattern 1: def (name params...): body
 	if let Node::Key(sig, Op::Colon, body) = first {
 		if let Node::List(sig_items, _, _) = sig.drop_meta() {
-			if !sig_items.is_empty() {
-				if let Node::Symbol(name) = sig_items[0].drop_meta() {
-					let params: Vec<(String, Option<Node>)> =
-						sig_items.iter().skip(1).filter_map(extract_param).collect();
-					let return_kind = infer_function_return_kind(&params, body);
-					return Some(UserFunctionDef {
-						name: name.clone(),
-						params,
-						body: body.clone(),
-						return_kind,
-						func_index: None,
-					});
-				}
-			}
+			return declared_function(sig_items, None, body);
+		}
+	}
+
+	// Pattern 1 with a return type: def (name params...) -> type: body
+	if let Node::Key(sig, Op::Arrow, returns) = first {
+		if let (Node::List(sig_items, _, _), Node::Key(return_type, Op::Colon, body)) = (sig.drop_meta(), returns.drop_meta()) {
+			return declared_function(sig_items, written_type(return_type), body);
 		}
 	}
 
@@ -761,28 +2100,22 @@ fn extract_def_function(items: &[Node]) -> Option<UserFunctionDef> {
 		if inner_items.len() >= 2 {
 			if let Node::List(sig_items, _, _) = inner_items[0].drop_meta() {
 				if !sig_items.is_empty() {
-					if let Node::Symbol(name) = sig_items[0].drop_meta() {
-						let params: Vec<(String, Option<Node>)> = sig_items
+					if let Some((name, type_params)) = function_head(&sig_items[0]) {
+						// (a, b) lists its parameters, (float b) is one
+						let param_items: Vec<Node> = sig_items
 							.iter()
 							.skip(1)
 							.flat_map(|item| {
 								match item.drop_meta() {
-									Node::List(param_items, _, _) => {
-										param_items.iter().filter_map(extract_param).collect::<Vec<_>>()
+									Node::List(items, _, separator) if !(*separator == Separator::Space && is_typed_param(items)) => {
+										items.clone()
 									}
-									_ => extract_param(item).into_iter().collect(),
+									_ => vec![item.clone()],
 								}
 							})
 							.collect();
-						let body = inner_items[1].clone();
-						let return_kind = infer_function_return_kind(&params, &body);
-						return Some(UserFunctionDef {
-							name: name.clone(),
-							params,
-							body: Box::new(body),
-							return_kind,
-							func_index: None,
-						});
+						let (params, param_types) = extract_params(&param_items);
+						return Some(generic_function(name, type_params, params, param_types, None, Box::new(inner_items[1].clone())));
 					}
 				}
 			}
@@ -791,6 +2124,299 @@ fn extract_def_function(items: &[Node]) -> Option<UserFunctionDef> {
 	None
 }
 
+/// Extract the closures `x => body` and the user functions used as values, like `apply(grows, 3)`,
+/// with the variables they capture; closures inside a closure capture from its scope
+pub fn extract_closures(ctx: &mut Context, node: &Node, scope: &Scope) {
+	let functions: Vec<UserFunctionDef> = ctx.user_functions.values().cloned().collect();
+	for user_fn in &functions {
+		let mut function_scope = Scope::new();
+		define_params(&mut function_scope, user_fn);
+		collect_variables(&user_fn.body, &mut function_scope);
+		extract_closures_inner(ctx, &user_fn.body, &function_scope);
+	}
+	extract_closures_inner(ctx, node, scope);
+}
+
+fn extract_closures_inner(ctx: &mut Context, node: &Node, scope: &Scope) {
+	if defines_user_function(ctx, node) {
+		return; // extracted with the scope of its function
+	}
+	if let Some(((name, _, _), args)) = list_function(node, scope) {
+		if !(matches!(node.drop_meta(), Node::List(..)) && ctx.user_functions.contains_key(*name)) {
+			// map(xs, it * 2) passes the closure it => it * 2
+			for (i, arg) in args.iter().enumerate() {
+				if i > 0 && is_it_lambda(arg) {
+					add_closure(ctx, &Node::Symbol("it".to_string()), arg, scope);
+				} else {
+					extract_closures_inner(ctx, arg, scope);
+				}
+			}
+			return;
+		}
+	}
+	match node.drop_meta() {
+		Node::Key(subject, Op::Match, arms) => {
+			extract_closures_inner(ctx, subject, scope);
+			for (_, guard, value) in match_arms(arms).filter_map(match_arm) {
+				if let Some(guard) = guard {
+					extract_closures_inner(ctx, guard, scope);
+				}
+				extract_closures_inner(ctx, value, scope);
+			}
+		}
+		Node::Key(params, Op::FatArrow, body) => add_closure(ctx, params, body, scope),
+		Node::Symbol(name) if scope.lookup(name).is_none() => {
+			if let Some(user_fn) = ctx.user_functions.get(name) {
+				let (params, body) = function_closure(user_fn);
+				add_closure(ctx, &params, &body, scope);
+			}
+		}
+		Node::Key(left, _, right) => {
+			extract_closures_inner(ctx, left, scope);
+			extract_closures_inner(ctx, right, scope);
+		}
+		Node::List(items, _, _) => {
+			// The name in a call is no value
+			let is_call = matches!(items.first().map(Node::drop_meta), Some(Node::Symbol(name)) if ctx.user_functions.contains_key(name));
+			for item in items.iter().skip(is_call as usize) {
+				extract_closures_inner(ctx, item, scope);
+			}
+		}
+		_ => {}
+	}
+}
+
+fn add_closure(ctx: &mut Context, params: &Node, body: &Node, scope: &Scope) {
+	let params_names = closure_params(params);
+	let captures = closure_captures(body, &params_names, scope);
+	let key = closure_key(params, body, &captures);
+	if ctx.closures.iter().any(|closure| closure.key == key) {
+		return;
+	}
+	let closure = ClosureDef {
+		key,
+		params: params_names,
+		body: Box::new(body.clone()),
+		captures,
+		func_index: None,
+		env_type: None,
+	};
+	let (closure_scope, _) = closure_scope(&closure);
+	ctx.closures.push(closure);
+	analyze_text_functions(ctx, body, &closure_scope);
+	extract_closures_inner(ctx, body, &closure_scope);
+}
+
+/// Whether node is the definition of an extracted user function
+fn defines_user_function(ctx: &Context, node: &Node) -> bool {
+	let is_user_function = |node: &Node| match node.drop_meta() {
+		Node::Symbol(name) => ctx.user_functions.contains_key(name),
+		Node::List(items, _, _) => items.first().and_then(function_head).is_some_and(|(name, _)| ctx.user_functions.contains_key(&name)),
+		_ => false,
+	};
+	match node.drop_meta() {
+		Node::Key(left, Op::Define, _) => is_user_function(left),
+		Node::Key(left, Op::Assign, _) => {
+			let (left, _) = split_return_type(left);
+			matches!(left.drop_meta(), Node::List(..)) && is_user_function(left)
+		}
+		Node::List(items, _, _) => matches!(items.first().map(Node::drop_meta), Some(Node::Symbol(s)) if is_function_keyword(s)),
+		_ => false,
+	}
+}
+
+/// Parameter names of a closure: `x`, `(a, b)` or `()`
+pub fn closure_params(params: &Node) -> Vec<String> {
+	match params.drop_meta() {
+		Node::Empty => vec![],
+		Node::List(names, _, _) => names.iter().map(|name| name.drop_meta().to_string()).collect(),
+		name => vec![name.to_string()],
+	}
+}
+
+/// Variables of scope a closure body uses, in order of first use
+pub fn closure_captures(body: &Node, params: &[String], scope: &Scope) -> Vec<(String, Kind)> {
+	fn collect(node: &Node, params: &[String], scope: &Scope, captures: &mut Vec<(String, Kind)>) {
+		match node.drop_meta() {
+			Node::Symbol(name) => {
+				if let Some(local) = scope.lookup(name) {
+					if !params.contains(name) && !captures.iter().any(|(captured, _)| captured == name) {
+						captures.push((name.clone(), local.kind));
+					}
+				}
+			}
+			Node::Key(left, _, right) => {
+				collect(left, params, scope, captures);
+				collect(right, params, scope, captures);
+			}
+			Node::List(items, _, _) => items.iter().for_each(|item| collect(item, params, scope, captures)),
+			_ => {}
+		}
+	}
+	let mut captures = Vec::new();
+	collect(body, params, scope, &mut captures);
+	captures
+}
+
+/// Identity of a closure literal together with what it captures where it is created
+pub fn closure_key(params: &Node, body: &Node, captures: &[(String, Kind)]) -> String {
+	format!("{} => {} {:?}", params.serialize(), body.serialize(), captures)
+}
+
+/// A user function used as a value is the closure `(params) => name(params)`
+pub fn function_closure(user_fn: &UserFunctionDef) -> (Node, Node) {
+	let params: Vec<Node> = user_fn.params.iter().map(|(name, _)| Node::Symbol(name.clone())).collect();
+	let mut call = vec![Node::Symbol(user_fn.name.clone())];
+	call.extend(params.iter().cloned());
+	(Node::List(params, Bracket::Round, Separator::Colon), Node::List(call, Bracket::Round, Separator::None))
+}
+
+/// Scope of a closure function: the environment, the parameters (any node), the captured
+/// variables, then the variables of the body; returns the temp locals the body needs too
+pub fn closure_scope(closure: &ClosureDef) -> (Scope, u32) {
+	let mut scope = Scope::new();
+	scope.define("#env".to_string(), None, Kind::Data);
+	for name in &closure.params {
+		scope.define(name.clone(), None, Kind::Data);
+	}
+	for (name, kind) in &closure.captures {
+		scope.define(name.clone(), None, *kind);
+	}
+	let temp_locals = collect_variables(&closure.body, &mut scope);
+	(scope, temp_locals)
+}
+
+/// Runtime functions behind building text: the allocator, conversions and concatenation
+pub const TEXT_RUNTIME: [&str; 7] = ["alloc", "free", "int_to_text", "float_to_text", "codepoint_to_text", "to_text", "text_concat"];
+
+/// Wasp-callable text functions: result kind and the runtime functions they compile to
+pub static TEXT_FUNCTIONS: [(&str, Kind, &[&str]); 13] = [
+	("substring", Kind::Text, &["utf8_offset", "text_substring"]),
+	("slice", Kind::Text, &["utf8_offset", "utf8_count", "text_substring", "text_slice"]),
+	("repeat", Kind::Text, &["text_repeat"]),
+	("split", Kind::List, &["utf8_offset", "bytes_equal", "text_split"]),
+	("join", Kind::Text, &["text_join"]),
+	("replace", Kind::Text, &["bytes_equal", "text_replace"]),
+	("trim", Kind::Text, &["text_trim"]),
+	("upper", Kind::Text, &["codepoint_case", "text_case"]),
+	("lower", Kind::Text, &["codepoint_case", "text_case"]),
+	("starts_with", Kind::Int, &["bytes_equal", "text_starts_with"]),
+	("ends_with", Kind::Int, &["bytes_equal", "text_ends_with"]),
+	("contains", Kind::Int, &["bytes_equal", "utf8_count", "text_index_of"]),
+	("index_of", Kind::Int, &["bytes_equal", "utf8_count", "text_index_of"]),
+];
+
+/// The text function called by node: name(text, …), or text.name(…) and text.name on text, list.join(…) on lists
+pub fn text_function(node: &Node, scope: &Scope) -> Option<&'static (&'static str, Kind, &'static [&'static str])> {
+	let (receiver, call) = match node.drop_meta() {
+		Node::Key(receiver, Op::Dot, method) => (Some(receiver), method.drop_meta()),
+		call => (None, call),
+	};
+	let name = match call {
+		Node::List(items, _, _) => match items.first().map(Node::drop_meta) {
+			Some(Node::Symbol(name)) => name,
+			_ => return None,
+		},
+		Node::Symbol(name) if receiver.is_some() => name,
+		_ => return None,
+	};
+	if receiver.is_some_and(|receiver| name != "join" && infer_type(receiver, scope) != Kind::Text) {
+		return None;
+	}
+	TEXT_FUNCTIONS.iter().find(|(n, _, _)| n == name)
+}
+
+/// Wasp-callable list functions: result kind and the runtime loops they compile to
+pub static LIST_FUNCTIONS: [(&str, Kind, &[&str]); 8] = [
+	("map", Kind::List, &["list_map"]),
+	("filter", Kind::List, &["node_truthy", "list_filter"]),
+	("reduce", Kind::Data, &["list_reduce"]),
+	("sort", Kind::List, &["node_compare", "list_sort"]),
+	("any", Kind::Int, &["node_truthy", "list_any_all"]),
+	("all", Kind::Int, &["node_truthy", "list_any_all"]),
+	("zip", Kind::List, &["list_zip"]),
+	("enumerate", Kind::List, &["list_enumerate"]),
+];
+
+/// The list function called by node and its arguments, the list first:
+/// name(list, …), list.name(…) or list.name; variables of the same name take precedence
+pub fn list_function(node: &Node, scope: &Scope) -> Option<(&'static (&'static str, Kind, &'static [&'static str]), Vec<Node>)> {
+	let (name, args) = match node.drop_meta() {
+		Node::Key(receiver, Op::Dot, method) => match method.drop_meta() {
+			Node::List(items, _, _) => match items.first().map(Node::drop_meta) {
+				Some(Node::Symbol(name)) => (name, std::iter::once(receiver.as_ref()).chain(&items[1..]).cloned().collect()),
+				_ => return None,
+			},
+			Node::Symbol(name) => (name, vec![receiver.as_ref().clone()]),
+			_ => return None,
+		},
+		Node::List(items, _, Separator::None | Separator::Space) if items.len() >= 2 => match items[0].drop_meta() {
+			Node::Symbol(name) => (name, items[1..].to_vec()),
+			_ => return None,
+		},
+		_ => return None,
+	};
+	if scope.lookup(name).is_some() {
+		return None;
+	}
+	let function = LIST_FUNCTIONS.iter().find(|(n, _, _)| n == name)?;
+	// list.sort() passes no arguments
+	let args = match args.as_slice() {
+		[list, none] if matches!(none.drop_meta(), Node::Empty) => vec![list.clone()],
+		_ => args,
+	};
+	Some((function, args))
+}
+
+/// Require the text runtime where node builds, converts or slices text,
+/// and the list runtime where it maps, filters, sorts or zips
+/// Needs the variable types in scope, unlike analyze_required_functions
+pub fn analyze_text_functions(ctx: &mut Context, node: &Node, scope: &Scope) {
+	if let Some(((_, _, functions), _)) = list_function(node, scope) {
+		ctx.required_functions.extend(LOOP_RUNTIME);
+		ctx.required_functions.extend(*functions);
+	}
+	let required: Option<&[&str]> = match node.drop_meta() {
+		Node::Key(left, Op::Add, right) if infer_type(left, scope) == Kind::Text || infer_type(right, scope) == Kind::Text => Some(&[]),
+		Node::Key(left, Op::AddAssign, _) if infer_type(left, scope) == Kind::Text => Some(&[]),
+		Node::Key(value, Op::As, target) => text_cast_functions(target, value, scope),
+		Node::List(items, _, _) => match items.as_slice() {
+			[target, value] if text_cast_functions(target, value, scope).is_some() => text_cast_functions(target, value, scope),
+			_ => text_function(node, scope).map(|(_, _, functions)| *functions),
+		},
+		_ => None,
+	};
+	if let Some(functions) = required {
+		ctx.required_functions.extend(TEXT_RUNTIME);
+		ctx.required_functions.extend(functions);
+	}
+	match node.drop_meta() {
+		Node::Key(left, _, right) => {
+			analyze_text_functions(ctx, left, scope);
+			analyze_text_functions(ctx, right, scope);
+		}
+		Node::List(items, _, _) => items.iter().for_each(|item| analyze_text_functions(ctx, item, scope)),
+		Node::Type { body, .. } => analyze_text_functions(ctx, body, scope),
+		_ => {}
+	}
+}
+
+/// Runtime conversions for str(x), int(x), float(x), number(x) or x as str, if not folded at compile time
+fn text_cast_functions(target: &Node, value: &Node, scope: &Scope) -> Option<&'static [&'static str]> {
+	let Node::Symbol(target) = target.drop_meta() else { return None };
+	if matches!(value.drop_meta(), Node::Number(_) | Node::Text(_) | Node::Char(_) | Node::True | Node::False) {
+		return None;
+	}
+	let from_text = infer_type(value, scope) == Kind::Text;
+	match target.to_lowercase().as_str() {
+		"string" | "str" | "text" => Some(&[]),
+		"int" | "integer" | "i32" | "i64" | "long" if from_text => Some(&["text_to_int"]),
+		"float" | "real" | "double" | "f32" | "f64" if from_text => Some(&["text_to_float"]),
+		"number" | "num" if from_text => Some(&["text_to_int", "text_to_float", "text_to_number"]),
+		_ => None,
+	}
+}
+
 /// Analyze node tree for non-default required functions.
 /// Default functions (new_empty, new_int, new_float, new_text, new_symbol, new_codepoint, new_key, new_list)
 /// are always included and don't need to be inserted here.
@@ -817,6 +2443,15 @@ pub fn analyze_required_functions(ctx: &mut Context, node: &Node) {
 			} else if op.is_prefix() && matches!(key.drop_meta(), Node::Empty) {
 				analyze_required_functions(ctx, value);
 				return;
+			} else if *op == Op::In && !is_range_loop(value) {
+				ctx.required_functions.extend(LOOP_RUNTIME);
+			} else if *op == Op::Match {
+				ctx.required_functions.extend(MATCH_RUNTIME);
+			} else if matches!(op, Op::Dot | Op::SafeDot) && matches!(value.drop_meta(), Node::Symbol(field) if ctx.type_registry.has_field(field)) {
+				// instance fields are read like struct patterns of a match
+				ctx.required_functions.extend(MATCH_RUNTIME);
+				analyze_required_functions(ctx, key);
+				return;
 			} else if *op == Op::Hash {
 				if matches!(key.drop_meta(), Node::Empty) {
 					ctx.required_functions.insert("node_count");
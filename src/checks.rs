@@ -0,0 +1,222 @@
+//! Runs the `check` assertions of wasp files for `warp test`
+//!
+//! Every top level `check expr` is evaluated after the statements before it.
+//! The whole file runs once, each check keeping its values in locals read back at the end.
+//! For `==` and `!=` both sides are evaluated and compared like Node values,
+//! so float results compare the same way the Rust tests do.
+
+use crate::compiled::CompiledModule;
+use crate::error::{find_parse_error, WarpError};
+use crate::node::{Bracket, Node, Separator};
+use crate::operators::Op;
+use crate::wasp_parser::WaspParser;
+use serde_json::{json, Value};
+
+/// Outcome of one `check` statement
+#[derive(Clone, Debug)]
+pub struct CheckResult {
+	pub file: String,
+	pub line_nr: usize,
+	pub line: String,
+	pub passed: bool,
+	pub left: Option<Node>,
+	pub right: Option<Node>,
+	pub error: Option<String>,
+}
+
+impl CheckResult {
+	pub fn to_json(&self) -> Value {
+		json!({
+			"file": self.file,
+			"line": self.line_nr,
+			"source": self.line.trim(),
+			"passed": self.passed,
+			"left": self.left.as_ref().map(|n| n.serialize()),
+			"right": self.right.as_ref().map(|n| n.serialize()),
+			"error": self.error,
+		})
+	}
+}
+
+/// Results of all checks in a run
+#[derive(Clone, Debug, Default)]
+pub struct TestReport {
+	pub results: Vec<CheckResult>,
+}
+
+impl TestReport {
+	pub fn passed(&self) -> usize {
+		self.results.iter().filter(|r| r.passed).count()
+	}
+
+	pub fn failed(&self) -> usize {
+		self.results.len() - self.passed()
+	}
+
+	pub fn success(&self) -> bool {
+		self.failed() == 0
+	}
+
+	pub fn to_json(&self) -> String {
+		let report = json!({
+			"passed": self.passed(),
+			"failed": self.failed(),
+			"checks": self.results.iter().map(CheckResult::to_json).collect::<Vec<Value>>(),
+		});
+		serde_json::to_string_pretty(&report).unwrap_or_default()
+	}
+}
+
+/// Run every `check` statement of one source file
+/// The file runs once, so statements with side effects run once too
+pub fn run_checks(code: &str, file: &str) -> Vec<CheckResult> {
+	let root = WaspParser::parse(code);
+	if let Some(err) = find_parse_error(&root) {
+		let line_nr = err.position().map(|p| p.line_nr).unwrap_or(0);
+		return vec![failure(file, line_nr, String::new(), err)];
+	}
+	let statements = match root.drop_meta() {
+		Node::List(items, _, Separator::Newline | Separator::Semicolon) => items.clone(),
+		_ => vec![root.clone()],
+	};
+	let checks: Vec<usize> = (0..statements.len()).filter(|&i| as_check(&statements[i]).is_some()).collect();
+	match evaluate(&statements, &checks, code) {
+		Ok(values) => checks.iter().zip(values).map(|(&i, values)| check_result(file, &statements[i], Ok(values))).collect(),
+		// find the failing checks: each runs on its own after the statements before it
+		Err(_) => checks
+			.iter()
+			.map(|&i| {
+				let values = evaluate(&statements[..=i], &[i], code).map(|mut values| values.remove(0));
+				check_result(file, &statements[i], values)
+			})
+			.collect(),
+	}
+}
+
+/// Run the checks of all given wasp files
+pub fn run_check_files(paths: &[std::path::PathBuf]) -> TestReport {
+	let mut report = TestReport::default();
+	for path in paths {
+		let file = path.display().to_string();
+		match std::fs::read_to_string(path) {
+			Ok(code) => report.results.extend(run_checks(&code, &file)),
+			Err(e) => report.results.push(CheckResult {
+				file,
+				line_nr: 0,
+				line: String::new(),
+				passed: false,
+				left: None,
+				right: None,
+				error: Some(e.to_string()),
+			}),
+		}
+	}
+	report
+}
+
+/// `check expr` as (check, expr); a user function called check(…) is left alone
+fn as_check(statement: &Node) -> Option<(&Node, &Node)> {
+	match statement.drop_meta() {
+		Node::List(items, Bracket::None, _) if items.len() == 2 => match items[0].drop_meta() {
+			Node::Symbol(s) if s == "check" => Some((&items[0], &items[1])),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Run the statements with the given checks in place, reading back the values of each check
+/// Checks not given are left out
+fn evaluate(statements: &[Node], checks: &[usize], code: &str) -> Result<Vec<Vec<Node>>, WarpError> {
+	let mut program = Vec::new();
+	let mut captured: Vec<Vec<String>> = Vec::new();
+	for (i, statement) in statements.iter().enumerate() {
+		match as_check(statement) {
+			Some((_, expr)) if checks.contains(&i) => {
+				let (assignments, names) = capture(i, expr);
+				program.extend(assignments);
+				captured.push(names);
+			}
+			Some(_) => {}
+			None => program.push(statement.clone()),
+		}
+	}
+	let names: Vec<Node> = captured.iter().flatten().map(|name| Node::Symbol(name.clone())).collect();
+	let count = names.len();
+	program.push(Node::List(names, Bracket::Square, Separator::Colon));
+	let program = Node::List(program, Bracket::None, Separator::Newline);
+	let mut values = match CompiledModule::from_node(&program, code)?.run()?.drop_meta() {
+		Node::List(values, ..) if values.len() == count => values.clone(),
+		value if count == 1 => vec![value.clone()],
+		value => {
+			return Err(WarpError::Compile {
+				message: format!("cannot read back the checked values from {}", value.serialize()),
+				position: None,
+			})
+		}
+	};
+	Ok(captured.iter().map(|names| values.drain(..names.len()).collect()).collect())
+}
+
+/// Assignments keeping the values of the check in statement i, and the names they are kept under:
+/// both sides of a comparison and its outcome, else the value of expr
+/// Each side is evaluated once, comparing the kept sides
+fn capture(i: usize, expr: &Node) -> (Vec<Node>, Vec<String>) {
+	let assign = |name: &str, value: Node| Node::Key(Box::new(Node::Symbol(name.to_string())), Op::Assign, Box::new(value));
+	let outcome = format!("checked{}", i);
+	match expr.drop_meta() {
+		Node::Key(left, op, right) if op.is_comparison() => {
+			let (l, r) = (format!("checked{}_left", i), format!("checked{}_right", i));
+			let mut assignments = vec![assign(&l, left.as_ref().clone()), assign(&r, right.as_ref().clone())];
+			let mut names = vec![l.clone(), r.clone()];
+			// == and != compare like Node values, outside
+			if !matches!(op, Op::Eq | Op::Ne) {
+				let compared = Node::Key(Box::new(Node::Symbol(l)), *op, Box::new(Node::Symbol(r)));
+				assignments.push(assign(&outcome, compared));
+				names.push(outcome);
+			}
+			(assignments, names)
+		}
+		_ => (vec![assign(&outcome, expr.clone())], vec![outcome]),
+	}
+}
+
+/// Result of the check statement from its captured values
+fn check_result(file: &str, statement: &Node, values: Result<Vec<Node>, WarpError>) -> CheckResult {
+	let (check, expr) = as_check(statement).expect("a check statement");
+	let info = check.get_lineinfo().or_else(|| expr.get_lineinfo()).unwrap_or_default();
+	let outcome = values.map(|values| match (expr.drop_meta(), values.as_slice()) {
+		(Node::Key(_, op @ (Op::Eq | Op::Ne), _), [l, r]) => ((l == r) == (*op == Op::Eq), Some(l.clone()), Some(r.clone())),
+		(_, [l, r, compared]) => (truthy(compared), Some(l.clone()), Some(r.clone())),
+		(_, [value]) => (truthy(value), Some(value.clone()), None),
+		_ => (false, None, None),
+	});
+	match outcome {
+		Ok((passed, left, right)) => CheckResult {
+			file: file.to_string(),
+			line_nr: info.line_nr,
+			line: info.line,
+			passed,
+			left,
+			right,
+			error: None,
+		},
+		Err(err) => failure(file, info.line_nr, info.line, err),
+	}
+}
+
+fn truthy(node: &Node) -> bool {
+	!matches!(node.drop_meta(), Node::False | Node::Empty) && *node != 0
+}
+
+fn failure(file: &str, line_nr: usize, line: String, err: WarpError) -> CheckResult {
+	CheckResult {
+		file: file.to_string(),
+		line_nr,
+		line,
+		passed: false,
+		left: None,
+		right: None,
+		error: Some(err.to_string()),
+	}
+}
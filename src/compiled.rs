@@ -0,0 +1,293 @@
+//! Compiled wasm modules that can be run or called many times
+//!
+//! `compile` does the parse → analyze → emit work once; the resulting
+//! `CompiledModule` keeps one wasmtime Engine/Module and only instantiates per run.
+
+use crate::analyzer::check_types;
+use crate::error::{find_parse_error, WarpError};
+use crate::extensions::numbers::Number;
+use crate::gc_traits::register_gc_types_from_wasm;
+use crate::node::Node;
+use crate::type_kinds::TypeRegistry;
+use crate::util::gc_engine;
+use crate::wasm_emitter::{emit_position, uses_ffi, uses_fetch, uses_wasi, WasmGcEmitter};
+use crate::wasm_reader::{call_with_ffi, call_with_host, call_with_wasi, GcObject};
+use crate::wasp_parser::WaspParser;
+use std::cell::{Cell, RefCell};
+use std::panic::{catch_unwind, AssertUnwindSafe, PanicHookInfo};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use wasmtime::{Engine, ExternType, Instance, Linker, Module, Store, Val, ValType};
+
+/// A wasm module emitted from wasp source, ready to run
+pub struct CompiledModule {
+	bytes: Vec<u8>,
+	types: TypeRegistry,
+	functions: Vec<String>,
+	needs_host: bool,
+	needs_wasi: bool,
+	needs_ffi: bool,
+	engine: Engine,
+	module: Module,
+}
+
+/// Compile code to a reusable module without running it
+pub fn compile(code: &str) -> Result<CompiledModule, WarpError> {
+	let node = WaspParser::parse(code);
+	if let Some(err) = find_parse_error(&node) {
+		return Err(err);
+	}
+	check_types(&node)?;
+	CompiledModule::from_node(&node, code)
+}
+
+impl CompiledModule {
+	/// Emit an already parsed and checked program
+	/// `code` is only consulted to detect host, WASI and FFI usage
+	pub fn from_node(node: &Node, code: &str) -> Result<CompiledModule, WarpError> {
+		let needs_host = uses_fetch(code); // todo iterate over all used functions and check if they are a host import.
+		let needs_wasi = uses_wasi(code);
+		let needs_ffi = uses_ffi(code);
+		let emitted = catch_silently(|| {
+			let mut emitter = WasmGcEmitter::new();
+			emitter.set_host_imports(needs_host);
+			emitter.set_wasi_imports(needs_wasi);
+			emitter.emit_for_node(node);
+			let types = emitter.ctx.type_registry.clone();
+			let mut functions: Vec<String> = emitter.ctx.user_functions.keys().cloned().collect();
+			functions.sort();
+			emitter.try_finish().map(|bytes| (bytes, types, functions))
+		});
+		let (bytes, types, functions) = match emitted {
+			Ok(Ok(result)) => result,
+			Ok(Err(message)) => return Err(WarpError::Validation { message }),
+			Err(payload) => {
+				return Err(WarpError::Compile {
+					message: panic_message(payload),
+					position: emit_position().or_else(|| node.get_lineinfo()),
+				})
+			}
+		};
+
+		let engine = gc_engine();
+		let module = Module::new(&engine, &bytes).map_err(|e| WarpError::Validation {
+			message: format!("{:#}", e),
+		})?;
+		// type and field names for reading user structs back
+		let _ = register_gc_types_from_wasm(&bytes);
+		Ok(CompiledModule {
+			bytes,
+			types,
+			functions,
+			needs_host,
+			needs_wasi,
+			needs_ffi,
+			engine,
+			module,
+		})
+	}
+
+	/// A module built elsewhere, like the output of the linker
+	/// Host, WASI and FFI support follow from the module's imports, all its exported functions are callable
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<CompiledModule, WarpError> {
+		let engine = gc_engine();
+		let module = Module::new(&engine, &bytes).map_err(|e| WarpError::Validation {
+			message: format!("{:#}", e),
+		})?;
+		let imported: Vec<String> = module.imports().map(|import| import.module().to_string()).collect();
+		let needs_host = imported.iter().any(|m| m == "host");
+		let needs_wasi = imported.iter().any(|m| m == "wasi_snapshot_preview1");
+		let needs_ffi = imported.iter().any(|m| m != "host" && m != "wasi_snapshot_preview1");
+		let mut functions: Vec<String> = module
+			.exports()
+			.filter(|export| matches!(export.ty(), ExternType::Func(_)))
+			.map(|export| export.name().to_string())
+			.collect();
+		functions.sort();
+		let _ = register_gc_types_from_wasm(&bytes);
+		Ok(CompiledModule {
+			bytes,
+			types: TypeRegistry::new(),
+			functions,
+			needs_host,
+			needs_wasi,
+			needs_ffi,
+			engine,
+			module,
+		})
+	}
+
+	/// The wasm binary
+	pub fn bytes(&self) -> &[u8] {
+		&self.bytes
+	}
+
+	/// The module in wasm text format
+	pub fn wat(&self) -> String {
+		wasmprinter::print_bytes(&self.bytes).unwrap_or_else(|e| format!(";; cannot print module: {}", e))
+	}
+
+	/// User types declared by the program
+	pub fn types(&self) -> &TypeRegistry {
+		&self.types
+	}
+
+	/// Names of the exported user functions, sorted
+	pub fn functions(&self) -> &[String] {
+		&self.functions
+	}
+
+	/// Run main and read back its result
+	/// Modules with host, WASI or FFI imports get those linked into each instance
+	pub fn run(&self) -> Result<Node, WarpError> {
+		self.invoke("main", &[]).map_err(WarpError::from_runtime)
+	}
+
+	/// Call an exported user function with Int, Float, Codepoint or Bool arguments
+	/// Constructors of classes and variants like new_Circle are callable too,
+	/// their instances read back as Circle{r:…}
+	pub fn call(&self, name: &str, args: &[Node]) -> Result<Node, WarpError> {
+		let is_constructor = name
+			.strip_prefix("new_")
+			.and_then(|type_name| self.types.get_by_name(type_name))
+			.is_some_and(|type_def| type_def.variants.is_empty());
+		if !is_constructor && !self.functions.iter().any(|f| f == name) {
+			return Err(WarpError::Link {
+				message: format!("no exported function '{}'", name),
+			});
+		}
+		let param_types: Vec<ValType> = match self.module.get_export(name) {
+			Some(ExternType::Func(func)) => func.params().collect(),
+			_ => {
+				return Err(WarpError::Link {
+					message: format!("no exported function '{}'", name),
+				})
+			}
+		};
+		if param_types.len() != args.len() {
+			return Err(WarpError::Type {
+				message: format!("{} takes {} arguments, got {}", name, param_types.len(), args.len()),
+				position: None,
+			});
+		}
+		let params = args
+			.iter()
+			.zip(&param_types)
+			.map(|(arg, param)| node_to_val(arg, param))
+			.collect::<Result<Vec<Val>, WarpError>>()?;
+		self.invoke(name, &params).map_err(WarpError::from_runtime)
+	}
+
+	fn instantiate(&self) -> anyhow::Result<(Rc<RefCell<Store<()>>>, Instance)> {
+		let store = Rc::new(RefCell::new(Store::new(&self.engine, ())));
+		let linker = Linker::new(&self.engine);
+		let instance = linker.instantiate(&mut *store.borrow_mut(), &self.module)?;
+		Ok((store, instance))
+	}
+
+	/// Instantiate the cached module with the imports it needs and call an export
+	fn invoke(&self, name: &str, params: &[Val]) -> anyhow::Result<Node> {
+		if self.needs_ffi {
+			return call_with_ffi(&self.engine, &self.module, name, params);
+		}
+		if self.needs_wasi {
+			return match call_with_wasi(&self.engine, &self.module, name, params)? {
+				Val::I64(n) => Ok(Node::Number(Number::Int(n))),
+				Val::F64(bits) => Ok(Node::Number(Number::Float(f64::from_bits(bits)))),
+				other => Err(anyhow::anyhow!("cannot read {:?} returned by {}", other, name)),
+			};
+		}
+		if self.needs_host {
+			return call_with_host(&self.engine, &self.module, name, params);
+		}
+		let (store, instance) = self.instantiate()?;
+		let func = instance
+			.get_func(&mut *store.borrow_mut(), name)
+			.ok_or_else(|| anyhow::anyhow!("No {} function", name))?;
+		let mut results = vec![Val::I32(0)];
+		func.call(&mut *store.borrow_mut(), params, &mut results)?;
+		match results[0] {
+			Val::I64(n) => Ok(Node::Number(Number::Int(n))),
+			Val::F64(bits) => Ok(Node::Number(Number::Float(f64::from_bits(bits)))),
+			result => Ok(Node::from_gc_object(&GcObject::new(result, store, instance))),
+		}
+	}
+}
+
+/// Argument for a parameter of the exported function: i64 for ints, codepoints and bools, f64 for floats
+fn node_to_val(node: &Node, param: &ValType) -> Result<Val, WarpError> {
+	match (node.drop_meta(), param) {
+		(Node::Number(Number::Int(n)), ValType::I64) => Ok(Val::I64(*n)),
+		(Node::Number(Number::Int(n)), ValType::I32) => Ok(Val::I32(*n as i32)),
+		(Node::Number(Number::Int(n)), ValType::F64) => Ok(Val::F64((*n as f64).to_bits())),
+		(Node::Number(Number::Float(f)), ValType::F64) => Ok(Val::F64(f.to_bits())),
+		(Node::Char(c), ValType::I64) => Ok(Val::I64(*c as i64)),
+		(Node::True, ValType::I64) => Ok(Val::I64(1)),
+		(Node::False, ValType::I64) => Ok(Val::I64(0)),
+		(other, param) => Err(WarpError::Type {
+			message: format!("cannot pass {} as an {} argument", other.serialize(), param),
+			position: None,
+		}),
+	}
+}
+
+thread_local! {
+	static SILENT_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// Calls of catch_silently running, and the panic hook they replaced
+static QUIET_HOOK: Mutex<(usize, Option<Arc<PanicHook>>)> = Mutex::new((0, None));
+
+/// Run f, returning its panic instead of unwinding, without the panic hook printing it
+/// The previous hook stays in charge of other threads and is put back once no call runs
+pub(crate) fn catch_silently<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+	quiet_hook(true);
+	let silent = SILENT_PANICS.with(|s| s.replace(true));
+	let result = catch_unwind(AssertUnwindSafe(f));
+	SILENT_PANICS.with(|s| s.set(silent));
+	quiet_hook(false);
+	result
+}
+
+/// Install a hook skipping panics of silenced threads for the first call, restore the previous after the last
+fn quiet_hook(install: bool) {
+	let mut quiet = QUIET_HOOK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+	if install {
+		quiet.0 += 1;
+		if quiet.0 == 1 {
+			let previous = Arc::new(std::panic::take_hook());
+			let hook = previous.clone();
+			std::panic::set_hook(Box::new(move |info| {
+				if !SILENT_PANICS.with(Cell::get) {
+					(**hook)(info)
+				}
+			}));
+			quiet.1 = Some(previous);
+		}
+		return;
+	}
+	quiet.0 -= 1;
+	if quiet.0 == 0 {
+		// dropping the quiet hook leaves the previous one with a single owner
+		drop(std::panic::take_hook());
+		if let Some(previous) = quiet.1.take() {
+			match Arc::try_unwrap(previous) {
+				Ok(previous) => std::panic::set_hook(previous),
+				Err(previous) => std::panic::set_hook(Box::new(move |info| (**previous)(info))),
+			}
+		}
+	}
+}
+
+/// Message of a caught emitter panic
+pub(crate) fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+	if let Some(s) = payload.downcast_ref::<String>() {
+		s.clone()
+	} else if let Some(s) = payload.downcast_ref::<&str>() {
+		s.to_string()
+	} else {
+		"emitter panicked".to_string()
+	}
+}
@@ -9,11 +9,28 @@ use std::collections::{HashMap, HashSet};
 pub struct UserFunctionDef {
     pub name: String,
     pub params: Vec<(String, Option<Node>)>,
+    /// Types the parameters declare, like float in combine(a: float), None for untyped ones
+    pub param_types: Vec<Option<String>>,
+    /// Type parameters of a generic function, T in first<T>(xs: List<T>) -> T
+    pub type_params: Vec<String>,
+    /// Type the function declares it returns, like T after ->
+    pub return_type: Option<String>,
     pub body: Box<Node>,
     pub return_kind: Kind,
     pub func_index: Option<u32>,
 }
 
+/// Anonymous function `params => body` with the outer variables it captures by value
+#[derive(Clone, Debug)]
+pub struct ClosureDef {
+    pub key: String,
+    pub params: Vec<String>,
+    pub body: Box<Node>,
+    pub captures: Vec<(String, Kind)>,
+    pub func_index: Option<u32>,
+    pub env_type: Option<u32>,
+}
+
 /// Compilation context for WASM GC emission
 /// Contains state that tracks functions, types, variables, and strings during compilation
 /// GLOBAL module scope containing several function scopes.
@@ -28,6 +45,7 @@ pub struct Context {
     pub type_registry: TypeRegistry,
     pub user_globals: HashMap<String, (u32, Kind)>,
     pub user_functions: HashMap<String, UserFunctionDef>,
+    pub closures: Vec<ClosureDef>,
 }
 
 impl Default for Context {
@@ -58,6 +76,7 @@ impl Context {
             type_registry: TypeRegistry::new(),
             user_globals: HashMap::new(),
             user_functions: HashMap::new(),
+            closures: Vec::new(),
         }
     }
 
@@ -0,0 +1,178 @@
+//! Conversion between wasp, JSON, XML, wisp and YAML for `warp convert`
+//!
+//! Everything goes through Node. Comments and other metadata that the target
+//! cannot represent are dropped and reported, so a conversion never loses data silently.
+
+use crate::error::{find_parse_error, WarpError};
+use crate::formatter::format_node;
+use crate::meta::LineInfo;
+use crate::node::Node;
+use crate::normalize::style;
+use crate::wasp_parser::{is_interpolation, parse_xml, WaspParser};
+use crate::wisp_parser::{emit_wisp, parse_wisp};
+use crate::yaml::Anchor;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	Wasp,
+	Json,
+	Xml,
+	Wisp,
+	Yaml,
+}
+
+impl Format {
+	pub fn from_name(name: &str) -> Option<Format> {
+		match name.to_lowercase().as_str() {
+			"wasp" | "warp" => Some(Format::Wasp),
+			"json" => Some(Format::Json),
+			"xml" | "html" | "htm" => Some(Format::Xml),
+			"wisp" => Some(Format::Wisp),
+			"yaml" | "yml" => Some(Format::Yaml),
+			_ => None,
+		}
+	}
+
+	/// Format named by the file extension
+	pub fn from_path(path: &str) -> Option<Format> {
+		let extension = std::path::Path::new(path).extension()?.to_str()?;
+		Format::from_name(extension)
+	}
+
+	/// Guess the format of content without a file name
+	pub fn detect(content: &str) -> Format {
+		let trimmed = content.trim_start();
+		if trimmed.starts_with('<') {
+			Format::Xml
+		} else if (trimmed.starts_with('{') || trimmed.starts_with('[')) && serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
+			Format::Json
+		} else if trimmed.starts_with("---") || trimmed.starts_with("%YAML") {
+			Format::Yaml
+		} else if trimmed.starts_with('(') && trimmed.trim_end().ends_with(')') {
+			Format::Wisp
+		} else {
+			Format::Wasp
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Format::Wasp => "wasp",
+			Format::Json => "json",
+			Format::Xml => "xml",
+			Format::Wisp => "wisp",
+			Format::Yaml => "yaml",
+		}
+	}
+
+	fn keeps_comments(self) -> bool {
+		matches!(self, Format::Wasp | Format::Wisp)
+	}
+
+	fn keeps_meta(self) -> bool {
+		self == Format::Wisp
+	}
+}
+
+/// Converted text plus what could not be carried over
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversion {
+	pub output: String,
+	pub lost: Vec<String>,
+}
+
+/// Parse content in the given format
+pub fn read(content: &str, format: Format) -> Result<Node, WarpError> {
+	let node = match format {
+		Format::Wasp => WaspParser::parse(content),
+		Format::Json => Node::from_json(content).map_err(|e| WarpError::Parse {
+			message: format!("invalid json: {}", e),
+			position: Some(LineInfo { line_nr: e.line(), column: e.column(), ..Default::default() }),
+		})?,
+		Format::Xml => parse_xml(content),
+		Format::Wisp => parse_wisp(content),
+		Format::Yaml => Node::from_yaml(content)?,
+	};
+	match find_parse_error(&node) {
+		Some(err) => Err(err),
+		None => Ok(node),
+	}
+}
+
+/// Print a node in the given format, ending with a newline
+pub fn write(node: &Node, format: Format) -> Result<String, WarpError> {
+	let mut output = match format {
+		Format::Wasp => format_node(node, &style()),
+		Format::Json => node.to_json().map_err(|e| WarpError::Compile {
+			message: format!("cannot write json: {}", e),
+			position: None,
+		})?,
+		Format::Xml => node.to_xml(),
+		Format::Wisp => emit_wisp(node),
+		Format::Yaml => node.to_yaml(),
+	};
+	if !output.ends_with('\n') {
+		output.push('\n');
+	}
+	Ok(output)
+}
+
+/// Convert content between formats, dropping and reporting what the target cannot hold
+pub fn convert(content: &str, from: Format, to: Format) -> Result<Conversion, WarpError> {
+	let node = read(content, from)?;
+	let mut lost = Vec::new();
+	let node = strip(&node, to, &mut lost);
+	Ok(Conversion { output: write(&node, to)?, lost })
+}
+
+/// Remove source positions, and comments or metadata the target has no place for
+fn strip(node: &Node, to: Format, lost: &mut Vec<String>) -> Node {
+	match node {
+		Node::Meta { node: inner, data } => {
+			let inner = strip(inner, to, lost);
+			if is_position(data) || is_interpolation(node) {
+				return inner;
+			}
+			// YAML anchors: other formats get the aliases as plain copies
+			if is_anchor(data) {
+				return if to == Format::Yaml { Node::Meta { node: Box::new(inner), data: data.clone() } } else { inner };
+			}
+			let comment = comment_text(data);
+			let kept = if comment.is_some() { to.keeps_comments() } else { to.keeps_meta() };
+			if kept {
+				return Node::Meta { node: Box::new(inner), data: data.clone() };
+			}
+			let at = match node.get_lineinfo() {
+				Some(LineInfo { line_nr, .. }) => format!("line {}: ", line_nr),
+				None => String::new(),
+			};
+			lost.push(match comment {
+				Some(text) => format!("{}comment '{}' has no equivalent in {}", at, text, to.name()),
+				None => format!("{}metadata {} has no equivalent in {}", at, data.serialize(), to.name()),
+			});
+			inner
+		}
+		Node::List(items, bracket, separator) => Node::List(items.iter().map(|item| strip(item, to, lost)).collect(), bracket.clone(), separator.clone()),
+		Node::Key(left, op, right) => Node::Key(Box::new(strip(left, to, lost)), *op, Box::new(strip(right, to, lost))),
+		Node::Type { name, body } => Node::Type { name: name.clone(), body: Box::new(strip(body, to, lost)) },
+		other => other.clone(),
+	}
+}
+
+fn is_position(data: &Node) -> bool {
+	matches!(data, Node::Data(dada) if dada.downcast_ref::<LineInfo>().is_some())
+}
+
+fn is_anchor(data: &Node) -> bool {
+	matches!(data, Node::Data(dada) if dada.downcast_ref::<Anchor>().is_some())
+}
+
+fn comment_text(data: &Node) -> Option<String> {
+	match data {
+		Node::Key(key, _, value) if matches!(key.drop_meta(), Node::Symbol(k) if k == "comment") => match value.drop_meta() {
+			Node::Text(text) => Some(text.clone()),
+			_ => None,
+		},
+		_ => None,
+	}
+}
@@ -0,0 +1,463 @@
+//! Lossless concrete syntax tree for editing wasp files in place
+//!
+//! Every byte of the source belongs to exactly one token, whitespace and comments included,
+//! so printing the tree gives back the input unchanged. `Cst::set` rewrites the tokens of
+//! one value and leaves every other line byte-for-byte identical.
+
+use crate::error::WarpError;
+use crate::formatter::quote_text;
+use crate::node::Node;
+use crate::wasp_parser::WaspParser;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+	Space,
+	Newline,
+	Comment,
+	Text,
+	Number,
+	Symbol,
+	Operator,
+	Separator,
+	Open,
+	Close,
+}
+
+/// Byte span of one token in the source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+	pub kind: TokenKind,
+	pub span: Range<usize>,
+}
+
+/// A token, or a bracketed group; close is None for an unclosed bracket
+#[derive(Clone, Debug, PartialEq)]
+pub enum Element {
+	Token(Token),
+	Group { open: Token, children: Vec<Element>, close: Option<Token> },
+}
+
+impl Element {
+	pub fn span(&self) -> Range<usize> {
+		match self {
+			Element::Token(token) => token.span.clone(),
+			Element::Group { open, children, close } => {
+				let end = match (close, children.last()) {
+					(Some(close), _) => close.span.end,
+					(None, Some(last)) => last.span().end,
+					(None, None) => open.span.end,
+				};
+				open.span.start..end
+			}
+		}
+	}
+
+	fn kind(&self) -> Option<TokenKind> {
+		match self {
+			Element::Token(token) => Some(token.kind),
+			Element::Group { .. } => None,
+		}
+	}
+
+	fn write_to(&self, source: &str, out: &mut String) {
+		match self {
+			Element::Token(token) => out.push_str(&source[token.span.clone()]),
+			Element::Group { open, children, close } => {
+				out.push_str(&source[open.span.clone()]);
+				for child in children {
+					child.write_to(source, out);
+				}
+				if let Some(close) = close {
+					out.push_str(&source[close.span.clone()]);
+				}
+			}
+		}
+	}
+}
+
+/// `key: value` or `key = value` found in a list of elements
+struct Entry {
+	key: String,
+	key_at: usize,
+	/// Bytes between key and value, like ": " or " = "
+	assign: Range<usize>,
+	/// Element indices of the value, without surrounding spaces
+	value: Range<usize>,
+	/// Value is an indented block on the following lines
+	block: bool,
+}
+
+/// Source text together with its lossless token tree
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cst {
+	source: String,
+	elements: Vec<Element>,
+}
+
+impl Cst {
+	pub fn parse(source: &str) -> Cst {
+		let tokens = tokenize(source);
+		let mut tokens = tokens.into_iter().peekable();
+		let elements = group(&mut tokens, false);
+		Cst { source: source.to_string(), elements }
+	}
+
+	pub fn elements(&self) -> &[Element] {
+		&self.elements
+	}
+
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+
+	/// The document as Node, through the regular parser
+	pub fn to_node(&self) -> Node {
+		WaspParser::parse(&self.source)
+	}
+
+	/// Source text of the value at a dotted key path like `server.port`
+	pub fn raw(&self, path: &str) -> Option<&str> {
+		let (elements, entry) = self.find(path).ok()??;
+		Some(&self.source[value_span(elements, &entry)])
+	}
+
+	/// Parsed value at a dotted key path
+	pub fn get(&self, path: &str) -> Option<Node> {
+		self.raw(path).map(WaspParser::parse)
+	}
+
+	/// Replace the value at path, keeping its quote style, or add the key to its parent
+	/// Only the changed value (or the inserted line) differs in the output
+	pub fn set(&mut self, path: &str, value: &Node) -> Result<(), WarpError> {
+		let (edit, text) = match self.find(path)? {
+			Some((elements, entry)) => {
+				let span = value_span(elements, &entry);
+				let text = value_text(value, Some(&self.source[span.clone()]));
+				(span, text)
+			}
+			None => self.insertion(path, value)?,
+		};
+		let mut source = self.source.clone();
+		source.replace_range(edit, &text);
+		*self = Cst::parse(&source);
+		Ok(())
+	}
+
+	/// Walk the path; Ok(None) if only the last key is missing
+	fn find(&self, path: &str) -> Result<Option<(&[Element], Entry)>, WarpError> {
+		let keys: Vec<&str> = path.split('.').collect();
+		let mut elements: &[Element] = &self.elements;
+		for (i, key) in keys.iter().enumerate() {
+			let Some(entry) = entries(elements, &self.source).into_iter().find(|e| e.key == *key) else {
+				if i + 1 == keys.len() {
+					return Ok(None);
+				}
+				return Err(edit_error(format!("no key '{}' in {}", key, path)));
+			};
+			if i + 1 == keys.len() {
+				return Ok(Some((elements, entry)));
+			}
+			elements = match &elements[entry.value.clone()] {
+				inner if entry.block => inner,
+				[Element::Group { children, .. }] => children,
+				_ => return Err(edit_error(format!("'{}' in {} is not an object", key, path))),
+			};
+		}
+		Ok(None)
+	}
+
+	/// Where and what to insert for a new last key of path
+	fn insertion(&self, path: &str, value: &Node) -> Result<(Range<usize>, String), WarpError> {
+		let (parent, key) = match path.rsplit_once('.') {
+			Some((parent, key)) => (Some(parent), key),
+			None => (None, path),
+		};
+		let (elements, group) = match parent {
+			None => (&self.elements[..], None),
+			Some(parent) => match self.find(parent)? {
+				Some((elements, entry)) if entry.block => (&elements[entry.value.clone()], None),
+				Some((elements, entry)) => match &elements[entry.value.clone()] {
+					[group @ Element::Group { children, .. }] => (&children[..], Some(group)),
+					_ => return Err(edit_error(format!("'{}' is not an object", parent))),
+				},
+				None => return Err(edit_error(format!("no key '{}'", parent))),
+			},
+		};
+		let found = entries(elements, &self.source);
+		let value = value_text(value, None);
+		let Some(last) = found.last() else {
+			return Ok(match group {
+				Some(group) => {
+					let at = group.span().start + 1;
+					(at..at, format!("{}: {}", key, value))
+				}
+				None if self.source.is_empty() || self.source.ends_with('\n') => {
+					(self.source.len()..self.source.len(), format!("{}: {}\n", key, value))
+				}
+				None => (self.source.len()..self.source.len(), format!("\n{}: {}", key, value)),
+			});
+		};
+		let assign = &self.source[last.assign.clone()];
+		let end = value_span(elements, last).end;
+		let multiline = match group {
+			Some(group) => self.source[group.span()].contains('\n'),
+			None => true,
+		};
+		if multiline {
+			// new line after the last entry, its trailing comment included
+			let at = self.source[end..].find('\n').map(|i| end + i).unwrap_or(self.source.len());
+			let indent = &self.source[line_start(&self.source, last.key_at)..last.key_at];
+			Ok((at..at, format!("\n{}{}{}{}", indent, key, assign, value)))
+		} else {
+			let comma = elements.iter().any(|e| e.kind() == Some(TokenKind::Separator));
+			let separator = if comma { ", " } else { " " };
+			Ok((end..end, format!("{}{}{}{}", separator, key, assign, value)))
+		}
+	}
+}
+
+impl fmt::Display for Cst {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut out = String::with_capacity(self.source.len());
+		for element in &self.elements {
+			element.write_to(&self.source, &mut out);
+		}
+		write!(f, "{}", out)
+	}
+}
+
+fn edit_error(message: String) -> WarpError {
+	WarpError::Parse { message, position: None }
+}
+
+fn value_span(elements: &[Element], entry: &Entry) -> Range<usize> {
+	let values = &elements[entry.value.clone()];
+	match (values.first(), values.last()) {
+		(Some(first), Some(last)) => first.span().start..last.span().end,
+		_ => {
+			let at = entry.assign.end;
+			at..at
+		}
+	}
+}
+
+/// New source for a value; text keeps the quote of the value it replaces
+fn value_text(value: &Node, old: Option<&str>) -> String {
+	match value.drop_meta() {
+		Node::Text(text) => {
+			let quote = match old.and_then(|old| old.chars().next()) {
+				Some('\'') => '\'',
+				_ => '"',
+			};
+			quote_text(text, quote)
+		}
+		other => other.serialize(),
+	}
+}
+
+fn line_start(source: &str, at: usize) -> usize {
+	source[..at].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+/// All entries of one level; values in indented blocks are skipped over, not searched
+fn entries(elements: &[Element], source: &str) -> Vec<Entry> {
+	let mut found = Vec::new();
+	let mut i = 0;
+	while i < elements.len() {
+		let Some(op) = key_operator(elements, i, source) else {
+			i += 1;
+			continue;
+		};
+		let key_at = elements[i].span().start;
+		let key = key_name(&source[elements[i].span()]);
+		let start = skip(elements, op + 1, &[TokenKind::Space]);
+		let mut end = start;
+		while end < elements.len() && !ends_value(elements, end, source) {
+			end += 1;
+		}
+		let mut value = start..trim_end(elements, start, end);
+		let key_end = elements[i].span().end;
+		let assign = if value.is_empty() { key_end..elements[op].span().end } else { key_end..elements[start].span().start };
+		let mut block = false;
+		if value.is_empty() {
+			let newline = skip(elements, end, &[TokenKind::Space, TokenKind::Comment]);
+			if elements.get(newline).and_then(Element::kind) == Some(TokenKind::Newline) {
+				let indent = key_at - line_start(source, key_at);
+				let block_end = block_end(elements, newline + 1, indent, source);
+				let block_start = skip(elements, newline + 1, &[TokenKind::Space, TokenKind::Newline]);
+				if block_start < block_end {
+					value = block_start..trim_end(elements, block_start, block_end);
+					end = block_end;
+					block = true;
+				}
+			}
+		}
+		found.push(Entry { key, key_at, assign, value, block });
+		i = end.max(i + 1);
+	}
+	found
+}
+
+/// Index of the `:` or `=` if a key starts at i
+fn key_operator(elements: &[Element], i: usize, source: &str) -> Option<usize> {
+	match elements[i].kind() {
+		Some(TokenKind::Symbol | TokenKind::Text) => {}
+		_ => return None,
+	}
+	let op = skip(elements, i + 1, &[TokenKind::Space]);
+	let element = elements.get(op)?;
+	let text = &source[element.span()];
+	(element.kind() == Some(TokenKind::Operator) && (text == ":" || text == "=")).then_some(op)
+}
+
+fn key_name(text: &str) -> String {
+	let quoted = text.len() >= 2 && (text.starts_with('"') || text.starts_with('\'')) && text.ends_with(&text[..1]);
+	if quoted { text[1..text.len() - 1].to_string() } else { text.to_string() }
+}
+
+/// Values end at separators, line ends, comments, closing brackets and before the next key
+fn ends_value(elements: &[Element], i: usize, source: &str) -> bool {
+	match elements[i].kind() {
+		Some(TokenKind::Newline | TokenKind::Comment | TokenKind::Separator | TokenKind::Close) => true,
+		Some(TokenKind::Space) => {
+			let next = skip(elements, i, &[TokenKind::Space]);
+			next < elements.len() && key_operator(elements, next, source).is_some()
+		}
+		_ => false,
+	}
+}
+
+/// First index from i whose element is not one of kinds
+fn skip(elements: &[Element], mut i: usize, kinds: &[TokenKind]) -> usize {
+	while let Some(kind) = elements.get(i).and_then(Element::kind) {
+		if !kinds.contains(&kind) {
+			break;
+		}
+		i += 1;
+	}
+	i
+}
+
+fn trim_end(elements: &[Element], start: usize, mut end: usize) -> usize {
+	while end > start && matches!(elements[end - 1].kind(), Some(TokenKind::Space | TokenKind::Newline)) {
+		end -= 1;
+	}
+	end
+}
+
+/// Index of the first line after from that is indented no deeper than indent
+fn block_end(elements: &[Element], from: usize, indent: usize, source: &str) -> usize {
+	let mut i = from;
+	while i < elements.len() {
+		let line_at = elements[i].span().start;
+		let text = &source[line_at..];
+		let content = text.trim_start_matches([' ', '\t']);
+		let blank = content.is_empty() || content.starts_with('\n') || content.starts_with("\r\n");
+		if !blank && text.len() - content.len() <= indent {
+			return i;
+		}
+		// next line
+		while i < elements.len() && elements[i].kind() != Some(TokenKind::Newline) {
+			i += 1;
+		}
+		i += 1;
+	}
+	elements.len()
+}
+
+/// Nest tokens into bracket groups; stray closers stay plain tokens
+fn group(tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>, nested: bool) -> Vec<Element> {
+	let mut elements = Vec::new();
+	// a closer ends the innermost group, even a mismatched one
+	while let Some(token) = tokens.next_if(|t| !(nested && t.kind == TokenKind::Close)) {
+		match token.kind {
+			TokenKind::Open => {
+				let children = group(tokens, true);
+				let close = match tokens.peek() {
+					Some(next) if next.kind == TokenKind::Close => tokens.next(),
+					_ => None,
+				};
+				elements.push(Element::Group { open: token, children, close });
+			}
+			_ => elements.push(Element::Token(token)),
+		}
+	}
+	elements
+}
+
+/// Split source into tokens that together cover every byte
+pub fn tokenize(source: &str) -> Vec<Token> {
+	let chars: Vec<(usize, char)> = source.char_indices().collect();
+	let at = |i: usize| chars.get(i).map(|(_, c)| *c).unwrap_or('\0');
+	let byte = |i: usize| chars.get(i).map(|(b, _)| *b).unwrap_or(source.len());
+	let mut tokens: Vec<Token> = Vec::new();
+	let mut i = 0;
+	let mut line_start = true;
+	while i < chars.len() {
+		let start = i;
+		let c = at(i);
+		let kind = if c == '\n' {
+			i += 1;
+			TokenKind::Newline
+		} else if c.is_whitespace() {
+			while i < chars.len() && at(i).is_whitespace() && at(i) != '\n' {
+				i += 1;
+			}
+			TokenKind::Space
+		} else if (c == '#' && line_start) || (c == '/' && at(i + 1) == '/' && (i == 0 || at(i - 1) != ':')) {
+			while i < chars.len() && at(i) != '\n' {
+				i += 1;
+			}
+			TokenKind::Comment
+		} else if c == '/' && at(i + 1) == '*' {
+			i += 2;
+			while i < chars.len() && !(at(i) == '*' && at(i + 1) == '/') {
+				i += 1;
+			}
+			i = (i + 2).min(chars.len());
+			TokenKind::Comment
+		} else if c == '"' || c == '\'' {
+			i += 1;
+			while i < chars.len() && at(i) != c {
+				i += if at(i) == '\\' { 2 } else { 1 };
+			}
+			i = (i + 1).min(chars.len());
+			TokenKind::Text
+		} else if c.is_ascii_digit() {
+			while i < chars.len() && (at(i).is_alphanumeric() || at(i) == '.' || at(i) == '_') {
+				i += 1;
+			}
+			TokenKind::Number
+		} else if c.is_alphabetic() || c == '_' || c == '$' {
+			while i < chars.len() && (at(i).is_alphanumeric() || at(i) == '_' || at(i) == '$') {
+				i += 1;
+			}
+			TokenKind::Symbol
+		} else if matches!(c, '(' | '[' | '{') {
+			i += 1;
+			TokenKind::Open
+		} else if matches!(c, ')' | ']' | '}') {
+			i += 1;
+			TokenKind::Close
+		} else if c == ',' || c == ';' {
+			i += 1;
+			TokenKind::Separator
+		} else {
+			i += 1;
+			// operator runs like := or ->, but a=-1 is = then -, and comments stay apart
+			while i < chars.len()
+				&& "=:<>!&|+-*/%^?.@~".contains(at(i))
+				&& !(at(i) == '/' && matches!(at(i + 1), '/' | '*'))
+				&& !(matches!(at(i - 1), ':' | '=') && !matches!(at(i), '=' | '>'))
+			{
+				i += 1;
+			}
+			TokenKind::Operator
+		};
+		line_start = kind == TokenKind::Newline || (kind == TokenKind::Space && line_start);
+		tokens.push(Token { kind, span: byte(start)..byte(i) });
+	}
+	tokens
+}
@@ -0,0 +1,170 @@
+//! Structured errors for the parse → analyze → emit → run pipeline
+
+use crate::meta::LineInfo;
+use crate::node::Node;
+use std::fmt;
+
+/// Why evaluating a program failed, with the source position where known
+#[derive(Clone, Debug, PartialEq)]
+pub enum WarpError {
+	/// Syntax the parser could not make sense of
+	Parse { message: String, position: Option<LineInfo> },
+	/// Type mismatch reported by the analyzer
+	Type { message: String, position: Option<LineInfo> },
+	/// The emitter could not lower the program (unknown function, undefined variable, …)
+	Compile { message: String, position: Option<LineInfo> },
+	/// The emitted module was rejected by the wasm validator
+	Validation { message: String },
+	/// The module could not be instantiated: missing imports, no main, …
+	Link { message: String },
+	/// The program trapped while running
+	Trap { message: String },
+}
+
+impl WarpError {
+	pub fn message(&self) -> &str {
+		match self {
+			WarpError::Parse { message, .. }
+			| WarpError::Type { message, .. }
+			| WarpError::Compile { message, .. }
+			| WarpError::Validation { message }
+			| WarpError::Link { message }
+			| WarpError::Trap { message } => message,
+		}
+	}
+
+	pub fn position(&self) -> Option<&LineInfo> {
+		match self {
+			WarpError::Parse { position, .. }
+			| WarpError::Type { position, .. }
+			| WarpError::Compile { position, .. } => position.as_ref(),
+			_ => None,
+		}
+	}
+
+	fn kind_name(&self) -> &'static str {
+		match self {
+			WarpError::Parse { .. } => "parse error",
+			WarpError::Type { .. } => "type error",
+			WarpError::Compile { .. } => "compile error",
+			WarpError::Validation { .. } => "wasm validation error",
+			WarpError::Link { .. } => "link error",
+			WarpError::Trap { .. } => "runtime trap",
+		}
+	}
+
+	/// Classify a wasmtime failure: traps during execution vs instantiation problems
+	pub fn from_runtime(e: anyhow::Error) -> Self {
+		let message = format!("{:#}", e);
+		if e.downcast_ref::<wasmtime::Trap>().is_some() || message.contains("wasm trap") {
+			WarpError::Trap { message }
+		} else {
+			WarpError::Link { message }
+		}
+	}
+}
+
+impl fmt::Display for WarpError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.position() {
+			Some(pos) => write!(
+				f,
+				"{} at line {}, column {}: {}",
+				self.kind_name(),
+				pos.line_nr,
+				pos.column,
+				self.message()
+			),
+			None => write!(f, "{}: {}", self.kind_name(), self.message()),
+		}
+	}
+}
+
+impl std::error::Error for WarpError {}
+
+/// Whether a syntax problem stops strict parsing or was repaired by the generous parser
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+/// One syntax problem with its byte span in the source and 1-based line/column
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub message: String,
+	pub span: std::ops::Range<usize>,
+	pub line_nr: usize,
+	pub column: usize,
+}
+
+impl Diagnostic {
+	/// As a parse error; the LineInfo carries no line text
+	pub fn to_error(&self) -> WarpError {
+		WarpError::Parse {
+			message: self.message.clone(),
+			position: Some(LineInfo {
+				line_nr: self.line_nr,
+				column: self.column,
+				line: String::new(),
+			}),
+		}
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let kind = match self.severity {
+			Severity::Error => "error",
+			Severity::Warning => "warning",
+		};
+		write!(f, "{} at line {}, column {}: {}", kind, self.line_nr, self.column, self.message)
+	}
+}
+
+/// Text of an Error node, without the surrounding Error/Meta wrappers
+pub fn error_message(node: &Node) -> String {
+	match node.drop_meta() {
+		Node::Error(inner) => error_message(inner),
+		Node::Text(s) | Node::Symbol(s) => s.clone(),
+		other => other.serialize(),
+	}
+}
+
+/// Find the first Error node the parser left in the tree
+pub fn find_parse_error(node: &Node) -> Option<WarpError> {
+	collect_parse_errors(node).into_iter().next()
+}
+
+/// All Error nodes the parser left in the tree, in source order
+/// The position comes from the innermost Meta wrapper carrying a LineInfo
+pub fn collect_parse_errors(node: &Node) -> Vec<WarpError> {
+	let mut errors = Vec::new();
+	collect_errors_inner(node, None, &mut errors);
+	errors
+}
+
+fn collect_errors_inner(node: &Node, position: Option<LineInfo>, errors: &mut Vec<WarpError>) {
+	match node {
+		Node::Meta { node: inner, .. } => {
+			let position = node.get_lineinfo().or(position);
+			collect_errors_inner(inner, position, errors)
+		}
+		Node::Error(_) => errors.push(WarpError::Parse {
+			message: error_message(node),
+			position,
+		}),
+		Node::Key(left, _, right) => {
+			collect_errors_inner(left, position.clone(), errors);
+			collect_errors_inner(right, position, errors)
+		}
+		Node::List(items, _, _) => {
+			for item in items {
+				collect_errors_inner(item, position.clone(), errors);
+			}
+		}
+		Node::Type { body, .. } => collect_errors_inner(body, position, errors),
+		_ => {}
+	}
+}
@@ -0,0 +1,996 @@
+//! Canonical source formatter
+//!
+//! Rewrites wasp source into the dialect configured by `normalize::Style`:
+//! casts, function and variable definitions, logical and power operators,
+//! quotes, indexing and conditionals. Comments are scanned from the source text
+//! and printed as `//` lines above the statement that follows them, after the
+//! statement they trailed, or before the brace that closes their block.
+
+use crate::analyzer::{match_arm, match_arms};
+use crate::error::{find_parse_error, WarpError};
+use crate::extensions::numbers::Number;
+use crate::node::{Bracket, Node, Separator};
+use crate::normalize::{
+	style, CastStyle, ConditionalStyle, FunctionStyle, IndexStyle, LogicalStyle, PowerStyle, QuoteStyle, Style,
+	VarStyle,
+};
+use crate::operators::{is_function_keyword, Op};
+use crate::type_kinds::enum_variants;
+use crate::wasp_parser::WaspParser;
+use std::cell::RefCell;
+
+/// Type names accepted as constructor-style casts: int(x)
+const CAST_TYPES: [&str; 8] = ["int", "float", "str", "string", "String", "char", "bool", "number"];
+
+/// Format code in the globally configured style
+pub fn format_source(code: &str) -> Result<String, WarpError> {
+	format_with_style(code, &style())
+}
+
+/// Format code in the given style; refuses code with parse errors instead of mangling it
+pub fn format_with_style(code: &str, style: &Style) -> Result<String, WarpError> {
+	let node = WaspParser::parse(code);
+	if let Some(err) = find_parse_error(&node) {
+		return Err(err);
+	}
+	let comments = source_comments(code);
+	let formatter = Formatter {
+		style,
+		source: code.lines().collect(),
+		used: RefCell::new(vec![false; comments.len()]),
+		comments,
+	};
+	let mut formatted = formatter.statements(&node, 0);
+	if !formatted.is_empty() {
+		formatted.push('\n');
+	}
+	Ok(formatted)
+}
+
+/// Format an already parsed node; without the source, blank lines are not kept
+/// and only comments attached via `Node::with_comment` are printed
+pub fn format_node(node: &Node, style: &Style) -> String {
+	Formatter { style, source: Vec::new(), comments: Vec::new(), used: RefCell::new(Vec::new()) }.statements(node, 0)
+}
+
+/// A comment token of the source
+struct SourceComment {
+	line_nr: usize,
+	text: String,
+	/// code precedes it on its line: `x = 1 // why`
+	trailing: bool,
+	/// byte offset of the bracket it directly precedes, for comments at the end of a block
+	closes: Option<usize>,
+}
+
+/// Comments of the source, skipping quoted strings; `//` right after `:` belongs to a url
+fn source_comments(code: &str) -> Vec<SourceComment> {
+	let chars: Vec<(usize, char)> = code.char_indices().collect();
+	let at = |i: usize| chars.get(i).map(|(_, c)| *c).unwrap_or('\0');
+	let byte = |i: usize| chars.get(i).map(|(b, _)| *b).unwrap_or(code.len());
+	let mut comments = Vec::new();
+	let mut line_nr = 1;
+	let mut line_start = true;
+	let mut trailing = false;
+	let mut i = 0;
+	while i < chars.len() {
+		let c = at(i);
+		if c == '\n' {
+			line_nr += 1;
+			line_start = true;
+			trailing = false;
+			i += 1;
+			continue;
+		}
+		if c.is_whitespace() {
+			i += 1;
+			continue;
+		}
+		let block = c == '/' && at(i + 1) == '*';
+		let line = (c == '#' && line_start) || (c == '/' && at(i + 1) == '/' && (i == 0 || at(i - 1) != ':'));
+		line_start = false;
+		if !block && !line {
+			if c == '"' || c == '\'' {
+				i += 1;
+				while i < chars.len() && at(i) != c {
+					i += if at(i) == '\\' { 2 } else { 1 };
+				}
+			}
+			i += 1;
+			trailing = true;
+			continue;
+		}
+		let start = i;
+		if block {
+			i += 2;
+			while i < chars.len() && !(at(i) == '*' && at(i + 1) == '/') {
+				i += 1;
+			}
+			i = (i + 2).min(chars.len());
+		} else {
+			while i < chars.len() && at(i) != '\n' {
+				i += 1;
+			}
+		}
+		let raw = &code[byte(start)..byte(i)];
+		let text = match raw.strip_prefix("/*") {
+			Some(rest) => rest.strip_suffix("*/").unwrap_or(rest),
+			None => raw.strip_prefix("//").or_else(|| raw.strip_prefix('#')).unwrap_or(raw),
+		};
+		comments.push(SourceComment {
+			line_nr,
+			text: text.trim().to_string(),
+			trailing,
+			closes: closing_bracket(code, byte(i)),
+		});
+		line_nr += raw.matches('\n').count();
+		trailing = trailing || block;
+	}
+	comments
+}
+
+/// Byte offset of the closing bracket that comes next, past whitespace and further comments
+fn closing_bracket(code: &str, mut at: usize) -> Option<usize> {
+	loop {
+		let rest = &code[at..];
+		let trimmed = rest.trim_start();
+		at += rest.len() - trimmed.len();
+		if trimmed.starts_with("/*") {
+			at += trimmed.find("*/").map(|end| end + 2).unwrap_or(trimmed.len());
+		} else if trimmed.starts_with("//") || trimmed.starts_with('#') {
+			at += trimmed.find('\n').unwrap_or(trimmed.len());
+		} else {
+			return trimmed.starts_with([')', ']', '}']).then_some(at);
+		}
+	}
+}
+
+fn line_of(node: &Node) -> usize {
+	node.get_lineinfo().map(|info| info.line_nr).unwrap_or(0)
+}
+
+struct Formatter<'a> {
+	style: &'a Style,
+	source: Vec<&'a str>,
+	comments: Vec<SourceComment>,
+	/// comments already printed
+	used: RefCell<Vec<bool>>,
+}
+
+impl Formatter<'_> {
+	/// One statement per line: newline and semicolon lists at statement level are split up
+	fn statements(&self, node: &Node, depth: usize) -> String {
+		let mut statements = Vec::new();
+		flatten_statements(node, &mut statements);
+		self.statement_lines(&statements, depth)
+	}
+
+	fn statement_lines(&self, statements: &[&Node], depth: usize) -> String {
+		let indent = "\t".repeat(depth);
+		let mut lines = Vec::new();
+		let mut previous_line = 0;
+		for (i, statement) in statements.iter().enumerate() {
+			let line_nr = line_of(statement);
+			let leading = self.take_comments(|c| line_nr > 0 && !c.trailing && c.line_nr <= line_nr);
+			let first_line = leading.first().map_or(line_nr, |c| c.line_nr);
+			if !lines.is_empty() && line_nr > previous_line && self.blank_line_before(first_line) {
+				lines.push(String::new());
+			}
+			previous_line = previous_line.max(line_nr);
+			self.comment_lines(&leading, &indent, &mut lines);
+			// with several statements on one line, the comment goes after the last
+			let shares_line = statements.get(i + 1).is_some_and(|next| line_of(next) == line_nr);
+			let trailing = self.take_comments(|c| line_nr > 0 && !shares_line && c.trailing && c.line_nr == line_nr);
+			let mut line = self.statement(statement, depth);
+			if !trailing.is_empty() {
+				let texts: Vec<&str> = trailing.iter().map(|c| c.text.as_str()).collect();
+				line.push(' ');
+				line.push_str(&line_comment(&texts.join(" ")));
+			}
+			lines.push(line);
+		}
+		let tail = if depth == 0 { self.take_comments(|_| true) } else { self.block_end_comments(previous_line) };
+		if let Some(first) = tail.first() {
+			if !lines.is_empty() && self.blank_line_before(first.line_nr) {
+				lines.push(String::new());
+			}
+			self.comment_lines(&tail, &indent, &mut lines);
+		}
+		lines.join("\n")
+	}
+
+	/// Unprinted source comments matching the filter, marked as printed
+	fn take_comments(&self, filter: impl Fn(&SourceComment) -> bool) -> Vec<&SourceComment> {
+		let mut used = self.used.borrow_mut();
+		let mut taken = Vec::new();
+		for (index, comment) in self.comments.iter().enumerate() {
+			if !used[index] && filter(comment) {
+				used[index] = true;
+				taken.push(comment);
+			}
+		}
+		taken
+	}
+
+	/// Comments after the last statement of a block, up to its closing bracket
+	fn block_end_comments(&self, last_line: usize) -> Vec<&SourceComment> {
+		let used = self.used.borrow();
+		let close = self
+			.comments
+			.iter()
+			.enumerate()
+			.find(|(index, c)| !used[*index] && c.line_nr > last_line && c.closes.is_some())
+			.and_then(|(_, c)| c.closes);
+		drop(used);
+		match close {
+			Some(close) => self.take_comments(|c| c.line_nr > last_line && c.closes == Some(close)),
+			None => Vec::new(),
+		}
+	}
+
+	fn comment_lines(&self, comments: &[&SourceComment], indent: &str, lines: &mut Vec<String>) {
+		for comment in comments {
+			for text in comment.text.lines() {
+				lines.push(format!("{}{}", indent, line_comment(text.trim())));
+			}
+		}
+	}
+
+	/// Whether any unprinted source comment lies within the given lines
+	fn has_comments_within(&self, first_line: usize, last_line: usize) -> bool {
+		let used = self.used.borrow();
+		self.comments
+			.iter()
+			.enumerate()
+			.any(|(index, c)| !used[index] && c.line_nr >= first_line && c.line_nr <= last_line)
+	}
+
+	/// Whether the source had an empty line between this statement (and its comments) and the previous one
+	fn blank_line_before(&self, line_nr: usize) -> bool {
+		let mut index = line_nr.saturating_sub(1);
+		while index > 0 {
+			index -= 1;
+			let line = self.source.get(index).map(|l| l.trim()).unwrap_or("x");
+			if line.is_empty() {
+				return true;
+			}
+			if !is_comment_line(line) {
+				return false;
+			}
+		}
+		false
+	}
+
+	/// Comments attached to the node print above it, unless the source tokens supply them
+	fn statement(&self, node: &Node, depth: usize) -> String {
+		let indent = "\t".repeat(depth);
+		let (comments, inner) = split_comments(node);
+		let mut out = String::new();
+		if self.source.is_empty() {
+			for comment in comments {
+				for line in comment.lines() {
+					out.push_str(&indent);
+					out.push_str(&line_comment(line.trim()));
+					out.push('\n');
+				}
+			}
+		}
+		out.push_str(&indent);
+		out.push_str(&self.statement_body(inner, depth));
+		out
+	}
+
+	/// Statement-level rewrites: function and variable definitions
+	fn statement_body(&self, node: &Node, depth: usize) -> String {
+		if let Some(def) = function_definition(node) {
+			return self.function(&def, depth);
+		}
+		if let Some((name, value)) = variable_definition(node) {
+			let value = self.right_operand(value, Op::Assign.binding_power().1, depth);
+			return match self.style.var_def {
+				VarStyle::ColonEquals => format!("{} := {}", name, value),
+				VarStyle::Let => format!("let {} = {}", name, value),
+				VarStyle::Var => format!("var {} = {}", name, value),
+			};
+		}
+		self.expr(node, depth)
+	}
+
+	fn function(&self, def: &FunctionDef, depth: usize) -> String {
+		let params: Vec<String> = def.params.iter().map(|p| self.param(p, depth)).collect();
+		let signature = format!("{}({})", def.name, params.join(", "));
+		let body = def.body;
+		let (_, define_bp) = Op::Define.binding_power();
+		let (_, colon_bp) = Op::Colon.binding_power();
+		match self.style.function_def {
+			// `f() := …` would define a variable, so parameterless functions keep `=`
+			FunctionStyle::ColonEquals if params.is_empty() => {
+				format!("{} = {}", signature, self.right_operand(body, define_bp, depth))
+			}
+			FunctionStyle::ColonEquals => format!("{} := {}", signature, self.right_operand(body, define_bp, depth)),
+			FunctionStyle::Def if is_curly(body) || is_application(body) || self.binds_looser(body, colon_bp) => {
+				format!("def {} {}", signature, self.block(body, depth))
+			}
+			FunctionStyle::Def => format!("def {}: {}", signature, self.expr(body, depth)),
+			FunctionStyle::Fn | FunctionStyle::Fun => {
+				let keyword = if self.style.function_def == FunctionStyle::Fn { "fn" } else { "fun" };
+				if is_curly(body) {
+					format!("{} {} {}", keyword, signature, self.expr(body, depth))
+				} else {
+					format!("{} {} = {}", keyword, signature, self.right_operand(body, define_bp, depth))
+				}
+			}
+			FunctionStyle::Function => format!("function {} {}", signature, self.block(body, depth)),
+		}
+	}
+
+	fn param(&self, param: &Param, depth: usize) -> String {
+		match param {
+			Param::Node(node) => self.expr(node, depth),
+			Param::Words(words) => words.iter().map(|w| self.expr(w, depth)).collect::<Vec<_>>().join(" "),
+		}
+	}
+
+	/// Body wrapped in braces unless it already is a block
+	fn block(&self, body: &Node, depth: usize) -> String {
+		if is_curly(body) {
+			self.expr(body, depth)
+		} else {
+			format!("{{{}}}", self.statement_body(body, depth))
+		}
+	}
+
+	fn expr(&self, node: &Node, depth: usize) -> String {
+		match node {
+			Node::Meta { node: inner, data } => match comment_text(data) {
+				Some(comment) if self.source.is_empty() => format!("/* {} */ {}", comment, self.expr(inner, depth)),
+				_ => self.expr(inner, depth),
+			},
+			Node::True => "true".to_string(),
+			Node::False => "false".to_string(),
+			Node::Empty => "null".to_string(),
+			Node::Number(n) => number(n),
+			Node::Text(s) => self.quote(s),
+			Node::Char(c) => self.quote(&c.to_string()),
+			Node::Symbol(s) => s.clone(),
+			Node::Type { name, body } => self.type_node(name, body, depth),
+			Node::Key(left, op, right) => self.key(node, left, *op, right, depth),
+			Node::List(items, bracket, separator) => self.list(node, items, bracket, separator, depth),
+			Node::Error(_) | Node::Data(_) => node.serialize(),
+		}
+	}
+
+	fn quote(&self, s: &str) -> String {
+		let quote = match self.style.quotes {
+			QuoteStyle::Single => '\'',
+			QuoteStyle::Double => '"',
+		};
+		quote_text(s, quote)
+	}
+
+	fn type_name<'n>(&self, name: &'n str) -> &'n str {
+		if self.style.prefer_string_over_str && matches!(name, "str" | "String") {
+			"string"
+		} else {
+			name
+		}
+	}
+
+	fn type_node(&self, name: &Node, body: &Node, depth: usize) -> String {
+		let name = match name.drop_meta() {
+			Node::Symbol(s) => self.type_name(s).to_string(),
+			other => self.expr(other, depth),
+		};
+		if let Some(variants) = enum_variants(body) {
+			let variants: Vec<String> = variants.iter().map(|variant| self.variant(variant, depth)).collect();
+			return format!("enum {}{{{}}}", name, variants.join(" "));
+		}
+		match body.drop_meta() {
+			Node::Empty => name, // type reference, e.g. a field type
+			_ => format!("class {}{}", name, self.expr(body, depth)),
+		}
+	}
+
+	/// Enum variant with its fields: Circle(r:float), or just Dot
+	fn variant(&self, variant: &Node, depth: usize) -> String {
+		match variant.drop_meta() {
+			Node::Type { name, body } if !matches!(body.drop_meta(), Node::Empty) => {
+				let fields: Vec<String> = match body.drop_meta() {
+					Node::List(fields, _, _) => fields.iter().map(|field| self.expr(field, depth)).collect(),
+					field => vec![self.expr(field, depth)],
+				};
+				format!("{}({})", self.expr(name, depth), fields.join(", "))
+			}
+			Node::Type { name, .. } => self.expr(name, depth),
+			other => self.expr(other, depth),
+		}
+	}
+
+	fn key(&self, node: &Node, left: &Node, op: Op, right: &Node, depth: usize) -> String {
+		if let Some((cond, then, otherwise)) = as_conditional(node) {
+			return self.conditional(cond, then, otherwise, depth);
+		}
+		if let Some((value, type_name)) = as_cast(node) {
+			return self.cast(value, type_name, depth);
+		}
+		let (left_bp, right_bp) = op.binding_power();
+		if is_prefix_form(left, op) {
+			return self.prefix(op, right, depth);
+		}
+		if op.is_suffix() || is_optional_type(op, right) {
+			return format!("{}{}", self.left_operand(left, left_bp, depth), op);
+		}
+		if op == Op::Match {
+			return self.match_expr(left, right, depth);
+		}
+		match op {
+			Op::Do => {
+				if let Node::Key(while_left, Op::While, cond) = left.drop_meta() {
+					if matches!(while_left.drop_meta(), Node::Empty) {
+						return if is_curly(right) {
+							format!("while {} {}", self.block_condition(cond, depth), self.expr(right, depth))
+						} else {
+							format!(
+								"while {} do {}",
+								self.right_operand(cond, Op::While.binding_power().1, depth),
+								self.right_operand(right, right_bp, depth)
+							)
+						};
+					}
+				}
+				if let Node::Key(for_left, Op::For, each) = left.drop_meta() {
+					if let (Node::Empty, Node::Key(names, Op::In, items)) = (for_left.drop_meta(), each.drop_meta()) {
+						let names = match names.drop_meta() {
+							Node::List(names, _, _) => names.iter().map(|name| self.expr(name, depth)).collect::<Vec<_>>().join(", "),
+							name => self.expr(name, depth),
+						};
+						return if is_curly(right) {
+							format!("for {} in {} {}", names, self.block_condition(items, depth), self.expr(right, depth))
+						} else {
+							format!(
+								"for {} in {} do {}",
+								names,
+								self.right_operand(items, Op::For.binding_power().1, depth),
+								self.right_operand(right, right_bp, depth)
+							)
+						};
+					}
+				}
+			}
+			Op::Hash if self.style.index == IndexStyle::Bracket => return self.bracket_index(left, right, depth),
+			Op::Colon => {
+				if let Node::Symbol(name) = left.drop_meta() {
+					match right.drop_meta() {
+						// `global x = 1` keeps its keyword form
+						_ if name == "global" => return format!("global {}", self.expr(right, depth)),
+						Node::List(_, Bracket::Curly, _) => return format!("{}{}", name, self.expr(right, depth)),
+						// type arguments: Pair<A, B>
+						Node::List(_, Bracket::Less, _) => return format!("{}{}", name, self.expr(right, depth)),
+						// indentation block: name, then the body one tab deeper
+						Node::List(_, Bracket::None, Separator::Newline) => {
+							return format!("{}\n{}", name, self.statements(right, depth + 1))
+						}
+						_ => {}
+					}
+				}
+			}
+			_ => {}
+		}
+		format!(
+			"{}{}{}",
+			self.left_operand(left, left_bp, depth),
+			self.infix(op),
+			self.right_operand(right, right_bp, depth)
+		)
+	}
+
+	/// `match x {` then one `pattern if guard => value` per line
+	fn match_expr(&self, subject: &Node, arms: &Node, depth: usize) -> String {
+		let indent = "\t".repeat(depth + 1);
+		let lines: Vec<String> = match_arms(arms)
+			.map(|arm| match match_arm(arm) {
+				Some((pattern, guard, value)) => {
+					let guard = guard.map(|guard| format!(" if {}", self.expr(guard, depth + 1))).unwrap_or_default();
+					format!("{}{}{} => {}", indent, self.expr(pattern, depth + 1), guard, self.expr(value, depth + 1))
+				}
+				None => format!("{}{}", indent, self.expr(arm, depth + 1)),
+			})
+			.collect();
+		format!("match {} {{\n{}\n{}}}", self.block_condition(subject, depth), lines.join("\n"), "\t".repeat(depth))
+	}
+
+	fn infix(&self, op: Op) -> String {
+		let symbols = self.style.logical == LogicalStyle::Symbols;
+		let double_star = self.style.power == PowerStyle::DoubleStar;
+		match op {
+			Op::Dot | Op::SafeDot | Op::Scope | Op::Range | Op::Hash => op.as_str().to_string(),
+			Op::Colon => ": ".to_string(),
+			Op::And if symbols => " && ".to_string(),
+			Op::Or if symbols => " || ".to_string(),
+			Op::Pow if double_star => " ** ".to_string(),
+			Op::PowAssign if double_star => " **= ".to_string(),
+			_ => format!(" {} ", op.as_str()),
+		}
+	}
+
+	fn prefix(&self, op: Op, right: &Node, depth: usize) -> String {
+		let operand = self.right_operand(right, op.binding_power().1, depth);
+		match op {
+			Op::Not if self.style.logical == LogicalStyle::Symbols => format!("!{}", operand),
+			Op::Not | Op::If | Op::While | Op::For => format!("{} {}", op.as_str(), operand),
+			Op::Abs => format!("abs {}", operand),
+			_ => format!("{}{}", op.as_str(), operand),
+		}
+	}
+
+	fn conditional(&self, cond: &Node, then: &Node, otherwise: Option<&Node>, depth: usize) -> String {
+		if is_curly(then) && otherwise.is_none_or(is_curly) {
+			let mut out = format!("if {} {}", self.block_condition(cond, depth), self.expr(then, depth));
+			if let Some(otherwise) = otherwise {
+				out.push_str(" else ");
+				out.push_str(&self.expr(otherwise, depth));
+			}
+			return out;
+		}
+		if let (ConditionalStyle::Ternary, Some(otherwise)) = (self.style.conditional, otherwise) {
+			let (question_bp, _) = Op::Question.binding_power();
+			let (colon_left, colon_right) = Op::Colon.binding_power();
+			return format!(
+				"{} ? {} : {}",
+				self.left_operand(cond, question_bp, depth),
+				self.left_operand(then, colon_left, depth),
+				self.right_operand(otherwise, colon_right, depth)
+			);
+		}
+		let mut out = format!(
+			"if {} then {}",
+			self.right_operand(cond, Op::If.binding_power().1, depth),
+			self.right_operand(then, Op::Then.binding_power().1, depth)
+		);
+		if let Some(otherwise) = otherwise {
+			out.push_str(" else ");
+			out.push_str(&self.right_operand(otherwise, Op::Else.binding_power().1, depth));
+		}
+		out
+	}
+
+	/// Condition before a `{block}`: a trailing symbol would swallow the block as a call argument
+	fn block_condition(&self, cond: &Node, depth: usize) -> String {
+		let printed = self.expr(cond, depth);
+		if matches!(cond.drop_meta(), Node::Key(..)) && ends_with_symbol(cond) {
+			format!("({})", printed)
+		} else {
+			printed
+		}
+	}
+
+	fn cast(&self, value: &Node, type_name: &str, depth: usize) -> String {
+		let type_name = self.type_name(type_name);
+		match self.style.cast {
+			CastStyle::AsOperator => {
+				let printed = self.left_operand(value, Op::As.binding_power().0, depth);
+				if matches!(value.drop_meta(), Node::List(_, Bracket::None, _)) {
+					format!("({}) as {}", printed, type_name)
+				} else {
+					format!("{} as {}", printed, type_name)
+				}
+			}
+			CastStyle::Constructor => format!("{}({})", type_name, self.expr(unparenthesized(value), depth)),
+		}
+	}
+
+	/// `x#2` printed as the zero-based `x[1]`
+	fn bracket_index(&self, target: &Node, index: &Node, depth: usize) -> String {
+		let index = match index.drop_meta() {
+			Node::Number(Number::Int(n)) => (n - 1).to_string(),
+			Node::Key(inner, Op::Add, one) if matches!(one.drop_meta(), Node::Number(Number::Int(1))) => {
+				self.expr(inner, depth)
+			}
+			_ => format!("{} - 1", self.left_operand(index, Op::Sub.binding_power().0, depth)),
+		};
+		format!("{}[{}]", self.left_operand(target, Op::Hash.binding_power().0, depth), index)
+	}
+
+	fn list(&self, node: &Node, items: &[Node], bracket: &Bracket, separator: &Separator, depth: usize) -> String {
+		if let Some((value, type_name)) = as_cast(node) {
+			return self.cast(value, type_name, depth);
+		}
+		if *bracket == Bracket::Round && *separator == Separator::None {
+			if let Some(def) = function_definition(node) {
+				return self.function_with_body(&def, depth);
+			}
+			if let Some(Node::Symbol(name)) = items.first().map(Node::drop_meta) {
+				let args: Vec<String> = items[1..].iter().map(|arg| self.expr(arg, depth)).collect();
+				return format!("{}({})", name, args.join(", "));
+			}
+			// generic function: first<T>(xs)
+			if let Some(head) = items.first().filter(|head| is_generic(head)) {
+				let args: Vec<String> = items[1..].iter().map(|arg| self.expr(arg, depth)).collect();
+				return format!("{}({})", self.expr(head.drop_meta(), depth), args.join(", "));
+			}
+		}
+		let (open, close) = match bracket {
+			Bracket::Curly => ('{', '}'),
+			Bracket::Square => ('[', ']'),
+			Bracket::Round => ('(', ')'),
+			Bracket::Less => ('<', '>'),
+			Bracket::Other(open, close) => (*open, *close),
+			// a statement sequence nested in an expression needs braces
+			Bracket::None if *separator == Separator::Newline => ('{', '}'),
+			Bracket::None => {
+				let items: Vec<String> = items.iter().map(|item| self.expr(item, depth)).collect();
+				return items.join(separator_str(separator));
+			}
+		};
+		let is_block = open == '{';
+		let has_comments = if self.source.is_empty() {
+			items.iter().any(|item| !split_comments(item).0.is_empty())
+		} else {
+			let lines: Vec<usize> = items.iter().map(line_of).filter(|&line| line > 0).collect();
+			match (lines.iter().min(), lines.iter().max()) {
+				(Some(&first), Some(&last)) => self.has_comments_within(first, last),
+				_ => false,
+			}
+		};
+		if *separator == Separator::Newline || (is_block && has_comments) {
+			let mut statements = Vec::new();
+			for item in items {
+				flatten_statements(item, &mut statements);
+			}
+			return format!(
+				"{}\n{}\n{}{}",
+				open,
+				self.statement_lines(&statements, depth + 1),
+				"\t".repeat(depth),
+				close
+			);
+		}
+		let statement_list = is_block && *separator == Separator::Semicolon;
+		let items: Vec<String> = items
+			.iter()
+			.map(|item| if statement_list { self.statement_body(item, depth) } else { self.expr(item, depth) })
+			.collect();
+		format!("{}{}{}", open, items.join(separator_str(separator)), close)
+	}
+
+	/// `name(params) {body}` met outside statement position
+	fn function_with_body(&self, def: &FunctionDef, depth: usize) -> String {
+		let params: Vec<String> = def.params.iter().map(|p| self.param(p, depth)).collect();
+		format!("{}({}) {}", def.name, params.join(", "), self.block(def.body, depth))
+	}
+
+	/// Binding powers of a node the way it is printed; None for atoms
+	fn printed_bp(&self, node: &Node) -> Option<(u8, u8)> {
+		if let Some((_, then, otherwise)) = as_conditional(node) {
+			let ternary = self.style.conditional == ConditionalStyle::Ternary;
+			return Some(match otherwise {
+				Some(otherwise) if ternary && !(is_curly(then) && is_curly(otherwise)) => Op::Question.binding_power(),
+				Some(_) => Op::Else.binding_power(),
+				None => Op::Then.binding_power(),
+			});
+		}
+		if as_cast(node).is_some() {
+			return match self.style.cast {
+				CastStyle::AsOperator => Some(Op::As.binding_power()),
+				CastStyle::Constructor => None,
+			};
+		}
+		match node.drop_meta() {
+			Node::Key(left, op, _) if is_prefix_form(left, *op) => Some((u8::MAX, op.binding_power().1)),
+			Node::Key(_, op, right) if op.is_suffix() || is_optional_type(*op, right) => Some((op.binding_power().0, u8::MAX)),
+			Node::Key(_, Op::Hash, _) if self.style.index == IndexStyle::Bracket => {
+				Some((Op::Hash.binding_power().0, u8::MAX))
+			}
+			Node::Key(_, op, _) => Some(op.binding_power()),
+			_ => None,
+		}
+	}
+
+	fn binds_looser(&self, node: &Node, bp: u8) -> bool {
+		self.printed_bp(node).is_some_and(|(left, _)| left < bp)
+	}
+
+	/// Left operand, parenthesized when the parser would otherwise regroup it
+	fn left_operand(&self, node: &Node, parent_left_bp: u8, depth: usize) -> String {
+		let printed = self.expr(node, depth);
+		match self.printed_bp(node) {
+			Some((_, right)) if right <= parent_left_bp => format!("({})", printed),
+			_ => printed,
+		}
+	}
+
+	fn right_operand(&self, node: &Node, parent_right_bp: u8, depth: usize) -> String {
+		let printed = self.expr(node, depth);
+		if self.binds_looser(node, parent_right_bp) {
+			format!("({})", printed)
+		} else {
+			printed
+		}
+	}
+}
+
+struct FunctionDef<'n> {
+	name: String,
+	params: Vec<Param<'n>>,
+	body: &'n Node,
+}
+
+enum Param<'n> {
+	Node(&'n Node),
+	/// C-style `(float a)` parameter
+	Words(&'n [Node]),
+}
+
+/// A name with type arguments: Pair<A, B>
+fn is_generic(node: &Node) -> bool {
+	matches!(node.drop_meta(), Node::Key(name, Op::Colon, args)
+		if matches!((name.drop_meta(), args.drop_meta()), (Node::Symbol(_), Node::List(_, Bracket::Less, _))))
+}
+
+/// Recognize every function definition form:
+/// `f(x) := b`, `f(x) = b`, `def f(x): b`, `fn f(x) = b`, `def f(x) {b}`, `function f(x) {b}`
+fn function_definition(node: &Node) -> Option<FunctionDef<'_>> {
+	match node.drop_meta() {
+		Node::Key(left, Op::Define | Op::Assign, body) => {
+			let (name, args) = call_parts(left)?;
+			Some(FunctionDef {
+				name: name.to_string(),
+				params: args.iter().map(Param::Node).collect(),
+				body,
+			})
+		}
+		Node::List(items, Bracket::None, Separator::Space) if items.len() == 2 => {
+			match items[0].drop_meta() {
+				Node::Symbol(keyword) if is_function_keyword(keyword) => {}
+				_ => return None,
+			}
+			match items[1].drop_meta() {
+				Node::Key(left, Op::Colon | Op::Define | Op::Assign, body) => {
+					let (name, args) = call_parts(left)?;
+					Some(FunctionDef {
+						name: name.to_string(),
+						params: args.iter().map(Param::Node).collect(),
+						body,
+					})
+				}
+				inner @ Node::List(..) => function_definition(inner),
+				_ => None,
+			}
+		}
+		// name(params) {body}: the signature keeps its parameter list as one node
+		Node::List(items, Bracket::Round, Separator::None) if items.len() == 2 && is_curly(&items[1]) => {
+			let Node::List(signature, Bracket::Round, Separator::None) = items[0].drop_meta() else {
+				return None;
+			};
+			let name = match signature.first()?.drop_meta() {
+				Node::Symbol(name) => name.clone(),
+				_ => return None,
+			};
+			let params = match signature.get(1).map(Node::drop_meta) {
+				None | Some(Node::Empty) => Vec::new(),
+				Some(Node::List(params, Bracket::Round, Separator::Space)) if params.len() > 1 => {
+					vec![Param::Words(params)]
+				}
+				Some(Node::List(params, Bracket::Round, _)) => params.iter().map(Param::Node).collect(),
+				Some(_) => signature[1..].iter().map(Param::Node).collect(),
+			};
+			Some(FunctionDef {
+				name,
+				params,
+				body: &items[1],
+			})
+		}
+		_ => None,
+	}
+}
+
+/// `let x = v`, `var x = v` and `x := v`, except implicit-parameter functions like `double := it * 2`
+fn variable_definition(node: &Node) -> Option<(&str, &Node)> {
+	let (name, value) = match node.drop_meta() {
+		Node::Key(left, Op::Define, value) => (left, value),
+		Node::List(items, Bracket::None, Separator::Space) if items.len() == 2 => {
+			match items[0].drop_meta() {
+				Node::Symbol(keyword) if keyword == "let" || keyword == "var" => {}
+				_ => return None,
+			}
+			match items[1].drop_meta() {
+				Node::Key(left, Op::Assign | Op::Define, value) => (left, value),
+				_ => return None,
+			}
+		}
+		_ => return None,
+	};
+	match name.drop_meta() {
+		Node::Symbol(name) if !uses_implicit_param(value) => Some((name.as_str(), value.as_ref())),
+		_ => None,
+	}
+}
+
+/// Name and arguments of a call-shaped node `f(a, b)`
+fn call_parts(node: &Node) -> Option<(&str, &[Node])> {
+	match node.drop_meta() {
+		Node::List(items, Bracket::Round, Separator::None) => match items.first()?.drop_meta() {
+			Node::Symbol(name) => Some((name.as_str(), &items[1..])),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// (condition, then, else) of `if c then a else b`, `if c {a} else {b}` and `c ? a : b`
+fn as_conditional(node: &Node) -> Option<(&Node, &Node, Option<&Node>)> {
+	fn if_then(node: &Node) -> Option<(&Node, &Node)> {
+		match node.drop_meta() {
+			Node::Key(if_part, Op::Then, then) => match if_part.drop_meta() {
+				Node::Key(empty, Op::If, cond) if matches!(empty.drop_meta(), Node::Empty) => Some((cond, then)),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+	match node.drop_meta() {
+		Node::Key(left, Op::Else, otherwise) => if_then(left).map(|(cond, then)| (cond, then, Some(otherwise.as_ref()))),
+		Node::Key(cond, Op::Question, branches) => match branches.drop_meta() {
+			Node::Key(then, Op::Colon, otherwise) => Some((cond.as_ref(), then.as_ref(), Some(otherwise.as_ref()))),
+			_ => None,
+		},
+		other => if_then(other).map(|(cond, then)| (cond, then, None)),
+	}
+}
+
+/// An optional type is a ? suffix: Tree?
+fn is_optional_type(op: Op, right: &Node) -> bool {
+	op == Op::Question && matches!(right.drop_meta(), Node::Empty)
+}
+
+/// (value, type name) of `x as int` or `int(x)`
+fn as_cast(node: &Node) -> Option<(&Node, &str)> {
+	match node.drop_meta() {
+		Node::Key(value, Op::As, type_name) => match type_name.drop_meta() {
+			Node::Symbol(name) => Some((value, name.as_str())),
+			_ => None,
+		},
+		Node::List(items, Bracket::Round, Separator::None) if items.len() == 2 => {
+			let is_typed_decl = matches!(items[1].drop_meta(), Node::Key(_, Op::Assign | Op::Define, _));
+			match items[0].drop_meta() {
+				Node::Symbol(name) if CAST_TYPES.contains(&name.as_str()) && !is_typed_decl => {
+					Some((&items[1], name.as_str()))
+				}
+				_ => None,
+			}
+		}
+		_ => None,
+	}
+}
+
+fn is_prefix_form(left: &Node, op: Op) -> bool {
+	matches!(left.drop_meta(), Node::Empty) && (op.is_prefix() || matches!(op, Op::If | Op::While | Op::For | Op::Hash))
+}
+
+/// `print x`: after `def f(x):` only `print` would become the body
+fn is_application(node: &Node) -> bool {
+	matches!(node.drop_meta(), Node::List(_, Bracket::None, _))
+}
+
+fn is_curly(node: &Node) -> bool {
+	matches!(node.drop_meta(), Node::List(_, Bracket::Curly, _))
+}
+
+fn unparenthesized(node: &Node) -> &Node {
+	match node.drop_meta() {
+		Node::List(items, Bracket::Round, separator) if items.len() == 1 && *separator != Separator::None => &items[0],
+		_ => node,
+	}
+}
+
+fn ends_with_symbol(node: &Node) -> bool {
+	match node.drop_meta() {
+		Node::Symbol(_) => true,
+		Node::Key(_, op, right) if !op.is_suffix() => ends_with_symbol(right),
+		_ => false,
+	}
+}
+
+fn uses_implicit_param(node: &Node) -> bool {
+	match node.drop_meta() {
+		Node::Symbol(s) => s == "it" || (s.starts_with('$') && s[1..].parse::<u32>().is_ok()),
+		Node::Key(left, _, right) => uses_implicit_param(left) || uses_implicit_param(right),
+		Node::List(items, _, _) => items.iter().any(uses_implicit_param),
+		_ => false,
+	}
+}
+
+fn flatten_statements<'n>(node: &'n Node, out: &mut Vec<&'n Node>) {
+	let has_comments = !split_comments(node).0.is_empty();
+	match node.drop_meta() {
+		Node::List(items, Bracket::None, Separator::Newline | Separator::Semicolon) if !has_comments => {
+			for item in items {
+				flatten_statements(item, out);
+			}
+		}
+		Node::Empty => {}
+		_ => out.push(node),
+	}
+}
+
+/// Comments of all Meta wrappers in source order, and the bare node
+fn split_comments(node: &Node) -> (Vec<String>, &Node) {
+	let mut comments = Vec::new();
+	let mut current = node;
+	while let Node::Meta { node: inner, data } = current {
+		if let Some(comment) = comment_text(data) {
+			comments.push(comment);
+		}
+		current = inner;
+	}
+	// trailing comments wrap leading ones
+	comments.reverse();
+	(comments, current)
+}
+
+fn comment_text(data: &Node) -> Option<String> {
+	match data {
+		Node::Key(key, _, value) if matches!(key.drop_meta(), Node::Symbol(k) if k == "comment") => {
+			match value.drop_meta() {
+				Node::Text(text) => Some(text.clone()),
+				_ => None,
+			}
+		}
+		_ => None,
+	}
+}
+
+fn line_comment(text: &str) -> String {
+	if text.is_empty() || text.starts_with('/') {
+		format!("//{}", text)
+	} else {
+		format!("// {}", text)
+	}
+}
+
+fn is_comment_line(line: &str) -> bool {
+	line.starts_with("//") || line.starts_with('#') || line.starts_with("/*") || line.starts_with('*') || line.ends_with("*/")
+}
+
+/// Text as a wasp string literal in the given quote, escaped so it reparses verbatim
+pub(crate) fn quote_text(s: &str, quote: char) -> String {
+	let mut out = String::from(quote);
+	for c in s.chars() {
+		match c {
+			'\n' => out.push_str("\\n"),
+			'\t' => out.push_str("\\t"),
+			'\r' => out.push_str("\\r"),
+			'\\' => out.push_str("\\\\"),
+			c if c == quote => {
+				out.push('\\');
+				out.push(c);
+			}
+			// keep "{" and "$" literal instead of interpolating on reparse
+			'{' | '$' if quote == '"' => {
+				out.push('\\');
+				out.push(c);
+			}
+			c => out.push(c),
+		}
+	}
+	out.push(quote);
+	out
+}
+
+fn separator_str(separator: &Separator) -> &'static str {
+	match separator {
+		Separator::Colon => ", ",
+		Separator::Semicolon => "; ",
+		Separator::Tab => "\t",
+		Separator::Newline => "\n",
+		Separator::Space | Separator::None => " ",
+	}
+}
+
+fn number(n: &Number) -> String {
+	match n {
+		// keep floats floats: 1.0 must not come back as the int 1
+		Number::Float(f) if f.is_finite() && f.fract() == 0.0 => format!("{:.1}", f),
+		other => other.to_string(),
+	}
+}
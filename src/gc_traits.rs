@@ -1299,12 +1299,18 @@ pub mod wasm_name_resolver {
 			group_start: u32,
 		) -> Result<Self> {
 			use wp::CompositeInnerType::*;
+			let supertype = subtype
+				.supertype_idx
+				.map(|idx| resolve_index(idx.unpack(), group_start))
+				.transpose()?;
+			let is_final = subtype.is_final;
 			match subtype.composite_type.inner {
-				Struct(ty) => Ok(Self::Struct(ParsedStructType::from_parser(
-					type_index,
-					ty,
-					group_start,
-				)?)),
+				Struct(ty) => {
+					let mut parsed = ParsedStructType::from_parser(type_index, ty, group_start)?;
+					parsed.supertype = supertype;
+					parsed.is_final = is_final;
+					Ok(Self::Struct(parsed))
+				}
 				Array(ty) => Ok(Self::Array(ParsedArrayType::from_parser(
 					type_index,
 					ty,
@@ -1327,6 +1333,9 @@ pub mod wasm_name_resolver {
 		type_name: Option<String>,
 		fields: Vec<ParsedField>,
 		field_names: Vec<Option<String>>,
+		// a subclass has the fields of its supertype, these tell them apart
+		supertype: Option<u32>,
+		is_final: bool,
 	}
 
 	impl ParsedStructType {
@@ -1341,6 +1350,8 @@ pub mod wasm_name_resolver {
 				type_name: None,
 				fields,
 				field_names,
+				supertype: None,
+				is_final: true,
 			})
 		}
 	}
@@ -1569,6 +1580,14 @@ pub mod wasm_name_resolver {
 		}
 
 		fn compare_struct(&mut self, parsed: &ParsedStructType, ty: &StructType) -> bool {
+			if parsed.is_final != ty.is_final() {
+				return false;
+			}
+			match (parsed.supertype, ty.supertype()) {
+				(None, None) => {}
+				(Some(idx), Some(supertype)) if self.match_struct(idx, &supertype) => {}
+				_ => return false,
+			}
 			let mut runtime_fields = ty.fields();
 			if parsed.fields.len() != runtime_fields.len() {
 				return false;
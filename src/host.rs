@@ -10,7 +10,7 @@ use wasmtime::{Caller, Engine, Extern, Linker, Memory, Module, Store, Val};
 
 /// Memory allocator state for host functions
 pub struct HostState {
-	/// Next free offset in linear memory for string allocation
+	/// Next free offset in linear memory, for modules without an exported `alloc`
 	next_alloc: u32,
 	/// Pending result node from `run` call (stored until it can be returned)
 	pending_result: Option<Node>,
@@ -54,7 +54,11 @@ fn write_string_to_caller(
 ) -> Result<(u32, u32)> {
 	let bytes = s.as_bytes();
 	let len = bytes.len() as u32;
-	let ptr = caller.data_mut().alloc(len);
+	// Share the module's heap if it has one, so runtime text never overlaps host text
+	let ptr = match caller.get_export("alloc") {
+		Some(Extern::Func(alloc)) => alloc.typed::<i32, i32>(&*caller)?.call(&mut *caller, len as i32)? as u32,
+		_ => caller.data_mut().alloc(len),
+	};
 
 	// Grow memory if needed
 	let pages_needed = ((ptr + len) as usize).div_ceil(65536);
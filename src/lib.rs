@@ -13,17 +13,25 @@ pub mod smarty;
 pub mod util; // reexported for tests
 pub use util::gc_engine;
 pub mod analyzer;
+pub mod checks;
+pub mod compiled;
 pub mod compiler;
 pub mod node;
 pub mod run;
 pub mod type_kinds;
 pub mod gc_traits;
 pub mod context;
+pub mod convert;
+pub mod cst;
+pub mod error;
+pub mod formatter;
 pub mod wasm_emitter;
 pub mod wasm_reader;
+pub mod wasm_linker;
 pub mod wasm_optimizer;
 pub mod wasp_parser;
 pub mod wisp_parser;
+pub mod yaml;
 pub mod operators;
 pub mod ast;
 pub mod meta;
@@ -33,6 +41,8 @@ pub mod ffi_parser;
 pub mod function;
 pub mod normalize;
 pub mod local;
+pub mod lsp;
+pub mod session;
 // ⚠️ modules also need to be used in main.rs AND lib.rs to be compiled
 
 // ==================== Core Re-exports ====================
@@ -44,14 +54,18 @@ pub use node::{block, codepoint, error, error_node, float, floats, data, int, in
 // Node variants (except Number/List which conflict with extension types)
 pub use node::Node::{Char, Data, Empty, Error, False, Key, Meta, Symbol, Text, True};
 // Parser
-pub use wasp_parser::{parse, parse_file, parse_xml, WaspParser};
+pub use wasp_parser::{parse, parse_file, parse_xml, parse_yaml, WaspParser};
 pub use wisp_parser::{emit_wisp, parse_wisp, WispEmitter, WispParser};
 // Type system
 pub use type_kinds::{AstKind, NodeKind, Kind, TypeRegistry, TypeDef, FieldDef, USER_TYPE_TAG_START, extract_instance_values, RawFieldValue};
 // Metadata
 pub use meta::{Dada, LineInfo, DataType};
 // WASM
-pub use wasm_emitter::{WasmGcEmitter};
+pub use wasm_emitter::{try_eval, WasmGcEmitter};
+pub use compiled::{compile, CompiledModule};
+pub use session::Session;
+// Errors
+pub use error::WarpError;
 // Host functions
 pub use host::{HostState, link_host_functions, create_host_linker};
 // Functions
@@ -0,0 +1,441 @@
+//! Language server for .wasp files: JSON-RPC over stdio
+//!
+//! Supports diagnostics (parser + analyzer), hover (inferred Kind),
+//! go-to-definition (user functions and types) and completion.
+//! `LspServer::handle` maps one message to its replies, so sessions can be scripted without an editor.
+
+use crate::analyzer::{check_types, collect_all_types, collect_variables, extract_user_functions, infer_type, Scope};
+use crate::compiled::{catch_silently, panic_message};
+use crate::context::Context;
+use crate::error::{collect_parse_errors, WarpError};
+use crate::meta::LineInfo;
+use crate::node::Node;
+use crate::operators::FUNCTION_KEYWORDS;
+use crate::type_kinds::TypeRegistry;
+use crate::wasm_emitter::WasmGcEmitter;
+use crate::wasp_parser::WaspParser;
+use log::warn;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Read, Write};
+
+const KEYWORDS: [&str; 16] = [
+	"if", "then", "else", "while", "for", "in", "do", "break", "continue", "return", "global", "class", "struct", "type",
+	"import", "use",
+];
+
+// CompletionItemKind values from the LSP spec
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_CLASS: u32 = 7;
+const COMPLETION_KEYWORD: u32 = 14;
+
+/// Analysis results for one open document
+struct Document {
+	text: String,
+	node: Node,
+	scope: Scope,
+	ctx: Context,
+}
+
+impl Document {
+	fn new(text: String) -> Self {
+		let node = WaspParser::parse(&text);
+		let mut scope = Scope::new();
+		collect_variables(&node, &mut scope);
+		let mut ctx = Context::new();
+		collect_all_types(&mut ctx.type_registry, &node);
+		extract_user_functions(&mut ctx, &node);
+		Document { text, node, scope, ctx }
+	}
+
+	/// Identifier under the cursor (0-based line and character)
+	fn word_at(&self, line: usize, character: usize) -> Option<String> {
+		let chars: Vec<char> = self.text.lines().nth(line)?.chars().collect();
+		let is_ident = |c: &char| c.is_alphanumeric() || *c == '_';
+		let mut start = character.min(chars.len());
+		while start > 0 && is_ident(&chars[start - 1]) {
+			start -= 1;
+		}
+		let mut end = character.min(chars.len());
+		while end < chars.len() && is_ident(&chars[end]) {
+			end += 1;
+		}
+		if start == end {
+			return None;
+		}
+		Some(chars[start..end].iter().collect())
+	}
+}
+
+pub struct LspServer {
+	documents: HashMap<String, Document>,
+	builtins: Vec<String>,
+	shutdown: bool,
+	exited: bool,
+}
+
+impl Default for LspServer {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LspServer {
+	pub fn new() -> Self {
+		// An emitter without tree-shaking registers every builtin runtime function
+		let mut emitter = WasmGcEmitter::new();
+		emitter.emit();
+		let mut builtins: Vec<String> = emitter.ctx.func_registry.all().iter().map(|f| f.name.clone()).collect();
+		builtins.sort();
+		LspServer {
+			documents: HashMap::new(),
+			builtins,
+			shutdown: false,
+			exited: false,
+		}
+	}
+
+	/// True once the client sent `exit`
+	pub fn exited(&self) -> bool {
+		self.exited
+	}
+
+	/// Handle one JSON-RPC message, returning responses and notifications to send
+	pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+		let method = message["method"].as_str().unwrap_or("");
+		let id = message.get("id").cloned();
+		let params = &message["params"];
+		let result = match method {
+			"initialize" => Some(json!({
+				"capabilities": {
+					"textDocumentSync": 1,
+					"hoverProvider": true,
+					"definitionProvider": true,
+					"completionProvider": { "triggerCharacters": ["."] }
+				},
+				"serverInfo": { "name": "warp", "version": env!("CARGO_PKG_VERSION") }
+			})),
+			"shutdown" => {
+				self.shutdown = true;
+				Some(Value::Null)
+			}
+			"exit" => {
+				self.exited = true;
+				return vec![];
+			}
+			"textDocument/didOpen" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+				let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+				return vec![self.update(uri, text)];
+			}
+			"textDocument/didChange" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+				// Full sync: the last change holds the whole document
+				let text = params["contentChanges"]
+					.as_array()
+					.and_then(|changes| changes.last())
+					.and_then(|change| change["text"].as_str())
+					.unwrap_or("")
+					.to_string();
+				return vec![self.update(uri, text)];
+			}
+			"textDocument/didClose" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+				self.documents.remove(&uri);
+				return vec![publish_diagnostics(&uri, vec![])];
+			}
+			"textDocument/hover" => Some(self.hover(params)),
+			"textDocument/definition" => Some(self.definition(params)),
+			"textDocument/completion" => Some(self.completion(params)),
+			_ => None,
+		};
+		match (id, result) {
+			(Some(id), Some(result)) => vec![json!({"jsonrpc": "2.0", "id": id, "result": result})],
+			// Unknown request: reply so the client does not wait forever
+			(Some(id), None) => vec![json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"error": {"code": -32601, "message": format!("method not found: {}", method)}
+			})],
+			_ => vec![],
+		}
+	}
+
+	/// Re-analyze a document and publish its diagnostics
+	fn update(&mut self, uri: String, text: String) -> Value {
+		// check_types runs check_type_errors on every statement plus the exhaustiveness, null-safety
+		// and overload checks, so the editor reports exactly what `warp compile` rejects
+		let checked = catch_silently(|| {
+			let document = Document::new(text.clone());
+			let mut errors = collect_parse_errors(&document.node);
+			if let Err(err) = check_types(&document.node) {
+				errors.push(err);
+			}
+			(document, errors)
+		});
+		let (document, errors) = match checked {
+			Ok(checked) => checked,
+			Err(payload) => {
+				// Keep the server alive; report the crash on the first line
+				self.documents.remove(&uri);
+				let error = WarpError::Parse {
+					message: format!("analyzer crashed on this document: {}", panic_message(payload)),
+					position: None,
+				};
+				return publish_diagnostics(&uri, vec![diagnostic(&error, &text)]);
+			}
+		};
+		let diagnostics = errors.iter().map(|e| diagnostic(e, &document.text)).collect();
+		self.documents.insert(uri.clone(), document);
+		publish_diagnostics(&uri, diagnostics)
+	}
+
+	fn document_at<'a>(&'a self, params: &Value) -> Option<(&'a Document, usize, usize)> {
+		let uri = params["textDocument"]["uri"].as_str()?;
+		let document = self.documents.get(uri)?;
+		let line = params["position"]["line"].as_u64()? as usize;
+		let character = params["position"]["character"].as_u64()? as usize;
+		let character = char_column(document.text.lines().nth(line).unwrap_or(""), character);
+		Some((document, line, character))
+	}
+
+	fn hover(&self, params: &Value) -> Value {
+		let Some((document, line, character)) = self.document_at(params) else {
+			return Value::Null;
+		};
+		let text = match document.word_at(line, character) {
+			Some(word) => describe_symbol(document, &word),
+			None => None,
+		};
+		// Fall back to the type of the expression starting at the cursor
+		let text = text.or_else(|| {
+			node_at(&document.node, line + 1, character + 1)
+				.map(|node| format!("{}: {}", node.serialize(), infer_type(node, &document.scope)))
+		});
+		match text {
+			Some(text) => json!({"contents": {"kind": "markdown", "value": format!("```wasp\n{}\n```", text)}}),
+			None => Value::Null,
+		}
+	}
+
+	fn definition(&self, params: &Value) -> Value {
+		let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+		let Some((document, line, character)) = self.document_at(params) else {
+			return Value::Null;
+		};
+		let Some(word) = document.word_at(line, character) else {
+			return Value::Null;
+		};
+		let site = if document.ctx.user_functions.contains_key(&word) {
+			definition_site(&document.node, &|node: &Node| {
+				let mut ctx = Context::new();
+				extract_user_functions(&mut ctx, node);
+				ctx.user_functions.contains_key(&word)
+			})
+		} else if document.ctx.type_registry.get_by_name(&word).is_some() {
+			definition_site(&document.node, &|node: &Node| {
+				let mut registry = TypeRegistry::new();
+				collect_all_types(&mut registry, node);
+				registry.get_by_name(&word).is_some()
+			})
+		} else {
+			None
+		};
+		match site {
+			Some(info) => json!({"uri": uri, "range": line_range(&info, &document.text)}),
+			None => Value::Null,
+		}
+	}
+
+	fn completion(&self, params: &Value) -> Value {
+		let mut items = Vec::new();
+		if let Some((document, _, _)) = self.document_at(params) {
+			let mut functions: Vec<_> = document.ctx.user_functions.values().collect();
+			functions.sort_by(|a, b| a.name.cmp(&b.name));
+			for f in functions {
+				items.push(completion_item(&f.name, COMPLETION_FUNCTION, &function_signature(f)));
+			}
+			for t in document.ctx.type_registry.types() {
+				items.push(completion_item(&t.name, COMPLETION_CLASS, "class"));
+			}
+			let mut variables: Vec<_> = document.scope.locals.values().collect();
+			variables.sort_by(|a, b| a.name.cmp(&b.name));
+			for v in variables {
+				items.push(completion_item(&v.name, COMPLETION_VARIABLE, &v.kind.to_string()));
+			}
+		}
+		for name in &self.builtins {
+			items.push(completion_item(name, COMPLETION_FUNCTION, "builtin"));
+		}
+		for keyword in KEYWORDS.iter().chain(FUNCTION_KEYWORDS.iter()) {
+			items.push(completion_item(keyword, COMPLETION_KEYWORD, "keyword"));
+		}
+		Value::Array(items)
+	}
+}
+
+fn completion_item(label: &str, kind: u32, detail: &str) -> Value {
+	json!({"label": label, "kind": kind, "detail": detail})
+}
+
+/// Hover text for a named function, type or variable
+fn describe_symbol(document: &Document, word: &str) -> Option<String> {
+	if let Some(f) = document.ctx.user_functions.get(word) {
+		return Some(function_signature(f));
+	}
+	if let Some(t) = document.ctx.type_registry.get_by_name(word) {
+		let fields: Vec<String> = t.fields.iter().map(|f| format!("{}:{}", f.name, f.type_name)).collect();
+		return Some(format!("class {}{{{}}}", t.name, fields.join(" ")));
+	}
+	let local = document.scope.lookup(word)?;
+	Some(format!("{}: {}", word, local.kind))
+}
+
+fn function_signature(f: &crate::context::UserFunctionDef) -> String {
+	let params: Vec<&str> = f.params.iter().map(|(name, _)| name.as_str()).collect();
+	format!("{}({}) -> {}", f.name, params.join(", "), f.return_kind)
+}
+
+/// Innermost node whose LineInfo starts on `line` at or before `column` (both 1-based)
+fn node_at(node: &Node, line: usize, column: usize) -> Option<&Node> {
+	let children: Vec<&Node> = match node.drop_meta() {
+		Node::Key(left, _, right) => vec![&**left, &**right],
+		Node::List(items, _, _) => items.iter().collect(),
+		_ => vec![],
+	};
+	let inner = children.into_iter().filter_map(|child| node_at(child, line, column)).last();
+	if inner.is_some() {
+		return inner;
+	}
+	match node.get_lineinfo() {
+		Some(info) if info.line_nr == line && info.column <= column => Some(node),
+		_ => None,
+	}
+}
+
+/// Position of the innermost statement for which `defines` holds
+fn definition_site(node: &Node, defines: &dyn Fn(&Node) -> bool) -> Option<LineInfo> {
+	if let Node::List(items, _, _) = node.drop_meta() {
+		if let Some(info) = items.iter().find_map(|item| definition_site(item, defines)) {
+			return Some(info);
+		}
+	}
+	if defines(node) {
+		node.get_lineinfo()
+	} else {
+		None
+	}
+}
+
+/// Range from the LineInfo column to the end of its line, 0-based, in UTF-16 code units
+fn line_range(info: &LineInfo, text: &str) -> Value {
+	let line = info.line_nr.saturating_sub(1);
+	let line_text = text.lines().nth(line).unwrap_or("");
+	let start = utf16_column(line_text, info.column.saturating_sub(1));
+	let end = line_text.encode_utf16().count().max(start + 1);
+	json!({
+		"start": {"line": line, "character": start},
+		"end": {"line": line, "character": end}
+	})
+}
+
+fn diagnostic(error: &WarpError, text: &str) -> Value {
+	let range = match error.position() {
+		Some(info) => line_range(info, text),
+		None => json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 1}}),
+	};
+	json!({
+		"range": range,
+		"severity": 1,
+		"source": "warp",
+		"message": error.to_string()
+	})
+}
+
+/// LSP counts columns in UTF-16 code units, LineInfo and word_at in chars
+fn utf16_column(line: &str, chars: usize) -> usize {
+	line.chars().take(chars).map(char::len_utf16).sum()
+}
+
+fn char_column(line: &str, utf16: usize) -> usize {
+	let mut units = 0;
+	line.chars().take_while(|c| {
+		units += c.len_utf16();
+		units <= utf16
+	}).count()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+	json!({
+		"jsonrpc": "2.0",
+		"method": "textDocument/publishDiagnostics",
+		"params": {"uri": uri, "diagnostics": diagnostics}
+	})
+}
+
+/// Read the next Content-Length framed message; None only at the end of input
+/// Messages with a broken header or body are logged and skipped
+pub fn read_message<R: BufRead>(reader: &mut R) -> Option<Value> {
+	loop {
+		let mut length = None;
+		let mut header_error = None;
+		loop {
+			let mut header = String::new();
+			if reader.read_line(&mut header).ok()? == 0 {
+				return None;
+			}
+			let header = header.trim();
+			if header.is_empty() {
+				break;
+			}
+			if let Some(value) = header.strip_prefix("Content-Length:") {
+				match value.trim().parse::<usize>() {
+					Ok(value) => length = Some(value),
+					Err(_) => header_error = Some(format!("bad header '{}'", header)),
+				}
+			}
+		}
+		let length = match (length, header_error) {
+			(Some(length), None) => length,
+			(_, error) => {
+				warn!("lsp: skipping message: {}", error.unwrap_or_else(|| "no Content-Length".to_string()));
+				continue;
+			}
+		};
+		let mut body = vec![0; length];
+		reader.read_exact(&mut body).ok()?;
+		match serde_json::from_slice(&body) {
+			Ok(message) => return Some(message),
+			Err(err) => warn!("lsp: skipping message with invalid JSON: {}", err),
+		}
+	}
+}
+
+/// Write one Content-Length framed message
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> std::io::Result<()> {
+	let body = message.to_string();
+	write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+	writer.flush()
+}
+
+/// Serve a session until `exit` or end of input
+pub fn serve<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> std::io::Result<()> {
+	let mut server = LspServer::new();
+	while let Some(message) = read_message(reader) {
+		for reply in server.handle(&message) {
+			write_message(writer, &reply)?;
+		}
+		if server.exited() {
+			break;
+		}
+	}
+	Ok(())
+}
+
+/// `warp lsp`: serve on stdin/stdout
+pub fn serve_stdio() -> std::io::Result<()> {
+	let stdin = std::io::stdin();
+	let stdout = std::io::stdout();
+	serve(&mut stdin.lock(), &mut stdout.lock())
+}
@@ -5,13 +5,22 @@ use extensions::numbers::*;
 use extensions::strings::*;
 use extensions::utils::*;
 pub mod node;
+pub mod compiled;
 pub mod context;
+pub mod convert;
+pub mod cst;
+pub mod error;
+pub mod formatter;
 pub mod wasm_emitter;
 pub mod wasm_reader;
+pub mod wasm_linker;
 pub mod wasp_parser;
+pub mod wisp_parser;
+pub mod yaml;
 pub mod type_kinds;
 pub mod gc_traits;
 pub mod analyzer;
+pub mod checks;
 pub mod ast;
 pub mod meta;
 pub mod smarty;
@@ -24,6 +33,8 @@ pub mod function;
 pub mod normalize;
 pub mod run;
 pub mod local;
+pub mod lsp;
+pub mod session;
 use std::env;
 use std::fs;
 use std::io::{self, Read, IsTerminal};
@@ -73,6 +84,26 @@ fn main() {
         return;
     }
 
+    if matches!(args[1].as_str(), "compile" | "build") {
+        compile_command(&args[2..]);
+        return;
+    }
+
+    if matches!(args[1].as_str(), "fmt" | "format") {
+        fmt_command(&args[2..]);
+        return;
+    }
+
+    if args[1] == "convert" {
+        convert_command(&args[2..]);
+        return;
+    }
+
+    if matches!(args[1].as_str(), "test" | "tests") {
+        test_command(&args[2..]);
+        return;
+    }
+
     if arg_string.ends_with(".html") || arg_string.ends_with(".htm") {
         #[cfg(feature = "WEBAPP")]
         {
@@ -96,27 +127,12 @@ fn main() {
         std::process::exit(node_to_i32(&result));
     } else if arg_string.ends_with(".wasm") {
         if args.len() >= 3 {
-            #[cfg(any(feature = "WABT_MERGE", feature = "INCLUDE_MERGER"))]
-            {
-                // merge_files
-                todo!("linking files needs compilation with WABT_MERGE");
-            }
-            #[cfg(not(any(feature = "WABT_MERGE", feature = "INCLUDE_MERGER")))]
-            {
-                todo!("linking files needs compilation with WABT_MERGE");
-            }
+            link_command(&args[1..]);
         } else {
             let result = run::wasmtime_runner::run(&arg_string);
             println!("{}", result.serialize());
             std::process::exit(node_to_i32(&result));
         }
-    } else if arg_string == "test" || arg_string == "tests" {
-        #[cfg(not(feature = "release"))]
-        {
-            println!("Run tests with: cargo test");
-        }
-        #[cfg(feature = "release")]
-        println!("warp release compiled without tests");
     } else if matches!(arg_string.as_str(), "home" | "wiki" | "docs" | "documentation") {
         println!("Wasp documentation can be found at https://github.com/pannous/warp/wiki");
         #[cfg(not(feature = "wasm"))]
@@ -167,17 +183,15 @@ fn main() {
     } else if arg_string == "lsp" {
         #[cfg(not(feature = "wasm"))]
         {
-            // lsp_main();
-            println!("LSP not yet implemented");
+            if let Err(e) = lsp::serve_stdio() {
+                eprintln!("LSP error: {}", e);
+                std::process::exit(1);
+            }
         }
     } else if arg_string.contains("help") {
         println!("detailed documentation can be found at https://github.com/pannous/warp/wiki");
     } else if arg_string == "version" || arg_string == "--version" || arg_string == "-v" {
         println!("Wasp 🐝 {}", WARP_VERSION);
-    } else if arg_string.contains("compile") || arg_string.contains("build") || arg_string.contains("link") {
-        let code = extract_after(&arg_string, " ");
-        let _result = eval(&code);
-        // TODO: don't run, just compile and save binary
     } else {
         // Default: eval and print
         let result = eval(&arg_string);
@@ -189,19 +203,361 @@ fn usage() {
     // println!("Usage: warp [options] [file]");
     println!("  warp <file.warp>     Execute a warp file");
     println!("  warp <file.wasm>     Run a wasm file");
+    println!("  warp a.wasm b.wasm [-o out.wasm]  Link modules, then run or write them");
     println!("  warp eval <code>     Evaluate code");
+    println!("  warp compile <file.wasp> [-o out.wasm]  Compile to wasm without running");
+    println!("      --no-tree-shaking --no-kind-globals --host --wasi --ffi");
+    println!("  warp fmt [--check|--write] <files|dirs>  Rewrite sources in the canonical style");
+    println!("  warp convert [in] --to wasp|json|xml|wisp|yaml [--from fmt] [-o out]  Convert data files");
     println!("  warp repl            Start interactive console");
-    println!("  warp test            Run tests");
+    println!("  warp test [--json] [files|dirs]  Run check statements in wasp files");
+    println!("  warp lsp             Start language server on stdio");
     println!("  warp docs            Open documentation");
     println!("  warp version         Show version");
     println!("  warp help            Show this help");
 }
 
+/// warp compile foo.wasp -o foo.wasm [--no-tree-shaking] [--no-kind-globals] [--host] [--wasi] [--ffi]
+fn compile_command(args: &[String]) {
+    use wasm_emitter::{compile_bytes, EmitterConfig};
+
+    let mut input: Option<&str> = None;
+    let mut output: Option<&str> = None;
+    let mut builder = EmitterConfig::builder().tree_shaking(true);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = iter.next().map(|s| s.as_str()),
+            "--no-tree-shaking" => builder = builder.tree_shaking(false),
+            "--no-kind-globals" => builder = builder.kind_globals(false),
+            "--host" => builder = builder.host_imports(true),
+            "--wasi" => builder = builder.wasi_imports(true),
+            "--ffi" => builder = builder.ffi_imports(true),
+            flag if flag.starts_with('-') => {
+                eprintln!("Unknown option '{}'", flag);
+                std::process::exit(1);
+            }
+            file => input = Some(file),
+        }
+    }
+
+    let input = match input {
+        Some(file) => file,
+        None => {
+            eprintln!("Usage: warp compile <file.wasp> [-o out.wasm]");
+            std::process::exit(1);
+        }
+    };
+    if !file_exists(input) {
+        eprintln!("Error: Could not read file '{}'", input);
+        std::process::exit(1);
+    }
+    let output = output
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::Path::new(input).with_extension("wasm"));
+
+    let code = load_file(input);
+    let bytes = match compile_bytes(&code, builder.build()) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = fs::write(&output, &bytes) {
+        eprintln!("Error: Could not write '{}': {}", output.display(), e);
+        std::process::exit(1);
+    }
+    println!("{} → {} ({} bytes)", input, output.display(), bytes.len());
+}
+
+/// warp fmt [--check | --write] [paths…]; without paths, formats stdin to stdout
+/// --check exits with 1 if any file would change, parse errors exit with 2
+fn fmt_command(args: &[String]) {
+    use formatter::format_source;
+    use normalize::{set_hint_mode, HintMode};
+
+    set_hint_mode(HintMode::Off); // the formatter applies the hints itself
+    let mut check = false;
+    let mut write = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-w" | "--write" => write = true,
+            flag if flag.starts_with('-') => {
+                eprintln!("Unknown option '{}'", flag);
+                std::process::exit(1);
+            }
+            path => collect_sources(std::path::Path::new(path), &mut files),
+        }
+    }
+
+    if args.iter().all(|arg| arg.starts_with('-')) {
+        let mut code = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut code) {
+            eprintln!("Error: Could not read stdin: {}", e);
+            std::process::exit(1);
+        }
+        match format_source(&code) {
+            Ok(formatted) if check && formatted != code => {
+                eprintln!("stdin is not formatted");
+                std::process::exit(1);
+            }
+            Ok(_) if check => {}
+            Ok(formatted) => print!("{}", formatted),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+
+    let mut unformatted = 0;
+    let mut failed = 0;
+    for file in &files {
+        let code = match fs::read_to_string(file) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("Error: Could not read '{}': {}", file.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        let formatted = match format_source(&code) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}: {}", file.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        if check {
+            if formatted != code {
+                println!("would reformat {}", file.display());
+                unformatted += 1;
+            }
+        } else if write {
+            if formatted != code {
+                match fs::write(file, &formatted) {
+                    Ok(()) => println!("formatted {}", file.display()),
+                    Err(e) => {
+                        eprintln!("Error: Could not write '{}': {}", file.display(), e);
+                        failed += 1;
+                    }
+                }
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+    if failed > 0 {
+        std::process::exit(2);
+    }
+    if unformatted > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// warp convert [in] --to fmt [--from fmt] [-o out]: stdin and stdout when no files are given
+fn convert_command(args: &[String]) {
+    use convert::{convert, Format};
+    let mut input: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut from: Option<Format> = None;
+    let mut to: Option<Format> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("--to", Some(name)) | ("--from", Some(name)) => {
+                let Some(format) = Format::from_name(name) else {
+                    eprintln!("Unknown format {} (wasp, json, xml, wisp, yaml)", name);
+                    std::process::exit(2);
+                };
+                if args[i] == "--to" { to = Some(format) } else { from = Some(format) }
+                i += 1;
+            }
+            ("-o", Some(path)) => {
+                output = Some(path.clone());
+                i += 1;
+            }
+            ("-", _) => input = None,
+            (flag, _) if flag.starts_with('-') => {
+                eprintln!("Unknown option {}", flag);
+                std::process::exit(2);
+            }
+            (path, _) => input = Some(path.to_string()),
+        }
+        i += 1;
+    }
+    let to = to.or_else(|| output.as_deref().and_then(Format::from_path)).unwrap_or_else(|| {
+        eprintln!("Usage: warp convert [in] --to wasp|json|xml|wisp|yaml [--from fmt] [-o out]");
+        std::process::exit(2);
+    });
+
+    let content = match &input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Error: could not read {}: {}", path, e);
+            std::process::exit(2);
+        }),
+        None => {
+            let mut content = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut content) {
+                eprintln!("Error: could not read stdin: {}", e);
+                std::process::exit(2);
+            }
+            content
+        }
+    };
+    let from = from
+        .or_else(|| input.as_deref().and_then(Format::from_path))
+        .unwrap_or_else(|| Format::detect(&content));
+
+    let conversion = match convert(&content, from, to) {
+        Ok(conversion) => conversion,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    for lost in &conversion.lost {
+        eprintln!("warning: {}", lost);
+    }
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, &conversion.output) {
+                eprintln!("Error: could not write {}: {}", path, e);
+                std::process::exit(2);
+            }
+        }
+        None => print!("{}", conversion.output),
+    }
+}
+
+/// warp test [--json] [paths]: run every `check` statement, exit 1 if one fails
+fn test_command(args: &[String]) {
+    use normalize::{set_hint_mode, HintMode};
+    set_hint_mode(HintMode::Off);
+    let mut json = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            flag if flag.starts_with('-') => {
+                eprintln!("Unknown option {}", flag);
+                std::process::exit(2);
+            }
+            path => collect_sources(std::path::Path::new(path), &mut files),
+        }
+    }
+    if args.iter().all(|a| a.starts_with('-')) {
+        collect_sources(std::path::Path::new("."), &mut files);
+    }
+
+    let report = checks::run_check_files(&files);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        for result in &report.results {
+            let mark = if result.passed { "✓" } else { "✗" };
+            println!("{} {}:{} {}", mark, result.file, result.line_nr, result.line.trim());
+            if result.passed {
+                continue;
+            }
+            if let Some(error) = &result.error {
+                println!("    error: {}", error);
+            }
+            if let Some(left) = &result.left {
+                println!("    left:  {}", left.serialize());
+            }
+            if let Some(right) = &result.right {
+                println!("    right: {}", right.serialize());
+            }
+        }
+        println!("{} passed, {} failed", report.passed(), report.failed());
+    }
+    if !report.success() {
+        std::process::exit(1);
+    }
+}
+
+/// Directories a search for sources skips: build output, dependencies and hidden ones like .git
+fn is_skipped_dir(path: &std::path::Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    name.starts_with('.') || name == "target" || name == "node_modules"
+}
+
+/// Files given directly, or the .wasp/.warp files below a directory
+fn collect_sources(path: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let mut entries: Vec<_> = match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) => {
+            eprintln!("Error: Could not read directory '{}': {}", path.display(), e);
+            return;
+        }
+    };
+    entries.sort();
+    for entry in entries {
+        let is_source = matches!(entry.extension().and_then(|e| e.to_str()), Some("wasp" | "warp"));
+        if (entry.is_dir() && !is_skipped_dir(&entry)) || is_source {
+            collect_sources(&entry, files);
+        }
+    }
+}
+
+/// warp a.wasm b.wasm [-o merged.wasm]: link modules, then run or write the result
+fn link_command(args: &[String]) {
+    let mut files = Vec::new();
+    let mut output: Option<String> = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "-o" && i + 1 < args.len() {
+            output = Some(args[i + 1].clone());
+            i += 1;
+        } else {
+            files.push(args[i].clone());
+        }
+        i += 1;
+    }
+    let bytes = match wasm_linker::link_files(&files) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = output {
+        if let Err(e) = fs::write(&path, &bytes) {
+            eprintln!("Error: could not write {}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("Linked {} modules into {} ({} bytes)", files.len(), path, bytes.len());
+        return;
+    }
+    // the merged module keeps the host, wasi and ffi imports of its parts
+    match compiled::CompiledModule::from_bytes(bytes).and_then(|module| module.run()) {
+        Ok(result) => {
+            println!("{}", result.serialize());
+            std::process::exit(node_to_i32(&result));
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn console() {
     use rustyline::error::ReadlineError;
     use rustyline::DefaultEditor;
 
-    println!("Interactive console (Ctrl+D to exit)");
+    println!("Interactive console (Ctrl+D to exit, :help for commands)");
 
     let mut rl = match DefaultEditor::new() {
         Ok(editor) => editor,
@@ -214,6 +570,7 @@ fn console() {
     // Load history if exists
     let history_path = dirs_home().join(".warp_history");
     let _ = rl.load_history(&history_path);
+    let mut session = session::Session::new();
 
     loop {
         match rl.readline("🐝 ") {
@@ -222,8 +579,14 @@ fn console() {
                 if input.is_empty() { continue; }
                 if input == "exit" || input == "quit" { break; }
                 let _ = rl.add_history_entry(input);
-                let result = eval(input);
-                println!("» {}", result.serialize());
+                if input.starts_with(':') {
+                    console_command(&mut session, input);
+                    continue;
+                }
+                match session.eval(input) {
+                    Ok(result) => println!("» {}", result.serialize()),
+                    Err(e) => eprintln!("{}", e),
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("^C");
@@ -241,6 +604,41 @@ fn console() {
     let _ = rl.save_history(&history_path);
 }
 
+/// :ast :type :wat :load :save :clear inside the console
+fn console_command(session: &mut session::Session, input: &str) {
+    let (command, arg) = input.split_once(' ').unwrap_or((input, ""));
+    let arg = arg.trim();
+    match command {
+        ":ast" => println!("{}", session.ast(arg).serialize()),
+        ":type" => println!("{:?}", session.kind(arg)),
+        ":wat" => match session.wat(arg) {
+            Ok(wat) => println!("{}", wat),
+            Err(e) => eprintln!("{}", e),
+        },
+        ":load" => match session.load(arg) {
+            Ok(result) => println!("» {}", result.serialize()),
+            Err(e) => eprintln!("{}", e),
+        },
+        ":save" => {
+            let path = if arg.is_empty() { "session.wasp" } else { arg };
+            match session.save(path) {
+                Ok(()) => println!("saved {}", path),
+                Err(e) => eprintln!("Error: could not write {}: {}", path, e),
+            }
+        }
+        ":clear" => session.clear(),
+        ":help" => {
+            println!("  :ast <code>       Show the parsed node");
+            println!("  :type <code>      Show the inferred kind");
+            println!("  :wat <code>       Show the emitted module");
+            println!("  :load <file>      Evaluate a file and keep its definitions");
+            println!("  :save [file]      Write definitions to a file (session.wasp)");
+            println!("  :clear            Forget all definitions");
+        }
+        _ => eprintln!("Unknown command {} (try :help)", command),
+    }
+}
+
 fn dirs_home() -> std::path::PathBuf {
     env::var("HOME").map(std::path::PathBuf::from)
         .unwrap_or_else(|_| std::path::PathBuf::from("."))
@@ -257,7 +655,3 @@ fn file_exists(path: &str) -> bool {
     std::path::Path::new(path).exists()
 }
 
-fn extract_after(s: &str, sep: &str) -> String {
-    s.split_once(sep).map(|(_, after)| after.to_string()).unwrap_or_default()
-}
-
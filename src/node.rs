@@ -7,6 +7,7 @@ use crate::extensions::strings::StringExtensions;
 use crate::meta::{CloneAny, Dada, DataType, LineInfo};
 use crate::wasm_reader::GcObject;
 use regex::Regex;
+use serde::de::{MapAccess, SeqAccess, Visitor};
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::Any;
@@ -263,6 +264,9 @@ impl Node {
 	/// Convert compact 3-field WASM GC object to Node
 	/// Layout: kind (i64), data (ref null any), value (ref null $Node)
 	pub fn from_gc_object(obj: &GcObject) -> Node {
+		if obj.is_user_struct() {
+			return Self::read_instance_from_gc(obj);
+		}
 		// Read the kind field (i64), lower 8 bits are the tag
 		let kind = match obj.kind() {
 			Ok(k) => k,
@@ -376,10 +380,34 @@ impl Node {
 				Type { name, body }
 			}
 
+			t if t == Kind::Closure as u8 => Symbol("closure".to_string()),
+
 			_ => Text(format!("Unknown Kind: {}", tag)),
 		}
 	}
 
+	/// Read a user struct as its instance: Circle{r:2}
+	/// Enum variants and classes in a hierarchy lead with a tag, which is skipped
+	fn read_instance_from_gc(obj: &GcObject) -> Node {
+		let (type_name, field_names) = match obj.struct_names() {
+			Ok(names) => names,
+			Err(e) => return Text(format!("Error reading instance: {}", e)),
+		};
+		let first = obj.is_tagged() as usize;
+		let fields = (first..field_names.len())
+			.map(|idx| {
+				let name = field_names[idx].clone().unwrap_or_else(|| idx.to_string());
+				let value = obj.field_node(idx).unwrap_or(Empty);
+				Key(Box::new(Symbol(name)), Op::Colon, Box::new(value))
+			})
+			.collect();
+		Key(
+			Box::new(Symbol(type_name.unwrap_or_default())),
+			Op::Colon,
+			Box::new(List(fields, Bracket::Curly, Separator::None)),
+		)
+	}
+
 	/// Read a list from compact GC representation
 	fn read_list_from_gc(obj: &GcObject, bracket: Bracket, _kind: i64) -> Node {
 		let mut items = Vec::new();
@@ -727,12 +755,14 @@ impl Node {
 
 	pub fn get_lineinfo(&self) -> Option<LineInfo> {
 		match self {
-			Meta { data, .. } => {
+			Meta { node, data } => {
 				if let Data(dada) = data.as_ref() {
-					dada.downcast_ref::<LineInfo>().cloned()
-				} else {
-					None
+					if let Some(info) = dada.downcast_ref::<LineInfo>() {
+						return Some(info.clone());
+					}
 				}
+				// LineInfo may sit below other metadata such as comments
+				node.get_lineinfo()
 			}
 			_ => None,
 		}
@@ -1107,8 +1137,19 @@ impl Node {
 		}
 	}
 
+	/// Read plain JSON: objects become Curly lists of keys in source order, arrays Square lists
 	pub fn from_json(json: &str) -> Result<Node, serde_json::Error> {
-		serde_json::from_str(json)
+		serde_json::from_str::<JsonNode>(json).map(|json| json.0)
+	}
+
+	/// Read YAML: mappings become Curly lists of keys, sequences Square lists
+	pub fn from_yaml(yaml: &str) -> Result<Node, crate::error::WarpError> {
+		crate::yaml::parse_yaml(yaml)
+	}
+
+	/// Block style YAML, several documents for a Newline separated list of them
+	pub fn to_yaml(&self) -> String {
+		crate::yaml::to_yaml(self)
 	}
 }
 
@@ -1166,6 +1207,68 @@ impl IntoIterator for &Node {
 	}
 }
 
+/// Plain JSON value read into a node, keeping the key order of objects
+struct JsonNode(Node);
+
+impl<'de> Deserialize<'de> for JsonNode {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		deserializer.deserialize_any(JsonVisitor).map(JsonNode)
+	}
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+	type Value = Node;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a JSON value")
+	}
+
+	fn visit_bool<E>(self, b: bool) -> Result<Node, E> {
+		Ok(if b { True } else { False })
+	}
+
+	fn visit_i64<E>(self, n: i64) -> Result<Node, E> {
+		Ok(Node::int(n))
+	}
+
+	fn visit_u64<E>(self, n: u64) -> Result<Node, E> {
+		Ok(i64::try_from(n).map(Node::int).unwrap_or_else(|_| Node::float(n as f64)))
+	}
+
+	fn visit_f64<E>(self, n: f64) -> Result<Node, E> {
+		Ok(Node::float(n))
+	}
+
+	fn visit_str<E>(self, s: &str) -> Result<Node, E> {
+		Ok(Text(s.to_string()))
+	}
+
+	fn visit_unit<E>(self) -> Result<Node, E> {
+		Ok(Empty)
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+		let mut items = Vec::new();
+		while let Some(JsonNode(item)) = seq.next_element()? {
+			items.push(item);
+		}
+		Ok(List(items, Bracket::Square, Separator::Colon))
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+		let mut entries = Vec::new();
+		while let Some((key, JsonNode(value))) = map.next_entry::<String, JsonNode>()? {
+			entries.push(Key(Box::new(Text(key)), Op::Colon, Box::new(value)));
+		}
+		Ok(List(entries, Bracket::Curly, Separator::Colon))
+	}
+}
+
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum Bracket {
 	Curly,  // '{'
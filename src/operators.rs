@@ -30,6 +30,7 @@ pub enum Op {
 	// Structural operators (existing)
 	Colon,    // :   type annotation and object construction person:{name:"Joe" age:42}
 	Dot,      // .   member access
+	SafeDot,  // ?.  member access that gives null on null
 	Scope,    // ::  scope resolution
 	Define,   // :=  definition
 	Assign,   // =   assignment
@@ -81,7 +82,8 @@ pub enum Op {
 	Cube,   // ³
 
 	// Ternary
-	Question, // ? (ternary condition)
+	Question, // ? (ternary condition), as suffix an optional type: Tree?
+	Coalesce, // ?? (value unless null)
 
 	// Conditional
 	If,   // if
@@ -90,7 +92,12 @@ pub enum Op {
 
 	// Loop
 	While, // while
-	Do,    // do (used with while)
+	Do,    // do (used with while and for)
+	For,   // for
+	In,    // in (used with for)
+
+	// Pattern matching
+	Match, // match subject { pattern => value ... }
 
 	// Index/Range
 	Hash,  // #  (1-based index)
@@ -117,7 +124,7 @@ impl Op {
 			Op::Inc | Op::Dec => (195, 0),
 
 			// Member access (tightest infix)
-			Op::Dot => (180, 181),
+			Op::Dot | Op::SafeDot => (180, 181),
 			Op::Scope => (175, 176),
 			Op::Hash => (170, 171), // index operator #
 
@@ -133,6 +140,9 @@ impl Op {
 			// Range
 			Op::Range | Op::To => (130, 131),
 
+			// Null fallback (right-assoc: a ?? b ?? c = a ?? (b ?? c))
+			Op::Coalesce => (128, 127),
+
 			// Type cast (binds tighter than comparison: 1.5 as int == 1)
 			Op::As => (125, 126),
 
@@ -156,6 +166,9 @@ impl Op {
 			// Loop: while condition do body
 			Op::While => (0, 78), // prefix: while binds condition until do/block
 			Op::Do => (77, 10),   // do binds very loosely to capture whole body including assignments
+			Op::For => (0, 78),   // prefix: for x in items binds the items until do/block
+			Op::In => (120, 121),
+			Op::Match => (0, 78), // prefix: match binds the subject until its block of arms
 
 			// Structural/Key operators (existing, adjusted for consistency)
 			Op::Colon => (80, 81),    // type annotation: a:b:c → a:(b:c)
@@ -182,6 +195,7 @@ impl Op {
 			// Structural
 			Op::Colon => ":",
 			Op::Dot => ".",
+			Op::SafeDot => "?.",
 			Op::Scope => "::",
 			Op::Define => ":=",
 			Op::Assign => "=",
@@ -234,6 +248,7 @@ impl Op {
 
 			// Ternary/Index/Range
 			Op::Question => "?",
+			Op::Coalesce => "??",
 			Op::Hash => "#",
 			Op::Range => "..",
 			Op::To => "to",
@@ -249,6 +264,11 @@ impl Op {
 			// Loop
 			Op::While => "while",
 			Op::Do => "do",
+			Op::For => "for",
+			Op::In => "in",
+
+			// Pattern matching
+			Op::Match => "match",
 
 			Op::None => "",
 		}
@@ -0,0 +1,215 @@
+//! REPL session that remembers definitions between entries
+//!
+//! Every entry is evaluated after the functions and types kept from earlier entries and
+//! the values their globals were left with, so `x=3` on one line is still known on the next.
+//! Entries are run once: their other statements, like `puts`, are not replayed later.
+
+use crate::analyzer::{collect_variables, infer_type, Scope};
+use crate::compiled::compile;
+use crate::error::{find_parse_error, WarpError};
+use crate::formatter::format_node;
+use crate::node::{Bracket, Node, Separator};
+use crate::normalize::style;
+use crate::operators::{is_function_keyword, Op};
+use crate::type_kinds::Kind;
+use crate::wasm_emitter::try_eval_source;
+use crate::wasp_parser::WaspParser;
+use std::fs;
+
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+	/// Functions and types defined so far, in the order they were entered
+	definitions: Vec<String>,
+	/// Each global with the assignment of its current value, in the order they were first assigned
+	globals: Vec<(String, String)>,
+}
+
+impl Session {
+	pub fn new() -> Self {
+		Session::default()
+	}
+
+	/// Evaluate an entry in the context of all earlier definitions
+	/// Functions and types it defines and the values of globals it assigns are kept when it succeeds
+	pub fn eval(&mut self, input: &str) -> Result<Node, WarpError> {
+		let node = WaspParser::parse(input);
+		if let Some(err) = find_parse_error(&node) {
+			return Err(err);
+		}
+		if matches!(node.drop_meta(), Node::Empty) {
+			// blank and comment-only lines
+			return Ok(Node::Empty);
+		}
+		let statements = statements(&node);
+		let mut names: Vec<&str> = Vec::new();
+		for name in statements.iter().filter_map(|statement| assigned_global(statement)) {
+			if !names.contains(&name) {
+				names.push(name);
+			}
+		}
+		let (result, values) = if names.is_empty() {
+			(try_eval_source(&self.program(input))?, vec![])
+		} else {
+			self.eval_capturing(&statements, &names)?
+		};
+		let style = style();
+		for statement in &statements {
+			if is_definition(statement) && assigned_global(statement).is_none() {
+				self.definitions.push(format_node(statement, &style));
+			}
+		}
+		for (name, value) in names.into_iter().zip(values) {
+			if is_reproducible(&value) {
+				self.set_global(name, format!("{} = {}", name, format_node(&value, &style)));
+			} else {
+				// values like closures cannot be written down, their assignments are kept instead
+				let assignments = statements.iter().filter(|statement| assigned_global(statement) == Some(name));
+				self.definitions.extend(assignments.map(|statement| format_node(statement, &style)));
+				self.globals.retain(|(global, _)| global != name);
+			}
+		}
+		Ok(result)
+	}
+
+	/// Run the entry once, reading back its value and the values of the globals it assigns: x=3; x+1 runs as x=3; [x+1, x]
+	fn eval_capturing(&self, statements: &[&Node], names: &[&str]) -> Result<(Node, Vec<Node>), WarpError> {
+		let style = style();
+		let Some((last, body)) = statements.split_last() else {
+			return Ok((Node::Empty, Vec::new()));
+		};
+		let mut lines: Vec<String> = body.iter().map(|statement| format_node(statement, &style)).collect();
+		let mut captured: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+		let last_is_value = !is_definition(last);
+		if last_is_value {
+			captured.insert(0, format_node(last, &style));
+		} else {
+			lines.push(format_node(last, &style));
+		}
+		lines.push(format!("[{}]", captured.join(", ")));
+		let mut values = match try_eval_source(&self.program(&lines.join("\n")))?.drop_meta() {
+			Node::List(values, ..) if values.len() == captured.len() => values.clone(),
+			value if captured.len() == 1 => vec![value.clone()],
+			value => {
+				return Err(WarpError::Compile {
+					message: format!("cannot read back the globals {} from {}", names.join(", "), value.serialize()),
+					position: None,
+				})
+			}
+		};
+		let result = if last_is_value {
+			values.remove(0)
+		} else {
+			match assigned_global(last).and_then(|name| names.iter().position(|n| *n == name)) {
+				Some(i) => values[i].clone(),
+				None => Node::Empty,
+			}
+		};
+		Ok((result, values))
+	}
+
+	fn set_global(&mut self, name: &str, assignment: String) {
+		match self.globals.iter_mut().find(|(global, _)| global == name) {
+			Some((_, kept)) => *kept = assignment,
+			None => self.globals.push((name.to_string(), assignment)),
+		}
+	}
+
+	/// The parsed entry, for :ast
+	pub fn ast(&self, input: &str) -> Node {
+		WaspParser::parse(input)
+	}
+
+	/// Inferred kind of the entry's value, for :type
+	pub fn kind(&self, input: &str) -> Kind {
+		let program = WaspParser::parse(&self.program(input));
+		let mut scope = Scope::new();
+		collect_variables(&program, &mut scope);
+		match program.drop_meta() {
+			Node::List(items, _, _) if self.has_definitions() && !items.is_empty() => infer_type(&items[items.len() - 1], &scope),
+			_ => infer_type(&program, &scope),
+		}
+	}
+
+	/// The module emitted for the entry, for :wat
+	pub fn wat(&self, input: &str) -> Result<String, WarpError> {
+		Ok(compile(&self.program(input))?.wat())
+	}
+
+	/// Evaluate a source file as one entry, for :load
+	pub fn load(&mut self, path: &str) -> Result<Node, WarpError> {
+		let code = fs::read_to_string(path).map_err(|e| WarpError::Link {
+			message: format!("cannot read {}: {}", path, e),
+		})?;
+		self.eval(&code)
+	}
+
+	/// Write the kept definitions as a wasp file, for :save
+	pub fn save(&self, path: &str) -> std::io::Result<()> {
+		fs::write(path, self.source())
+	}
+
+	/// All kept definitions and global values as one program
+	pub fn source(&self) -> String {
+		let globals = self.globals.iter().map(|(_, assignment)| assignment);
+		self.definitions.iter().chain(globals).map(|d| format!("{}\n", d)).collect()
+	}
+
+	/// Forget everything defined so far
+	pub fn clear(&mut self) {
+		self.definitions.clear();
+		self.globals.clear();
+	}
+
+	fn has_definitions(&self) -> bool {
+		!self.definitions.is_empty() || !self.globals.is_empty()
+	}
+
+	fn program(&self, input: &str) -> String {
+		format!("{}{}", self.source(), input)
+	}
+}
+
+/// Top level statements of an entry: x=3; x+1 has two
+fn statements(node: &Node) -> Vec<&Node> {
+	match node.drop_meta() {
+		Node::List(items, Bracket::None, Separator::Newline | Separator::Semicolon) if !items.is_empty() => items.iter().collect(),
+		_ => vec![node],
+	}
+}
+
+/// The global a statement assigns: x in x = 3, x := 3, x += 1 or x:int = 3
+fn assigned_global(node: &Node) -> Option<&str> {
+	match node.drop_meta() {
+		Node::Key(left, op, _) if matches!(op, Op::Assign | Op::Define) || op.is_compound_assign() => match left.drop_meta() {
+			Node::Symbol(name) => Some(name),
+			Node::Key(name, Op::Colon, _) => match name.drop_meta() {
+				Node::Symbol(name) => Some(name),
+				_ => None,
+			},
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Can the value be written as wasp and read back the same?
+fn is_reproducible(node: &Node) -> bool {
+	match node.drop_meta() {
+		Node::Data(_) | Node::Error(_) => false,
+		Node::List(items, _, _) => items.iter().all(is_reproducible),
+		Node::Key(left, _, right) => is_reproducible(left) && is_reproducible(right),
+		Node::Type { .. } => false,
+		_ => true,
+	}
+}
+
+fn is_definition(node: &Node) -> bool {
+	match node.drop_meta() {
+		Node::Key(_, Op::Assign | Op::Define, _) => true,
+		Node::Key(_, op, _) if op.is_compound_assign() => true,
+		Node::Key(left, Op::Colon, _) => matches!(left.drop_meta(), Node::Symbol(s) if s == "global"),
+		Node::Type { .. } => true,
+		Node::List(items, _, _) => matches!(items.first().map(|i| i.drop_meta()), Some(Node::Symbol(s)) if is_function_keyword(s) || s == "class"),
+		_ => false,
+	}
+}
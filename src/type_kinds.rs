@@ -25,6 +25,7 @@ pub enum Kind {
 	Pointer = 13,  // FFI pointer (i64 handle)
 	Int32 = 14,    // explicit i32 (for FFI)
 	Float32 = 15,  // explicit f32 (for FFI)
+	Closure = 16,  // data=$Closure struct: funcref + captured environment
 }
 
 impl Kind {
@@ -96,6 +97,7 @@ impl std::fmt::Display for Kind {
 			Kind::Error => write!(f, "error"),
 			Kind::TypeDef => write!(f, "typedef"),
 			Kind::Pointer => write!(f, "pointer"),
+			Kind::Closure => write!(f, "closure"),
 		}
 	}
 }
@@ -114,6 +116,18 @@ pub struct FieldDef {
 	pub type_name: String, // type as string for now, could be TypeRef later
 }
 
+impl FieldDef {
+	/// Optional fields may hold null: left: Tree?
+	pub fn is_optional(&self) -> bool {
+		self.type_name.ends_with('?')
+	}
+
+	/// The type without its optional mark: Tree for Tree?
+	pub fn base_type(&self) -> &str {
+		self.type_name.trim_end_matches('?')
+	}
+}
+
 /// Definition of a user-defined type
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeDef {
@@ -121,6 +135,10 @@ pub struct TypeDef {
 	pub tag: u32,               // tag value (>= USER_TYPE_TAG_START)
 	pub fields: Vec<FieldDef>,
 	pub wasm_type_idx: Option<u32>, // WASM GC type index when emitted
+	pub supertype: Option<String>,  // enum a variant belongs to, or class a class extends
+	pub variants: Vec<String>,      // variant names of an enum
+	pub subtypes: Vec<String>,      // classes extending a class
+	pub type_params: Vec<String>,   // A and B of the generic class Pair<A, B>
 }
 
 impl TypeDef {
@@ -130,19 +148,29 @@ impl TypeDef {
 		use crate::node::Node;
 		match node.drop_meta() {
 			Node::Type { name, body } => {
-				let type_name = name.drop_meta().to_string();
+				let (type_name, supertype) = declared_name(name)?;
 				let fields = Self::extract_fields(body);
 				Some(TypeDef {
-					name: type_name,
+					name: type_name.to_string(),
 					tag: USER_TYPE_TAG_START, // will be assigned by registry
 					fields,
 					wasm_type_idx: None,
+					supertype: supertype.map(str::to_string),
+					variants: vec![],
+					subtypes: vec![],
+					type_params: type_params(name),
 				})
 			}
 			_ => None,
 		}
 	}
 
+	/// Enums, their variants and classes extending or extended lead with an i32 tag field
+	/// telling the types apart
+	pub fn is_tagged(&self) -> bool {
+		self.supertype.is_some() || !self.variants.is_empty() || !self.subtypes.is_empty()
+	}
+
 	fn extract_fields(body: &crate::node::Node) -> Vec<FieldDef> {
 		use crate::node::Node;
 		let mut fields = Vec::new();
@@ -222,7 +250,7 @@ fn extract_field_values(body: &crate::node::Node) -> Vec<type_kinds::RawFieldVal
 
 /// Registry for user-defined types
 /// Maps type names to TypeDef and provides tag allocation
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct TypeRegistry {
 	types: Vec<TypeDef>,
 	name_to_idx: std::collections::HashMap<String, usize>,
@@ -245,11 +273,90 @@ impl TypeRegistry {
 			tag,
 			fields,
 			wasm_type_idx: None,
+			supertype: None,
+			variants: vec![],
+			subtypes: vec![],
+			type_params: vec![],
 		});
 		self.name_to_idx.insert(name, idx);
 		tag
 	}
 
+	/// Register a class extending supertype, returns its tag
+	/// Its fields follow the inherited ones, so its struct is a subtype of the supertype's
+	/// Enums and their variants can't be extended, nor types declared later
+	pub fn register_subclass(&mut self, name: String, supertype: &str, fields: Vec<FieldDef>) -> u32 {
+		let inherited = match self.get_by_name(supertype) {
+			Some(parent) if parent.variants.is_empty() && self.variant(supertype).is_none() => parent.fields.clone(),
+			_ => return self.register(name, fields),
+		};
+		let tag = self.register(name.clone(), inherited.into_iter().chain(fields).collect());
+		let idx = self.name_to_idx[&name];
+		self.types[idx].supertype = Some(supertype.to_string());
+		let parent = self.name_to_idx[supertype];
+		if !self.types[parent].subtypes.contains(&name) {
+			self.types[parent].subtypes.push(name);
+		}
+		tag
+	}
+
+	/// Register an enum, then each variant as its subtype, returns the enum's tag
+	pub fn register_enum(&mut self, name: String, variants: Vec<(String, Vec<FieldDef>)>) -> u32 {
+		let tag = self.register(name.clone(), vec![]);
+		for (variant, fields) in variants {
+			let variant_tag = self.register(variant.clone(), fields);
+			self.types[(variant_tag - USER_TYPE_TAG_START) as usize].supertype = Some(name.clone());
+			let idx = self.name_to_idx[&name];
+			if !self.types[idx].variants.contains(&variant) {
+				self.types[idx].variants.push(variant);
+			}
+		}
+		tag
+	}
+
+	/// Variant names of an enum, empty for other types
+	pub fn variants_of(&self, name: &str) -> &[String] {
+		self.get_by_name(name).map(|t| t.variants.as_slice()).unwrap_or_default()
+	}
+
+	/// The enum variant called name
+	pub fn variant(&self, name: &str) -> Option<&TypeDef> {
+		self.get_by_name(name)
+			.filter(|t| t.supertype.as_deref().is_some_and(|enum_name| !self.variants_of(enum_name).is_empty()))
+	}
+
+	/// Types whose instances are instances of name: the variants of an enum,
+	/// a class and the classes extending it
+	pub fn instance_types(&self, name: &str) -> Vec<String> {
+		let Some(type_def) = self.get_by_name(name) else { return vec![name.to_string()] };
+		if !type_def.variants.is_empty() {
+			return type_def.variants.clone();
+		}
+		let mut names = vec![name.to_string()];
+		for subtype in &type_def.subtypes {
+			names.extend(self.instance_types(subtype));
+		}
+		names
+	}
+
+	/// Whether sub is supertype or extends it, maybe through other classes
+	pub fn is_subtype(&self, sub: &str, supertype: &str) -> bool {
+		sub == supertype
+			|| self.get_by_name(sub)
+				.and_then(|t| t.supertype.as_deref())
+				.is_some_and(|parent| self.is_subtype(parent, supertype))
+	}
+
+	/// Whether some type declares the field name optional
+	pub fn is_optional_field(&self, name: &str) -> bool {
+		self.types.iter().any(|t| t.fields.iter().any(|f| f.name == name && f.is_optional()))
+	}
+
+	/// Whether some type declares a field of this name
+	pub fn has_field(&self, name: &str) -> bool {
+		self.types.iter().any(|t| t.fields.iter().any(|f| f.name == name))
+	}
+
 	/// Look up type by name
 	pub fn get_by_name(&self, name: &str) -> Option<&TypeDef> {
 		self.name_to_idx.get(name).map(|&idx| &self.types[idx])
@@ -274,6 +381,13 @@ impl TypeRegistry {
 		&self.types
 	}
 
+	/// Set the type parameters of a generic class
+	fn set_type_params(&mut self, name: &str, type_params: Vec<String>) {
+		if let Some(&idx) = self.name_to_idx.get(name) {
+			self.types[idx].type_params = type_params;
+		}
+	}
+
 	/// Set WASM type index for a registered type
 	pub fn set_wasm_type_idx(&mut self, name: &str, wasm_idx: u32) {
 		if let Some(&idx) = self.name_to_idx.get(name) {
@@ -293,12 +407,24 @@ impl TypeRegistry {
 			if matches!(body.drop_meta(), Node::Empty) {
 				return None;
 			}
-			let type_name = match name.drop_meta() {
-				Node::Symbol(s) | Node::Text(s) => s.clone(),
-				_ => return None,
-			};
+			let (type_name, supertype) = declared_name(name)?;
+			let type_name = type_name.to_string();
+			if let Some(supertype) = supertype {
+				let tag = self.register_subclass(type_name.clone(), supertype, Self::extract_fields(body));
+				self.set_type_params(&type_name, type_params(name));
+				return Some(tag);
+			}
+			if let Some(variants) = enum_variants(body) {
+				let variants = variants.iter().filter_map(|variant| match variant.drop_meta() {
+					Node::Type { name, body } => Some((name.drop_meta().to_string(), Self::extract_fields(body))),
+					_ => None,
+				}).collect();
+				return Some(self.register_enum(type_name, variants));
+			}
 			let fields = Self::extract_fields(body);
-			Some(self.register(type_name, fields))
+			let tag = self.register(type_name.clone(), fields);
+			self.set_type_params(&type_name, type_params(name));
+			Some(tag)
 		} else {
 			None
 		}
@@ -311,10 +437,12 @@ impl TypeRegistry {
 		// Unwrap any Meta wrappers
 		let body = body.drop_meta();
 		match body {
-			Node::List(items, _, _) => {
+			// Methods declared next to the fields are no fields, fields may share a line
+			Node::List(items, _, _) if !is_definition(items) => {
 				for item in items {
-					if let Some(field) = Self::extract_field(item) {
-						fields.push(field);
+					match item.drop_meta() {
+						Node::List(..) => fields.extend(Self::extract_fields(item)),
+						_ => fields.extend(Self::extract_field(item)),
 					}
 				}
 			}
@@ -333,21 +461,12 @@ impl TypeRegistry {
 		use crate::node::Node;
 		let node = node.drop_meta();
 		match node {
-			Node::Key(name_node, _, type_node) => {
+			Node::Key(name_node, crate::operators::Op::Colon, type_node) => {
 				let name = match name_node.drop_meta() {
 					Node::Symbol(s) | Node::Text(s) => s.clone(),
 					_ => return None,
 				};
-				let type_name = match type_node.drop_meta() {
-					Node::Symbol(s) | Node::Text(s) => s.clone(),
-					Node::Type { name: type_name_node, .. } => {
-						match type_name_node.drop_meta() {
-							Node::Symbol(s) | Node::Text(s) => s.clone(),
-							_ => "Any".to_string(),
-						}
-					}
-					_ => "Any".to_string(), // default type
-				};
+				let type_name = written_type(type_node).unwrap_or_else(|| "Any".to_string()); // default type
 				Some(FieldDef { name, type_name })
 			}
 			_ => None,
@@ -355,6 +474,88 @@ impl TypeRegistry {
 	}
 }
 
+/// Name and supertype of a type declaration: Person, Employee: Person or Pair<A, B>
+pub fn declared_name(name: &crate::node::Node) -> Option<(&str, Option<&str>)> {
+	use crate::node::Node;
+	match name.drop_meta() {
+		Node::Symbol(s) | Node::Text(s) => Some((s, None)),
+		Node::Key(name, crate::operators::Op::Colon, supertype) => match (name.drop_meta(), supertype.drop_meta()) {
+			(Node::Symbol(name), Node::List(_, crate::node::Bracket::Less, _)) => Some((name, None)),
+			(name, Node::Symbol(supertype)) => Some((declared_name(name)?.0, Some(supertype))),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Type parameters of a declaration: A and B of Pair<A, B>, none for other types
+pub fn type_params(name: &crate::node::Node) -> Vec<String> {
+	use crate::node::Node;
+	match name.drop_meta() {
+		Node::Key(name, crate::operators::Op::Colon, params) => match params.drop_meta() {
+			Node::List(params, crate::node::Bracket::Less, _) => params.iter().filter_map(written_type).collect(),
+			_ => type_params(name),
+		},
+		_ => vec![],
+	}
+}
+
+/// A type as written in a field or parameter: int, Tree? or List<Pair<A,B>>
+pub fn written_type(node: &crate::node::Node) -> Option<String> {
+	use crate::node::Node;
+	match node.drop_meta() {
+		Node::Symbol(s) | Node::Text(s) => Some(s.clone()),
+		Node::Type { name, .. } => written_type(name),
+		Node::Key(name, crate::operators::Op::Colon, args) => match (name.drop_meta(), args.drop_meta()) {
+			(Node::Symbol(name), Node::List(args, crate::node::Bracket::Less, _)) => {
+				let args: Vec<String> = args.iter().filter_map(written_type).collect();
+				Some(format!("{}<{}>", name, args.join(",")))
+			}
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// A type without its type arguments: List for List<int>
+pub fn generic_base(type_name: &str) -> &str {
+	type_name.split('<').next().unwrap_or(type_name)
+}
+
+/// The type arguments of a type: int and text for Pair<int,text>, nested ones whole
+pub fn type_args(type_name: &str) -> Vec<String> {
+	let Some(args) = type_name.split_once('<').and_then(|(_, args)| args.strip_suffix('>')) else { return vec![] };
+	let mut result = vec![String::new()];
+	let mut depth = 0;
+	for c in args.chars() {
+		match c {
+			',' if depth == 0 => result.push(String::new()),
+			_ => {
+				depth += (c == '<') as i32 - (c == '>') as i32;
+				result.last_mut().unwrap().push(c);
+			}
+		}
+	}
+	result
+}
+
+/// Items of a function definition like def area(): w * h
+fn is_definition(items: &[crate::node::Node]) -> bool {
+	matches!(items.first().map(|item| item.drop_meta()), Some(crate::node::Node::Symbol(s)) if crate::operators::is_function_keyword(s))
+}
+
+/// The variants in the body of an enum: enum Shape { Circle(r: float) Dot }
+/// Each variant is a Type whose body lists its fields, empty for unit variants
+pub fn enum_variants(body: &crate::node::Node) -> Option<&[crate::node::Node]> {
+	use crate::node::Node;
+	match body.drop_meta() {
+		Node::List(items, _, _) if !items.is_empty() && items.iter().all(|item| matches!(item.drop_meta(), Node::Type { .. })) => {
+			Some(items)
+		}
+		_ => None,
+	}
+}
+
 pub enum AstKind {
 	Declaration,
 	Expression,
@@ -375,7 +576,7 @@ pub enum AstKind {
 
 /// Convert FieldDef to ValType for function parameters
 pub fn field_def_to_val_type(field: &FieldDef, emitter: &WasmGcEmitter) -> ValType {
-	match field.type_name.as_str() {
+	match field.base_type() {
 		"Int" | "i64" | "long" => ValType::I64,
 		"Float" | "f64" | "double" => ValType::F64,
 		"i32" | "int" => ValType::I32,
@@ -391,10 +592,11 @@ pub fn field_def_to_val_type(field: &FieldDef, emitter: &WasmGcEmitter) -> ValTy
 		other => {
 			if let Some(&type_idx) = emitter.ctx.user_type_indices.get(other) {
 				Ref(RefType {
-					nullable: true,
+					nullable: field.is_optional(),
 					heap_type: HeapType::Concrete(type_idx),
 				})
 			} else {
+				// type parameters like A of Pair<A, B> and type arguments are erased
 				Ref(RefType {
 					nullable: true,
 					heap_type: any_heap_type(),
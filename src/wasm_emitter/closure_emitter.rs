@@ -0,0 +1,178 @@
+//! Closures: `x => x + n`, `(a, b) => a * b`, or a user function used as a value
+//!
+//! A closure is a Node of kind Closure whose data is a $Closure struct: a funcref and a struct
+//! with the values it captured when it was created. Calls go through call_ref with the
+//! environment first and every argument as a node.
+
+use crate::analyzer::{closure_captures, closure_key, closure_params, closure_scope, function_closure};
+use crate::context::ClosureDef;
+use crate::node::{Node, Separator};
+use crate::type_kinds::{any_heap_type, Kind};
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+
+impl WasmGcEmitter {
+	/// Declare the function and environment type of every extracted closure (PASS 1)
+	pub(super) fn register_closure_signatures(&mut self) {
+		for i in 0..self.ctx.closures.len() {
+			let closure = &self.ctx.closures[i];
+			let (func_type, _) = self.type_manager.closure_type(closure.params.len());
+			let fields: Vec<ValType> = closure.captures.iter().map(|(_, kind)| self.local_type(*kind)).collect();
+			let env_type = if fields.is_empty() { None } else { Some(self.type_manager.add_struct_type(fields)) };
+			self.functions.function(func_type);
+			let closure = &mut self.ctx.closures[i];
+			closure.func_index = Some(self.next_func_idx);
+			closure.env_type = env_type;
+			self.next_func_idx += 1;
+		}
+	}
+
+	/// Compile the closure bodies, unpacking the captured values into locals first (PASS 2)
+	pub(super) fn compile_closures(&mut self) {
+		for i in 0..self.ctx.closures.len() {
+			let closure = self.ctx.closures[i].clone();
+			let (scope, temp_locals) = closure_scope(&closure);
+			let saved_scope = std::mem::replace(&mut self.scope, scope);
+			let mut locals = self.local_types(closure.params.len() as u32 + 1);
+			if temp_locals > 0 {
+				locals.push((temp_locals, ValType::I64));
+			}
+			let saved_temp_local = std::mem::replace(&mut self.next_temp_local, self.scope.local_count());
+
+			let mut func = Function::new(locals);
+			if let Some(env_type) = closure.env_type {
+				for (field, (name, _)) in closure.captures.iter().enumerate() {
+					func.instruction(&Instruction::LocalGet(0));
+					func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(env_type)));
+					func.instruction(&Instruction::StructGet {
+						struct_type_index: env_type,
+						field_index: field as u32,
+					});
+					func.instruction(&Instruction::LocalSet(self.local_position(name)));
+				}
+			}
+			self.emit_node_instructions(&mut func, &closure.body);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+
+			self.scope = saved_scope;
+			self.next_temp_local = saved_temp_local;
+		}
+	}
+
+	/// Create the closure `params => body`, copying the variables it captures into its environment
+	pub(super) fn emit_closure(&mut self, func: &mut Function, params: &Node, body: &Node) {
+		let closure = match self.find_closure(params, body) {
+			Some(closure) => closure,
+			None => panic!("Closure not extracted: {} => {}", params.serialize(), body.serialize()),
+		};
+		let (_, closure_type) = self.type_manager.closure_type(closure.params.len());
+
+		self.emit_kind(func, Kind::Closure);
+		func.instruction(&Instruction::RefFunc(closure.func_index.unwrap()));
+		match closure.env_type {
+			Some(env_type) => {
+				for (name, _) in &closure.captures {
+					func.instruction(&Instruction::LocalGet(self.local_position(name)));
+				}
+				func.instruction(&Instruction::StructNew(env_type));
+			}
+			None => {
+				func.instruction(&Instruction::RefNull(any_heap_type()));
+			}
+		}
+		func.instruction(&Instruction::StructNew(closure_type));
+		self.emit_node_null(func);
+		func.instruction(&Instruction::StructNew(self.type_manager.node_type));
+	}
+
+	/// Wrap the user function name as a closure value, if it is used as one
+	pub(super) fn emit_function_value(&mut self, func: &mut Function, name: &str) -> bool {
+		let Some(user_fn) = self.ctx.user_functions.get(name) else {
+			return false;
+		};
+		let (params, body) = function_closure(user_fn);
+		if self.find_closure(&params, &body).is_none() {
+			return false;
+		}
+		self.emit_closure(func, &params, &body);
+		true
+	}
+
+	/// The extracted closure for this literal and what it captures in the current scope
+	fn find_closure(&self, params: &Node, body: &Node) -> Option<ClosureDef> {
+		let captures = closure_captures(body, &closure_params(params), &self.scope);
+		let key = closure_key(params, body, &captures);
+		self.ctx.closures.iter().find(|closure| closure.key == key).cloned()
+	}
+
+	/// Position of the local a call like f(x), f x or f() invokes as a closure
+	/// Any node (Data) may hold one too, like a loop variable walking a list of closures
+	pub(super) fn closure_callee(&self, items: &[Node], separator: &Separator) -> Option<u32> {
+		if !matches!(separator, Separator::None | Separator::Space) {
+			return None; // statements
+		}
+		let Node::Symbol(name) = items.first()?.drop_meta() else {
+			return None;
+		};
+		let local = self.scope.lookup(name)?;
+		match local.kind {
+			Kind::Closure => Some(local.position),
+			Kind::Data if items.len() >= 2 => Some(local.position),
+			_ => None,
+		}
+	}
+
+	/// Call the closure in local callee: call_ref(env, args as nodes..., fn)
+	pub(super) fn emit_closure_call(&mut self, func: &mut Function, callee: u32, args: &[Node]) {
+		let args = match args {
+			[arg] if matches!(arg.drop_meta(), Node::Empty) => &[][..],
+			_ => args,
+		};
+		let (func_type, closure_type) = self.type_manager.closure_type(args.len());
+		self.emit_closure_struct(func, callee, closure_type);
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: closure_type,
+			field_index: 1,
+		});
+		for arg in args {
+			self.emit_node_instructions(func, arg);
+		}
+		self.emit_closure_struct(func, callee, closure_type);
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: closure_type,
+			field_index: 0,
+		});
+		func.instruction(&Instruction::CallRef(func_type));
+	}
+
+	/// Call the closure in local f with the nodes in the args locals, as the runtime list functions do
+	pub(super) fn emit_apply(&mut self, func: &mut Function, f: u32, args: &[u32]) {
+		let (func_type, closure_type) = self.type_manager.closure_type(args.len());
+		self.emit_closure_struct(func, f, closure_type);
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: closure_type,
+			field_index: 1,
+		});
+		for &arg in args {
+			func.instruction(&Instruction::LocalGet(arg));
+			func.instruction(&Instruction::RefAsNonNull);
+		}
+		self.emit_closure_struct(func, f, closure_type);
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: closure_type,
+			field_index: 0,
+		});
+		func.instruction(&Instruction::CallRef(func_type));
+	}
+
+	/// The $Closure struct of the node in local, trapping if it holds no closure of that arity
+	fn emit_closure_struct(&self, func: &mut Function, local: u32, closure_type: u32) {
+		func.instruction(&Instruction::LocalGet(local));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(closure_type)));
+	}
+}
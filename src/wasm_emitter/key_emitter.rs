@@ -1,7 +1,9 @@
 //! Key node emission - handles all Key(left, op, right) patterns
 
+use crate::analyzer::{instance_type, method_function, optional_local};
 use crate::node::{Bracket, Node};
 use crate::operators::Op;
+use crate::type_kinds::Kind;
 use wasm_encoder::*;
 
 use super::WasmGcEmitter;
@@ -54,6 +56,22 @@ impl WasmGcEmitter {
 			}
 		}
 
+		// Text concatenation: "a" + 1, or interpolated "a{x}"
+		if *op == Op::Add && (self.get_type(left) == Kind::Text || self.get_type(right) == Kind::Text) {
+			self.emit_text_concat(func, left, right);
+			return;
+		}
+		if *op == Op::AddAssign {
+			if let Node::Symbol(name) = left.drop_meta() {
+				if let Some(local) = self.scope.lookup(name).filter(|l| l.kind == Kind::Text) {
+					let position = local.position;
+					self.emit_text_concat(func, left, right);
+					func.instruction(&Instruction::LocalTee(position));
+					return;
+				}
+			}
+		}
+
 		// Route to emit_arithmetic for numeric operations
 		let is_numeric_assign = *op == Op::Assign
 			&& matches!(left.drop_meta(), Node::Symbol(s) if {
@@ -124,6 +142,9 @@ impl WasmGcEmitter {
 			// If-then (no else): (if condition) then then_expr
 			let full_node = Node::Key(Box::new(left.clone()), Op::Then, Box::new(right.clone()));
 			self.emit_if_then_else(func, &full_node, None);
+		} else if *op == Op::Do && matches!(left.drop_meta(), Node::Key(_, Op::For, _)) {
+			// For loop: (for (x in items)) do body
+			self.emit_for_loop_impl(func, left, right, true);
 		} else if *op == Op::Do {
 			// While loop: (while condition) do body
 			self.emit_while_loop(func, left, right);
@@ -134,6 +155,14 @@ impl WasmGcEmitter {
 			self.emit_cast(func, left, right);
 		} else if *op == Op::Dot {
 			self.emit_dot_op(func, left, right);
+		} else if *op == Op::SafeDot {
+			self.emit_safe_dot(func, left, right);
+		} else if *op == Op::Coalesce {
+			self.emit_coalesce(func, left, right);
+		} else if *op == Op::Match {
+			self.emit_match(func, left, right);
+		} else if *op == Op::FatArrow {
+			self.emit_closure(func, left, right);
 		} else if *op == Op::Range || *op == Op::To {
 			// Range operators: 0..3 (exclusive) or 0...3 / 0…3 (inclusive)
 			self.emit_range(func, left, right, *op == Op::To);
@@ -265,6 +294,14 @@ impl WasmGcEmitter {
 
 	/// Emit dot operator: method calls and property access
 	fn emit_dot_op(&mut self, func: &mut Function, left: &Node, right: &Node) {
+		// Methods declared in class bodies: rect.scale(2) is Rect.scale(rect, 2)
+		if let Some((method, args)) = self.method_call(left, right) {
+			let mut receiver_and_args = vec![left.clone()];
+			receiver_and_args.extend_from_slice(args);
+			self.emit_user_function_call(func, &method, &receiver_and_args);
+			return;
+		}
+
 		// Check for introspection methods: count, number, size
 		let method_name = match right.drop_meta() {
 			Node::Symbol(s) => Some(s.clone()),
@@ -301,6 +338,37 @@ impl WasmGcEmitter {
 			}
 		}
 
+		// Text methods: s.substring(1, 3) is substring(s, 1, 3), s.trim is trim(s), list.join(",") is join(list, ",")
+		let (method, rest) = match right.drop_meta() {
+			Node::List(items, _, _) if !items.is_empty() => (items[0].drop_meta(), &items[1..]),
+			method => (method, &[][..]),
+		};
+		if let Node::Symbol(method) = method {
+			if method == "join" || self.get_type(left) == Kind::Text {
+				let mut args = vec![left.clone()];
+				args.extend_from_slice(rest);
+				if self.emit_text_call(func, method, &args) {
+					return;
+				}
+			}
+		}
+
+		// List methods: xs.map(it * 2), xs.sort, xs.any(x => x > 2)
+		if let Some((name, args)) = self.list_call(&Node::Key(Box::new(left.clone()), Op::Dot, Box::new(right.clone()))) {
+			if self.emit_list_call(func, name, &args) {
+				return;
+			}
+		}
+
+		// Fields of user type instances: tree.value
+		if let Some(field) = self.instance_field(right) {
+			self.emit_node_instructions(func, left);
+			self.emit_string_call(func, field, "new_symbol");
+			self.emit_call(func, "node_field");
+			func.instruction(&Instruction::RefAsNonNull);
+			return;
+		}
+
 		// Default: emit as Key node
 		self.emit_node_instructions(func, left);
 		self.emit_node_instructions(func, right);
@@ -308,12 +376,83 @@ impl WasmGcEmitter {
 		self.emit_call(func, "new_key");
 	}
 
+	/// Name of a field some user type declares: value in tree.value
+	pub(super) fn instance_field<'n>(&self, right: &'n Node) -> Option<&'n str> {
+		match right.drop_meta() {
+			Node::Symbol(name) if self.ctx.type_registry.has_field(name) => Some(name.as_str()),
+			_ => None,
+		}
+	}
+
+	/// The user function and arguments of a method call like rect.area() or rect.scale(2),
+	/// if the receiver is known to be an instance of a class with that method
+	pub(super) fn method_call<'n>(&self, receiver: &Node, right: &'n Node) -> Option<(String, &'n [Node])> {
+		if self.instance_field(right).is_some() {
+			return None;
+		}
+		let (method, args) = match right.drop_meta() {
+			Node::List(items, _, _) if !items.is_empty() => match &items[1..] {
+				[empty] if matches!(empty.drop_meta(), Node::Empty) => (items[0].drop_meta(), &[][..]),
+				args => (items[0].drop_meta(), args),
+			},
+			method => (method, &[][..]),
+		};
+		let Node::Symbol(method) = method else { return None };
+		let type_name = instance_type(&self.ctx, receiver, &self.scope)?;
+		method_function(&self.ctx, &type_name, method).map(|name| (name, args))
+	}
+
+	/// tree?.value: null if tree is null or has no value, else its value
+	fn emit_safe_dot(&mut self, func: &mut Function, left: &Node, right: &Node) {
+		let Some(field) = self.instance_field(right) else {
+			panic!("?. expects a field of a type, got {}", right.serialize());
+		};
+		let local = self.local_position(&optional_local(left));
+		let node_type = BlockType::Result(ValType::Ref(self.node_ref(false)));
+		self.emit_node_instructions(func, left);
+		func.instruction(&Instruction::LocalTee(local));
+		self.emit_kind_is(func, Kind::Empty);
+		self.open_block(func, Instruction::If(node_type));
+		self.emit_call(func, "new_empty");
+		func.instruction(&Instruction::Else);
+		// block { node_field(tree, 'value') br_on_null 0; br 1 } new_empty
+		self.open_block(func, Instruction::Block(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(local));
+		self.emit_string_call(func, field, "new_symbol");
+		self.emit_call(func, "node_field");
+		func.instruction(&Instruction::BrOnNull(0));
+		func.instruction(&Instruction::Br(1));
+		self.close_block(func);
+		self.emit_call(func, "new_empty");
+		self.close_block(func);
+	}
+
+	/// value ?? fallback: the fallback only if value is null
+	fn emit_coalesce(&mut self, func: &mut Function, left: &Node, right: &Node) {
+		let local = self.local_position(&optional_local(left));
+		self.emit_node_instructions(func, left);
+		func.instruction(&Instruction::LocalTee(local));
+		self.emit_kind_is(func, Kind::Empty);
+		self.open_block(func, Instruction::If(BlockType::Result(ValType::Ref(self.node_ref(false)))));
+		self.emit_node_instructions(func, right);
+		func.instruction(&Instruction::Else);
+		func.instruction(&Instruction::LocalGet(local));
+		self.close_block(func);
+	}
+
 	/// Emit default Key node (preserve structure for roundtrip)
 	fn emit_default_key(&mut self, func: &mut Function, left: &Node, right: &Node, op: &Op) {
 		self.emit_node_instructions(func, left);
 		// For struct instances like Person{...}, emit block as list
 		let right_node = right.drop_meta();
-		if let Node::List(items, Bracket::Curly, sep) = right_node {
+		let is_instance = matches!(left.drop_meta(), Node::Symbol(name) if self.ctx.type_registry.get_by_name(name).is_some());
+		if let (true, Node::List(fields, Bracket::Curly, _)) = (is_instance, right_node) {
+			if fields.is_empty() {
+				self.emit_node_instructions(func, right_node);
+			} else {
+				self.emit_instance_fields(func, fields);
+			}
+		} else if let Node::List(items, Bracket::Curly, sep) = right_node {
 			// Convert curly block to square list, preserving inner ops
 			let list_node = Node::List(items.clone(), Bracket::Square, sep.clone());
 			self.emit_node_instructions(func, &list_node);
@@ -324,4 +463,25 @@ impl WasmGcEmitter {
 		func.instruction(&Instruction::I64Const(crate::operators::op_to_code(op)));
 		self.emit_call(func, "new_key");
 	}
+
+	/// Fields of a user type instance as a square list
+	/// Field names stay symbols even where a local shares their name
+	fn emit_instance_fields(&mut self, func: &mut Function, fields: &[Node]) {
+		match fields[0].drop_meta() {
+			Node::Key(name, op, value) if matches!(name.drop_meta(), Node::Symbol(_)) => {
+				self.emit_string_call(func, &name.drop_meta().to_string(), "new_symbol");
+				self.emit_node_instructions(func, value);
+				func.instruction(&Instruction::I64Const(crate::operators::op_to_code(op)));
+				self.emit_call(func, "new_key");
+			}
+			field => self.emit_node_instructions(func, field),
+		}
+		if fields.len() > 1 {
+			self.emit_instance_fields(func, &fields[1..]);
+		} else {
+			self.emit_node_null(func);
+		}
+		func.instruction(&Instruction::I64Const(1)); // square brackets
+		self.emit_call(func, "new_list");
+	}
 }
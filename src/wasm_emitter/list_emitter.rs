@@ -1,5 +1,6 @@
 //! List node emission - handles all List(items, bracket, separator) patterns
 
+use crate::analyzer::{function_head, split_return_type};
 use crate::node::{Bracket, Node, Separator};
 use crate::operators::{is_function_keyword, Op};
 use crate::normalize::hints as norm;
@@ -10,12 +11,20 @@ use super::WasmGcEmitter;
 impl WasmGcEmitter {
 	/// Emit instructions for List(items, bracket, separator) nodes
 	/// Dispatches based on list contents and bracket type
-	pub(super) fn emit_list_node(&mut self, func: &mut Function, items: &[Node], bracket: &Bracket) {
+	pub(super) fn emit_list_node(&mut self, func: &mut Function, items: &[Node], bracket: &Bracket, separator: &Separator) {
 		if items.is_empty() {
 			self.emit_call(func, "new_empty");
 			return;
 		}
 
+		// Closure call: f(x), f x, f()
+		if items.len() >= 2 || *bracket == Bracket::Round {
+			if let Some(callee) = self.closure_callee(items, separator) {
+				self.emit_closure_call(func, callee, &items[1..]);
+				return;
+			}
+		}
+
 		if items.len() == 1 {
 			// Check for zero-argument function call: (funcname)
 			if *bracket == Bracket::Round {
@@ -137,13 +146,23 @@ impl WasmGcEmitter {
 					self.emit_ffi_call(func, fn_name, &items[1..], None);
 					return;
 				}
+				// Text functions: substring(s, 1, 3), repeat(s, 2)
+				if self.emit_text_call(func, fn_name, &items[1..]) {
+					return;
+				}
+				// List functions: map(xs, it * 2), sort(xs)
+				if let Some((name, args)) = self.list_call(&Node::List(items.to_vec(), bracket.clone(), separator.clone())) {
+					if self.emit_list_call(func, name, &args) {
+						return;
+					}
+				}
 			}
 		}
 
 		// Check if this list contains type definitions
 		let has_type_def = items.iter().any(|item| matches!(item.drop_meta(), Node::Type { .. }));
 		if has_type_def {
-			self.emit_type_def_list(func, items);
+			self.emit_type_def_list(func, items, bracket, separator);
 			return;
 		}
 
@@ -248,17 +267,15 @@ impl WasmGcEmitter {
 		})
 	}
 
-	/// Emit a list containing type definitions
-	fn emit_type_def_list(&mut self, func: &mut Function, items: &[Node]) {
-		let last_expr = items
+	/// Emit a list containing type definitions: the definitions are registered already,
+	/// the statements around them run as usual
+	fn emit_type_def_list(&mut self, func: &mut Function, items: &[Node], bracket: &Bracket, separator: &Separator) {
+		let statements: Vec<Node> = items
 			.iter()
-			.rev()
-			.find(|item| !matches!(item.drop_meta(), Node::Type { .. }));
-		if let Some(expr) = last_expr {
-			self.emit_node_instructions(func, expr);
-		} else {
-			self.emit_call(func, "new_empty");
-		}
+			.filter(|item| !matches!(item.drop_meta(), Node::Type { .. }))
+			.cloned()
+			.collect();
+		self.emit_list_node(func, &statements, bracket, separator);
 	}
 
 	/// Emit a statement sequence (filter out functions, execute in order, return last)
@@ -298,14 +315,13 @@ impl WasmGcEmitter {
 					}
 				}
 			}
-			// Pattern: name(params...) = body
+			// Pattern: name(params...) = body, first<T>(xs) -> T = body
 			Node::Key(left, Op::Assign, _) => {
+				let (left, _) = split_return_type(left);
 				if let Node::List(items, _, _) = left.drop_meta() {
-					if !items.is_empty() {
-						if let Node::Symbol(name) = items[0].drop_meta() {
-							if self.ctx.user_functions.contains_key(name) {
-								return true;
-							}
+					if let Some((name, _)) = items.first().and_then(function_head) {
+						if self.ctx.user_functions.contains_key(&name) {
+							return true;
 						}
 					}
 				}
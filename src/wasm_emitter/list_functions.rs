@@ -0,0 +1,745 @@
+//! List functions for WASM: map, filter, reduce, sort, any, all, zip and enumerate
+//!
+//! Each walks the cells loop_items hands out in a wasm loop and calls its closure through
+//! call_ref; `it * 2` as an argument is the closure `it => it * 2`. Results are fresh lists.
+
+use crate::analyzer::{is_it_lambda, list_function};
+use crate::node::Node;
+use crate::operators::{op_to_code, Op};
+use crate::type_kinds::Kind;
+use crate::wasm_emitter::string_ops::BYTE;
+use crate::wasm_emitter::text_ops::SQUARE;
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+use Instruction::I32Const;
+use ValType::Ref;
+
+impl WasmGcEmitter {
+	pub(crate) fn emit_list_functions(&mut self) {
+		let node_ref = self.node_ref(false);
+		let node_ref_nullable = self.node_ref(true);
+
+		// node_truthy(node: ref $Node) -> i32
+		// Empty, 0, 0.0 and '' are false, anything else true
+		if self.should_emit_function("node_truthy") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![ValType::I32]);
+			self.functions.function(func_type);
+			// Locals: 0=node, 1=kind
+			let mut func = Function::new(vec![(1, ValType::I64)]);
+			self.emit_kind_of(&mut func, 0, 1);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Empty);
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Int);
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "get_int_value");
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::I64Ne);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Float);
+			self.emit_float_data(&mut func, 0);
+			func.instruction(&Instruction::F64Const(Ieee64::new(0.0f64.to_bits())));
+			func.instruction(&Instruction::F64Ne);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Text);
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_string_data(&mut func);
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.string_type,
+				field_index: 1,
+			});
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::I32Ne);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("node_truthy");
+			self.exports.export("node_truthy", ExportKind::Func, idx);
+		}
+
+		// node_compare(a: ref $Node, b: ref $Node) -> i64
+		// Negative, 0 or positive: text and symbols bytewise, codepoints by value, numbers by value
+		if self.should_emit_function("node_compare") {
+			let string_ref = RefType {
+				nullable: true,
+				heap_type: HeapType::Concrete(self.type_manager.string_type),
+			};
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![ValType::I64]);
+			self.functions.function(func_type);
+			// Locals: 0=a, 1=b, 2=kind of a, 3=kind of b, 4=string, 5=a ptr, 6=a len, 7=b ptr, 8=b len,
+			// 9=i, 10=byte of a, 11=byte of b
+			let mut func = Function::new(vec![(2, ValType::I64), (1, Ref(string_ref)), (7, ValType::I32)]);
+			self.emit_kind_of(&mut func, 0, 2);
+			self.emit_kind_of(&mut func, 1, 3);
+			Self::emit_if_kind(&mut func, 2, Kind::Symbol);
+			func.instruction(&Instruction::I64Const(Kind::Text as i64));
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::End);
+
+			// Text: the first differing byte, else the shorter one first
+			Self::emit_if_kind(&mut func, 2, Kind::Text);
+			for (node, ptr, len) in [(0, 5, 6), (1, 7, 8)] {
+				func.instruction(&Instruction::LocalGet(node));
+				self.emit_string_data(&mut func);
+				func.instruction(&Instruction::LocalTee(4));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.string_type,
+					field_index: 0,
+				});
+				func.instruction(&Instruction::LocalSet(ptr));
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.string_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::LocalSet(len));
+			}
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::I32Or);
+			func.instruction(&Instruction::BrIf(1));
+			for (ptr, byte) in [(5, 10), (7, 11)] {
+				func.instruction(&Instruction::LocalGet(ptr));
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::I32Load8U(BYTE));
+				func.instruction(&Instruction::LocalSet(byte));
+			}
+			func.instruction(&Instruction::LocalGet(10));
+			func.instruction(&Instruction::LocalGet(11));
+			func.instruction(&Instruction::I32Ne);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(10));
+			func.instruction(&Instruction::LocalGet(11));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I64ExtendI32S);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			Self::emit_increment(&mut func, 9, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I64ExtendI32S);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 2, Kind::Codepoint);
+			for node in [0, 1] {
+				func.instruction(&Instruction::LocalGet(node));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::RefCastNonNull(HeapType::Abstract {
+					shared: false,
+					ty: AbstractHeapType::I31,
+				}));
+				func.instruction(&Instruction::I31GetU);
+				func.instruction(&Instruction::I64ExtendI32U);
+			}
+			func.instruction(&Instruction::I64Sub);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			// Numbers: as floats if either is one, else as ints; (a > b) - (a < b)
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I64Const(Kind::Float as i64));
+			func.instruction(&Instruction::I64Eq);
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::I64Const(Kind::Float as i64));
+			func.instruction(&Instruction::I64Eq);
+			func.instruction(&Instruction::I32Or);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			for instruction in [Instruction::F64Gt, Instruction::F64Lt] {
+				self.emit_as_float(&mut func, 0, 2);
+				self.emit_as_float(&mut func, 1, 3);
+				func.instruction(&instruction);
+			}
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I64ExtendI32S);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			for instruction in [Instruction::I64GtS, Instruction::I64LtS] {
+				func.instruction(&Instruction::LocalGet(0));
+				self.emit_call(&mut func, "get_int_value");
+				func.instruction(&Instruction::LocalGet(1));
+				self.emit_call(&mut func, "get_int_value");
+				func.instruction(&instruction);
+			}
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I64ExtendI32S);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("node_compare");
+			self.exports.export("node_compare", ExportKind::Func, idx);
+		}
+
+		// list_map(list: ref $Node, f: ref $Node) -> ref $Node
+		// list_filter(list: ref $Node, f: ref $Node) -> ref $Node
+		// The results of f for each item, or the items f is true for
+		for (name, keep) in [("list_map", false), ("list_filter", true)] {
+			if !self.should_emit_function(name) {
+				continue;
+			}
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=list, 1=f, 2=current, 3=item, 4=reversed, 5=result
+			let mut func = Function::new(vec![(4, Ref(node_ref_nullable))]);
+			self.emit_loop_items(&mut func, 0);
+			self.emit_each_item(&mut func, 0, 2, |this, func| {
+				func.instruction(&Instruction::LocalSet(3));
+				this.emit_apply(func, 1, &[3]);
+				if keep {
+					this.emit_call(func, "node_truthy");
+					func.instruction(&Instruction::If(BlockType::Empty));
+					func.instruction(&Instruction::LocalGet(3));
+				}
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::I64Const(SQUARE));
+				this.emit_call(func, "new_list");
+				func.instruction(&Instruction::LocalSet(4));
+				if keep {
+					func.instruction(&Instruction::End);
+				}
+			});
+			self.emit_reverse(&mut func, 4, 5);
+			self.emit_or_empty(&mut func, 5);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func(name);
+			self.exports.export(name, ExportKind::Func, idx);
+		}
+
+		// list_reduce(list: ref $Node, f: ref $Node, init: ref null $Node) -> ref $Node
+		// f(f(init, first), second)…; without init the first item starts, Empty if there is none
+		if self.should_emit_function("list_reduce") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref), Ref(node_ref_nullable)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=list, 1=f, 2=accumulator, 3=current, 4=item
+			let mut func = Function::new(vec![(2, Ref(node_ref_nullable))]);
+			self.emit_loop_items(&mut func, 0);
+			self.emit_each_item(&mut func, 0, 3, |this, func| {
+				func.instruction(&Instruction::LocalSet(4));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::RefIsNull);
+				func.instruction(&Instruction::If(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::LocalSet(2));
+				func.instruction(&Instruction::Else);
+				this.emit_apply(func, 1, &[2, 4]);
+				func.instruction(&Instruction::LocalSet(2));
+				func.instruction(&Instruction::End);
+			});
+			self.emit_or_empty(&mut func, 2);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("list_reduce");
+			self.exports.export("list_reduce", ExportKind::Func, idx);
+		}
+
+		// list_any_all(list: ref $Node, f: ref null $Node, all: i32) -> i64
+		// Whether f, or the item itself without f, is true for any item, or with all for every one
+		if self.should_emit_function("list_any_all") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref_nullable), ValType::I32], vec![ValType::I64]);
+			self.functions.function(func_type);
+			// Locals: 0=list, 1=f, 2=all, 3=current, 4=item, 5=truth
+			let mut func = Function::new(vec![(2, Ref(node_ref_nullable)), (1, ValType::I32)]);
+			self.emit_loop_items(&mut func, 0);
+			self.emit_each_item(&mut func, 0, 3, |this, func| {
+				func.instruction(&Instruction::LocalSet(4));
+				this.emit_apply_or_item(func, 1, 4);
+				this.emit_call(func, "node_truthy");
+				func.instruction(&Instruction::LocalTee(5));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::I32Ne);
+				func.instruction(&Instruction::If(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::I64ExtendI32U);
+				func.instruction(&Instruction::Return);
+				func.instruction(&Instruction::End);
+			});
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I64ExtendI32U);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("list_any_all");
+			self.exports.export("list_any_all", ExportKind::Func, idx);
+		}
+
+		// list_sort(list: ref $Node, f: ref null $Node) -> ref $Node
+		// Stable bottom-up merge sort in two arrays; f(a, b) < 0 puts a first, node_compare without f
+		if self.should_emit_function("list_sort") {
+			let array_type = self.type_manager.node_array_type();
+			let array_ref = RefType {
+				nullable: true,
+				heap_type: HeapType::Concrete(array_type),
+			};
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref_nullable)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=list, 1=f, 2=current, 3=a, 4=b, 5=result, 6=source, 7=target,
+			// 8=n, 9=k, 10=width, 11=i, 12=mid, 13=end, 14=left, 15=right, 16=take left
+			let mut func = Function::new(vec![(4, Ref(node_ref_nullable)), (2, Ref(array_ref)), (9, ValType::I32)]);
+			self.emit_loop_items(&mut func, 0);
+			// n = kind == Empty ? 0 : node_count(list)
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "node_count");
+			func.instruction(&Instruction::I32WrapI64);
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "get_kind");
+			func.instruction(&Instruction::I64Const(0xFF));
+			func.instruction(&Instruction::I64And);
+			func.instruction(&Instruction::I64Const(Kind::Empty as i64));
+			func.instruction(&Instruction::I64Ne);
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::LocalSet(8));
+			for array in [6, 7] {
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::ArrayNewDefault(array_type));
+				func.instruction(&Instruction::LocalSet(array));
+			}
+			self.emit_each_item(&mut func, 0, 2, |_, func| {
+				func.instruction(&Instruction::LocalSet(3));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::LocalGet(3));
+				func.instruction(&Instruction::ArraySet(array_type));
+				Self::emit_increment(func, 9, 1);
+			});
+
+			// Merge runs of width from source into target, then swap them, doubling width
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::LocalSet(10));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(10));
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::I32GeS);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::LocalSet(11));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(11));
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::I32GeS);
+			func.instruction(&Instruction::BrIf(1));
+			// mid = min(i + width, n), end = min(i + 2 * width, n)
+			for (local, runs) in [(12, 1), (13, 2)] {
+				func.instruction(&Instruction::LocalGet(11));
+				func.instruction(&Instruction::LocalGet(10));
+				func.instruction(&I32Const(runs));
+				func.instruction(&Instruction::I32Mul);
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalTee(local));
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::LocalGet(local));
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::I32LtS);
+				func.instruction(&Instruction::Select);
+				func.instruction(&Instruction::LocalSet(local));
+			}
+			func.instruction(&Instruction::LocalGet(11));
+			func.instruction(&Instruction::LocalTee(14));
+			func.instruction(&Instruction::LocalSet(9));
+			func.instruction(&Instruction::LocalGet(12));
+			func.instruction(&Instruction::LocalSet(15));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::LocalGet(13));
+			func.instruction(&Instruction::I32GeS);
+			func.instruction(&Instruction::BrIf(1));
+			// take left = left < mid && (right >= end || compare(left, right) <= 0)
+			func.instruction(&Instruction::LocalGet(14));
+			func.instruction(&Instruction::LocalGet(12));
+			func.instruction(&Instruction::I32LtS);
+			func.instruction(&Instruction::LocalTee(16));
+			func.instruction(&Instruction::LocalGet(15));
+			func.instruction(&Instruction::LocalGet(13));
+			func.instruction(&Instruction::I32LtS);
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			for (index, node) in [(14, 3), (15, 4)] {
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::LocalGet(index));
+				func.instruction(&Instruction::ArrayGet(array_type));
+				func.instruction(&Instruction::LocalSet(node));
+			}
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::RefIsNull);
+			func.instruction(&Instruction::If(BlockType::Result(ValType::I64)));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::RefAsNonNull);
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::RefAsNonNull);
+			self.emit_call(&mut func, "node_compare");
+			func.instruction(&Instruction::Else);
+			self.emit_apply(&mut func, 1, &[3, 4]);
+			self.emit_call(&mut func, "get_int_value");
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::I64LeS);
+			func.instruction(&Instruction::LocalSet(16));
+			func.instruction(&Instruction::End);
+			// target[k] = source[take left ? left : right]
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(14));
+			func.instruction(&Instruction::LocalGet(15));
+			func.instruction(&Instruction::LocalGet(16));
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::ArrayGet(array_type));
+			func.instruction(&Instruction::ArraySet(array_type));
+			// left += take left, right += !take left
+			func.instruction(&Instruction::LocalGet(14));
+			func.instruction(&Instruction::LocalGet(16));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(14));
+			func.instruction(&Instruction::LocalGet(15));
+			func.instruction(&Instruction::LocalGet(16));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(15));
+			Self::emit_increment(&mut func, 9, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(13));
+			func.instruction(&Instruction::LocalSet(11));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalSet(6));
+			func.instruction(&Instruction::LocalSet(7));
+			func.instruction(&Instruction::LocalGet(10));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Shl);
+			func.instruction(&Instruction::LocalSet(10));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+
+			// Cons the sorted items from the back
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::LocalSet(9));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::BrIf(1));
+			Self::emit_increment(&mut func, 9, -1);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::ArrayGet(array_type));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::I64Const(SQUARE));
+			self.emit_call(&mut func, "new_list");
+			func.instruction(&Instruction::LocalSet(5));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			self.emit_or_empty(&mut func, 5);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("list_sort");
+			self.exports.export("list_sort", ExportKind::Func, idx);
+		}
+
+		// list_zip(a: ref $Node, b: ref $Node) -> ref $Node
+		// Pairs a:b of the items at the same place, as long as the shorter list
+		if self.should_emit_function("list_zip") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=a, 1=b, 2=a cell, 3=b cell, 4=reversed, 5=result
+			let mut func = Function::new(vec![(4, Ref(node_ref_nullable))]);
+			self.emit_loop_items(&mut func, 0);
+			self.emit_loop_items(&mut func, 1);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::LocalSet(3));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			for cell in [2, 3] {
+				func.instruction(&Instruction::LocalGet(cell));
+				func.instruction(&Instruction::RefIsNull);
+				func.instruction(&Instruction::BrIf(1));
+				func.instruction(&Instruction::LocalGet(cell));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::RefIsNull);
+				func.instruction(&Instruction::BrIf(1));
+			}
+			for cell in [2, 3] {
+				func.instruction(&Instruction::LocalGet(cell));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.node_type)));
+			}
+			func.instruction(&Instruction::I64Const(op_to_code(&Op::Colon)));
+			self.emit_call(&mut func, "new_key");
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I64Const(SQUARE));
+			self.emit_call(&mut func, "new_list");
+			func.instruction(&Instruction::LocalSet(4));
+			for cell in [2, 3] {
+				func.instruction(&Instruction::LocalGet(cell));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 2,
+				});
+				func.instruction(&Instruction::LocalSet(cell));
+			}
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			self.emit_reverse(&mut func, 4, 5);
+			self.emit_or_empty(&mut func, 5);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("list_zip");
+			self.exports.export("list_zip", ExportKind::Func, idx);
+		}
+
+		// list_enumerate(list: ref $Node) -> ref $Node
+		// Pairs index:item with indices from 0
+		if self.should_emit_function("list_enumerate") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=list, 1=current, 2=item, 3=reversed, 4=result, 5=index
+			let mut func = Function::new(vec![(4, Ref(node_ref_nullable)), (1, ValType::I64)]);
+			self.emit_loop_items(&mut func, 0);
+			self.emit_each_item(&mut func, 0, 1, |this, func| {
+				func.instruction(&Instruction::LocalSet(2));
+				func.instruction(&Instruction::LocalGet(5));
+				this.emit_call(func, "new_int");
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::RefAsNonNull);
+				func.instruction(&Instruction::I64Const(op_to_code(&Op::Colon)));
+				this.emit_call(func, "new_key");
+				func.instruction(&Instruction::LocalGet(3));
+				func.instruction(&Instruction::I64Const(SQUARE));
+				this.emit_call(func, "new_list");
+				func.instruction(&Instruction::LocalSet(3));
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::I64Const(1));
+				func.instruction(&Instruction::I64Add);
+				func.instruction(&Instruction::LocalSet(5));
+			});
+			self.emit_reverse(&mut func, 3, 4);
+			self.emit_or_empty(&mut func, 4);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("list_enumerate");
+			self.exports.export("list_enumerate", ExportKind::Func, idx);
+		}
+	}
+
+	/// Wasp-callable list functions, also as methods: map(xs, it * 2), xs.sort, zip(xs, ys)
+	/// Returns false if name is not one of them or the arguments don't fit
+	pub(crate) fn emit_list_call(&mut self, func: &mut Function, name: &str, args: &[Node]) -> bool {
+		if self.emit_list_test(func, name, args) {
+			self.emit_call(func, "new_int");
+			return true;
+		}
+		match (name, args) {
+			("map" | "filter", [list, f]) => {
+				self.emit_node_instructions(func, list);
+				self.emit_function_arg(func, f);
+				self.emit_call(func, if name == "map" { "list_map" } else { "list_filter" });
+			}
+			("reduce", [list, f, init @ ..]) if init.len() <= 1 => {
+				self.emit_node_instructions(func, list);
+				self.emit_function_arg(func, f);
+				match init.first() {
+					Some(init) => self.emit_node_instructions(func, init),
+					None => self.emit_node_null(func),
+				}
+				self.emit_call(func, "list_reduce");
+			}
+			("sort", [list, f @ ..]) if f.len() <= 1 => {
+				self.emit_node_instructions(func, list);
+				match f.first() {
+					Some(f) => self.emit_function_arg(func, f),
+					None => self.emit_node_null(func),
+				}
+				self.emit_call(func, "list_sort");
+			}
+			("zip", [a, b]) => {
+				self.emit_node_instructions(func, a);
+				self.emit_node_instructions(func, b);
+				self.emit_call(func, "list_zip");
+			}
+			("enumerate", [list]) => {
+				self.emit_node_instructions(func, list);
+				self.emit_call(func, "list_enumerate");
+			}
+			_ => return false,
+		}
+		true
+	}
+
+	/// any and all as a plain i64, 0 or 1
+	pub(crate) fn emit_list_test(&mut self, func: &mut Function, name: &str, args: &[Node]) -> bool {
+		let all = match name {
+			"any" => 0,
+			"all" => 1,
+			_ => return false,
+		};
+		let [list, f @ ..] = args else { return false };
+		if f.len() > 1 {
+			return false;
+		}
+		self.emit_node_instructions(func, list);
+		match f.first() {
+			Some(f) => self.emit_function_arg(func, f),
+			None => self.emit_node_null(func),
+		}
+		func.instruction(&I32Const(all));
+		self.emit_call(func, "list_any_all");
+		true
+	}
+
+	/// Name and arguments of a list function call, the list first
+	/// User functions of the same name take precedence
+	pub(crate) fn list_call(&self, node: &Node) -> Option<(&'static str, Vec<Node>)> {
+		let (&(name, _, _), args) = list_function(node, &self.scope)?;
+		if matches!(node.drop_meta(), Node::List(..)) && self.ctx.user_functions.contains_key(name) {
+			return None;
+		}
+		Some((name, args))
+	}
+
+	/// The closure argument of a list function: `it * 2` is `it => it * 2`
+	fn emit_function_arg(&mut self, func: &mut Function, f: &Node) {
+		if is_it_lambda(f) {
+			self.emit_closure(func, &Node::Symbol("it".to_string()), f);
+		} else {
+			self.emit_node_instructions(func, f);
+		}
+	}
+
+	/// f(item) for the closure in local f, or the item itself if f is null
+	fn emit_apply_or_item(&mut self, func: &mut Function, f: u32, item: u32) {
+		func.instruction(&Instruction::LocalGet(f));
+		func.instruction(&Instruction::RefIsNull);
+		func.instruction(&Instruction::If(BlockType::Result(Ref(self.node_ref(false)))));
+		func.instruction(&Instruction::LocalGet(item));
+		func.instruction(&Instruction::RefAsNonNull);
+		func.instruction(&Instruction::Else);
+		self.emit_apply(func, f, &[item]);
+		func.instruction(&Instruction::End);
+	}
+
+	/// The node in local through loop_items, back into local
+	fn emit_loop_items(&mut self, func: &mut Function, local: u32) {
+		func.instruction(&Instruction::LocalGet(local));
+		self.emit_call(func, "loop_items");
+		func.instruction(&Instruction::LocalSet(local));
+	}
+
+	/// The list in the nullable local, Empty if it is null
+	fn emit_or_empty(&mut self, func: &mut Function, local: u32) {
+		func.instruction(&Instruction::LocalGet(local));
+		func.instruction(&Instruction::RefIsNull);
+		func.instruction(&Instruction::If(BlockType::Result(Ref(self.node_ref(false)))));
+		self.emit_call(func, "new_empty");
+		func.instruction(&Instruction::Else);
+		func.instruction(&Instruction::LocalGet(local));
+		func.instruction(&Instruction::RefAsNonNull);
+		func.instruction(&Instruction::End);
+	}
+
+	/// kind local = kind of the node in local, without its op or bracket bits
+	fn emit_kind_of(&mut self, func: &mut Function, node: u32, kind: u32) {
+		func.instruction(&Instruction::LocalGet(node));
+		self.emit_call(func, "get_kind");
+		func.instruction(&Instruction::I64Const(0xFF));
+		func.instruction(&Instruction::I64And);
+		func.instruction(&Instruction::LocalSet(kind));
+	}
+
+	/// The $String data of the Text node on the stack
+	fn emit_string_data(&self, func: &mut Function) {
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.string_type)));
+	}
+
+	/// The f64 of the Float node in local
+	fn emit_float_data(&self, func: &mut Function, node: u32) {
+		func.instruction(&Instruction::LocalGet(node));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.f64_box_type)));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.f64_box_type,
+			field_index: 0,
+		});
+	}
+
+	/// The number in local as f64, by its kind local
+	fn emit_as_float(&mut self, func: &mut Function, node: u32, kind: u32) {
+		func.instruction(&Instruction::LocalGet(kind));
+		func.instruction(&Instruction::I64Const(Kind::Float as i64));
+		func.instruction(&Instruction::I64Eq);
+		func.instruction(&Instruction::If(BlockType::Result(ValType::F64)));
+		self.emit_float_data(func, node);
+		func.instruction(&Instruction::Else);
+		func.instruction(&Instruction::LocalGet(node));
+		self.emit_call(func, "get_int_value");
+		func.instruction(&Instruction::F64ConvertI64S);
+		func.instruction(&Instruction::End);
+	}
+}
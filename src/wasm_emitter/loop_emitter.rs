@@ -0,0 +1,350 @@
+//! For loops: `for i in 1..10`, `for x in list`, `for key, value in map`, `for c in text`
+//!
+//! Ranges count in a local, everything else walks the runtime list cells that loop_items
+//! hands out. Like while, a loop's value is the last value of its body.
+
+use crate::analyzer::{is_range_loop, loop_local, loop_names};
+use crate::node::{Bracket, Node};
+use crate::operators::Op;
+use crate::type_kinds::Kind;
+use crate::wasm_emitter::text_ops::SQUARE;
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+use ValType::Ref;
+
+impl WasmGcEmitter {
+	pub(crate) fn emit_loop_ops(&mut self) {
+		let node_ref = self.node_ref(false);
+
+		// loop_items(node: ref $Node) -> ref $Node
+		// What a for loop walks: lists and Empty as they are, text as its codepoints,
+		// anything else as a one item list
+		if self.should_emit_function("loop_items") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=node, 1=kind
+			let mut func = Function::new(vec![(1, ValType::I64)]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::I64Const(0xFF));
+			func.instruction(&Instruction::I64And);
+			func.instruction(&Instruction::LocalSet(1));
+
+			Self::emit_if_kind(&mut func, 1, Kind::Text);
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "text_codepoints");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::List);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Empty);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_node_null(&mut func);
+			func.instruction(&Instruction::I64Const(SQUARE));
+			self.emit_call(&mut func, "new_list");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("loop_items");
+			self.exports.export("loop_items", ExportKind::Func, idx);
+		}
+	}
+
+	/// Emit for loop: (for (vars in items)) do body
+	/// If wrap_result is true, wraps result in Node; otherwise returns raw i64
+	pub(crate) fn emit_for_loop_impl(&mut self, func: &mut Function, left: &Node, body: &Node, wrap_result: bool) {
+		let (vars, items) = match left.drop_meta() {
+			Node::Key(_, Op::For, each) => match each.drop_meta() {
+				Node::Key(vars, Op::In, items) => (vars, items),
+				other => panic!("Expected for x in items, got {:?}", other),
+			},
+			other => panic!("Expected for loop, got {:?}", other),
+		};
+		let names = loop_names(vars);
+
+		let result_local = self.next_temp_local;
+		self.next_temp_local += 1;
+
+		func.instruction(&Instruction::I64Const(0));
+		func.instruction(&Instruction::LocalSet(result_local));
+
+		// the hidden locals of this loop are keyed by its depth, loops in items and body go one deeper
+		let depth = self.scope.loop_depth;
+		self.scope.loop_depth += 1;
+		if is_range_loop(items) {
+			self.emit_range_loop(func, &names, depth, items, body, result_local);
+		} else {
+			self.emit_items_loop(func, &names, depth, items, body, result_local);
+		}
+		self.scope.loop_depth -= 1;
+
+		func.instruction(&Instruction::LocalGet(result_local));
+		if wrap_result {
+			self.emit_call(func, "new_int");
+		}
+	}
+
+	/// for i in start..end counts up to end, exclusive; with `to`, `...` or `…` inclusive
+	/// for index, i in start..end also counts from 0 in index
+	fn emit_range_loop(&mut self, func: &mut Function, names: &[String], depth: usize, range: &Node, body: &Node, result_local: u32) {
+		let Node::Key(start, op, end) = range.drop_meta() else { unreachable!("not a range: {:?}", range) };
+		let inclusive = *op == Op::To;
+		let value = self.local_position(&names[names.len() - 1]);
+		let index = (names.len() > 1).then(|| self.local_position(&names[0]));
+		let end_local = self.local_position(&loop_local(depth, "end"));
+
+		self.emit_numeric_value(func, start);
+		func.instruction(&Instruction::LocalSet(value));
+		self.emit_numeric_value(func, end);
+		func.instruction(&Instruction::LocalSet(end_local));
+		if let Some(index) = index {
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::LocalSet(index));
+		}
+
+		let head = |_: &mut Self, func: &mut Function| {
+			func.instruction(&Instruction::LocalGet(value));
+			func.instruction(&Instruction::LocalGet(end_local));
+			func.instruction(&if inclusive { Instruction::I64GtS } else { Instruction::I64GeS });
+			func.instruction(&Instruction::BrIf(1));
+		};
+		let step = |_: &mut Self, func: &mut Function| {
+			for local in std::iter::once(value).chain(index) {
+				func.instruction(&Instruction::LocalGet(local));
+				func.instruction(&Instruction::I64Const(1));
+				func.instruction(&Instruction::I64Add);
+				func.instruction(&Instruction::LocalSet(local));
+			}
+		};
+		self.emit_loop(func, head, body, result_local, step);
+	}
+
+	/// for x in items walks the list cells of loop_items; with two names Key items bind
+	/// key and value, other items their index from 0 and themselves
+	fn emit_items_loop(&mut self, func: &mut Function, names: &[String], depth: usize, items: &Node, body: &Node, result_local: u32) {
+		let items_local = self.local_position(&loop_local(depth, "items"));
+		let item = self.local_position(&loop_local(depth, "item"));
+		let count = self.local_position(&loop_local(depth, "count"));
+		let index = self.local_position(&loop_local(depth, "index"));
+		let vars: Vec<(u32, Kind)> = names.iter()
+			.map(|name| (self.local_position(name), self.scope.lookup(name).unwrap().kind))
+			.collect();
+
+		self.emit_node_instructions(func, items);
+		self.emit_call(func, "loop_items");
+		func.instruction(&Instruction::LocalTee(items_local));
+		// count = kind == Empty ? 0 : node_count(items)
+		self.emit_call(func, "node_count");
+		func.instruction(&Instruction::I64Const(0));
+		func.instruction(&Instruction::LocalGet(items_local));
+		self.emit_call(func, "get_kind");
+		func.instruction(&Instruction::I64Const(0xFF));
+		func.instruction(&Instruction::I64And);
+		func.instruction(&Instruction::I64Const(Kind::Empty as i64));
+		func.instruction(&Instruction::I64Ne);
+		func.instruction(&Instruction::Select);
+		func.instruction(&Instruction::LocalSet(count));
+		func.instruction(&Instruction::I64Const(0));
+		func.instruction(&Instruction::LocalSet(index));
+
+		let head = |this: &mut Self, func: &mut Function| {
+			func.instruction(&Instruction::LocalGet(index));
+			func.instruction(&Instruction::LocalGet(count));
+			func.instruction(&Instruction::I64GeS);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(items_local));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: this.type_manager.node_type,
+				field_index: 1,
+			});
+			func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(this.type_manager.node_type)));
+			func.instruction(&Instruction::LocalSet(item));
+			match vars.as_slice() {
+				[(value, kind)] => {
+					func.instruction(&Instruction::LocalGet(item));
+					this.emit_loop_value(func, *kind);
+					func.instruction(&Instruction::LocalSet(*value));
+				}
+				[(key, key_kind), (value, kind), ..] => {
+					// Key items bind key and value, anything else its index and itself
+					this.emit_is_key_item(func, item);
+					func.instruction(&Instruction::If(BlockType::Result(this.loop_value_type(*key_kind))));
+					func.instruction(&Instruction::LocalGet(item));
+					func.instruction(&Instruction::StructGet {
+						struct_type_index: this.type_manager.node_type,
+						field_index: 1,
+					});
+					func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(this.type_manager.node_type)));
+					this.emit_loop_value(func, *key_kind);
+					func.instruction(&Instruction::Else);
+					func.instruction(&Instruction::LocalGet(index));
+					if key_kind.is_ref() {
+						this.emit_call(func, "new_int");
+					}
+					func.instruction(&Instruction::End);
+					func.instruction(&Instruction::LocalSet(*key));
+					this.emit_is_key_item(func, item);
+					func.instruction(&Instruction::If(BlockType::Result(this.loop_value_type(*kind))));
+					func.instruction(&Instruction::LocalGet(item));
+					func.instruction(&Instruction::StructGet {
+						struct_type_index: this.type_manager.node_type,
+						field_index: 2,
+					});
+					func.instruction(&Instruction::RefAsNonNull);
+					this.emit_loop_value(func, *kind);
+					func.instruction(&Instruction::Else);
+					func.instruction(&Instruction::LocalGet(item));
+					this.emit_loop_value(func, *kind);
+					func.instruction(&Instruction::End);
+					func.instruction(&Instruction::LocalSet(*value));
+				}
+				[] => {}
+			}
+		};
+		let step = |this: &mut Self, func: &mut Function| {
+			func.instruction(&Instruction::LocalGet(index));
+			func.instruction(&Instruction::I64Const(1));
+			func.instruction(&Instruction::I64Add);
+			func.instruction(&Instruction::LocalTee(index));
+			func.instruction(&Instruction::LocalGet(count));
+			func.instruction(&Instruction::I64LtS);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(items_local));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: this.type_manager.node_type,
+				field_index: 2,
+			});
+			func.instruction(&Instruction::RefAsNonNull);
+			func.instruction(&Instruction::LocalSet(items_local));
+			func.instruction(&Instruction::End);
+		};
+		self.emit_loop(func, head, body, result_local, step);
+	}
+
+	/// item.kind == Key
+	fn emit_is_key_item(&mut self, func: &mut Function, item: u32) {
+		func.instruction(&Instruction::LocalGet(item));
+		self.emit_call(func, "get_kind");
+		func.instruction(&Instruction::I64Const(0xFF));
+		func.instruction(&Instruction::I64And);
+		func.instruction(&Instruction::I64Const(Kind::Key as i64));
+		func.instruction(&Instruction::I64Eq);
+	}
+
+	/// Local type of a loop variable of kind
+	fn loop_value_type(&self, kind: Kind) -> ValType {
+		if kind.is_ref() {
+			Ref(self.node_ref(false))
+		} else {
+			ValType::I64
+		}
+	}
+
+	/// The Node on the stack as a loop variable of kind: unboxed ints and codepoints, else as is
+	pub(crate) fn emit_loop_value(&mut self, func: &mut Function, kind: Kind) {
+		match kind {
+			Kind::Codepoint => {
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::RefCastNonNull(HeapType::Abstract {
+					shared: false,
+					ty: AbstractHeapType::I31,
+				}));
+				func.instruction(&Instruction::I31GetU);
+				func.instruction(&Instruction::I64ExtendI32U);
+			}
+			kind if kind.is_ref() => {}
+			_ => self.emit_call(func, "get_int_value"),
+		}
+	}
+
+	/// block { loop { head; block { body } step; br loop } }
+	/// head leaves the loop with br_if 1; break leaves the outer block, continue the inner one
+	fn emit_loop(
+		&mut self,
+		func: &mut Function,
+		head: impl Fn(&mut Self, &mut Function),
+		body: &Node,
+		result_local: u32,
+		step: impl Fn(&mut Self, &mut Function),
+	) {
+		self.open_block(func, Instruction::Block(BlockType::Empty));
+		let break_depth = self.block_depth;
+		self.open_block(func, Instruction::Loop(BlockType::Empty));
+		head(self, func);
+		self.open_block(func, Instruction::Block(BlockType::Empty));
+		self.loop_labels.push((break_depth, self.block_depth));
+		self.emit_loop_body(func, body, result_local);
+		self.loop_labels.pop();
+		self.close_block(func);
+		step(self, func);
+		func.instruction(&Instruction::Br(0));
+		self.close_block(func);
+		self.close_block(func);
+	}
+
+	/// Run the statements of body, keeping the last numeric value in result_local
+	/// Text and other node statements run for their effect: for w in words { s += w }
+	pub(crate) fn emit_loop_body(&mut self, func: &mut Function, body: &Node, result_local: u32) {
+		let statements = match body.drop_meta() {
+			Node::List(items, Bracket::Curly, _) => items.clone(),
+			other => vec![other.clone()],
+		};
+		for (i, statement) in statements.iter().enumerate() {
+			if self.get_type(statement).is_ref() && !self.is_numeric(statement) {
+				self.emit_node_instructions(func, statement);
+				func.instruction(&Instruction::Drop);
+			} else {
+				self.emit_numeric_value(func, statement);
+				if i == statements.len() - 1 {
+					func.instruction(&Instruction::LocalSet(result_local));
+				} else {
+					func.instruction(&Instruction::Drop);
+				}
+			}
+		}
+	}
+
+	/// break and continue inside a while or for loop; false for anything else
+	pub(crate) fn emit_loop_jump(&mut self, func: &mut Function, name: &str) -> bool {
+		let Some(&(break_depth, continue_depth)) = self.loop_labels.last() else { return false };
+		let depth = match name {
+			"break" => break_depth,
+			"continue" => continue_depth,
+			_ => return false,
+		};
+		func.instruction(&Instruction::Br(self.block_depth - depth));
+		true
+	}
+
+	/// Enter a block, loop or if around user code, so break and continue know their depth
+	pub(crate) fn open_block(&mut self, func: &mut Function, block: Instruction) {
+		func.instruction(&block);
+		self.block_depth += 1;
+	}
+
+	pub(crate) fn close_block(&mut self, func: &mut Function) {
+		func.instruction(&Instruction::End);
+		self.block_depth -= 1;
+	}
+
+	pub(super) fn local_position(&self, name: &str) -> u32 {
+		self.scope
+			.lookup(name)
+			.map(|l| l.position)
+			.unwrap_or_else(|| panic!("Undefined variable: {}", name))
+	}
+}
@@ -0,0 +1,429 @@
+//! Pattern matching: `match x { 0 => 'zero' n if n < 0 => 'negative' _ => 'positive' }`
+//!
+//! The subject waits in a hidden local while each arm runs in a block its pattern tests leave
+//! with br_if; bindings, guard and value follow and the value leaves the match. Arms of int
+//! literals over a number jump through a br_table instead. Without a matching arm it is Empty.
+
+use crate::analyzer::{is_struct_pattern, is_type_pattern, match_arm, match_arms, match_local, pattern_kind};
+use crate::extensions::numbers::Number;
+use crate::node::{Bracket, Node};
+use crate::operators::Op;
+use crate::type_kinds::Kind;
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+use ValType::Ref;
+
+/// Most int values a br_table of match arms may span
+const MAX_TABLE_SPAN: i64 = 64;
+
+/// One step from the subject to the part of it a pattern looks at
+#[derive(Clone)]
+enum Step {
+	Data,          // the key of a Key, the item of a list cell
+	Rest,          // the value of a Key, the next list cell
+	Field(String), // the field of a user type instance
+}
+
+impl WasmGcEmitter {
+	pub(crate) fn emit_match_ops(&mut self) {
+		let node_ref = self.node_ref(false);
+		let node_ref_nullable = self.node_ref(true);
+
+		// node_field(node: ref $Node, name: ref $Node) -> ref null $Node
+		// The value of field name in the instance Person{name:'A' age:30}, null if it has none
+		if self.should_emit_function("node_field") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref_nullable)]);
+			self.functions.function(func_type);
+			// Locals: 0=node, 1=name, 2=current, 3=field
+			let mut func = Function::new(vec![(2, Ref(node_ref_nullable))]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 2,
+			});
+			func.instruction(&Instruction::LocalTee(2));
+			func.instruction(&Instruction::RefAsNonNull);
+			self.emit_kind_is(&mut func, Kind::List);
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			self.emit_node_null(&mut func);
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::RefIsNull);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 1,
+			});
+			func.instruction(&Instruction::RefCastNullable(HeapType::Concrete(self.type_manager.node_type)));
+			func.instruction(&Instruction::LocalTee(3));
+			func.instruction(&Instruction::RefIsNull);
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			// a name: value field whose name is a symbol equal to name
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::RefAsNonNull);
+			self.emit_kind_is(&mut func, Kind::Key);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			self.emit_step(&mut func, &Step::Data);
+			self.emit_kind_is(&mut func, Kind::Symbol);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			self.emit_step(&mut func, &Step::Data);
+			func.instruction(&Instruction::LocalGet(1));
+			self.emit_call(&mut func, "node_compare");
+			func.instruction(&Instruction::I64Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 2,
+			});
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 2,
+			});
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			self.emit_node_null(&mut func);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("node_field");
+			self.exports.export("node_field", ExportKind::Func, idx);
+		}
+	}
+
+	/// Emit `match subject {arms}` as a node
+	pub(crate) fn emit_match(&mut self, func: &mut Function, subject: &Node, arms: &Node) {
+		let arms: Vec<(&Node, Option<&Node>, &Node)> = match_arms(arms)
+			.map(|arm| match_arm(arm).unwrap_or_else(|| panic!("Expected pattern => value in match, got {}", arm.serialize())))
+			.collect();
+		if self.emit_int_table(func, subject, &arms) {
+			return;
+		}
+		let subject_local = self.local_position(&match_local(subject));
+		self.emit_node_instructions(func, subject);
+		func.instruction(&Instruction::LocalSet(subject_local));
+
+		self.open_block(func, Instruction::Block(BlockType::Result(Ref(self.node_ref(false)))));
+		for (pattern, guard, value) in arms {
+			self.open_block(func, Instruction::Block(BlockType::Empty));
+			self.emit_pattern_tests(func, subject_local, pattern, &[]);
+			self.emit_pattern_bindings(func, subject_local, pattern, &[]);
+			if let Some(guard) = guard {
+				self.emit_numeric_value(func, guard);
+				func.instruction(&Instruction::I64Eqz);
+				func.instruction(&Instruction::BrIf(0));
+			}
+			self.emit_node_instructions(func, value);
+			func.instruction(&Instruction::Br(1));
+			self.close_block(func);
+		}
+		self.emit_call(func, "new_empty");
+		self.close_block(func);
+	}
+
+	/// Int literal arms over a number, maybe closed by `_`, jump straight to their value:
+	/// block { block { block {…} value_0 br } value_1 br … } with br_table picking the block
+	fn emit_int_table(&mut self, func: &mut Function, subject: &Node, arms: &[(&Node, Option<&Node>, &Node)]) -> bool {
+		let (literals, default) = match arms {
+			[literals @ .., (pattern, None, value)] if matches!(pattern.drop_meta(), Node::Symbol(s) if s == "_") => (literals, Some(*value)),
+			literals => (literals, None),
+		};
+		let values: Option<Vec<i64>> = literals.iter()
+			.map(|(pattern, guard, _)| match (pattern.drop_meta(), guard) {
+				(Node::Number(Number::Int(n)), None) => Some(*n),
+				_ => None,
+			})
+			.collect();
+		let Some(values) = values else { return false };
+		if values.len() < 2 || self.get_type(subject) != Kind::Int {
+			return false;
+		}
+		let min = *values.iter().min().unwrap();
+		let span = values.iter().max().unwrap() - min + 1;
+		if span > MAX_TABLE_SPAN {
+			return false;
+		}
+		let n = values.len() as u32;
+		// the first arm of a value wins, values without one go to the default
+		let targets: Vec<u32> = (min..min + span)
+			.map(|v| values.iter().position(|&value| value == v).map_or(n, |i| i as u32))
+			.collect();
+
+		let temp = self.next_temp_local;
+		self.next_temp_local += 1;
+		self.emit_numeric_value(func, subject);
+		func.instruction(&Instruction::LocalSet(temp));
+
+		self.open_block(func, Instruction::Block(BlockType::Result(Ref(self.node_ref(false)))));
+		for _ in 0..=n {
+			self.open_block(func, Instruction::Block(BlockType::Empty));
+		}
+		// index = subject - min if below span, else span, the default
+		func.instruction(&Instruction::LocalGet(temp));
+		func.instruction(&Instruction::I64Const(min));
+		func.instruction(&Instruction::I64Sub);
+		func.instruction(&Instruction::LocalTee(temp));
+		func.instruction(&Instruction::I32WrapI64);
+		func.instruction(&Instruction::I32Const(span as i32));
+		func.instruction(&Instruction::LocalGet(temp));
+		func.instruction(&Instruction::I64Const(span));
+		func.instruction(&Instruction::I64LtU);
+		func.instruction(&Instruction::Select);
+		func.instruction(&Instruction::BrTable(targets.into(), n));
+		for (i, (_, _, value)) in literals.iter().enumerate() {
+			self.close_block(func);
+			self.emit_node_instructions(func, value);
+			func.instruction(&Instruction::Br(n - i as u32));
+		}
+		self.close_block(func);
+		match default {
+			Some(value) => self.emit_node_instructions(func, value),
+			None => self.emit_call(func, "new_empty"),
+		}
+		self.close_block(func);
+		true
+	}
+
+	/// Leave the arm with br_if 0 unless the part of the subject at path matches pattern
+	fn emit_pattern_tests(&mut self, func: &mut Function, subject: u32, pattern: &Node, path: &[Step]) {
+		match pattern.drop_meta() {
+			Node::Symbol(name) if name == "_" => {}
+			Node::Symbol(name) => {
+				if let Some(kind) = pattern_kind(name) {
+					self.emit_kind_test(func, subject, path, &[kind]);
+				} else if is_type_pattern(name) {
+					self.emit_type_test(func, subject, path, name);
+				}
+			}
+			Node::Number(Number::Int(n)) => {
+				self.emit_kind_test(func, subject, path, &[Kind::Int]);
+				self.emit_path(func, subject, path);
+				self.emit_call(func, "get_int_value");
+				func.instruction(&Instruction::I64Const(*n));
+				func.instruction(&Instruction::I64Ne);
+				func.instruction(&Instruction::BrIf(0));
+			}
+			Node::True | Node::False => {
+				self.emit_kind_test(func, subject, path, &[Kind::Int]);
+				self.emit_path(func, subject, path);
+				self.emit_call(func, "get_int_value");
+				func.instruction(&Instruction::I64Const(matches!(pattern.drop_meta(), Node::True) as i64));
+				func.instruction(&Instruction::I64Ne);
+				func.instruction(&Instruction::BrIf(0));
+			}
+			Node::Number(_) | Node::Text(_) | Node::Char(_) => {
+				self.emit_kind_test(func, subject, path, literal_kinds(pattern));
+				self.emit_compare(func, subject, path, pattern);
+				func.instruction(&Instruction::I64Const(0));
+				func.instruction(&Instruction::I64Ne);
+				func.instruction(&Instruction::BrIf(0));
+			}
+			Node::Empty => self.emit_kind_test(func, subject, path, &[Kind::Empty]),
+			// 1..10 excludes its end, 'a' to 'z' and 1...10 include it
+			Node::Key(start, op @ (Op::Range | Op::To), end) => {
+				self.emit_kind_test(func, subject, path, literal_kinds(start));
+				self.emit_compare(func, subject, path, start);
+				func.instruction(&Instruction::I64Const(0));
+				func.instruction(&Instruction::I64LtS);
+				func.instruction(&Instruction::BrIf(0));
+				self.emit_compare(func, subject, path, end);
+				func.instruction(&Instruction::I64Const(0));
+				func.instruction(&if *op == Op::To { Instruction::I64GtS } else { Instruction::I64GeS });
+				func.instruction(&Instruction::BrIf(0));
+			}
+			Node::Key(name, Op::Colon, fields) if is_struct_pattern(name, fields) => {
+				self.emit_type_test(func, subject, path, &name.drop_meta().to_string());
+				for (field, pattern) in struct_fields(fields) {
+					// the instance has the field
+					self.emit_path(func, subject, path);
+					self.emit_string_call(func, &field, "new_symbol");
+					self.emit_call(func, "node_field");
+					func.instruction(&Instruction::RefIsNull);
+					func.instruction(&Instruction::BrIf(0));
+					self.emit_pattern_tests(func, subject, pattern, &then(path, Step::Field(field)));
+				}
+			}
+			Node::Key(key, Op::Colon, value) => {
+				self.emit_kind_test(func, subject, path, &[Kind::Key]);
+				self.emit_pattern_tests(func, subject, key, &then(path, Step::Data));
+				self.emit_pattern_tests(func, subject, value, &then(path, Step::Rest));
+			}
+			Node::List(items, Bracket::Square, _) => {
+				self.emit_kind_test(func, subject, path, &[Kind::List]);
+				self.emit_path(func, subject, path);
+				self.emit_call(func, "node_count");
+				func.instruction(&Instruction::I64Const(items.len() as i64));
+				func.instruction(&Instruction::I64Ne);
+				func.instruction(&Instruction::BrIf(0));
+				for (i, item) in items.iter().enumerate() {
+					self.emit_pattern_tests(func, subject, item, &item_path(path, i));
+				}
+			}
+			other => panic!("Unsupported match pattern: {}", other.serialize()),
+		}
+	}
+
+	/// Set the locals pattern binds to the parts of the subject at path
+	fn emit_pattern_bindings(&mut self, func: &mut Function, subject: u32, pattern: &Node, path: &[Step]) {
+		match pattern.drop_meta() {
+			Node::Symbol(name) if name == "_" || pattern_kind(name).is_some() || is_type_pattern(name) => {}
+			Node::Symbol(name) => {
+				let local = self.scope.lookup(name).unwrap_or_else(|| panic!("Undefined variable: {}", name));
+				let (position, kind) = (local.position, local.kind);
+				self.emit_path(func, subject, path);
+				self.emit_loop_value(func, kind);
+				func.instruction(&Instruction::LocalSet(position));
+			}
+			Node::Key(name, Op::Colon, fields) if is_struct_pattern(name, fields) => {
+				for (field, pattern) in struct_fields(fields) {
+					self.emit_pattern_bindings(func, subject, pattern, &then(path, Step::Field(field)));
+				}
+			}
+			Node::Key(key, Op::Colon, value) => {
+				self.emit_pattern_bindings(func, subject, key, &then(path, Step::Data));
+				self.emit_pattern_bindings(func, subject, value, &then(path, Step::Rest));
+			}
+			Node::List(items, Bracket::Square, _) => {
+				for (i, item) in items.iter().enumerate() {
+					self.emit_pattern_bindings(func, subject, item, &item_path(path, i));
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Leave the arm unless the part at path is an instance of the user type name: Person{…}
+	/// An enum matches instances of any of its variants, a class those of classes extending it
+	fn emit_type_test(&mut self, func: &mut Function, subject: u32, path: &[Step], name: &str) {
+		let key_path = then(path, Step::Data);
+		self.emit_kind_test(func, subject, path, &[Kind::Key]);
+		self.emit_kind_test(func, subject, &key_path, &[Kind::Symbol]);
+		let names = self.ctx.type_registry.instance_types(name);
+		for (i, name) in names.iter().enumerate() {
+			self.emit_path(func, subject, &key_path);
+			self.emit_string_call(func, name, "new_symbol");
+			self.emit_call(func, "node_compare");
+			func.instruction(&Instruction::I64Eqz);
+			if i > 0 {
+				func.instruction(&Instruction::I32Or);
+			}
+		}
+		func.instruction(&Instruction::I32Eqz);
+		func.instruction(&Instruction::BrIf(0));
+	}
+
+	/// Leave the arm unless the part at path is of one of the kinds
+	fn emit_kind_test(&mut self, func: &mut Function, subject: u32, path: &[Step], kinds: &[Kind]) {
+		for (i, kind) in kinds.iter().enumerate() {
+			self.emit_path(func, subject, path);
+			self.emit_kind_is(func, *kind);
+			if i > 0 {
+				func.instruction(&Instruction::I32Or);
+			}
+		}
+		func.instruction(&Instruction::I32Eqz);
+		func.instruction(&Instruction::BrIf(0));
+	}
+
+	/// node_compare(part at path, literal)
+	fn emit_compare(&mut self, func: &mut Function, subject: u32, path: &[Step], literal: &Node) {
+		self.emit_path(func, subject, path);
+		self.emit_node_instructions(func, literal);
+		self.emit_call(func, "node_compare");
+	}
+
+	/// The part of the subject at path
+	fn emit_path(&mut self, func: &mut Function, subject: u32, path: &[Step]) {
+		func.instruction(&Instruction::LocalGet(subject));
+		for step in path {
+			self.emit_step(func, step);
+		}
+	}
+
+	fn emit_step(&mut self, func: &mut Function, step: &Step) {
+		match step {
+			Step::Data => {
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.node_type)));
+			}
+			Step::Rest => {
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 2,
+				});
+				func.instruction(&Instruction::RefAsNonNull);
+			}
+			Step::Field(name) => {
+				self.emit_string_call(func, name, "new_symbol");
+				self.emit_call(func, "node_field");
+				func.instruction(&Instruction::RefAsNonNull);
+			}
+		}
+	}
+
+	/// kind of the node on the stack == kind, as i32
+	pub(super) fn emit_kind_is(&mut self, func: &mut Function, kind: Kind) {
+		self.emit_call(func, "get_kind");
+		func.instruction(&Instruction::I64Const(0xFF));
+		func.instruction(&Instruction::I64And);
+		func.instruction(&Instruction::I64Const(kind as i64));
+		func.instruction(&Instruction::I64Eq);
+	}
+}
+
+/// Kinds a literal pattern compares with: numbers with numbers, text with text
+fn literal_kinds(literal: &Node) -> &'static [Kind] {
+	match literal.drop_meta() {
+		Node::Text(_) => &[Kind::Text],
+		Node::Char(_) => &[Kind::Codepoint],
+		_ => &[Kind::Int, Kind::Float],
+	}
+}
+
+/// path, one step further
+fn then(path: &[Step], step: Step) -> Vec<Step> {
+	let mut path = path.to_vec();
+	path.push(step);
+	path
+}
+
+/// The cell of item i in the list at path, then its item
+fn item_path(path: &[Step], i: usize) -> Vec<Step> {
+	let mut path = path.to_vec();
+	path.extend(std::iter::repeat_n(Step::Rest, i));
+	path.push(Step::Data);
+	path
+}
+
+/// Field names and their patterns in Person{name, age: a}; a bare name is its own pattern
+fn struct_fields(fields: &Node) -> Vec<(String, &Node)> {
+	let Node::List(fields, _, _) = fields.drop_meta() else { return vec![] };
+	fields.iter()
+		.map(|field| match field.drop_meta() {
+			Node::Key(name, Op::Colon, pattern) => (name.drop_meta().to_string(), pattern.as_ref()),
+			name => (name.to_string(), field),
+		})
+		.collect()
+}
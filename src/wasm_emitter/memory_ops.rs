@@ -0,0 +1,191 @@
+//! Linear memory allocator for WASM: `alloc` and `free`
+//!
+//! Blocks carry an 8 byte header [capacity: i32, next free: i32] before the payload.
+//! Freed blocks go on a free list reused first fit, new blocks are bumped from the
+//! end of memory, growing it as needed. The heap starts after the initial pages,
+//! which hold the data segments. Hosts allocate through the exported `alloc` too.
+//!
+//! The runtime itself never frees: built text is referenced from GC `$String` structs,
+//! whose lifetime wasm cannot observe, so for compiled code this is a bump allocator.
+//! `free` is for hosts handing back buffers they allocated.
+
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+use Instruction::I32Const;
+
+/// Header word access
+const WORD: MemArg = MemArg {
+	offset: 0,
+	align: 2,
+	memory_index: 0,
+};
+
+/// The next free block link, second header word
+const NEXT: MemArg = MemArg {
+	offset: 4,
+	align: 2,
+	memory_index: 0,
+};
+
+impl WasmGcEmitter {
+	pub(crate) fn emit_memory_ops(&mut self) {
+		if !self.should_emit_function("alloc") && !self.should_emit_function("free") {
+			return;
+		}
+		let heap = self.emit_i32_global();
+		let free_list = self.emit_i32_global();
+
+		// alloc(size: i32) -> i32
+		{
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::I32], vec![ValType::I32]);
+			self.functions.function(func_type);
+			// Locals: 0=size, 1=previous block, 2=block, 3=end
+			let mut func = Function::new(vec![(3, ValType::I32)]);
+
+			// size = align8(size)
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&I32Const(7));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&I32Const(-8));
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::LocalSet(0));
+
+			// first fit from the free list
+			func.instruction(&Instruction::GlobalGet(free_list));
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32Load(WORD));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				// unlink and hand out
+				func.instruction(&Instruction::LocalGet(1));
+				func.instruction(&Instruction::I32Eqz);
+				func.instruction(&Instruction::If(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::I32Load(NEXT));
+				func.instruction(&Instruction::GlobalSet(free_list));
+				func.instruction(&Instruction::Else);
+				func.instruction(&Instruction::LocalGet(1));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::I32Load(NEXT));
+				func.instruction(&Instruction::I32Store(NEXT));
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&I32Const(8));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::Return);
+			}
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::LocalSet(1));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32Load(NEXT));
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+
+			// first call: heap starts after the initial pages
+			func.instruction(&Instruction::GlobalGet(heap));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			Self::emit_memory_bytes(&mut func);
+			func.instruction(&Instruction::GlobalSet(heap));
+			func.instruction(&Instruction::End);
+
+			// end = block + header + size
+			func.instruction(&Instruction::GlobalGet(heap));
+			func.instruction(&Instruction::LocalTee(2));
+			func.instruction(&I32Const(8));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalTee(3));
+
+			// grow by the missing pages
+			Self::emit_memory_bytes(&mut func);
+			func.instruction(&Instruction::I32GtU);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			Self::emit_memory_bytes(&mut func);
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&I32Const(65535));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&I32Const(16));
+			func.instruction(&Instruction::I32ShrU);
+			func.instruction(&Instruction::MemoryGrow(0));
+			func.instruction(&I32Const(-1));
+			func.instruction(&Instruction::I32Eq);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::Unreachable); // out of memory
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I32Store(WORD));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::GlobalSet(heap));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&I32Const(8));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("alloc");
+			self.exports.export("alloc", ExportKind::Func, idx);
+		}
+
+		// free(ptr: i32), called by hosts only
+		// Pushes the block on the free list; free(0) does nothing
+		{
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::I32], vec![]);
+			self.functions.function(func_type);
+			let mut func = Function::new(vec![]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::BrIf(0));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&I32Const(8));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::LocalTee(0));
+			func.instruction(&Instruction::GlobalGet(free_list));
+			func.instruction(&Instruction::I32Store(NEXT));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::GlobalSet(free_list));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("free");
+			self.exports.export("free", ExportKind::Func, idx);
+		}
+	}
+
+	/// Mutable i32 global starting at 0, returns its index
+	fn emit_i32_global(&mut self) -> u32 {
+		self.globals.global(
+			GlobalType {
+				val_type: ValType::I32,
+				mutable: true,
+				shared: false,
+			},
+			&ConstExpr::i32_const(0),
+		);
+		self.next_global_idx += 1;
+		self.next_global_idx - 1
+	}
+
+	/// Current memory size in bytes
+	pub(crate) fn emit_memory_bytes(func: &mut Function) {
+		func.instruction(&Instruction::MemorySize(0));
+		func.instruction(&I32Const(16));
+		func.instruction(&Instruction::I32Shl);
+	}
+}
@@ -2,14 +2,21 @@
 
 #[macro_use]
 mod constructors;
+mod closure_emitter;
 mod config;
 mod ffi_emitter;
 mod import_manager;
 mod key_emitter;
 mod list_emitter;
+mod list_functions;
 mod list_ops;
+mod loop_emitter;
+mod match_emitter;
+mod memory_ops;
 mod node_emitter;
+mod string_ops;
 mod string_table;
+mod text_ops;
 mod type_manager;
 mod wasi_emitter;
 
@@ -18,11 +25,14 @@ pub use import_manager::ImportManager;
 pub use string_table::StringTable;
 pub use type_manager::TypeManager;
 
-use crate::analyzer::{analyze_required_functions, collect_all_types, collect_variables, extract_ffi_imports, extract_user_functions, infer_type, Scope};
+use crate::analyzer::{analyze_required_functions, analyze_text_functions, arg_type, bind_type_params, check_types, collect_all_types, collect_instance_types, collect_variables, construct_variants, define_params, extract_closures, extract_ffi_imports, extract_user_functions, infer_type, is_overloaded, param_kinds, resolve_overload, with_definitions, Scope};
+use crate::compiled::{catch_silently, panic_message, CompiledModule};
 use crate::context::{Context, UserFunctionDef};
+use crate::error::{find_parse_error, WarpError};
 use crate::extensions::numbers::Number;
 use crate::function::{Function as FuncDef, Signature};
 use crate::gc_traits::GcObject as ErgonomicGcObject;
+use crate::meta::LineInfo;
 use crate::node::{Bracket, Node};
 use crate::normalize::hints as norm;
 use crate::operators::{is_function_keyword, op_to_code, Op};
@@ -31,6 +41,7 @@ use crate::util::gc_engine;
 use crate::wasm_reader::read_bytes;
 use crate::wasp_parser::WaspParser;
 use log::{trace, warn};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::thread::scope;
 use wasm_ast::instruction;
@@ -51,6 +62,22 @@ use ValType::Ref;
 ///   (field $value (ref null $Node)) ;; Child/value node (for Key, Pair, List, etc.)
 /// ))
 /// ```
+thread_local! {
+	/// Position of the latest node emitted, for errors raised while emitting it
+	static EMIT_POSITION: RefCell<Option<LineInfo>> = const { RefCell::new(None) };
+}
+
+/// Source position of the node the emitter was working on, after it failed
+pub fn emit_position() -> Option<LineInfo> {
+	EMIT_POSITION.with(|position| position.borrow().clone())
+}
+
+fn track_position(node: &Node) {
+	if let Some(info) = node.get_lineinfo() {
+		EMIT_POSITION.with(|position| *position.borrow_mut() = Some(info));
+	}
+}
+
 pub struct WasmGcEmitter {
 	module: Module,
 	functions: FunctionSection,
@@ -73,6 +100,10 @@ pub struct WasmGcEmitter {
 	next_global_idx: u32,
 	next_temp_local: u32,
 
+	// Blocks around user code, and the (break, continue) block depths of the loops they are in
+	block_depth: u32,
+	loop_labels: Vec<(u32, u32)>,
+
 	// Compilation context
 	pub(crate) ctx: Context, // module scope: globals, functions, types, etc.
 
@@ -102,11 +133,21 @@ impl WasmGcEmitter {
 			next_func_idx: 0,
 			next_global_idx: 0,
 			next_temp_local: 0,
+			block_depth: 0,
+			loop_labels: Vec::new(),
 			ctx: Context::new(),
 			scope: Default::default(),
 		}
 	}
 
+	/// Create an emitter with an explicit configuration
+	pub fn with_config(config: EmitterConfig) -> Self {
+		WasmGcEmitter {
+			config,
+			..Self::new()
+		}
+	}
+
 	/// Enable/disable emitting Kind globals for documentation
 	pub fn set_emit_kind_globals(&mut self, enabled: bool) {
 		self.config.emit_kind_globals = enabled;
@@ -226,27 +267,24 @@ impl WasmGcEmitter {
 		for name in &func_names {
 			self.register_user_function_signature(name);
 		}
+		self.register_closure_signatures();
 
 		// PASS 2: Compile all function bodies
 		for name in func_names {
 			self.compile_user_function_body(&name);
 		}
+		self.compile_closures();
 	}
 
 	/// Register a user function's signature and assign it an index (PASS 1)
 	fn register_user_function_signature(&mut self, name: &str) {
 		let user_fn = self.ctx.user_functions.get(name).unwrap().clone();
-		let returns_node = user_fn.return_kind.is_ref();  // Text, Symbol, List, etc. return Node refs
 
-		// Create function type: (params...) -> i64 or (ref $Node) depending on return type
+		// Create function type: (params...) -> i64, f64 or (ref $Node) depending on return type
 		let func_type_idx = self.type_manager.types().len();
-		let param_types: Vec<ValType> = user_fn.params.iter().map(|_| ValType::I64).collect();
-		if returns_node {
-			let node_ref = self.node_ref(false);
-			self.type_manager.types_mut().ty().function(param_types, vec![Ref(node_ref)]);
-		} else {
-			self.type_manager.types_mut().ty().function(param_types, vec![ValType::I64]);
-		}
+		let param_types: Vec<ValType> = param_kinds(&user_fn).into_iter().map(|kind| self.local_type(kind)).collect();
+		let result = self.local_type(user_fn.return_kind);
+		self.type_manager.types_mut().ty().function(param_types, vec![result]);
 
 		// Register function in function section
 		self.functions.function(func_type_idx);
@@ -262,27 +300,33 @@ impl WasmGcEmitter {
 	/// Compile a user function's body (PASS 2)
 	fn compile_user_function_body(&mut self, name: &str) {
 		let user_fn = self.ctx.user_functions.get(name).unwrap().clone();
-		let returns_node = user_fn.return_kind.is_ref();  // Text, Symbol, List, etc. return Node refs
+		// Text, Symbol, List, etc. return Node refs, floats f64
+		let returns_node = user_fn.return_kind.is_ref();
+		let returns_float = user_fn.return_kind.is_float();
 
 		// Create function scope with parameters
 		let saved_scope = std::mem::replace(&mut self.scope, Scope::new());
-		for (param_name, _default) in user_fn.params.iter() {
-			self.scope.define(param_name.clone(), None, Kind::Int);
-		}
+		define_params(&mut self.scope, &user_fn);
 
 		// Collect any additional variables in the body
-		collect_variables(&user_fn.body, &mut self.scope);
+		let temp_locals = collect_variables(&user_fn.body, &mut self.scope);
+		collect_instance_types(&self.ctx, &user_fn.body, &mut self.scope);
 
-		// Declare locals (parameters are already accounted for)
+		// Declare locals typed by kind like in main (parameters are already accounted for)
 		let num_params = user_fn.params.len() as u32;
-		let num_locals = self.scope.local_count();
-		let extra_locals = num_locals.saturating_sub(num_params);
+		let mut locals = self.local_types(num_params);
+		if temp_locals > 0 {
+			locals.push((temp_locals, ValType::I64));
+		}
+		let saved_temp_local = std::mem::replace(&mut self.next_temp_local, self.scope.local_count());
 
-		let mut func = Function::new(vec![(extra_locals, ValType::I64)]);
+		let mut func = Function::new(locals);
 
 		// Compile the function body - use node instructions for Node-returning functions
 		if returns_node {
 			self.emit_node_instructions(&mut func, &user_fn.body);
+		} else if returns_float {
+			self.emit_float_value(&mut func, &user_fn.body);
 		} else {
 			self.emit_numeric_value(&mut func, &user_fn.body);
 		}
@@ -293,6 +337,7 @@ impl WasmGcEmitter {
 
 		// Restore scope
 		self.scope = saved_scope;
+		self.next_temp_local = saved_temp_local;
 
 		// Export the function (get func_idx from the stored function definition)
 		let func_idx = self.ctx.user_functions.get(name).unwrap().func_index.unwrap();
@@ -301,17 +346,15 @@ impl WasmGcEmitter {
 
 	/// Emit a call to a user-defined function (returns Node)
 	fn emit_user_function_call(&mut self, func: &mut Function, fn_name: &str, args: &[Node]) {
-		let user_fn = match self.ctx.user_functions.get(fn_name) {
-			Some(f) => f.clone(),
-			None => panic!("Unknown user function: {}", fn_name),
-		};
-		let returns_node = user_fn.return_kind.is_ref();
+		let user_fn = self.user_function(fn_name, args);
 
 		// Emit arguments and call
 		self.emit_user_function_call_inner(func, &user_fn, args);
 
-		// If function returns i64, wrap in new_int for Node context
-		if !returns_node {
+		// If function returns i64 or f64, wrap in new_int or new_float for Node context
+		if user_fn.return_kind.is_float() {
+			self.emit_call(func, "new_float");
+		} else if !user_fn.return_kind.is_ref() {
 			self.emit_call(func, "new_int");
 		}
 	}
@@ -319,22 +362,51 @@ impl WasmGcEmitter {
 	/// Emit a call to a user-defined function (returns raw i64)
 	/// Note: For Node-returning functions, this extracts the integer value from the Node
 	fn emit_user_function_call_numeric(&mut self, func: &mut Function, fn_name: &str, args: &[Node]) {
-		let user_fn = match self.ctx.user_functions.get(fn_name) {
-			Some(f) => f.clone(),
-			None => panic!("Unknown user function: {}", fn_name),
-		};
-		let returns_node = user_fn.return_kind.is_ref();
+		let user_fn = self.user_function(fn_name, args);
 
 		// Emit arguments and call
 		self.emit_user_function_call_inner(func, &user_fn, args);
 
-		// If function returns Node but we need i64, extract the value
-		if returns_node {
+		// If function returns Node or f64 but we need i64, extract the value
+		if user_fn.return_kind.is_float() {
+			func.instruction(&Instruction::I64TruncF64S);
+		} else if user_fn.return_kind.is_ref() {
 			// Call get_int_value to extract integer from Node
 			self.emit_call(func, "get_int_value");
 		}
 	}
 
+	/// Emit a call to a user-defined function (returns raw f64)
+	fn emit_user_function_call_float(&mut self, func: &mut Function, fn_name: &str, args: &[Node]) {
+		let user_fn = self.user_function(fn_name, args);
+		if user_fn.return_kind.is_float() {
+			self.emit_user_function_call_inner(func, &user_fn, args);
+		} else if user_fn.return_kind.is_ref() {
+			// a generic result like T of first<T>, bound to float here
+			self.emit_user_function_call_inner(func, &user_fn, args);
+			self.emit_call(func, "get_float_value");
+		} else {
+			self.emit_user_function_call_numeric(func, fn_name, args);
+			func.instruction(&Instruction::F64ConvertI64S);
+		}
+	}
+
+	/// The user function a call runs: for an overloaded one the variant taking the types of the
+	/// arguments, else its dispatcher choosing at runtime
+	fn user_function(&self, fn_name: &str, args: &[Node]) -> UserFunctionDef {
+		let mut name = fn_name.to_string();
+		if is_overloaded(&self.ctx, fn_name) {
+			let arg_types: Vec<String> = args.iter().map(|arg| arg_type(arg, self.get_type(arg), &self.ctx.type_registry)).collect();
+			if let Ok(Some(variant)) = resolve_overload(&self.ctx, fn_name, &arg_types) {
+				name = variant;
+			}
+		}
+		match self.ctx.user_functions.get(&name) {
+			Some(f) => f.clone(),
+			None => panic!("Unknown user function: {}", fn_name),
+		}
+	}
+
 	/// Inner helper for emitting user function calls
 	fn emit_user_function_call_inner(&mut self, func: &mut Function, user_fn: &UserFunctionDef, args: &[Node]) {
 		let func_index = match user_fn.func_index {
@@ -343,13 +415,21 @@ impl WasmGcEmitter {
 		};
 
 		// Emit arguments, using defaults for missing ones
-		for (i, (_param_name, default_value)) in user_fn.params.iter().enumerate() {
-			if i < args.len() {
-				// Use provided argument
-				self.emit_numeric_value(func, &args[i]);
-			} else if let Some(default) = default_value {
-				// Use default value
-				self.emit_numeric_value(func, default);
+		for (i, ((_, default_value), kind)) in user_fn.params.iter().zip(param_kinds(user_fn)).enumerate() {
+			let value = if i < args.len() { Some(&args[i]) } else { default_value.as_ref() };
+			if let Some(value) = value {
+				// Closures the function calls, instances it reads and texts are passed as nodes
+				if kind.is_ref() {
+					self.emit_node_instructions(func, value);
+				} else if kind.is_float() && self.get_type(value).is_ref() {
+					// a node passed on by a dispatcher
+					self.emit_node_instructions(func, value);
+					self.emit_call(func, "get_float_value");
+				} else if kind.is_float() {
+					self.emit_float_value(func, value);
+				} else {
+					self.emit_numeric_value(func, value);
+				}
 			} else {
 				panic!("Missing argument {} for function {} (no default)", i, user_fn.name);
 			}
@@ -405,25 +485,52 @@ impl WasmGcEmitter {
 			Node::Key(left, op, right) if op.is_arithmetic() => {
 				let left_kind = self.get_type(left);
 				let right_kind = self.get_type(right);
-				if left_kind == Kind::Float || right_kind == Kind::Float {
+				if *op == Op::Add && (left_kind == Kind::Text || right_kind == Kind::Text) {
+					Kind::Text
+				} else if left_kind == Kind::Float || right_kind == Kind::Float {
 					Kind::Float
 				} else {
 					Kind::Int
 				}
 			}
+			// Calls of user functions returning floats: combine(1.5, 2.0)
+			Node::List(items, _, _) if self.calls_float_function(items) => Kind::Float,
 			// For other nodes, use analyzer's infer_type
 			_ => infer_type(node, &self.scope),
 		}
 	}
 
+	fn calls_float_function(&self, items: &[Node]) -> bool {
+		match items.first().map(Node::drop_meta) {
+			Some(Node::Symbol(name)) if self.ctx.user_functions.contains_key(name) => {
+				let user_fn = self.user_function(name, &items[1..]);
+				user_fn.return_kind.is_float() || self.generic_result(&user_fn, &items[1..]).as_deref() == Some("float")
+			}
+			_ => false,
+		}
+	}
+
+	/// The type a generic function returns for these arguments: int for first([7, 8]) of first<T>(xs: List<T>) -> T
+	/// Its result is erased to a node, unboxed by the caller knowing its type
+	fn generic_result(&self, user_fn: &UserFunctionDef, args: &[Node]) -> Option<String> {
+		let return_type = user_fn.return_type.as_ref().filter(|t| user_fn.type_params.contains(t))?;
+		let kind_of = |arg: &Node| self.get_type(arg);
+		let mut bindings = bind_type_params(user_fn, args, &kind_of, &self.ctx.type_registry).ok()?;
+		bindings.remove(return_type)
+	}
+
 	/// Check if an expression is numeric (int, float, or bool)
 	fn is_numeric(&self, node: &Node) -> bool {
 		let node = node.drop_meta();
 		match node {
 			Node::Number(_) | Node::True | Node::False => true,
-			Node::Key(_left, op, _right) if op.is_arithmetic() || op.is_comparison() => true,
+			Node::Key(_left, op, _right) if op.is_comparison() => true,
+			Node::Key(_left, op, _right) if op.is_arithmetic() => self.get_type(node) != Kind::Text,
 			Node::Key(left, op, right) if op.is_logical() => self.is_numeric(left) && self.is_numeric(right),
 			Node::Key(_, Op::Define | Op::Assign, right) => self.is_numeric(right),
+			Node::List(..) | Node::Key(_, Op::Dot, _) if self.text_call(node).is_some() => self.get_type(node) == Kind::Int,
+			Node::List(..) | Node::Key(_, Op::Dot, _) if self.list_call(node).is_some() => self.get_type(node) == Kind::Int,
+			Node::Key(_, Op::Match, _) => self.get_type(node) == Kind::Int,
 			Node::Symbol(name) => {
 				// Check if symbol is a known numeric variable
 				if let Some(local) = self.scope.lookup(name) {
@@ -562,14 +669,30 @@ impl WasmGcEmitter {
 
 	pub fn emit_for_node(&mut self, node: &Node) {
 		self.config.emit_all_functions = false;
+		self.emit_program(node);
+	}
+
+	/// Like emit_for_node, but keeps the configured tree-shaking setting
+	pub fn emit_program(&mut self, node: &Node) {
+		EMIT_POSITION.with(|position| *position.borrow_mut() = None);
 		// First pass: register all types (forward reference support)
 		collect_all_types(&mut self.ctx.type_registry, node);
+		let node = &with_definitions(&construct_variants(&self.ctx.type_registry, node));
 		// Analyze: Extract FFI imports, user functions, and required functions
 		extract_ffi_imports(&mut self.ctx, node);
 		extract_user_functions(&mut self.ctx, node);
 		analyze_required_functions(&mut self.ctx, node);
+		// Methods read fields through self, dispatchers match on it
+		let bodies: Vec<Box<Node>> = self.ctx.user_functions.values().map(|f| f.body.clone()).collect();
+		for body in &bodies {
+			analyze_required_functions(&mut self.ctx, body);
+		}
+		let mut scope = Scope::new();
+		collect_variables(node, &mut scope);
+		analyze_text_functions(&mut self.ctx, node, &scope);
+		extract_closures(&mut self.ctx, node, &scope);
 		// Set emit flag based on whether any FFI imports were found
-		self.config.emit_ffi_imports = !self.ctx.ffi_imports.is_empty();
+		self.config.emit_ffi_imports |= !self.ctx.ffi_imports.is_empty();
 		let len = self.ctx.required_functions.len();
 		trace!(
 			"tree-shaking: {} functions required: {:?}",
@@ -631,11 +754,15 @@ impl WasmGcEmitter {
 	}
 
 	/// Emit a constructor function for a single user type: new_TypeName(fields...) -> ref $TypeName
+	/// Enums only get constructors for their variants, which pass their tag first
 	fn emit_user_type_constructor(&mut self, type_def: &TypeDef) {
 		let type_idx = match self.ctx.user_type_indices.get(&type_def.name) {
 			Some(idx) => *idx,
 			None => return,
 		};
+		if !type_def.variants.is_empty() {
+			return;
+		}
 
 		let type_ref = RefType {
 			nullable: false,
@@ -652,6 +779,9 @@ impl WasmGcEmitter {
 
 		// Function body: get all params, struct.new
 		let mut func = Function::new(vec![]);
+		if type_def.is_tagged() {
+			func.instruction(&Instruction::I32Const(type_def.tag as i32));
+		}
 		for i in 0..type_def.fields.len() {
 			func.instruction(&Instruction::LocalGet(i as u32));
 		}
@@ -664,9 +794,8 @@ impl WasmGcEmitter {
 		let func_name = format!("new_{}", type_def.name);
 		// Leak the string to get a 'static str for the export
 		let func_name_static: &'static str = Box::leak(func_name.clone().into_boxed_str());
-		self.exports
-			.export(func_name_static, ExportKind::Func, self.next_func_idx);
-		self.next_func_idx += 1;
+		let idx = self.register_func(func_name_static);
+		self.exports.export(func_name_static, ExportKind::Func, idx);
 	}
 
 
@@ -679,6 +808,13 @@ impl WasmGcEmitter {
 		// Emit helper functions
 		self.emit_getters();
 		self.emit_math_helpers();
+		// Allocator and runtime text building, needs new_text
+		self.emit_memory_ops();
+		self.emit_string_ops();
+		self.emit_text_ops();
+		self.emit_loop_ops();
+		self.emit_list_functions();
+		self.emit_match_ops();
 	}
 
 	fn emit_getters(&mut self) {
@@ -720,6 +856,29 @@ impl WasmGcEmitter {
 		self.code.function(&func);
 		let idx = self.register_func("get_int_value");
 		self.exports.export("get_int_value", ExportKind::Func, idx);
+
+		// get_float_value(node: ref $Node) -> f64
+		// Extract float from Node's data field (f64box)
+		if self.should_emit_function("get_float_value") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![ValType::F64]);
+			self.functions.function(func_type);
+			let mut func = Function::new(vec![]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 1,
+			});
+			func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.f64_box_type)));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.f64_box_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("get_float_value");
+			self.exports.export("get_float_value", ExportKind::Func, idx);
+		}
 	}
 
 	/// Emit math helper functions (i64_pow, etc.)
@@ -789,6 +948,7 @@ impl WasmGcEmitter {
 	pub fn emit_node_main(&mut self, node: &Node) {
 		// Pre-pass: collect variables first so scope is populated
 		let temp_locals = collect_variables(node, &mut self.scope);
+		collect_instance_types(&self.ctx, node, &mut self.scope);
 
 		// Allocate strings and update Local data pointers
 		self.collect_and_allocate_strings(node);
@@ -805,23 +965,8 @@ impl WasmGcEmitter {
 		}
 		self.functions.function(func_type);
 
-		// Build locals list based on variable types
 		// Each variable gets its own entry, then temp locals (all i64)
-		let mut locals: Vec<(u32, ValType)> = Vec::new();
-
-		// Sort locals by position to ensure correct order
-		let mut sorted_locals: Vec<_> = self.scope.locals.values().collect();
-		sorted_locals.sort_by_key(|l| l.position);
-
-		for local in sorted_locals {
-			if local.kind.is_ref() {
-				locals.push((1, Ref(node_ref)));
-			} else if local.kind.is_float() {
-				locals.push((1, ValType::F64));
-			} else {
-				locals.push((1, ValType::I64));
-			}
-		}
+		let mut locals = self.local_types(0);
 
 		// Add temp locals (i64 for now)
 		if temp_locals > 0 {
@@ -837,6 +982,25 @@ impl WasmGcEmitter {
 		self.next_func_idx += 1;
 	}
 
+	/// Build locals list based on variable types, from position first on
+	fn local_types(&self, first: u32) -> Vec<(u32, ValType)> {
+		// Sort locals by position to ensure correct order
+		let mut sorted_locals: Vec<_> = self.scope.locals.values().filter(|l| l.position >= first).collect();
+		sorted_locals.sort_by_key(|l| l.position);
+		sorted_locals.iter().map(|local| (1, self.local_type(local.kind))).collect()
+	}
+
+	/// WASM type of a local of kind: a Node reference, f64 or i64
+	fn local_type(&self, kind: Kind) -> ValType {
+		if kind.is_ref() {
+			Ref(self.node_ref(false))
+		} else if kind.is_float() {
+			ValType::F64
+		} else {
+			ValType::I64
+		}
+	}
+
 	fn collect_and_allocate_strings(&mut self, node: &Node) {
 		self.string_table.collect_from_node(node, &mut self.scope);
 	}
@@ -852,6 +1016,7 @@ impl WasmGcEmitter {
 
 	/// Emit instructions to construct a Node
 	fn emit_node_instructions(&mut self, func: &mut Function, node: &Node) {
+		track_position(node);
 		let node = node.drop_meta();
 
 		match node {
@@ -886,11 +1051,17 @@ impl WasmGcEmitter {
 						return; // Already a Node reference
 					} else if local.kind.is_float() {
 						self.emit_call(func, "new_float");
+					} else if local.kind == Kind::Codepoint {
+						func.instruction(&Instruction::I32WrapI64);
+						self.emit_call(func, "new_codepoint");
 					} else {
 						self.emit_call(func, "new_int");
 					}
 					return;
 				}
+				if self.emit_loop_jump(func, s) {
+					return;
+				}
 				// Check if this is a global variable lookup
 				if let Some(&(idx, kind)) = self.ctx.user_globals.get(s) {
 					func.instruction(&Instruction::GlobalGet(idx));
@@ -901,13 +1072,16 @@ impl WasmGcEmitter {
 					}
 					return;
 				}
+				if self.emit_function_value(func, s) {
+					return;
+				}
 				self.emit_string_call(func, s, "new_symbol");
 			}
 			Node::Key(left, op, right) => {
 				self.emit_key_node(func, left, op, right);
 			}
-			Node::List(items, bracket, _separator) => {
-				self.emit_list_node(func, items, bracket);
+			Node::List(items, bracket, separator) => {
+				self.emit_list_node(func, items, bracket, separator);
 			}
 			Node::Data(dada) => {
 				self.emit_string_call(func, &dada.type_name, "new_symbol");
@@ -1450,7 +1624,7 @@ impl WasmGcEmitter {
 		func.instruction(&Instruction::I32WrapI64);
 
 		// if (condition) { then_expr } else { else_expr }
-		func.instruction(&Instruction::If(BlockType::Result(Ref(self.node_ref(false)))));
+		self.open_block(func, Instruction::If(BlockType::Result(Ref(self.node_ref(false)))));
 
 		// Then branch - use emit_node_instructions to handle any type (Text, Number, etc.)
 		self.emit_node_instructions(func, then_expr);
@@ -1460,7 +1634,7 @@ impl WasmGcEmitter {
 		// Else branch - use emit_node_instructions to handle any type
 		self.emit_node_instructions(func, else_expr);
 
-		func.instruction(&Instruction::End);
+		self.close_block(func);
 	}
 
 	/// Emit ternary expression returning i64: condition ? then_expr : else_expr
@@ -1476,7 +1650,7 @@ impl WasmGcEmitter {
 		func.instruction(&Instruction::I32WrapI64);
 
 		// if (condition) { then_expr } else { else_expr }
-		func.instruction(&Instruction::If(BlockType::Result(ValType::I64)));
+		self.open_block(func, Instruction::If(BlockType::Result(ValType::I64)));
 
 		// Then branch
 		self.emit_numeric_value(func, then_expr);
@@ -1486,7 +1660,7 @@ impl WasmGcEmitter {
 		// Else branch
 		self.emit_numeric_value(func, else_expr);
 
-		func.instruction(&Instruction::End);
+		self.close_block(func);
 	}
 
 	/// Emit if-then-else returning i64: if condition then then_expr else else_expr
@@ -1509,7 +1683,7 @@ impl WasmGcEmitter {
 		func.instruction(&Instruction::I32WrapI64);
 
 		// if (condition) { then_expr } else { else_expr }
-		func.instruction(&Instruction::If(BlockType::Result(ValType::I64)));
+		self.open_block(func, Instruction::If(BlockType::Result(ValType::I64)));
 
 		// Then branch
 		self.emit_numeric_value(func, then_expr);
@@ -1524,7 +1698,7 @@ impl WasmGcEmitter {
 			func.instruction(&Instruction::I64Const(0));
 		}
 
-		func.instruction(&Instruction::End);
+		self.close_block(func);
 	}
 
 	/// Emit if-then-else expression: if condition then then_expr [else else_expr]
@@ -1549,7 +1723,7 @@ impl WasmGcEmitter {
 		func.instruction(&Instruction::I32WrapI64);
 
 		// if (condition) { then_expr } else { else_expr }
-		func.instruction(&Instruction::If(BlockType::Result(Ref(self.node_ref(false)))));
+		self.open_block(func, Instruction::If(BlockType::Result(Ref(self.node_ref(false)))));
 
 		// Then branch - extract value from block if needed
 		self.emit_block_value(func, then_expr);
@@ -1565,7 +1739,7 @@ impl WasmGcEmitter {
 		}
 		self.emit_call(func, "new_int");
 
-		func.instruction(&Instruction::End);
+		self.close_block(func);
 	}
 
 	/// Emit while loop: (while condition) do body
@@ -1582,20 +1756,23 @@ impl WasmGcEmitter {
 		func.instruction(&Instruction::I64Const(0));
 		func.instruction(&Instruction::LocalSet(result_local));
 
-		func.instruction(&Instruction::Block(BlockType::Empty));
-		func.instruction(&Instruction::Loop(BlockType::Empty));
+		self.open_block(func, Instruction::Block(BlockType::Empty));
+		self.open_block(func, Instruction::Loop(BlockType::Empty));
 
 		self.emit_block_value(func, condition);
 		func.instruction(&Instruction::I32WrapI64);
 		func.instruction(&Instruction::I32Eqz);
 		func.instruction(&Instruction::BrIf(1));
 
+		// break leaves the block, continue checks the condition again
+		self.loop_labels.push((self.block_depth - 1, self.block_depth));
 		self.emit_block_value(func, body);
+		self.loop_labels.pop();
 		func.instruction(&Instruction::LocalSet(result_local));
 		func.instruction(&Instruction::Br(0));
 
-		func.instruction(&Instruction::End);
-		func.instruction(&Instruction::End);
+		self.close_block(func);
+		self.close_block(func);
 
 		func.instruction(&Instruction::LocalGet(result_local));
 		if wrap_result {
@@ -1650,6 +1827,12 @@ impl WasmGcEmitter {
 						func.instruction(&Instruction::I64Const(n));
 						self.emit_call(func, "new_int");
 					}
+					// Runtime: parse text
+					_ if self.get_type(value) == Kind::Text => {
+						self.emit_node_instructions(func, value);
+						self.emit_call(func, "text_to_int");
+						self.emit_call(func, "new_int");
+					}
 					// Runtime: float expression to int
 					_ if self.get_type(value).is_float() => {
 						self.emit_float_value(func, value);
@@ -1683,6 +1866,12 @@ impl WasmGcEmitter {
 						func.instruction(&Instruction::F64Const((*n as f64).into()));
 						self.emit_call(func, "new_float");
 					}
+					// Runtime: parse text
+					_ if self.get_type(value) == Kind::Text => {
+						self.emit_node_instructions(func, value);
+						self.emit_call(func, "text_to_float");
+						self.emit_call(func, "new_float");
+					}
 					// Runtime: emit as float
 					_ => {
 						self.emit_float_value(func, value);
@@ -1716,7 +1905,7 @@ impl WasmGcEmitter {
 					// Runtime: use cast function
 					_ => {
 						self.emit_node_instructions(func, value);
-						self.emit_call(func, "cast_to_string");
+						self.emit_call(func, "to_text");
 					}
 				}
 			}
@@ -1816,6 +2005,11 @@ impl WasmGcEmitter {
 							self.emit_call(func, "new_int");
 						}
 					}
+					// Runtime: parse text, int or float by its content
+					_ if self.get_type(value) == Kind::Text => {
+						self.emit_node_instructions(func, value);
+						self.emit_call(func, "text_to_number");
+					}
 					_ => {
 						// Already numeric
 						self.emit_node_instructions(func, value);
@@ -1845,7 +2039,24 @@ impl WasmGcEmitter {
 
 	/// Emit the numeric value of a node onto the stack (as i64)
 	fn emit_numeric_value(&mut self, func: &mut Function, node: &Node) {
+		track_position(node);
 		let node = node.drop_meta();
+		// Text searches are plain integers: s.index_of("b") + 1
+		if let Some((name, args)) = self.text_call(node) {
+			if self.emit_text_search(func, name, &args) {
+				return;
+			}
+		}
+		// any and all are plain integers, other list functions any node: reduce(xs, (a, b) => a + b)
+		if let Some((name, args)) = self.list_call(node) {
+			if self.emit_list_test(func, name, &args) {
+				return;
+			}
+			if self.emit_list_call(func, name, &args) {
+				self.emit_call(func, "get_int_value");
+				return;
+			}
+		}
 		// Handle global declaration: global:Key(name, =, value)
 		if let Node::Key(left, Op::Colon, right) = node {
 			if let Node::Symbol(kw) = left.drop_meta() {
@@ -2104,7 +2315,7 @@ impl WasmGcEmitter {
 			// Variable lookup (local or global)
 			Node::Symbol(name) => {
 				// Handle $n parameter reference (e.g., $0 = first param)
-				if let Some(rest) = name.strip_prefix('$') {
+				if let Some(rest) = name.strip_prefix('$').filter(|_| self.scope.lookup(name).is_none()) {
 					if let Ok(idx) = rest.parse::<u32>() {
 						func.instruction(&Instruction::LocalGet(idx));
 						return;
@@ -2115,7 +2326,12 @@ impl WasmGcEmitter {
 					if local.kind.is_float() {
 						// Convert f64 local to i64 for integer operations
 						func.instruction(&Instruction::I64TruncF64S);
+					} else if local.kind.is_ref() {
+						// Node local, e.g. the items of for x in list
+						self.emit_call(func, "get_int_value");
 					}
+				} else if self.emit_loop_jump(func, name) {
+					// break or continue
 				} else if let Some(&(idx, kind)) = self.ctx.user_globals.get(name) {
 					func.instruction(&Instruction::GlobalGet(idx));
 					if kind.is_float() {
@@ -2127,7 +2343,15 @@ impl WasmGcEmitter {
 				}
 			}
 			// Statement sequence or function call
-			Node::List(items, bracket, _) if !items.is_empty() => {
+			Node::List(items, bracket, separator) if !items.is_empty() => {
+				// Closure call: f(x), f x, f()
+				if items.len() >= 2 || *bracket == Bracket::Round {
+					if let Some(callee) = self.closure_callee(items, separator) {
+						self.emit_closure_call(func, callee, &items[1..]);
+						self.emit_call(func, "get_int_value");
+						return;
+					}
+				}
 				// Check for zero-argument function call: (funcname)
 				if items.len() == 1 && *bracket == Bracket::Round {
 					if let Node::Symbol(fn_name) = items[0].drop_meta() {
@@ -2183,10 +2407,35 @@ impl WasmGcEmitter {
 					}
 				}
 			}
+			// For loop: emit loop and get numeric result
+			Node::Key(left, Op::Do, right) if matches!(left.drop_meta(), Node::Key(_, Op::For, _)) => {
+				self.emit_for_loop_impl(func, left, right, false);
+			}
 			// While loop: emit loop and get numeric result
 			Node::Key(left, Op::Do, right) => {
 				self.emit_while_loop_value(func, left, right);
 			}
+			// match: the int of the value of its arm
+			Node::Key(_, Op::Match, _) => {
+				self.emit_node_instructions(func, node);
+				self.emit_call(func, "get_int_value");
+			}
+			// Methods: rect.area() + 1
+			Node::Key(receiver, Op::Dot, member) if self.method_call(receiver, member).is_some() => {
+				let (method, args) = self.method_call(receiver, member).unwrap();
+				let mut receiver_and_args = vec![(**receiver).clone()];
+				receiver_and_args.extend_from_slice(args);
+				self.emit_user_function_call_numeric(func, &method, &receiver_and_args);
+			}
+			// Fields of instances, maybe through null: tree.value, tree?.value ?? 0
+			Node::Key(_, Op::Dot, field) if self.instance_field(field).is_some() => {
+				self.emit_node_instructions(func, node);
+				self.emit_call(func, "get_int_value");
+			}
+			Node::Key(_, Op::SafeDot | Op::Coalesce, _) => {
+				self.emit_node_instructions(func, node);
+				self.emit_call(func, "get_int_value");
+			}
 			other => {
 				panic!("Cannot extract numeric value from {:?}", other)
 			}
@@ -2304,6 +2553,11 @@ impl WasmGcEmitter {
 				self.emit_float_value(func, right);
 				func.instruction(&Instruction::F64Abs);
 			}
+			// match: the float of the value of its arm, like a dispatcher of float functions
+			Node::Key(_, Op::Match, _) => {
+				self.emit_node_instructions(func, node);
+				self.emit_call(func, "get_float_value");
+			}
 			// Variable lookup (local or global) - convert i64 to f64 if needed
 			Node::Symbol(name) => {
 				if let Some(local) = self.scope.lookup(name) {
@@ -2334,8 +2588,7 @@ impl WasmGcEmitter {
 						}
 						// Check for user function call
 						if self.ctx.user_functions.contains_key(fn_name) {
-							self.emit_user_function_call_numeric(func, fn_name, &items[1..]);
-							func.instruction(&Instruction::F64ConvertI64S);
+							self.emit_user_function_call_float(func, fn_name, &items[1..]);
 							return;
 						}
 					}
@@ -2445,12 +2698,6 @@ impl WasmGcEmitter {
 		panic!("new_list function required but not available");
 	}
 
-	fn validate_wasm(bytes: &[u8]) {
-		if let Err(e) = Self::try_validate_wasm(bytes) {
-			panic!("WASM validation failed: {}", e);
-		}
-	}
-
 	fn try_validate_wasm(bytes: &[u8]) -> Result<(), String> {
 		let mut features = WasmFeatures::default();
 		features.set(WasmFeatures::REFERENCE_TYPES, true);
@@ -2465,7 +2712,15 @@ impl WasmGcEmitter {
 		}
 	}
 
-	pub fn finish(mut self) -> Vec<u8> {
+	pub fn finish(self) -> Vec<u8> {
+		match self.try_finish() {
+			Ok(bytes) => bytes,
+			Err(e) => panic!("WASM validation failed: {}", e),
+		}
+	}
+
+	/// Assemble the module, returning the validator's message instead of panicking
+	pub fn try_finish(mut self) -> Result<Vec<u8>, String> {
 		// WASM section order: types, imports, functions, memory, globals, exports, code, data, names
 		self.module.section(self.type_manager.types());
 		if self.ctx.func_registry.import_count() > 0 {
@@ -2477,6 +2732,13 @@ impl WasmGcEmitter {
 			self.module.section(&self.globals);
 		}
 		self.module.section(&self.exports);
+		// Closure functions are referenced by ref.func, so they must be declared
+		let closures: Vec<u32> = self.ctx.closures.iter().filter_map(|closure| closure.func_index).collect();
+		if !closures.is_empty() {
+			let mut elements = ElementSection::new();
+			elements.declared(Elements::Functions(closures.into()));
+			self.module.section(&elements);
+		}
 		self.module.section(&self.code);
 		// Get data section from string table
 		self.module.section(self.string_table.data_section());
@@ -2484,9 +2746,8 @@ impl WasmGcEmitter {
 		self.module.section(&self.names);
 
 		let bytes = self.module.finish();
-		std::fs::write("test.wasm", &bytes).expect("Failed to write test.wasm");
-		Self::validate_wasm(&bytes);
-		bytes
+		Self::try_validate_wasm(&bytes)?;
+		Ok(bytes)
 	}
 
 	fn emit_names(&mut self) {
@@ -2532,8 +2793,12 @@ impl WasmGcEmitter {
 		for type_def in self.ctx.type_registry.types() {
 			if let Some(&type_idx) = self.ctx.user_type_indices.get(&type_def.name) {
 				let mut field_names = NameMap::new();
+				let offset = type_def.is_tagged() as u32;
+				if type_def.is_tagged() {
+					field_names.append(0, "tag");
+				}
 				for (i, field) in type_def.fields.iter().enumerate() {
-					field_names.append(i as u32, &field.name);
+					field_names.append(i as u32 + offset, &field.name);
 				}
 				type_field_names.append(type_idx, &field_names);
 			}
@@ -2649,7 +2914,7 @@ impl WasmGcEmitter {
 			.fields
 			.iter()
 			.map(|f| {
-				let element_type = match f.type_name.as_str() {
+				let element_type = match f.base_type() {
 					"i64" | "Int" | "long" => Val(ValType::I64),
 					"i32" | "int" => Val(ValType::I32),
 					"f64" | "Float" | "double" => Val(ValType::F64),
@@ -2773,9 +3038,7 @@ impl WasmGcEmitter {
 
 		module.section(&names);
 
-		let bytes = module.finish();
-		std::fs::write("test.wasm", &bytes).expect("Failed to write test.wasm");
-		bytes
+		module.finish()
 	}
 }
 
@@ -2808,14 +3071,39 @@ pub fn run_raw_struct(wasm_bytes: &[u8]) -> Result<Node, String> {
 	Ok(crate::node::data(gc_obj))
 }
 
+/// Compile code to a wasm module without running it
+/// Host and WASI imports are enabled automatically when the code needs them
+/// Parse, type and emitter errors and modules failing validation are returned, not panicked on
+pub fn compile_bytes(code: &str, mut config: EmitterConfig) -> Result<Vec<u8>, WarpError> {
+	let node = WaspParser::parse(code);
+	if let Some(err) = find_parse_error(&node) {
+		return Err(err);
+	}
+	check_types(&node)?;
+	config.emit_host_imports |= uses_fetch(code);
+	config.emit_wasi_imports |= uses_wasi(code);
+	let emitted = catch_silently(|| {
+		let mut emitter = WasmGcEmitter::with_config(config);
+		emitter.emit_program(&node);
+		emitter.try_finish()
+	});
+	match emitted {
+		Ok(bytes) => bytes.map_err(|message| WarpError::Validation { message }),
+		Err(payload) => Err(WarpError::Compile {
+			message: panic_message(payload),
+			position: emit_position().or_else(|| node.get_lineinfo()),
+		}),
+	}
+}
+
 /// Check if code uses fetch (needs host imports)
 ///  todo get rid of hard-coded logic, see usage
-fn uses_fetch(code: &str) -> bool {
+pub(crate) fn uses_fetch(code: &str) -> bool {
 	code.contains("fetch ")
 }
 
 /// Check if code uses WASI functions (puts, puti, putl, putf, fd_write)
-fn uses_wasi(code: &str) -> bool {
+pub(crate) fn uses_wasi(code: &str) -> bool {
 	code.contains("puts ")
 		|| code.contains("puti ")
 		|| code.contains("putl ")
@@ -2824,7 +3112,7 @@ fn uses_wasi(code: &str) -> bool {
 }
 
 /// Check if code uses FFI imports (import X from Y, use m/c)
-fn uses_ffi(code: &str) -> bool {
+pub(crate) fn uses_ffi(code: &str) -> bool {
 	// Todo: we need to pre-load the libraries in analyzer anyways,
 	// and in that process, we can check if the library is FFI or not.
 	if code.contains("import ") { return true;}
@@ -2846,7 +3134,8 @@ fn find_instantiation_recursive(registry: &TypeRegistry, node: &Node) -> Option<
 	match node {
 		Node::Key(left, Op::Colon, right) => {
 			if let Node::Symbol(name) = left.drop_meta() {
-				if let Some(type_def) = registry.get_by_name(name) {
+				// enum variants need their supertype, they compile as nodes
+				if let Some(type_def) = registry.get_by_name(name).filter(|t| !t.is_tagged()) {
 					if let Some((_, values)) = extract_instance_values(node) {
 						return Some((type_def.clone(), values));
 					}
@@ -2867,24 +3156,36 @@ fn find_instantiation_recursive(registry: &TypeRegistry, node: &Node) -> Option<
 	}
 }
 
-// Re-export eval function for tests
-pub fn eval(code: &str) -> Node {
-	use crate::analyzer::collect_all_types;
-	use crate::type_kinds::TypeRegistry;
-	use crate::wasm_reader::{read_bytes_with_host, read_bytes_with_wasi, read_bytes_with_ffi};
-
-	// Detect file path and load file content
-	let code = if !code.contains('\n') && (code.ends_with(".wasp") || code.ends_with(".warp")) {
+/// Load code from a .wasp/.warp file if given a path
+fn load_source(code: &str) -> String {
+	if !code.contains('\n') && (code.ends_with(".wasp") || code.ends_with(".warp")) {
 		if let Ok(content) = std::fs::read_to_string(code) {
-			content
-		} else {
-			code.to_string()
+			return content;
 		}
-	} else {
-		code.to_string()
-	};
+	}
+	code.to_string()
+}
 
-	let node = WaspParser::parse(&code);
+/// Evaluate code, reporting why it failed instead of handing back the parsed source
+pub fn try_eval(code: &str) -> Result<Node, WarpError> {
+	try_eval_source(&load_source(code))
+}
+
+/// Evaluate code as given, never reading it as a file path
+/// Type errors found before emitting, like a match missing a variant, are reported too
+pub fn try_eval_source(code: &str) -> Result<Node, WarpError> {
+	run_source(code, true)
+}
+
+/// Run code, checking its types first if asked; eval runs programs the checks would reject as it always did
+fn run_source(code: &str, check: bool) -> Result<Node, WarpError> {
+	let node = WaspParser::parse(code);
+	if let Some(err) = find_parse_error(&node) {
+		return Err(err);
+	}
+	if check {
+		check_types(&node)?;
+	}
 
 	// Pre-scan: collect all type definitions (supports forward references)
 	let mut type_registry = TypeRegistry::new();
@@ -2894,57 +3195,22 @@ pub fn eval(code: &str) -> Node {
 	if let Some((type_def, field_values)) = find_struct_instantiation(&type_registry, &node) {
 		let wasm_bytes = WasmGcEmitter::emit_raw_struct(&type_def, &field_values);
 		match run_raw_struct(&wasm_bytes) {
-			Ok(result) => return result,
+			Ok(result) => return Ok(result),
 			Err(e) => warn!("raw struct eval failed: {}", e),
 		}
 	}
 
 	// Fallback to standard Node encoding
-	let mut emitter = WasmGcEmitter::new();
-	let needs_host = uses_fetch(&code); // todo iterate over all used functions and check if they are a host import.
-	let needs_wasi = uses_wasi(&code);
-	let needs_ffi = uses_ffi(&code);
-	if needs_host {
-		emitter.set_host_imports(true);
-	}
-	if needs_wasi {
-		emitter.set_wasi_imports(true);
-	}
-	emitter.emit_for_node(&node);
-	let bytes = emitter.finish();
-
-	// Use appropriate linker based on imports needed
-	if needs_ffi {
-		match read_bytes_with_ffi(&bytes) {
-			Ok(result) => return result,
-			Err(e) => {
-				warn!("FFI eval failed: {}", e);
-				return node;
-			}
-		}
-	}
-
-	if needs_wasi {
-		match read_bytes_with_wasi(&bytes) {
-			Ok(result) => return Node::Number(Number::Int(result)),
-			Err(e) => {
-				warn!("WASI eval failed: {}", e);
-				return node;
-			}
-		}
-	}
-
-	let result = if needs_host {
-		read_bytes_with_host(&bytes)
-	} else {
-		read_bytes(&bytes)
-	};
+	CompiledModule::from_node(&node, code)?.run()
+}
 
-	match result {
+/// Evaluate code, falling back to the parsed source on failure
+pub fn eval(code: &str) -> Node {
+	match run_source(&load_source(code), false) {
 		Ok(result) => result,
 		Err(e) => {
 			warn!("eval failed: {}", e);
-			node // Return parsed node on failure
+			WaspParser::parse(&load_source(code)) // Return parsed node on failure
 		}
 	}
 }
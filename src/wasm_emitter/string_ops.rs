@@ -0,0 +1,1053 @@
+//! Runtime text building for WASM: number formatting, conversion and concatenation
+//!
+//! Built text lives in linear memory from `alloc`, string literals in the data segments,
+//! so a `$String` struct can point at either.
+
+use crate::node::Node;
+use crate::type_kinds::Kind;
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+use Instruction::I32Const;
+use ValType::Ref;
+
+/// Byte access to linear memory
+pub(crate) const BYTE: MemArg = MemArg {
+	offset: 0,
+	align: 0,
+	memory_index: 0,
+};
+
+impl WasmGcEmitter {
+	/// Emit the text runtime; each function is tree-shaken on its own
+	pub(crate) fn emit_string_ops(&mut self) {
+		let node_ref = self.node_ref(false);
+		let string_ref = RefType {
+			nullable: true,
+			heap_type: HeapType::Concrete(self.type_manager.string_type),
+		};
+
+		// int_to_text(n: i64) -> ref $Node
+		// Decimal digits written backwards into a 20 byte buffer
+		if self.should_emit_function("int_to_text") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::I64], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=n, 1=magnitude, 2=pos, 3=end, 4=negative
+			let mut func = Function::new(vec![(1, ValType::I64), (3, ValType::I32)]);
+
+			func.instruction(&I32Const(20));
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&I32Const(20));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalTee(3));
+			func.instruction(&Instruction::LocalSet(2));
+
+			// magnitude = n < 0 ? -n : n (i64::MIN stays correct as unsigned)
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::I64LtS);
+			func.instruction(&Instruction::LocalSet(4));
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I64Sub);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::LocalSet(1));
+
+			Self::emit_digits(&mut func, 1, 2);
+			Self::emit_minus_sign(&mut func, 4, 2);
+			self.emit_text_from(&mut func, 2, 3);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("int_to_text");
+			self.exports.export("int_to_text", ExportKind::Func, idx);
+		}
+
+		// float_to_text(x: f64) -> ref $Node
+		// Up to six fraction digits without trailing zeros; whole numbers print like ints
+		// Magnitudes from 1e15 and below 1e-4 print as a mantissa with exponent: 1e20, 2.5e-7
+		if self.should_emit_function("float_to_text") {
+			let (nan_ptr, nan_len) = self.allocate_string("NaN");
+			let (inf_ptr, inf_len) = self.allocate_string("∞");
+			let (neg_inf_ptr, neg_inf_len) = self.allocate_string("-∞");
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::F64], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=x, 1=whole, 2=fraction, 3=pos, 4=end, 5=negative, 6=fraction digits,
+			// 7=exponent, 8=scientific, 9=negative exponent
+			let mut func = Function::new(vec![(2, ValType::I64), (4, ValType::I32), (1, ValType::I64), (2, ValType::I32)]);
+
+			// NaN is the only value not equal to itself
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Ne);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&I32Const(nan_ptr as i32));
+			func.instruction(&I32Const(nan_len as i32));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Const(Ieee64::new(0.0f64.to_bits())));
+			func.instruction(&Instruction::F64Lt);
+			func.instruction(&Instruction::LocalSet(5));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Abs);
+			func.instruction(&Instruction::LocalSet(0));
+
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Const(Ieee64::new(f64::INFINITY.to_bits())));
+			func.instruction(&Instruction::F64Eq);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&I32Const(neg_inf_ptr as i32));
+			func.instruction(&I32Const(inf_ptr as i32));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::Select);
+			func.instruction(&I32Const(neg_inf_len as i32));
+			func.instruction(&I32Const(inf_len as i32));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::Select);
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			// scientific: scale x into [1, 10), counting the powers of ten in exponent
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Const(Ieee64::new(1e15f64.to_bits())));
+			func.instruction(&Instruction::F64Ge);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Const(Ieee64::new(1e-4f64.to_bits())));
+			func.instruction(&Instruction::F64Lt);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::F64Const(Ieee64::new(0.0f64.to_bits())));
+			func.instruction(&Instruction::F64Ne);
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::I32Or);
+			func.instruction(&Instruction::LocalTee(8));
+			func.instruction(&Instruction::If(BlockType::Empty));
+			for (limit, compare, scale, step) in [
+				(10.0f64, Instruction::F64Ge, Instruction::F64Div, 1i64),
+				(1.0f64, Instruction::F64Lt, Instruction::F64Mul, -1i64),
+			] {
+				func.instruction(&Instruction::Block(BlockType::Empty));
+				func.instruction(&Instruction::Loop(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(0));
+				func.instruction(&Instruction::F64Const(Ieee64::new(limit.to_bits())));
+				func.instruction(&compare);
+				func.instruction(&Instruction::I32Eqz);
+				func.instruction(&Instruction::BrIf(1));
+				func.instruction(&Instruction::LocalGet(0));
+				func.instruction(&Instruction::F64Const(Ieee64::new(10.0f64.to_bits())));
+				func.instruction(&scale);
+				func.instruction(&Instruction::LocalSet(0));
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::I64Const(step));
+				func.instruction(&Instruction::I64Add);
+				func.instruction(&Instruction::LocalSet(7));
+				func.instruction(&Instruction::Br(0));
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::End);
+			}
+			func.instruction(&Instruction::End);
+
+			// whole = trunc(x), fraction = round((x - whole) * 1e6)
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I64TruncSatF64S);
+			func.instruction(&Instruction::LocalSet(1));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::F64ConvertI64S);
+			func.instruction(&Instruction::F64Sub);
+			func.instruction(&Instruction::F64Const(Ieee64::new(1e6f64.to_bits())));
+			func.instruction(&Instruction::F64Mul);
+			func.instruction(&Instruction::F64Nearest);
+			func.instruction(&Instruction::I64TruncSatF64S);
+			func.instruction(&Instruction::LocalSet(2));
+
+			// 0.9999999 rounds up into the whole part
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I64Const(1_000_000));
+			func.instruction(&Instruction::I64GeS);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I64Const(1));
+			func.instruction(&Instruction::I64Add);
+			func.instruction(&Instruction::LocalSet(1));
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::End);
+
+			// a mantissa rounded up to 10 is 1 of the next power: 9.9999999e5 → 1e6
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I64Const(10));
+			func.instruction(&Instruction::I64Eq);
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::I64Const(1));
+			func.instruction(&Instruction::LocalSet(1));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::I64Const(1));
+			func.instruction(&Instruction::I64Add);
+			func.instruction(&Instruction::LocalSet(7));
+			func.instruction(&Instruction::End);
+
+			func.instruction(&I32Const(48));
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&I32Const(48));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalTee(4));
+			func.instruction(&Instruction::LocalSet(3));
+
+			// the exponent goes last, so it is written first: e-7
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::I64Const(0));
+				func.instruction(&Instruction::I64LtS);
+				func.instruction(&Instruction::LocalSet(9));
+				func.instruction(&Instruction::I64Const(0));
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::I64Sub);
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::Select);
+				func.instruction(&Instruction::LocalSet(7));
+				Self::emit_digits(&mut func, 7, 3);
+				Self::emit_minus_sign(&mut func, 9, 3);
+				Self::emit_store_byte(&mut func, 3, b'e');
+			}
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I64Eqz);
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				func.instruction(&I32Const(6));
+				func.instruction(&Instruction::LocalSet(6));
+				// drop trailing zeros
+				func.instruction(&Instruction::Block(BlockType::Empty));
+				func.instruction(&Instruction::Loop(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::I64Const(10));
+				func.instruction(&Instruction::I64RemU);
+				func.instruction(&Instruction::I64Eqz);
+				func.instruction(&Instruction::I32Eqz);
+				func.instruction(&Instruction::BrIf(1));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&Instruction::I64Const(10));
+				func.instruction(&Instruction::I64DivU);
+				func.instruction(&Instruction::LocalSet(2));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&I32Const(1));
+				func.instruction(&Instruction::I32Sub);
+				func.instruction(&Instruction::LocalSet(6));
+				func.instruction(&Instruction::Br(0));
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::End);
+
+				// exactly `digits` digits, keeping leading zeros: 0.05 → "05"
+				func.instruction(&Instruction::Loop(BlockType::Empty));
+				Self::emit_store_digit(&mut func, 2, 3);
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&I32Const(1));
+				func.instruction(&Instruction::I32Sub);
+				func.instruction(&Instruction::LocalTee(6));
+				func.instruction(&Instruction::BrIf(0));
+				func.instruction(&Instruction::End);
+
+				Self::emit_store_byte(&mut func, 3, b'.');
+			}
+			func.instruction(&Instruction::End);
+
+			Self::emit_digits(&mut func, 1, 3);
+			Self::emit_minus_sign(&mut func, 5, 3);
+			self.emit_text_from(&mut func, 3, 4);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("float_to_text");
+			self.exports.export("float_to_text", ExportKind::Func, idx);
+		}
+
+		// codepoint_to_text(c: i32) -> ref $Node
+		// UTF-8 encoding of one codepoint
+		if self.should_emit_function("codepoint_to_text") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::I32], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=c, 1=ptr, 2=len
+			let mut func = Function::new(vec![(2, ValType::I32)]);
+
+			func.instruction(&I32Const(4));
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&Instruction::LocalSet(1));
+
+			// len = 1 + (c >= 0x80) + (c >= 0x800) + (c >= 0x10000)
+			func.instruction(&I32Const(1));
+			for limit in [0x80, 0x800, 0x10000] {
+				func.instruction(&Instruction::LocalGet(0));
+				func.instruction(&I32Const(limit));
+				func.instruction(&Instruction::I32GeU);
+				func.instruction(&Instruction::I32Add);
+			}
+			func.instruction(&Instruction::LocalSet(2));
+
+			// single byte
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Eq);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::I32Store8(BYTE));
+			func.instruction(&Instruction::Else);
+			{
+				// lead byte: prefix by length, then the top bits
+				func.instruction(&Instruction::LocalGet(1));
+				func.instruction(&I32Const(0xC0));
+				func.instruction(&I32Const(0xE0));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&I32Const(2));
+				func.instruction(&Instruction::I32Eq);
+				func.instruction(&Instruction::Select);
+				func.instruction(&I32Const(0xF0));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&I32Const(4));
+				func.instruction(&Instruction::I32Ne);
+				func.instruction(&Instruction::Select);
+				func.instruction(&Instruction::LocalGet(0));
+				func.instruction(&Instruction::LocalGet(2));
+				func.instruction(&I32Const(1));
+				func.instruction(&Instruction::I32Sub);
+				func.instruction(&I32Const(6));
+				func.instruction(&Instruction::I32Mul);
+				func.instruction(&Instruction::I32ShrU);
+				func.instruction(&Instruction::I32Or);
+				func.instruction(&Instruction::I32Store8(BYTE));
+				// continuation bytes, 6 bits each, last byte holds the lowest bits
+				for i in 1..4 {
+					func.instruction(&Instruction::LocalGet(2));
+					func.instruction(&I32Const(i));
+					func.instruction(&Instruction::I32GtU);
+					func.instruction(&Instruction::If(BlockType::Empty));
+					func.instruction(&Instruction::LocalGet(1));
+					func.instruction(&I32Const(i));
+					func.instruction(&Instruction::I32Add);
+					func.instruction(&Instruction::LocalGet(0));
+					func.instruction(&Instruction::LocalGet(2));
+					func.instruction(&I32Const(i + 1));
+					func.instruction(&Instruction::I32Sub);
+					func.instruction(&I32Const(6));
+					func.instruction(&Instruction::I32Mul);
+					func.instruction(&Instruction::I32ShrU);
+					func.instruction(&I32Const(0x3F));
+					func.instruction(&Instruction::I32And);
+					func.instruction(&I32Const(0x80));
+					func.instruction(&Instruction::I32Or);
+					func.instruction(&Instruction::I32Store8(BYTE));
+					func.instruction(&Instruction::End);
+				}
+			}
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::LocalGet(2));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("codepoint_to_text");
+			self.exports.export("codepoint_to_text", ExportKind::Func, idx);
+		}
+
+		// to_text(node: ref $Node) -> ref $Node
+		// Text stays as is; symbols, numbers and codepoints are converted, anything else is ""
+		if self.should_emit_function("to_text") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=node, 1=kind, 2=string
+			let mut func = Function::new(vec![(1, ValType::I64), (1, Ref(string_ref))]);
+
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::I64Const(0xFF));
+			func.instruction(&Instruction::I64And);
+			func.instruction(&Instruction::LocalSet(1));
+
+			Self::emit_if_kind(&mut func, 1, Kind::Text);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Symbol);
+			self.emit_node_data(&mut func, self.type_manager.string_type);
+			func.instruction(&Instruction::LocalTee(2));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.string_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.string_type,
+				field_index: 1,
+			});
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Int);
+			self.emit_node_data(&mut func, self.type_manager.i64_box_type);
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.i64_box_type,
+				field_index: 0,
+			});
+			self.emit_call(&mut func, "int_to_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Float);
+			self.emit_node_data(&mut func, self.type_manager.f64_box_type);
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.f64_box_type,
+				field_index: 0,
+			});
+			self.emit_call(&mut func, "float_to_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			Self::emit_if_kind(&mut func, 1, Kind::Codepoint);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 1,
+			});
+			func.instruction(&Instruction::RefCastNonNull(HeapType::Abstract {
+				shared: false,
+				ty: AbstractHeapType::I31,
+			}));
+			func.instruction(&Instruction::I31GetU);
+			self.emit_call(&mut func, "codepoint_to_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&I32Const(0));
+			func.instruction(&I32Const(0));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("to_text");
+			self.exports.export("to_text", ExportKind::Func, idx);
+		}
+
+		// text_concat(a: ref $Node, b: ref $Node) -> ref $Node
+		// Both sides go through to_text, so "a" + 1 is "a1"
+		if self.should_emit_function("text_concat") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=a, 1=b, 2=a string, 3=b string, 4=a len, 5=b len, 6=ptr
+			let mut func = Function::new(vec![(2, Ref(string_ref)), (3, ValType::I32)]);
+
+			for (node, string, len) in [(0, 2, 4), (1, 3, 5)] {
+				func.instruction(&Instruction::LocalGet(node));
+				self.emit_call(&mut func, "to_text");
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.node_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.string_type)));
+				func.instruction(&Instruction::LocalTee(string));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.string_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::LocalSet(len));
+			}
+
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::I32Add);
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&Instruction::LocalSet(6));
+
+			// copy a to ptr, b to ptr + a len
+			for (string, offset) in [(2, None), (3, Some(4))] {
+				func.instruction(&Instruction::LocalGet(6));
+				if let Some(offset) = offset {
+					func.instruction(&Instruction::LocalGet(offset));
+					func.instruction(&Instruction::I32Add);
+				}
+				func.instruction(&Instruction::LocalGet(string));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.string_type,
+					field_index: 0,
+				});
+				func.instruction(&Instruction::LocalGet(string));
+				func.instruction(&Instruction::StructGet {
+					struct_type_index: self.type_manager.string_type,
+					field_index: 1,
+				});
+				func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+			}
+
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::I32Add);
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_concat");
+			self.exports.export("text_concat", ExportKind::Func, idx);
+		}
+
+		// utf8_offset(ptr: i32, len: i32, index: i64) -> i32
+		// Byte offset of the codepoint at index, clamped to 0..len
+		if self.should_emit_function("utf8_offset") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![ValType::I32, ValType::I32, ValType::I64], vec![ValType::I32]);
+			self.functions.function(func_type);
+			// Locals: 0=ptr, 1=len, 2=index, 3=offset
+			let mut func = Function::new(vec![(1, ValType::I32)]);
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::I64LeS);
+			func.instruction(&Instruction::BrIf(1));
+			// step over the lead byte and its continuation bytes 0b10xxxxxx
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalTee(3));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I32LtU);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::I32Load8U(BYTE));
+			func.instruction(&I32Const(0xC0));
+			func.instruction(&Instruction::I32And);
+			func.instruction(&I32Const(0x80));
+			func.instruction(&Instruction::I32Eq);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I64Const(1));
+			func.instruction(&Instruction::I64Sub);
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("utf8_offset");
+			self.exports.export("utf8_offset", ExportKind::Func, idx);
+		}
+
+		// text_substring(text: ref $Node, from: i64, to: i64) -> ref $Node
+		// Codepoints from..to as a copy, indices clamped to the text
+		if self.should_emit_function("text_substring") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), ValType::I64, ValType::I64], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=from, 2=to, 3=string, 4=ptr, 5=len, 6=start, 7=end
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (4, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 3, 4, 5);
+			for (index, offset) in [(1, 6), (2, 7)] {
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::LocalGet(index));
+				self.emit_call(&mut func, "utf8_offset");
+				func.instruction(&Instruction::LocalSet(offset));
+			}
+			// len = max(end - start, 0)
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32GtU);
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::LocalSet(5));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(4));
+			self.emit_copy_text(&mut func, 4, 5, 6);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_substring");
+			self.exports.export("text_substring", ExportKind::Func, idx);
+		}
+
+		// text_repeat(text: ref $Node, n: i64) -> ref $Node
+		if self.should_emit_function("text_repeat") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), ValType::I64], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=n, 2=string, 3=ptr, 4=len, 5=dest, 6=total, 7=pos
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (5, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 2, 3, 4);
+			// total = len * max(n, 0)
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I32WrapI64);
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::I64GtS);
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::I32Mul);
+			func.instruction(&Instruction::LocalTee(6));
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&Instruction::LocalSet(5));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(7));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalGet(6));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_repeat");
+			self.exports.export("text_repeat", ExportKind::Func, idx);
+		}
+
+		// text_to_int(text: ref $Node) -> i64
+		// Leading spaces, sign and decimal digits; stops at the first other char: "42.5" is 42
+		if self.should_emit_function("text_to_int") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![ValType::I64]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=string, 2=ptr, 3=end, 4=negative, 5=digit, 6=value
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (4, ValType::I32), (1, ValType::I64)]);
+			self.emit_number_start(&mut func, 0, 1, 2, 3, 4);
+			Self::emit_digit_loop(&mut func, 2, 3, 5, |func| {
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I64Const(10));
+				func.instruction(&Instruction::I64Mul);
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::I64ExtendI32U);
+				func.instruction(&Instruction::I64Add);
+				func.instruction(&Instruction::LocalSet(6));
+			});
+			func.instruction(&Instruction::I64Const(0));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I64Sub);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_to_int");
+			self.exports.export("text_to_int", ExportKind::Func, idx);
+		}
+
+		// text_to_float(text: ref $Node) -> f64
+		// Like text_to_int, plus fraction and exponent: " -1.5e3" is -1500
+		if self.should_emit_function("text_to_float") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![ValType::F64]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=string, 2=ptr, 3=end, 4=negative, 5=digit, 6=exponent, 7=negative exponent, 8=value, 9=scale
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (6, ValType::I32), (2, ValType::F64)]);
+			let accumulate = |func: &mut Function| {
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::F64Const(Ieee64::new(10f64.to_bits())));
+				func.instruction(&Instruction::F64Mul);
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::F64ConvertI32U);
+				func.instruction(&Instruction::F64Add);
+				func.instruction(&Instruction::LocalSet(8));
+			};
+			self.emit_number_start(&mut func, 0, 1, 2, 3, 4);
+			func.instruction(&Instruction::F64Const(Ieee64::new(1f64.to_bits())));
+			func.instruction(&Instruction::LocalSet(9));
+			Self::emit_digit_loop(&mut func, 2, 3, 5, accumulate);
+
+			// fraction: digits go on the value, scale remembers how many
+			Self::emit_if_next_byte(&mut func, 2, 3, |func| {
+				func.instruction(&I32Const(b'.' as i32));
+				func.instruction(&Instruction::I32Eq);
+			});
+			Self::emit_digit_loop(&mut func, 2, 3, 5, |func| {
+				accumulate(func);
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::F64Const(Ieee64::new(10f64.to_bits())));
+				func.instruction(&Instruction::F64Mul);
+				func.instruction(&Instruction::LocalSet(9));
+			});
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::F64Div);
+			func.instruction(&Instruction::LocalSet(8));
+
+			// exponent: e or E, optional sign, digits
+			Self::emit_if_next_byte(&mut func, 2, 3, |func| {
+				func.instruction(&I32Const(0x20));
+				func.instruction(&Instruction::I32Or);
+				func.instruction(&I32Const(b'e' as i32));
+				func.instruction(&Instruction::I32Eq);
+			});
+			Self::emit_sign(&mut func, 2, 3, 7);
+			Self::emit_digit_loop(&mut func, 2, 3, 5, |func| {
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&I32Const(10));
+				func.instruction(&Instruction::I32Mul);
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalSet(6));
+			});
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::If(BlockType::Result(ValType::F64)));
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::F64Const(Ieee64::new(10f64.to_bits())));
+			func.instruction(&Instruction::F64Div);
+			func.instruction(&Instruction::Else);
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::F64Const(Ieee64::new(10f64.to_bits())));
+			func.instruction(&Instruction::F64Mul);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalSet(8));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::LocalSet(6));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::F64Neg);
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::Select);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_to_float");
+			self.exports.export("text_to_float", ExportKind::Func, idx);
+		}
+
+		// text_to_number(text: ref $Node) -> ref $Node
+		// Int when the text holds a whole number, Float otherwise; numbers stay as they are
+		if self.should_emit_function("text_to_number") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=kind, 2=int value, 3=float value
+			let mut func = Function::new(vec![(2, ValType::I64), (1, ValType::F64)]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::I64Const(0xFF));
+			func.instruction(&Instruction::I64And);
+			func.instruction(&Instruction::LocalTee(1));
+			func.instruction(&Instruction::I64Const(Kind::Int as i64));
+			func.instruction(&Instruction::I64Eq);
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I64Const(Kind::Float as i64));
+			func.instruction(&Instruction::I64Eq);
+			func.instruction(&Instruction::I32Or);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "text_to_int");
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "text_to_float");
+			func.instruction(&Instruction::LocalTee(3));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::F64ConvertI64S);
+			func.instruction(&Instruction::F64Eq);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(2));
+			self.emit_call(&mut func, "new_int");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(3));
+			self.emit_call(&mut func, "new_float");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_to_number");
+			self.exports.export("text_to_number", ExportKind::Func, idx);
+		}
+	}
+
+	/// Text node local through to_text into its $String, ptr and len locals
+	pub(crate) fn emit_load_text(&mut self, func: &mut Function, node: u32, string: u32, ptr: u32, len: u32) {
+		func.instruction(&Instruction::LocalGet(node));
+		self.emit_text_fields(func, string, ptr, len);
+	}
+
+	/// Like emit_load_text for the node on the stack
+	pub(crate) fn emit_text_fields(&mut self, func: &mut Function, string: u32, ptr: u32, len: u32) {
+		self.emit_call(func, "to_text");
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.string_type)));
+		func.instruction(&Instruction::LocalTee(string));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.string_type,
+			field_index: 0,
+		});
+		func.instruction(&Instruction::LocalSet(ptr));
+		func.instruction(&Instruction::LocalGet(string));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.string_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::LocalSet(len));
+	}
+
+	/// new_text of a fresh copy of len bytes at ptr, dest is scratch
+	pub(crate) fn emit_copy_text(&mut self, func: &mut Function, ptr: u32, len: u32, dest: u32) {
+		func.instruction(&Instruction::LocalGet(len));
+		self.emit_call(func, "alloc");
+		func.instruction(&Instruction::LocalTee(dest));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(len));
+		func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+		func.instruction(&Instruction::LocalGet(dest));
+		func.instruction(&Instruction::LocalGet(len));
+		self.emit_call(func, "new_text");
+	}
+
+	/// Load the text into ptr..end, skip leading spaces and read the sign
+	fn emit_number_start(&mut self, func: &mut Function, node: u32, string: u32, ptr: u32, end: u32, negative: u32) {
+		self.emit_load_text(func, node, string, ptr, end);
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(end));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(end));
+		func.instruction(&Instruction::Block(BlockType::Empty));
+		func.instruction(&Instruction::Loop(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(end));
+		func.instruction(&Instruction::I32GeU);
+		func.instruction(&Instruction::BrIf(1));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::I32Load8U(BYTE));
+		func.instruction(&I32Const(b' ' as i32));
+		func.instruction(&Instruction::I32Ne);
+		func.instruction(&Instruction::BrIf(1));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(ptr));
+		func.instruction(&Instruction::Br(0));
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::End);
+		Self::emit_sign(func, ptr, end, negative);
+	}
+
+	/// negative = next byte is '-'; a '-' or '+' is consumed
+	fn emit_sign(func: &mut Function, ptr: u32, end: u32, negative: u32) {
+		Self::emit_if_next_byte(func, ptr, end, |func| {
+			func.instruction(&I32Const(b'-' as i32));
+			func.instruction(&Instruction::I32Eq);
+		});
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::LocalSet(negative));
+		func.instruction(&Instruction::Else);
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(end));
+		func.instruction(&Instruction::I32LtU);
+		func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::I32Load8U(BYTE));
+		func.instruction(&I32Const(b'+' as i32));
+		func.instruction(&Instruction::I32Eq);
+		func.instruction(&Instruction::Else);
+		func.instruction(&I32Const(0));
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::Select);
+		func.instruction(&Instruction::LocalSet(ptr));
+		func.instruction(&Instruction::End);
+	}
+
+	/// if (ptr < end && test(byte at ptr)) { ptr += 1; … — caller closes with End
+	fn emit_if_next_byte(func: &mut Function, ptr: u32, end: u32, test: impl Fn(&mut Function)) {
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(end));
+		func.instruction(&Instruction::I32LtU);
+		func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::I32Load8U(BYTE));
+		test(func);
+		func.instruction(&Instruction::Else);
+		func.instruction(&I32Const(0));
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::If(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(ptr));
+	}
+
+	/// while ptr < end and the byte is a decimal digit: digit = byte - '0'; body; ptr += 1
+	fn emit_digit_loop(func: &mut Function, ptr: u32, end: u32, digit: u32, body: impl Fn(&mut Function)) {
+		func.instruction(&Instruction::Block(BlockType::Empty));
+		func.instruction(&Instruction::Loop(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(end));
+		func.instruction(&Instruction::I32GeU);
+		func.instruction(&Instruction::BrIf(1));
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::I32Load8U(BYTE));
+		func.instruction(&I32Const(b'0' as i32));
+		func.instruction(&Instruction::I32Sub);
+		func.instruction(&Instruction::LocalTee(digit));
+		func.instruction(&I32Const(9));
+		func.instruction(&Instruction::I32GtU);
+		func.instruction(&Instruction::BrIf(1));
+		body(func);
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(ptr));
+		func.instruction(&Instruction::Br(0));
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::End);
+	}
+
+	/// pos -= 1; memory[pos] = byte
+	fn emit_store_byte(func: &mut Function, pos: u32, byte: u8) {
+		func.instruction(&Instruction::LocalGet(pos));
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::I32Sub);
+		func.instruction(&Instruction::LocalTee(pos));
+		func.instruction(&I32Const(byte as i32));
+		func.instruction(&Instruction::I32Store8(BYTE));
+	}
+
+	/// pos -= 1; memory[pos] = '0' + value % 10; value /= 10
+	fn emit_store_digit(func: &mut Function, value: u32, pos: u32) {
+		func.instruction(&Instruction::LocalGet(pos));
+		func.instruction(&I32Const(1));
+		func.instruction(&Instruction::I32Sub);
+		func.instruction(&Instruction::LocalTee(pos));
+		func.instruction(&Instruction::LocalGet(value));
+		func.instruction(&Instruction::I64Const(10));
+		func.instruction(&Instruction::I64RemU);
+		func.instruction(&Instruction::I32WrapI64);
+		func.instruction(&I32Const(b'0' as i32));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::I32Store8(BYTE));
+		func.instruction(&Instruction::LocalGet(value));
+		func.instruction(&Instruction::I64Const(10));
+		func.instruction(&Instruction::I64DivU);
+		func.instruction(&Instruction::LocalSet(value));
+	}
+
+	/// All decimal digits of an unsigned value, at least one, written backwards from pos
+	fn emit_digits(func: &mut Function, value: u32, pos: u32) {
+		func.instruction(&Instruction::Loop(BlockType::Empty));
+		Self::emit_store_digit(func, value, pos);
+		func.instruction(&Instruction::LocalGet(value));
+		func.instruction(&Instruction::I64Const(0));
+		func.instruction(&Instruction::I64Ne);
+		func.instruction(&Instruction::BrIf(0));
+		func.instruction(&Instruction::End);
+	}
+
+	fn emit_minus_sign(func: &mut Function, negative: u32, pos: u32) {
+		func.instruction(&Instruction::LocalGet(negative));
+		func.instruction(&Instruction::If(BlockType::Empty));
+		Self::emit_store_byte(func, pos, b'-');
+		func.instruction(&Instruction::End);
+	}
+
+	/// new_text(pos, end - pos)
+	fn emit_text_from(&mut self, func: &mut Function, pos: u32, end: u32) {
+		func.instruction(&Instruction::LocalGet(pos));
+		func.instruction(&Instruction::LocalGet(end));
+		func.instruction(&Instruction::LocalGet(pos));
+		func.instruction(&Instruction::I32Sub);
+		self.emit_call(func, "new_text");
+	}
+
+	/// if (kind local == kind) { … — caller closes with End
+	pub(crate) fn emit_if_kind(func: &mut Function, local: u32, kind: Kind) {
+		func.instruction(&Instruction::LocalGet(local));
+		func.instruction(&Instruction::I64Const(kind as i64));
+		func.instruction(&Instruction::I64Eq);
+		func.instruction(&Instruction::If(BlockType::Empty));
+	}
+
+	/// Data field of the Node in local 0, cast to the given struct
+	fn emit_node_data(&self, func: &mut Function, struct_type: u32) {
+		func.instruction(&Instruction::LocalGet(0));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(struct_type)));
+	}
+
+	/// Text concatenation: "a" + x, also the lowering of "a{x}" interpolation
+	pub(crate) fn emit_text_concat(&mut self, func: &mut Function, left: &Node, right: &Node) {
+		self.emit_node_instructions(func, left);
+		self.emit_node_instructions(func, right);
+		self.emit_call(func, "text_concat");
+	}
+}
@@ -0,0 +1,1014 @@
+//! Text library for WASM: search, split, join, replace, trim, case and slicing
+//!
+//! Indices count codepoints, not bytes, like substring. Results are fresh copies
+//! from `alloc`; each function is tree-shaken on its own.
+
+use crate::analyzer::text_function;
+use crate::node::Node;
+use crate::operators::Op;
+use crate::type_kinds::Kind;
+use crate::wasm_emitter::string_ops::BYTE;
+use crate::wasm_emitter::WasmGcEmitter;
+use wasm_encoder::*;
+use Instruction::I32Const;
+use ValType::Ref;
+
+/// Square bracket info of the lists split returns
+pub(crate) const SQUARE: i64 = 1;
+
+/// Byte access one past the address, the second byte of a sequence
+const NEXT_BYTE: MemArg = MemArg {
+	offset: 1,
+	align: 0,
+	memory_index: 0,
+};
+
+/// Case mapping ranges (first, last, delta, excluded) of Latin-1, Greek and Cyrillic
+/// Both cases encode to the same number of UTF-8 bytes, so text_case maps in place
+const TO_UPPER: [(i32, i32, i32, i32); 5] = [
+	(0x61, 0x7A, -32, -1),
+	(0xE0, 0xFE, -32, 0xF7),
+	(0x3B1, 0x3C9, -32, 0x3C2),
+	(0x430, 0x44F, -32, -1),
+	(0x450, 0x45F, -80, -1),
+];
+const TO_LOWER: [(i32, i32, i32, i32); 5] = [
+	(0x41, 0x5A, 32, -1),
+	(0xC0, 0xDE, 32, 0xD7),
+	(0x391, 0x3A9, 32, 0x3A2),
+	(0x410, 0x42F, 32, -1),
+	(0x400, 0x40F, 80, -1),
+];
+
+impl WasmGcEmitter {
+	pub(crate) fn emit_text_ops(&mut self) {
+		let node_ref = self.node_ref(false);
+		let node_ref_nullable = self.node_ref(true);
+		let string_ref = RefType {
+			nullable: true,
+			heap_type: HeapType::Concrete(self.type_manager.string_type),
+		};
+
+		// bytes_equal(a: i32, b: i32, len: i32) -> i32
+		if self.should_emit_function("bytes_equal") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![ValType::I32, ValType::I32, ValType::I32], vec![ValType::I32]);
+			self.functions.function(func_type);
+			// Locals: 0=a, 1=b, 2=len, 3=i
+			let mut func = Function::new(vec![(1, ValType::I32)]);
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			Self::emit_byte_at(&mut func, 0, 3);
+			Self::emit_byte_at(&mut func, 1, 3);
+			func.instruction(&Instruction::I32Ne);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			Self::emit_increment(&mut func, 3, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("bytes_equal");
+			self.exports.export("bytes_equal", ExportKind::Func, idx);
+		}
+
+		// utf8_count(ptr: i32, len: i32) -> i64
+		// Codepoints in len bytes: every byte but the continuation bytes 0b10xxxxxx
+		if self.should_emit_function("utf8_count") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::I32, ValType::I32], vec![ValType::I64]);
+			self.functions.function(func_type);
+			// Locals: 0=ptr, 1=len, 2=i, 3=count
+			let mut func = Function::new(vec![(1, ValType::I32), (1, ValType::I64)]);
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(3));
+			Self::emit_byte_at(&mut func, 0, 2);
+			func.instruction(&I32Const(0xC0));
+			func.instruction(&Instruction::I32And);
+			func.instruction(&I32Const(0x80));
+			func.instruction(&Instruction::I32Ne);
+			func.instruction(&Instruction::I64ExtendI32U);
+			func.instruction(&Instruction::I64Add);
+			func.instruction(&Instruction::LocalSet(3));
+			Self::emit_increment(&mut func, 2, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("utf8_count");
+			self.exports.export("utf8_count", ExportKind::Func, idx);
+		}
+
+		// text_index_of(text: ref $Node, part: ref $Node) -> i64
+		// Codepoint index of the first occurrence, -1 if there is none
+		if self.should_emit_function("text_index_of") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![ValType::I64]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=part, 2=string, 3=ptr, 4=len, 5=part ptr, 6=part len, 7=i
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (5, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 2, 3, 4);
+			self.emit_load_text(&mut func, 1, 2, 5, 6);
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			Self::emit_past_end(&mut func, 7, 6, 4);
+			func.instruction(&Instruction::BrIf(1));
+			self.emit_match_at(&mut func, 3, 7, 5, 6);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(7));
+			self.emit_call(&mut func, "utf8_count");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			Self::emit_increment(&mut func, 7, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::I64Const(-1));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_index_of");
+			self.exports.export("text_index_of", ExportKind::Func, idx);
+		}
+
+		// text_starts_with(text: ref $Node, part: ref $Node) -> i64
+		// text_ends_with(text: ref $Node, part: ref $Node) -> i64
+		for (name, at_end) in [("text_starts_with", false), ("text_ends_with", true)] {
+			if !self.should_emit_function(name) {
+				continue;
+			}
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![ValType::I64]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=part, 2=string, 3=ptr, 4=len, 5=part ptr, 6=part len
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (4, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 2, 3, 4);
+			self.emit_load_text(&mut func, 1, 2, 5, 6);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32LeU);
+			func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+			func.instruction(&Instruction::LocalGet(3));
+			if at_end {
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I32Sub);
+			}
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalGet(6));
+			self.emit_call(&mut func, "bytes_equal");
+			func.instruction(&Instruction::Else);
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::I64ExtendI32U);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func(name);
+			self.exports.export(name, ExportKind::Func, idx);
+		}
+
+		// text_trim(text: ref $Node) -> ref $Node
+		// Without leading and trailing spaces, tabs and line breaks
+		if self.should_emit_function("text_trim") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=string, 2=ptr, 3=end, 4=byte, 5=dest
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (4, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 1, 2, 3);
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(3));
+			// while ptr < end and memory[ptr] is space: ptr += 1
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32Load8U(BYTE));
+			func.instruction(&Instruction::LocalSet(4));
+			Self::emit_is_space(&mut func, 4);
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::BrIf(1));
+			Self::emit_increment(&mut func, 2, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			// while end > ptr and memory[end - 1] is space: end -= 1
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32LeU);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I32Load8U(BYTE));
+			func.instruction(&Instruction::LocalSet(4));
+			Self::emit_is_space(&mut func, 4);
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::BrIf(1));
+			Self::emit_increment(&mut func, 3, -1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::LocalSet(3));
+			self.emit_copy_text(&mut func, 2, 3, 5);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_trim");
+			self.exports.export("text_trim", ExportKind::Func, idx);
+		}
+
+		// codepoint_case(c: i32, upper: i32) -> i32
+		// Upper or lower case of ASCII, Latin-1, Greek and Cyrillic letters, others unchanged
+		if self.should_emit_function("codepoint_case") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![ValType::I32, ValType::I32], vec![ValType::I32]);
+			self.functions.function(func_type);
+			// Locals: 0=c, 1=upper
+			let mut func = Function::new(vec![]);
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::If(BlockType::Empty));
+			TO_UPPER.iter().for_each(|range| Self::emit_case_range(&mut func, *range));
+			func.instruction(&Instruction::Else);
+			TO_LOWER.iter().for_each(|range| Self::emit_case_range(&mut func, *range));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("codepoint_case");
+			self.exports.export("codepoint_case", ExportKind::Func, idx);
+		}
+
+		// text_case(text: ref $Node, upper: i32) -> ref $Node
+		// Maps one and two byte sequences through codepoint_case, longer ones are copied as is
+		if self.should_emit_function("text_case") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), ValType::I32], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=upper, 2=string, 3=ptr, 4=len, 5=dest, 6=i, 7=byte, 8=c
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (6, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 2, 3, 4);
+			func.instruction(&Instruction::LocalGet(4));
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&Instruction::LocalTee(5));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			Self::emit_byte_at(&mut func, 5, 6);
+			func.instruction(&Instruction::LocalTee(7));
+			func.instruction(&I32Const(0x80));
+			func.instruction(&Instruction::I32LtU);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				// ASCII
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::LocalGet(1));
+				self.emit_call(&mut func, "codepoint_case");
+				func.instruction(&Instruction::I32Store8(BYTE));
+				Self::emit_increment(&mut func, 6, 1);
+			}
+			func.instruction(&Instruction::Else);
+			// two byte sequence 110xxxxx 10xxxxxx
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&I32Const(0xE0));
+			func.instruction(&Instruction::I32And);
+			func.instruction(&I32Const(0xC0));
+			func.instruction(&Instruction::I32Eq);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32LtU);
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&I32Const(0x1F));
+				func.instruction(&Instruction::I32And);
+				func.instruction(&I32Const(6));
+				func.instruction(&Instruction::I32Shl);
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::I32Load8U(NEXT_BYTE));
+				func.instruction(&I32Const(0x3F));
+				func.instruction(&Instruction::I32And);
+				func.instruction(&Instruction::I32Or);
+				func.instruction(&Instruction::LocalGet(1));
+				self.emit_call(&mut func, "codepoint_case");
+				func.instruction(&Instruction::LocalSet(8));
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&I32Const(6));
+				func.instruction(&Instruction::I32ShrU);
+				func.instruction(&I32Const(0xC0));
+				func.instruction(&Instruction::I32Or);
+				func.instruction(&Instruction::I32Store8(BYTE));
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&I32Const(0x3F));
+				func.instruction(&Instruction::I32And);
+				func.instruction(&I32Const(0x80));
+				func.instruction(&Instruction::I32Or);
+				func.instruction(&Instruction::I32Store8(NEXT_BYTE));
+				Self::emit_increment(&mut func, 6, 2);
+			}
+			func.instruction(&Instruction::Else);
+			Self::emit_increment(&mut func, 6, 1);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalGet(4));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_case");
+			self.exports.export("text_case", ExportKind::Func, idx);
+		}
+
+		// text_replace(text: ref $Node, from: ref $Node, to: ref $Node) -> ref $Node
+		// Replaces every occurrence, left to right; an empty from leaves the text as is
+		if self.should_emit_function("text_replace") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=from, 2=to, 3=string, 4=ptr, 5=len, 6=from ptr, 7=from len,
+			// 8=to ptr, 9=to len, 10=i, 11=count, 12=dest, 13=pos
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (10, ValType::I32)]);
+			self.emit_load_text(&mut func, 0, 3, 4, 5);
+			self.emit_load_text(&mut func, 1, 3, 6, 7);
+			self.emit_load_text(&mut func, 2, 3, 8, 9);
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "to_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+
+			// count the occurrences to allocate the exact size
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			Self::emit_past_end(&mut func, 10, 7, 5);
+			func.instruction(&Instruction::BrIf(1));
+			self.emit_match_at(&mut func, 4, 10, 6, 7);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			Self::emit_increment(&mut func, 11, 1);
+			Self::emit_advance(&mut func, 10, 7);
+			func.instruction(&Instruction::Else);
+			Self::emit_increment(&mut func, 10, 1);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+
+			// dest = alloc(len + count * (to len - from len))
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalGet(11));
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I32Mul);
+			func.instruction(&Instruction::I32Add);
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&Instruction::LocalSet(12));
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::LocalSet(10));
+
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(10));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			Self::emit_past_end(&mut func, 10, 7, 5);
+			func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::Else);
+			self.emit_match_at(&mut func, 4, 10, 6, 7);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				func.instruction(&Instruction::LocalGet(12));
+				func.instruction(&Instruction::LocalGet(13));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+				Self::emit_advance(&mut func, 13, 9);
+				Self::emit_advance(&mut func, 10, 7);
+			}
+			func.instruction(&Instruction::Else);
+			{
+				func.instruction(&Instruction::LocalGet(12));
+				func.instruction(&Instruction::LocalGet(13));
+				func.instruction(&Instruction::I32Add);
+				Self::emit_byte_at(&mut func, 4, 10);
+				func.instruction(&Instruction::I32Store8(BYTE));
+				Self::emit_increment(&mut func, 13, 1);
+				Self::emit_increment(&mut func, 10, 1);
+			}
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(12));
+			func.instruction(&Instruction::LocalGet(13));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_replace");
+			self.exports.export("text_replace", ExportKind::Func, idx);
+		}
+
+		// text_split(text: ref $Node, separator: ref $Node) -> ref $Node
+		// List of the pieces between separators, an empty separator splits into codepoints
+		// Pieces are consed in reverse while scanning, then reversed into a [list]
+		if self.should_emit_function("text_split") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=separator, 2=string, 3=ptr, 4=len, 5=separator ptr, 6=separator len,
+			// 7=i, 8=start, 9=next, 10=reversed, 11=list, 12..14=piece scratch
+			let mut func = Function::new(vec![
+				(1, Ref(string_ref)),
+				(7, ValType::I32),
+				(2, Ref(node_ref_nullable)),
+				(3, ValType::I32),
+			]);
+			self.emit_load_text(&mut func, 0, 2, 3, 4);
+			self.emit_load_text(&mut func, 1, 2, 5, 6);
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::I32Eqz);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			{
+				// one piece per codepoint
+				func.instruction(&Instruction::Block(BlockType::Empty));
+				func.instruction(&Instruction::Loop(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::I32GeU);
+				func.instruction(&Instruction::BrIf(1));
+				func.instruction(&Instruction::LocalGet(3));
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::I32Sub);
+				func.instruction(&Instruction::I64Const(1));
+				self.emit_call(&mut func, "utf8_offset");
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalSet(9));
+				self.emit_push_piece(&mut func, 3, 7, 9, 10, 12);
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::LocalSet(7));
+				func.instruction(&Instruction::Br(0));
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::End);
+			}
+			func.instruction(&Instruction::Else);
+			{
+				func.instruction(&Instruction::Block(BlockType::Empty));
+				func.instruction(&Instruction::Loop(BlockType::Empty));
+				Self::emit_past_end(&mut func, 7, 6, 4);
+				func.instruction(&Instruction::BrIf(1));
+				self.emit_match_at(&mut func, 3, 7, 5, 6);
+				func.instruction(&Instruction::If(BlockType::Empty));
+				self.emit_push_piece(&mut func, 3, 8, 7, 10, 12);
+				Self::emit_advance(&mut func, 7, 6);
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::LocalSet(8));
+				func.instruction(&Instruction::Else);
+				Self::emit_increment(&mut func, 7, 1);
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::Br(0));
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::End);
+				// the rest after the last separator
+				self.emit_push_piece(&mut func, 3, 8, 4, 10, 12);
+			}
+			func.instruction(&Instruction::End);
+			// "" split into codepoints is [""]
+			func.instruction(&Instruction::LocalGet(10));
+			func.instruction(&Instruction::RefIsNull);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			self.emit_push_piece(&mut func, 3, 8, 8, 10, 12);
+			func.instruction(&Instruction::End);
+
+			self.emit_reverse(&mut func, 10, 11);
+			func.instruction(&Instruction::LocalGet(11));
+			func.instruction(&Instruction::RefAsNonNull);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_split");
+			self.exports.export("text_split", ExportKind::Func, idx);
+		}
+
+		// text_codepoints(text: ref $Node) -> ref $Node
+		// [list] of the codepoints of text as Codepoint nodes, empty text gives Empty
+		if self.should_emit_function("text_codepoints") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut().ty().function(vec![Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=string, 2=ptr, 3=len, 4=i, 5=next, 6=c, 7=k, 8=reversed, 9=list
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (6, ValType::I32), (2, Ref(node_ref_nullable))]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 1,
+			});
+			func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.string_type)));
+			func.instruction(&Instruction::LocalTee(1));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.string_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::LocalSet(2));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.string_type,
+				field_index: 1,
+			});
+			func.instruction(&Instruction::LocalSet(3));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			func.instruction(&Instruction::LocalGet(2));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalGet(3));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I64Const(1));
+			self.emit_call(&mut func, "utf8_offset");
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(5));
+			// the lead byte keeps 0xFF >> n bits of an n byte sequence
+			Self::emit_byte_at(&mut func, 2, 4);
+			func.instruction(&I32Const(0xFF));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::I32Sub);
+			func.instruction(&Instruction::I32ShrU);
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::LocalSet(6));
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&I32Const(1));
+			func.instruction(&Instruction::I32Add);
+			func.instruction(&Instruction::LocalSet(7));
+			func.instruction(&Instruction::Block(BlockType::Empty));
+			func.instruction(&Instruction::Loop(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(7));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::I32GeU);
+			func.instruction(&Instruction::BrIf(1));
+			// continuation bytes 10xxxxxx add six bits each
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&I32Const(6));
+			func.instruction(&Instruction::I32Shl);
+			Self::emit_byte_at(&mut func, 2, 7);
+			func.instruction(&I32Const(0x3F));
+			func.instruction(&Instruction::I32And);
+			func.instruction(&Instruction::I32Or);
+			func.instruction(&Instruction::LocalSet(6));
+			Self::emit_increment(&mut func, 7, 1);
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::LocalGet(6));
+			self.emit_call(&mut func, "new_codepoint");
+			func.instruction(&Instruction::LocalGet(8));
+			func.instruction(&Instruction::I64Const(SQUARE));
+			self.emit_call(&mut func, "new_list");
+			func.instruction(&Instruction::LocalSet(8));
+			func.instruction(&Instruction::LocalGet(5));
+			func.instruction(&Instruction::LocalSet(4));
+			func.instruction(&Instruction::Br(0));
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			self.emit_reverse(&mut func, 8, 9);
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::RefIsNull);
+			func.instruction(&Instruction::If(BlockType::Result(Ref(node_ref))));
+			self.emit_call(&mut func, "new_empty");
+			func.instruction(&Instruction::Else);
+			func.instruction(&Instruction::LocalGet(9));
+			func.instruction(&Instruction::RefAsNonNull);
+			func.instruction(&Instruction::End);
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_codepoints");
+			self.exports.export("text_codepoints", ExportKind::Func, idx);
+		}
+
+		// text_join(list: ref $Node, separator: ref $Node) -> ref $Node
+		// The items as text with the separator between them; a non-list is just its text
+		if self.should_emit_function("text_join") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), Ref(node_ref)], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=list, 1=separator, 2=string, 3=separator ptr, 4=separator len, 5=total,
+			// 6=dest, 7=pos, 8=started, 9=ptr, 10=len, 11=current
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (8, ValType::I32), (1, Ref(node_ref_nullable))]);
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::StructGet {
+				struct_type_index: self.type_manager.node_type,
+				field_index: 0,
+			});
+			func.instruction(&Instruction::I64Const(0xFF));
+			func.instruction(&Instruction::I64And);
+			func.instruction(&Instruction::I64Const(Kind::List as i64));
+			func.instruction(&Instruction::I64Ne);
+			func.instruction(&Instruction::If(BlockType::Empty));
+			func.instruction(&Instruction::LocalGet(0));
+			self.emit_call(&mut func, "to_text");
+			func.instruction(&Instruction::Return);
+			func.instruction(&Instruction::End);
+			self.emit_load_text(&mut func, 1, 2, 3, 4);
+
+			// total = sum of item lengths + separators between them
+			self.emit_each_item(&mut func, 0, 11, |this, func| {
+				this.emit_text_fields(func, 2, 9, 10);
+				func.instruction(&Instruction::LocalGet(5));
+				func.instruction(&Instruction::LocalGet(10));
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::I32Mul);
+				func.instruction(&Instruction::I32Add);
+				func.instruction(&Instruction::LocalSet(5));
+				func.instruction(&I32Const(1));
+				func.instruction(&Instruction::LocalSet(8));
+			});
+			func.instruction(&Instruction::LocalGet(5));
+			self.emit_call(&mut func, "alloc");
+			func.instruction(&Instruction::LocalTee(6));
+			func.instruction(&Instruction::LocalSet(7));
+			func.instruction(&I32Const(0));
+			func.instruction(&Instruction::LocalSet(8));
+
+			self.emit_each_item(&mut func, 0, 11, |this, func| {
+				this.emit_text_fields(func, 2, 9, 10);
+				func.instruction(&Instruction::LocalGet(8));
+				func.instruction(&Instruction::If(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::LocalGet(3));
+				func.instruction(&Instruction::LocalGet(4));
+				func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+				Self::emit_advance(func, 7, 4);
+				func.instruction(&Instruction::End);
+				func.instruction(&Instruction::LocalGet(7));
+				func.instruction(&Instruction::LocalGet(9));
+				func.instruction(&Instruction::LocalGet(10));
+				func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+				Self::emit_advance(func, 7, 10);
+				func.instruction(&I32Const(1));
+				func.instruction(&Instruction::LocalSet(8));
+			});
+			func.instruction(&Instruction::LocalGet(6));
+			func.instruction(&Instruction::LocalGet(5));
+			self.emit_call(&mut func, "new_text");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_join");
+			self.exports.export("text_join", ExportKind::Func, idx);
+		}
+
+		// text_slice(text: ref $Node, from: i64, to: i64) -> ref $Node
+		// substring where negative indices count from the end: slice(s, -3)
+		if self.should_emit_function("text_slice") {
+			let func_type = self.type_manager.types().len();
+			self.type_manager.types_mut()
+				.ty()
+				.function(vec![Ref(node_ref), ValType::I64, ValType::I64], vec![Ref(node_ref)]);
+			self.functions.function(func_type);
+			// Locals: 0=text, 1=from, 2=to, 3=string, 4=ptr, 5=len, 6=count
+			let mut func = Function::new(vec![(1, Ref(string_ref)), (2, ValType::I32), (1, ValType::I64)]);
+			self.emit_load_text(&mut func, 0, 3, 4, 5);
+			func.instruction(&Instruction::LocalGet(4));
+			func.instruction(&Instruction::LocalGet(5));
+			self.emit_call(&mut func, "utf8_count");
+			func.instruction(&Instruction::LocalSet(6));
+			for index in [1, 2] {
+				func.instruction(&Instruction::LocalGet(index));
+				func.instruction(&Instruction::I64Const(0));
+				func.instruction(&Instruction::I64LtS);
+				func.instruction(&Instruction::If(BlockType::Empty));
+				func.instruction(&Instruction::LocalGet(index));
+				func.instruction(&Instruction::LocalGet(6));
+				func.instruction(&Instruction::I64Add);
+				func.instruction(&Instruction::LocalSet(index));
+				func.instruction(&Instruction::End);
+			}
+			func.instruction(&Instruction::LocalGet(0));
+			func.instruction(&Instruction::LocalGet(1));
+			func.instruction(&Instruction::LocalGet(2));
+			self.emit_call(&mut func, "text_substring");
+			func.instruction(&Instruction::End);
+			self.code.function(&func);
+			let idx = self.register_func("text_slice");
+			self.exports.export("text_slice", ExportKind::Func, idx);
+		}
+	}
+
+	/// Wasp-callable text functions, also as methods: substring(s, 1, 3), s.split(","), s.upper
+	/// Returns false if name is not one of them
+	pub(crate) fn emit_text_call(&mut self, func: &mut Function, name: &str, args: &[Node]) -> bool {
+		if self.emit_text_search(func, name, args) {
+			self.emit_call(func, "new_int");
+			return true;
+		}
+		match (name, args) {
+			("substring" | "slice", [text, from, rest @ ..]) if rest.len() <= 1 => {
+				self.emit_node_instructions(func, text);
+				self.emit_numeric_value(func, from);
+				match rest.first() {
+					Some(to) => self.emit_numeric_value(func, to),
+					None => {
+						func.instruction(&Instruction::I64Const(i64::MAX));
+					}
+				}
+				self.emit_call(func, if name == "slice" { "text_slice" } else { "text_substring" });
+			}
+			("repeat", [text, n]) => {
+				self.emit_node_instructions(func, text);
+				self.emit_numeric_value(func, n);
+				self.emit_call(func, "text_repeat");
+			}
+			("split" | "join", [text, separator]) => {
+				self.emit_node_instructions(func, text);
+				self.emit_node_instructions(func, separator);
+				self.emit_call(func, if name == "split" { "text_split" } else { "text_join" });
+			}
+			("replace", [text, from, to]) => {
+				self.emit_node_instructions(func, text);
+				self.emit_node_instructions(func, from);
+				self.emit_node_instructions(func, to);
+				self.emit_call(func, "text_replace");
+			}
+			("trim", [text]) => {
+				self.emit_node_instructions(func, text);
+				self.emit_call(func, "text_trim");
+			}
+			("upper" | "lower", [text]) => {
+				self.emit_node_instructions(func, text);
+				func.instruction(&I32Const((name == "upper") as i32));
+				self.emit_call(func, "text_case");
+			}
+			_ => return false,
+		}
+		true
+	}
+
+	/// Text searches as a plain i64: starts_with, ends_with and contains are 0 or 1, index_of -1 if missing
+	pub(crate) fn emit_text_search(&mut self, func: &mut Function, name: &str, args: &[Node]) -> bool {
+		let [text, part] = args else { return false };
+		let function = match name {
+			"starts_with" => "text_starts_with",
+			"ends_with" => "text_ends_with",
+			"index_of" | "contains" => "text_index_of",
+			_ => return false,
+		};
+		self.emit_node_instructions(func, text);
+		self.emit_node_instructions(func, part);
+		self.emit_call(func, function);
+		if name == "contains" {
+			func.instruction(&Instruction::I64Const(-1));
+			func.instruction(&Instruction::I64Ne);
+			func.instruction(&Instruction::I64ExtendI32U);
+		}
+		true
+	}
+
+	/// Name and arguments of a text function call, the receiver of a method first
+	/// User functions of the same name take precedence
+	pub(crate) fn text_call(&self, node: &Node) -> Option<(&'static str, Vec<Node>)> {
+		let &(name, _, _) = text_function(node, &self.scope)?;
+		let args = match node.drop_meta() {
+			Node::List(..) if self.ctx.user_functions.contains_key(name) => return None,
+			Node::Key(receiver, Op::Dot, method) => match method.drop_meta() {
+				Node::List(items, _, _) => std::iter::once(receiver.as_ref()).chain(&items[1..]).cloned().collect(),
+				_ => vec![receiver.as_ref().clone()],
+			},
+			Node::List(items, _, _) => items[1..].to_vec(),
+			_ => return None,
+		};
+		Some((name, args))
+	}
+
+	/// Cons the bytes from..to after ptr as text onto the list local; uses scratch..scratch+2
+	fn emit_push_piece(&mut self, func: &mut Function, ptr: u32, from: u32, to: u32, list: u32, scratch: u32) {
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(from));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(scratch));
+		func.instruction(&Instruction::LocalGet(to));
+		func.instruction(&Instruction::LocalGet(from));
+		func.instruction(&Instruction::I32Sub);
+		func.instruction(&Instruction::LocalSet(scratch + 1));
+		self.emit_copy_text(func, scratch, scratch + 1, scratch + 2);
+		func.instruction(&Instruction::LocalGet(list));
+		func.instruction(&Instruction::I64Const(SQUARE));
+		self.emit_call(func, "new_list");
+		func.instruction(&Instruction::LocalSet(list));
+	}
+
+	/// Cons the items of the reversed list local onto the list local, in their original order
+	pub(crate) fn emit_reverse(&mut self, func: &mut Function, reversed: u32, list: u32) {
+		func.instruction(&Instruction::Block(BlockType::Empty));
+		func.instruction(&Instruction::Loop(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(reversed));
+		func.instruction(&Instruction::RefIsNull);
+		func.instruction(&Instruction::BrIf(1));
+		func.instruction(&Instruction::LocalGet(reversed));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.node_type)));
+		func.instruction(&Instruction::LocalGet(list));
+		func.instruction(&Instruction::I64Const(SQUARE));
+		self.emit_call(func, "new_list");
+		func.instruction(&Instruction::LocalSet(list));
+		func.instruction(&Instruction::LocalGet(reversed));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 2,
+		});
+		func.instruction(&Instruction::LocalSet(reversed));
+		func.instruction(&Instruction::Br(0));
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::End);
+	}
+
+	/// For each item of the list local with the item Node on the stack; skips empty cells
+	pub(crate) fn emit_each_item(&mut self, func: &mut Function, list: u32, current: u32, body: impl Fn(&mut Self, &mut Function)) {
+		func.instruction(&Instruction::LocalGet(list));
+		func.instruction(&Instruction::LocalSet(current));
+		func.instruction(&Instruction::Block(BlockType::Empty));
+		func.instruction(&Instruction::Loop(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(current));
+		func.instruction(&Instruction::RefIsNull);
+		func.instruction(&Instruction::BrIf(1));
+		func.instruction(&Instruction::LocalGet(current));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefIsNull);
+		func.instruction(&Instruction::I32Eqz);
+		func.instruction(&Instruction::If(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(current));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 1,
+		});
+		func.instruction(&Instruction::RefCastNonNull(HeapType::Concrete(self.type_manager.node_type)));
+		body(self, func);
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::LocalGet(current));
+		func.instruction(&Instruction::StructGet {
+			struct_type_index: self.type_manager.node_type,
+			field_index: 2,
+		});
+		func.instruction(&Instruction::LocalSet(current));
+		func.instruction(&Instruction::Br(0));
+		func.instruction(&Instruction::End);
+		func.instruction(&Instruction::End);
+	}
+
+	/// bytes_equal(ptr + i, part, part len)
+	fn emit_match_at(&mut self, func: &mut Function, ptr: u32, i: u32, part: u32, part_len: u32) {
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(i));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalGet(part));
+		func.instruction(&Instruction::LocalGet(part_len));
+		self.emit_call(func, "bytes_equal");
+	}
+
+	/// i + part len > len: no room left for another match
+	fn emit_past_end(func: &mut Function, i: u32, part_len: u32, len: u32) {
+		func.instruction(&Instruction::LocalGet(i));
+		func.instruction(&Instruction::LocalGet(part_len));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalGet(len));
+		func.instruction(&Instruction::I32GtU);
+	}
+
+	/// Byte at memory[ptr + i]
+	fn emit_byte_at(func: &mut Function, ptr: u32, i: u32) {
+		func.instruction(&Instruction::LocalGet(ptr));
+		func.instruction(&Instruction::LocalGet(i));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::I32Load8U(BYTE));
+	}
+
+	/// local += step
+	pub(crate) fn emit_increment(func: &mut Function, local: u32, step: i32) {
+		func.instruction(&Instruction::LocalGet(local));
+		func.instruction(&I32Const(step));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(local));
+	}
+
+	/// local += by local
+	fn emit_advance(func: &mut Function, local: u32, by: u32) {
+		func.instruction(&Instruction::LocalGet(local));
+		func.instruction(&Instruction::LocalGet(by));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::LocalSet(local));
+	}
+
+	/// ASCII whitespace: space, tab, line feed, vertical tab, form feed, carriage return
+	fn emit_is_space(func: &mut Function, byte: u32) {
+		func.instruction(&Instruction::LocalGet(byte));
+		func.instruction(&I32Const(b' ' as i32));
+		func.instruction(&Instruction::I32Eq);
+		func.instruction(&Instruction::LocalGet(byte));
+		func.instruction(&I32Const(9));
+		func.instruction(&Instruction::I32Sub);
+		func.instruction(&I32Const(4));
+		func.instruction(&Instruction::I32LeU);
+		func.instruction(&Instruction::I32Or);
+	}
+
+	/// if first <= c <= last and c != excluded: return c + delta
+	fn emit_case_range(func: &mut Function, (first, last, delta, excluded): (i32, i32, i32, i32)) {
+		func.instruction(&Instruction::LocalGet(0));
+		func.instruction(&I32Const(first));
+		func.instruction(&Instruction::I32Sub);
+		func.instruction(&I32Const(last - first));
+		func.instruction(&Instruction::I32LeU);
+		func.instruction(&Instruction::LocalGet(0));
+		func.instruction(&I32Const(excluded));
+		func.instruction(&Instruction::I32Ne);
+		func.instruction(&Instruction::I32And);
+		func.instruction(&Instruction::If(BlockType::Empty));
+		func.instruction(&Instruction::LocalGet(0));
+		func.instruction(&I32Const(delta));
+		func.instruction(&Instruction::I32Add);
+		func.instruction(&Instruction::Return);
+		func.instruction(&Instruction::End);
+	}
+}
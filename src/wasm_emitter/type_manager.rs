@@ -28,6 +28,12 @@ pub struct TypeManager {
 
 	/// Map from user type names to their WASM type indices
 	user_type_indices: HashMap<String, u32>,
+
+	/// Closure function and $Closure struct type indices by arity
+	closure_types: HashMap<usize, (u32, u32)>,
+
+	/// Type index for the $NodeArray sort works in, once added
+	node_array_type: Option<u32>,
 }
 
 impl Default for TypeManager {
@@ -47,6 +53,8 @@ impl TypeManager {
 			node_type: 0,
 			next_type_idx: 0,
 			user_type_indices: HashMap::new(),
+			closure_types: HashMap::new(),
+			node_array_type: None,
 		}
 	}
 
@@ -115,34 +123,60 @@ impl TypeManager {
 	/// Emit user-defined struct types from TypeRegistry
 	pub fn emit_user_types(&mut self, registry: &TypeRegistry) {
 		for type_def in registry.types() {
-			let fields: Vec<FieldType> = type_def
-				.fields
-				.iter()
-				.map(|f| self.field_def_to_wasm_field(f))
-				.collect();
-
-			self.types.ty().struct_(fields);
-			self.user_type_indices.insert(type_def.name.clone(), self.next_type_idx);
-			self.next_type_idx += 1;
+			self.emit_single_user_type(type_def);
 		}
 	}
 
 	/// Emit a single user-defined struct type
+	/// An enum is an open struct holding the tag, its variants final subtypes extending it:
+	/// (type $Shape (sub (struct (field $tag i32))))
+	/// (type $Circle (sub final $Shape (struct (field $tag i32) (field $r i64))))
+	/// A class extended by others is open the same way, its subclasses repeat its fields first:
+	/// (type $Employee (sub final $Person (struct (field $tag i32) (field $name …) (field $salary i64))))
+	/// A type holding itself sits in its own recursion group:
+	/// (rec (type $Tree (struct (field $value i64) (field $left (ref null $Tree)))))
 	pub fn emit_single_user_type(&mut self, type_def: &TypeDef) {
-		let fields: Vec<FieldType> = type_def
-			.fields
-			.iter()
-			.map(|f| self.field_def_to_wasm_field(f))
-			.collect();
-
-		self.types.ty().struct_(fields);
+		// known before its fields, which may refer to it
 		self.user_type_indices.insert(type_def.name.clone(), self.next_type_idx);
+		let recursive = type_def.fields.iter().any(|f| f.base_type() == type_def.name);
+		let mut fields: Vec<FieldType> = Vec::new();
+		if type_def.is_tagged() {
+			fields.push(FieldType {
+				element_type: Val(ValType::I32),
+				mutable: false,
+			});
+		}
+		fields.extend(type_def.fields.iter().map(|f| self.field_def_to_wasm_field(f)));
+
+		if type_def.is_tagged() || recursive {
+			let supertype_idx = type_def.supertype.as_ref().and_then(|name| self.get_user_type_idx(name));
+			let sub_type = SubType {
+				is_final: type_def.variants.is_empty() && type_def.subtypes.is_empty(),
+				supertype_idx,
+				composite_type: CompositeType {
+					inner: CompositeInnerType::Struct(StructType {
+						fields: fields.into_boxed_slice(),
+					}),
+					shared: false,
+					descriptor: None,
+					describes: None,
+				},
+			};
+			if recursive {
+				self.types.ty().rec([sub_type]);
+			} else {
+				self.types.ty().subtype(&sub_type);
+			}
+		} else {
+			self.types.ty().struct_(fields);
+		}
 		self.next_type_idx += 1;
 	}
 
 	/// Convert a FieldDef to a WASM FieldType
+	/// User types are non-null refs unless optional
 	pub fn field_def_to_wasm_field(&self, field: &FieldDef) -> FieldType {
-		let element_type = match field.type_name.as_str() {
+		let element_type = match field.base_type() {
 			// Node-mode: map wasp types to WASM types
 			"Int" | "i64" | "long" => Val(ValType::I64),
 			"Float" | "f64" | "double" => Val(ValType::F64),
@@ -160,7 +194,7 @@ impl TypeManager {
 			other => {
 				if let Some(&type_idx) = self.user_type_indices.get(other) {
 					Val(Ref(RefType {
-						nullable: true,
+						nullable: field.is_optional(),
 						heap_type: HeapType::Concrete(type_idx),
 					}))
 				} else {
@@ -196,6 +230,61 @@ impl TypeManager {
 		idx
 	}
 
+	/// Function and struct type of closures taking arity arguments, added on first use:
+	/// (func (param $env anyref) (param (ref $Node))* (result (ref $Node)))
+	/// $Closure = (struct (field $fn (ref $func)) (field $env anyref))
+	pub fn closure_type(&mut self, arity: usize) -> (u32, u32) {
+		if let Some(&types) = self.closure_types.get(&arity) {
+			return types;
+		}
+		let any_ref = Ref(RefType {
+			nullable: true,
+			heap_type: any_heap_type(),
+		});
+		let node_ref = Ref(self.node_ref(false));
+		let mut params = vec![any_ref];
+		params.resize(arity + 1, node_ref);
+		let func_type = self.types.len();
+		self.types.ty().function(params, vec![node_ref]);
+		let struct_type = self.types.len();
+		self.types.ty().struct_(vec![
+			FieldType {
+				element_type: Val(Ref(RefType {
+					nullable: false,
+					heap_type: HeapType::Concrete(func_type),
+				})),
+				mutable: false,
+			}, // fn
+			FieldType {
+				element_type: Val(any_ref),
+				mutable: false,
+			}, // env
+		]);
+		self.closure_types.insert(arity, (func_type, struct_type));
+		(func_type, struct_type)
+	}
+
+	/// $NodeArray = (array (mut (ref null $Node))), added on first use
+	pub fn node_array_type(&mut self) -> u32 {
+		if let Some(array_type) = self.node_array_type {
+			return array_type;
+		}
+		let array_type = self.types.len();
+		self.types.ty().array(&Val(Ref(self.node_ref(true))), true);
+		self.node_array_type = Some(array_type);
+		array_type
+	}
+
+	/// Add an immutable struct type, like the environment of a closure, and return its index
+	pub fn add_struct_type(&mut self, fields: Vec<ValType>) -> u32 {
+		let idx = self.types.len();
+		self.types.ty().struct_(fields.into_iter().map(|field| FieldType {
+			element_type: Val(field),
+			mutable: false,
+		}));
+		idx
+	}
+
 	/// Get the type section (for adding to WASM module)
 	pub fn types(&self) -> &TypeSection {
 		&self.types
@@ -0,0 +1,690 @@
+//! Merge several wasm modules into one
+//!
+//! Imports of one module are resolved against the exports of the others,
+//! preferring the module whose name matches the import's module field.
+//! Types, functions, tables, memories, globals, tags, elements and data segments
+//! are renumbered into one index space. Rec groups are copied whole, so GC types
+//! stay isorecursively equal across the original modules.
+//! Imports nobody exports (host, wasi, ffi) stay imports of the merged module.
+//! An import must match the type of the export it links to, compared structurally across modules.
+//! Exports keep their names; a name an earlier module already exports is namespaced as `module.name`.
+//! Name sections are merged and renumbered too, so user structs still read back by name.
+
+use crate::error::WarpError;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use wasm_encoder::reencode::{self, Reencode};
+use wasm_encoder::{
+	CodeSection, DataCountSection, DataSection, ElementSection, ExportKind, ExportSection, FunctionSection, GlobalSection, ImportSection,
+	IndirectNameMap, MemorySection, Module, NameMap, NameSection, StartSection, TableSection, TagSection, TypeSection,
+};
+use wasmparser::{
+	CompositeInnerType, Export, ExternalKind, FieldType, HeapType, Import, KnownCustom, Name, NameSectionReader, Parser, Payload, RefType,
+	StorageType, SubType, TypeRef, UnpackedIndex, ValType, Validator, WasmFeatures,
+};
+
+/// Index spaces that imports and exports can refer to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Space {
+	Func,
+	Table,
+	Memory,
+	Global,
+	Tag,
+}
+
+const SPACES: [Space; 5] = [Space::Func, Space::Table, Space::Memory, Space::Global, Space::Tag];
+
+impl Space {
+	fn of_import(ty: &TypeRef) -> Option<Space> {
+		match ty {
+			TypeRef::Func(_) => Some(Space::Func),
+			TypeRef::Table(_) => Some(Space::Table),
+			TypeRef::Memory(_) => Some(Space::Memory),
+			TypeRef::Global(_) => Some(Space::Global),
+			TypeRef::Tag(_) => Some(Space::Tag),
+			#[allow(unreachable_patterns)]
+			_ => None,
+		}
+	}
+
+	fn of_export(kind: ExternalKind) -> Option<Space> {
+		match kind {
+			ExternalKind::Func => Some(Space::Func),
+			ExternalKind::Table => Some(Space::Table),
+			ExternalKind::Memory => Some(Space::Memory),
+			ExternalKind::Global => Some(Space::Global),
+			ExternalKind::Tag => Some(Space::Tag),
+			#[allow(unreachable_patterns)]
+			_ => None,
+		}
+	}
+
+	fn export_kind(self) -> ExportKind {
+		match self {
+			Space::Func => ExportKind::Func,
+			Space::Table => ExportKind::Table,
+			Space::Memory => ExportKind::Memory,
+			Space::Global => ExportKind::Global,
+			Space::Tag => ExportKind::Tag,
+		}
+	}
+}
+
+/// What one input module declares, gathered before anything is emitted
+#[derive(Default)]
+struct Input<'a> {
+	name: String,
+	bytes: &'a [u8],
+	/// Every type with the index its rec group starts at
+	types: Vec<(u32, SubType)>,
+	imports: Vec<Import<'a>>,
+	defined: HashMap<Space, u32>,
+	/// Type of every entity in each index space, imports first
+	entities: HashMap<Space, Vec<TypeRef>>,
+	exports: Vec<Export<'a>>,
+	elements: u32,
+	data: u32,
+	start: Option<u32>,
+}
+
+impl<'a> Input<'a> {
+	fn read(name: &str, bytes: &'a [u8]) -> Result<Input<'a>, WarpError> {
+		let mut input = Input { name: name.to_string(), bytes, ..Input::default() };
+		for payload in Parser::new(0).parse_all(bytes) {
+			match payload.map_err(|e| link_error(name, e))? {
+				Payload::TypeSection(reader) => {
+					for group in reader {
+						let group = group.map_err(|e| link_error(name, e))?;
+						let start = input.types.len() as u32;
+						input.types.extend(group.types().map(|ty| (start, ty.clone())));
+					}
+				}
+				Payload::ImportSection(reader) => {
+					for import in reader {
+						let import = import.map_err(|e| link_error(name, e))?;
+						if let Some(space) = Space::of_import(&import.ty) {
+							input.entities.entry(space).or_default().push(import.ty);
+						}
+						input.imports.push(import);
+					}
+				}
+				Payload::FunctionSection(reader) => {
+					for ty in reader {
+						input.define(Space::Func, TypeRef::Func(ty.map_err(|e| link_error(name, e))?));
+					}
+				}
+				Payload::TableSection(reader) => {
+					for table in reader {
+						input.define(Space::Table, TypeRef::Table(table.map_err(|e| link_error(name, e))?.ty));
+					}
+				}
+				Payload::MemorySection(reader) => {
+					for memory in reader {
+						input.define(Space::Memory, TypeRef::Memory(memory.map_err(|e| link_error(name, e))?));
+					}
+				}
+				Payload::GlobalSection(reader) => {
+					for global in reader {
+						input.define(Space::Global, TypeRef::Global(global.map_err(|e| link_error(name, e))?.ty));
+					}
+				}
+				Payload::TagSection(reader) => {
+					for tag in reader {
+						input.define(Space::Tag, TypeRef::Tag(tag.map_err(|e| link_error(name, e))?));
+					}
+				}
+				Payload::ExportSection(reader) => {
+					for export in reader {
+						input.exports.push(export.map_err(|e| link_error(name, e))?);
+					}
+				}
+				Payload::StartSection { func, .. } => input.start = Some(func),
+				Payload::ElementSection(reader) => input.elements = reader.count(),
+				Payload::DataSection(reader) => input.data = reader.count(),
+				_ => {}
+			}
+		}
+		Ok(input)
+	}
+
+	fn define(&mut self, space: Space, ty: TypeRef) {
+		*self.defined.entry(space).or_insert(0) += 1;
+		self.entities.entry(space).or_default().push(ty);
+	}
+
+	fn defined(&self, space: Space) -> u32 {
+		self.defined.get(&space).copied().unwrap_or(0)
+	}
+
+	/// Imports of one index space, in index order
+	fn imports_in(&self, space: Space) -> Vec<usize> {
+		(0..self.imports.len()).filter(|&i| Space::of_import(&self.imports[i].ty) == Some(space)).collect()
+	}
+
+	fn export(&self, name: &str, space: Space) -> Option<u32> {
+		self.exports.iter().find(|e| e.name == name && Space::of_export(e.kind) == Some(space)).map(|e| e.index)
+	}
+
+	fn entity(&self, space: Space, index: u32) -> Option<TypeRef> {
+		self.entities.get(&space).and_then(|types| types.get(index as usize)).copied()
+	}
+}
+
+/// Where an import ends up in the merged module
+#[derive(Clone, Copy)]
+enum Target {
+	/// Still imported, at this merged index
+	Kept(u32),
+	/// Provided by another input module: (module, index in that module)
+	Linked(usize, u32),
+}
+
+/// Link named wasm modules into one module
+/// A later module's export whose name is already taken is exported as `module.name`
+pub fn link_modules(modules: &[(&str, &[u8])]) -> Result<Vec<u8>, WarpError> {
+	for (name, bytes) in modules {
+		Validator::new_with_features(features()).validate_all(bytes).map_err(|e| link_error(name, e))?;
+	}
+	let inputs = modules.iter().map(|(name, bytes)| Input::read(name, bytes)).collect::<Result<Vec<_>, _>>()?;
+	let maps = IndexMaps::build(&inputs)?;
+
+	let mut types = TypeSection::new();
+	let mut imports = ImportSection::new();
+	let mut functions = FunctionSection::new();
+	let mut tables = TableSection::new();
+	let mut memories = MemorySection::new();
+	let mut tags = TagSection::new();
+	let mut globals = GlobalSection::new();
+	let mut exports = ExportSection::new();
+	let mut elements = ElementSection::new();
+	let mut code = CodeSection::new();
+	let mut data = DataSection::new();
+	let mut names = Names::default();
+
+	for (m, import) in &maps.kept {
+		let mut renumber = maps.renumber(*m);
+		let import = &inputs[*m].imports[*import];
+		imports.import(import.module, import.name, renumber.entity_type(import.ty).map_err(reencode_error)?);
+	}
+
+	let mut exported: Vec<String> = Vec::new();
+	let mut start = None;
+	for (m, input) in inputs.iter().enumerate() {
+		let mut renumber = maps.renumber(m);
+		for payload in Parser::new(0).parse_all(input.bytes) {
+			match payload.map_err(|e| link_error(&input.name, e))? {
+				Payload::TypeSection(reader) => renumber.parse_type_section(&mut types, reader).map_err(reencode_error)?,
+				Payload::FunctionSection(reader) => renumber.parse_function_section(&mut functions, reader).map_err(reencode_error)?,
+				Payload::TableSection(reader) => renumber.parse_table_section(&mut tables, reader).map_err(reencode_error)?,
+				Payload::MemorySection(reader) => renumber.parse_memory_section(&mut memories, reader).map_err(reencode_error)?,
+				Payload::TagSection(reader) => renumber.parse_tag_section(&mut tags, reader).map_err(reencode_error)?,
+				Payload::GlobalSection(reader) => renumber.parse_global_section(&mut globals, reader).map_err(reencode_error)?,
+				Payload::ElementSection(reader) => renumber.parse_element_section(&mut elements, reader).map_err(reencode_error)?,
+				Payload::DataSection(reader) => renumber.parse_data_section(&mut data, reader).map_err(reencode_error)?,
+				Payload::CodeSectionEntry(body) => renumber.parse_function_body(&mut code, body).map_err(reencode_error)?,
+				Payload::CustomSection(section) => {
+					if let KnownCustom::Name(reader) = section.as_known() {
+						names.read(reader, &mut renumber).map_err(|e| link_error(&input.name, e))?;
+					}
+				}
+				_ => {}
+			}
+		}
+		for export in &input.exports {
+			let Some(space) = Space::of_export(export.kind) else { continue };
+			let mut name = export.name.to_string();
+			if exported.contains(&name) {
+				name = format!("{}.{}", input.name, export.name);
+			}
+			if exported.contains(&name) {
+				return Err(link_error(&input.name, format!("export {} is already taken", name)));
+			}
+			exports.export(&name, space.export_kind(), maps.index(m, space, export.index));
+			exported.push(name);
+		}
+		if let Some(func) = input.start {
+			if start.is_some() {
+				return Err(WarpError::Link {
+					message: format!("{} has a second start function", input.name),
+				});
+			}
+			start = Some(maps.index(m, Space::Func, func));
+		}
+	}
+
+	let mut module = Module::new();
+	module.section(&types);
+	module.section(&imports);
+	module.section(&functions);
+	module.section(&tables);
+	module.section(&memories);
+	module.section(&tags);
+	module.section(&globals);
+	module.section(&exports);
+	if let Some(function_index) = start {
+		module.section(&StartSection { function_index });
+	}
+	module.section(&elements);
+	let count = inputs.iter().map(|i| i.data).sum::<u32>();
+	if count > 0 {
+		module.section(&DataCountSection { count });
+	}
+	module.section(&code);
+	module.section(&data);
+	module.section(&names.section());
+
+	let bytes = module.finish();
+	Validator::new_with_features(features()).validate_all(&bytes).map_err(|e| WarpError::Link {
+		message: format!("merged module is invalid: {}", e),
+	})?;
+	Ok(bytes)
+}
+
+/// Names of the merged module; a merged index keeps the first name it is given
+#[derive(Default)]
+struct Names {
+	functions: BTreeMap<u32, String>,
+	locals: BTreeMap<u32, Vec<(u32, String)>>,
+	types: BTreeMap<u32, String>,
+	memories: BTreeMap<u32, String>,
+	globals: BTreeMap<u32, String>,
+	fields: BTreeMap<u32, Vec<(u32, String)>>,
+}
+
+impl Names {
+	/// Add the name section of one input module, renumbered into the merged index spaces
+	fn read(&mut self, reader: NameSectionReader, renumber: &mut Renumber) -> anyhow::Result<()> {
+		for name in reader {
+			match name? {
+				Name::Function(map) => add_names(&mut self.functions, map, |i| renumber.function_index(i))?,
+				Name::Local(map) => add_indirect_names(&mut self.locals, map, |i| renumber.function_index(i))?,
+				Name::Type(map) => add_names(&mut self.types, map, |i| renumber.type_index(i))?,
+				Name::Memory(map) => add_names(&mut self.memories, map, |i| renumber.memory_index(i))?,
+				Name::Global(map) => add_names(&mut self.globals, map, |i| renumber.global_index(i))?,
+				Name::Field(map) => add_indirect_names(&mut self.fields, map, |i| renumber.type_index(i))?,
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	fn section(&self) -> NameSection {
+		let mut section = NameSection::new();
+		section.functions(&name_map(&self.functions));
+		section.locals(&indirect_name_map(&self.locals));
+		section.types(&name_map(&self.types));
+		section.memories(&name_map(&self.memories));
+		section.globals(&name_map(&self.globals));
+		section.fields(&indirect_name_map(&self.fields));
+		section
+	}
+}
+
+fn add_names(
+	names: &mut BTreeMap<u32, String>,
+	map: wasmparser::NameMap,
+	mut index: impl FnMut(u32) -> Result<u32, reencode::Error<Infallible>>,
+) -> anyhow::Result<()> {
+	for naming in map {
+		let naming = naming?;
+		names.entry(index(naming.index)?).or_insert_with(|| naming.name.to_string());
+	}
+	Ok(())
+}
+
+fn add_indirect_names(
+	names: &mut BTreeMap<u32, Vec<(u32, String)>>,
+	map: wasmparser::IndirectNameMap,
+	mut index: impl FnMut(u32) -> Result<u32, reencode::Error<Infallible>>,
+) -> anyhow::Result<()> {
+	for naming in map {
+		let naming = naming?;
+		let inner = naming.names.into_iter().map(|n| n.map(|n| (n.index, n.name.to_string()))).collect::<Result<Vec<_>, _>>()?;
+		names.entry(index(naming.index)?).or_insert(inner);
+	}
+	Ok(())
+}
+
+fn name_map(names: &BTreeMap<u32, String>) -> NameMap {
+	let mut map = NameMap::new();
+	for (index, name) in names {
+		map.append(*index, name);
+	}
+	map
+}
+
+fn indirect_name_map(names: &BTreeMap<u32, Vec<(u32, String)>>) -> IndirectNameMap {
+	let mut map = IndirectNameMap::new();
+	for (index, inner) in names {
+		let mut inner_map = NameMap::new();
+		for (i, name) in inner {
+			inner_map.append(*i, name);
+		}
+		map.append(*index, &inner_map);
+	}
+	map
+}
+
+/// Link wasm files, naming each module after its file stem
+pub fn link_files(paths: &[String]) -> Result<Vec<u8>, WarpError> {
+	let mut contents = Vec::new();
+	for path in paths {
+		let bytes = std::fs::read(path).map_err(|e| WarpError::Link {
+			message: format!("cannot read {}: {}", path, e),
+		})?;
+		let stem = std::path::Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or(path).to_string();
+		contents.push((stem, bytes));
+	}
+	let modules: Vec<(&str, &[u8])> = contents.iter().map(|(name, bytes)| (name.as_str(), bytes.as_slice())).collect();
+	link_modules(&modules)
+}
+
+/// Old → merged index for every input module and index space
+struct IndexMaps {
+	types: Vec<u32>,
+	elements: Vec<u32>,
+	data: Vec<u32>,
+	spaces: Vec<HashMap<Space, Vec<u32>>>,
+	/// Imports left in the merged module: (module, import)
+	kept: Vec<(usize, usize)>,
+}
+
+impl IndexMaps {
+	fn build(inputs: &[Input]) -> Result<IndexMaps, WarpError> {
+		let mut kept: Vec<(usize, usize)> = Vec::new();
+		let mut kept_count: HashMap<Space, u32> = HashMap::new();
+		let mut kept_names: HashMap<(Space, &str, &str), u32> = HashMap::new();
+		let mut targets: Vec<Vec<Target>> = Vec::new();
+		for (m, input) in inputs.iter().enumerate() {
+			let mut module_targets = Vec::new();
+			for (i, import) in input.imports.iter().enumerate() {
+				let Some(space) = Space::of_import(&import.ty) else {
+					return Err(link_error(&input.name, format!("unsupported import {}.{}", import.module, import.name)));
+				};
+				if let Some(target) = provider(inputs, m, import, space) {
+					if let Target::Linked(n, index) = target {
+						let export = inputs[n].entity(space, index);
+						if !export.is_some_and(|export| TypeMatch::new(input, &inputs[n]).extern_type(import.ty, export)) {
+							let message = format!("import {}.{} does not match {}'s export", import.module, import.name, inputs[n].name);
+							return Err(link_error(&input.name, message));
+						}
+					}
+					module_targets.push(target);
+					continue;
+				}
+				let key = (space, import.module, import.name);
+				let index = match kept_names.get(&key) {
+					Some(&index) => index,
+					None => {
+						let count = kept_count.entry(space).or_insert(0);
+						let index = *count;
+						*count += 1;
+						kept_names.insert(key, index);
+						kept.push((m, i));
+						index
+					}
+				};
+				module_targets.push(Target::Kept(index));
+			}
+			targets.push(module_targets);
+		}
+
+		let mut types = Vec::new();
+		let mut elements = Vec::new();
+		let mut data = Vec::new();
+		let (mut type_base, mut element_base, mut data_base) = (0, 0, 0);
+		let mut bases: HashMap<Space, u32> = SPACES.iter().map(|s| (*s, kept_count.get(s).copied().unwrap_or(0))).collect();
+		let mut defined_bases: Vec<HashMap<Space, u32>> = Vec::new();
+		for input in inputs {
+			types.push(type_base);
+			elements.push(element_base);
+			data.push(data_base);
+			type_base += input.types.len() as u32;
+			element_base += input.elements;
+			data_base += input.data;
+			defined_bases.push(bases.clone());
+			for space in SPACES {
+				*bases.get_mut(&space).unwrap() += input.defined(space);
+			}
+		}
+
+		let mut spaces = Vec::new();
+		for (m, input) in inputs.iter().enumerate() {
+			let mut module_spaces = HashMap::new();
+			for space in SPACES {
+				let imported = input.imports_in(space).len() as u32;
+				let map = (0..imported + input.defined(space))
+					.map(|old| resolve(inputs, &targets, &defined_bases, m, space, old, 0))
+					.collect::<Result<Vec<u32>, WarpError>>()?;
+				module_spaces.insert(space, map);
+			}
+			spaces.push(module_spaces);
+		}
+		Ok(IndexMaps { types, elements, data, spaces, kept })
+	}
+
+	fn index(&self, module: usize, space: Space, old: u32) -> u32 {
+		self.spaces[module][&space][old as usize]
+	}
+
+	fn renumber(&self, module: usize) -> Renumber<'_> {
+		Renumber { maps: self, module }
+	}
+}
+
+/// The module that satisfies an import: the one named like the import's module, else any exporter
+fn provider(inputs: &[Input], importer: usize, import: &Import, space: Space) -> Option<Target> {
+	let candidates = inputs.iter().enumerate().filter(|(n, _)| *n != importer);
+	let mut named = candidates.clone().filter(|(_, input)| input.name == import.module);
+	let found = named.find_map(|(n, input)| input.export(import.name, space).map(|index| (n, index)));
+	let found = found.or_else(|| candidates.clone().find_map(|(n, input)| input.export(import.name, space).map(|index| (n, index))));
+	found.map(|(n, index)| Target::Linked(n, index))
+}
+
+/// Structural type comparison between an importing and an exporting module
+/// Types referenced by index are compared recursively; a pair already under comparison is assumed equal
+struct TypeMatch<'m, 'a> {
+	import: &'m Input<'a>,
+	export: &'m Input<'a>,
+	assumed: Vec<(u32, u32)>,
+}
+
+impl<'m, 'a> TypeMatch<'m, 'a> {
+	fn new(import: &'m Input<'a>, export: &'m Input<'a>) -> Self {
+		TypeMatch { import, export, assumed: Vec::new() }
+	}
+
+	fn extern_type(&mut self, import: TypeRef, export: TypeRef) -> bool {
+		match (import, export) {
+			(TypeRef::Func(a), TypeRef::Func(b)) => self.index(a, b),
+			(TypeRef::Table(a), TypeRef::Table(b)) => {
+				a.table64 == b.table64
+					&& a.shared == b.shared
+					&& self.ref_type(a.element_type, 0, b.element_type, 0)
+					&& limits(u64::from(a.initial), a.maximum.map(u64::from), u64::from(b.initial), b.maximum.map(u64::from))
+			}
+			(TypeRef::Memory(a), TypeRef::Memory(b)) => {
+				a.memory64 == b.memory64
+					&& a.shared == b.shared
+					&& a.page_size_log2 == b.page_size_log2
+					&& limits(a.initial, a.maximum, b.initial, b.maximum)
+			}
+			(TypeRef::Global(a), TypeRef::Global(b)) => {
+				a.mutable == b.mutable && a.shared == b.shared && self.val(a.content_type, 0, b.content_type, 0)
+			}
+			(TypeRef::Tag(a), TypeRef::Tag(b)) => self.index(a.func_type_idx, b.func_type_idx),
+			_ => false,
+		}
+	}
+
+	fn index(&mut self, a: u32, b: u32) -> bool {
+		if self.assumed.contains(&(a, b)) {
+			return true;
+		}
+		let (import, export) = (self.import, self.export);
+		let (Some((group_a, a_ty)), Some((group_b, b_ty))) = (import.types.get(a as usize), export.types.get(b as usize)) else {
+			return false;
+		};
+		self.assumed.push((a, b));
+		let supertypes = match (a_ty.supertype_idx, b_ty.supertype_idx) {
+			(None, None) => true,
+			(Some(x), Some(y)) => self.concrete(x.unpack(), *group_a, y.unpack(), *group_b),
+			_ => false,
+		};
+		supertypes
+			&& a_ty.is_final == b_ty.is_final
+			&& a_ty.composite_type.shared == b_ty.composite_type.shared
+			&& self.composite(&a_ty.composite_type.inner, *group_a, &b_ty.composite_type.inner, *group_b)
+	}
+
+	fn composite(&mut self, a: &CompositeInnerType, group_a: u32, b: &CompositeInnerType, group_b: u32) -> bool {
+		match (a, b) {
+			(CompositeInnerType::Func(a), CompositeInnerType::Func(b)) => {
+				self.vals(a.params(), group_a, b.params(), group_b) && self.vals(a.results(), group_a, b.results(), group_b)
+			}
+			(CompositeInnerType::Array(a), CompositeInnerType::Array(b)) => self.field(&a.0, group_a, &b.0, group_b),
+			(CompositeInnerType::Struct(a), CompositeInnerType::Struct(b)) => {
+				a.fields.len() == b.fields.len() && a.fields.iter().zip(b.fields.iter()).all(|(x, y)| self.field(x, group_a, y, group_b))
+			}
+			_ => false,
+		}
+	}
+
+	fn field(&mut self, a: &FieldType, group_a: u32, b: &FieldType, group_b: u32) -> bool {
+		a.mutable == b.mutable
+			&& match (a.element_type, b.element_type) {
+				(StorageType::Val(x), StorageType::Val(y)) => self.val(x, group_a, y, group_b),
+				(x, y) => x == y,
+			}
+	}
+
+	fn vals(&mut self, a: &[ValType], group_a: u32, b: &[ValType], group_b: u32) -> bool {
+		a.len() == b.len() && a.iter().zip(b).all(|(x, y)| self.val(*x, group_a, *y, group_b))
+	}
+
+	fn val(&mut self, a: ValType, group_a: u32, b: ValType, group_b: u32) -> bool {
+		match (a, b) {
+			(ValType::Ref(x), ValType::Ref(y)) => self.ref_type(x, group_a, y, group_b),
+			(x, y) => x == y,
+		}
+	}
+
+	fn ref_type(&mut self, a: RefType, group_a: u32, b: RefType, group_b: u32) -> bool {
+		a.is_nullable() == b.is_nullable()
+			&& match (a.heap_type(), b.heap_type()) {
+				(HeapType::Concrete(x), HeapType::Concrete(y)) => self.concrete(x, group_a, y, group_b),
+				(x, y) => x == y,
+			}
+	}
+
+	/// Compare two type references, each relative to its module or to its rec group
+	fn concrete(&mut self, a: UnpackedIndex, group_a: u32, b: UnpackedIndex, group_b: u32) -> bool {
+		match (type_index(a, group_a), type_index(b, group_b)) {
+			(Some(a), Some(b)) => self.index(a, b),
+			_ => false,
+		}
+	}
+}
+
+fn type_index(index: UnpackedIndex, group: u32) -> Option<u32> {
+	match index {
+		UnpackedIndex::Module(i) => Some(i),
+		UnpackedIndex::RecGroup(i) => Some(group + i),
+		#[allow(unreachable_patterns)]
+		_ => None,
+	}
+}
+
+/// Whether an export's limits fit the limits an import asks for
+fn limits(import_min: u64, import_max: Option<u64>, export_min: u64, export_max: Option<u64>) -> bool {
+	export_min >= import_min
+		&& match (import_max, export_max) {
+			(None, _) => true,
+			(Some(import_max), Some(export_max)) => export_max <= import_max,
+			(Some(_), None) => false,
+		}
+}
+
+/// Merged index of `old` in module `m`, following re-exported imports
+fn resolve(
+	inputs: &[Input],
+	targets: &[Vec<Target>],
+	bases: &[HashMap<Space, u32>],
+	m: usize,
+	space: Space,
+	old: u32,
+	depth: usize,
+) -> Result<u32, WarpError> {
+	if depth > inputs.len() {
+		return Err(link_error(&inputs[m].name, "imports form a cycle"));
+	}
+	let imported = inputs[m].imports_in(space);
+	if let Some(&import) = imported.get(old as usize) {
+		return match targets[m][import] {
+			Target::Kept(index) => Ok(index),
+			Target::Linked(n, index) => resolve(inputs, targets, bases, n, space, index, depth + 1),
+		};
+	}
+	Ok(bases[m][&space] + old - imported.len() as u32)
+}
+
+/// Rewrites every index of one input module into the merged index space
+struct Renumber<'a> {
+	maps: &'a IndexMaps,
+	module: usize,
+}
+
+impl Reencode for Renumber<'_> {
+	type Error = Infallible;
+
+	fn type_index(&mut self, ty: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.types[self.module] + ty)
+	}
+
+	fn function_index(&mut self, func: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.index(self.module, Space::Func, func))
+	}
+
+	fn table_index(&mut self, table: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.index(self.module, Space::Table, table))
+	}
+
+	fn memory_index(&mut self, memory: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.index(self.module, Space::Memory, memory))
+	}
+
+	fn global_index(&mut self, global: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.index(self.module, Space::Global, global))
+	}
+
+	fn tag_index(&mut self, tag: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.index(self.module, Space::Tag, tag))
+	}
+
+	fn element_index(&mut self, element: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.elements[self.module] + element)
+	}
+
+	fn data_index(&mut self, data: u32) -> Result<u32, reencode::Error<Infallible>> {
+		Ok(self.maps.data[self.module] + data)
+	}
+}
+
+fn features() -> WasmFeatures {
+	let mut features = WasmFeatures::default();
+	features.set(WasmFeatures::REFERENCE_TYPES, true);
+	features.set(WasmFeatures::GC, true);
+	features.set(WasmFeatures::MULTI_MEMORY, true);
+	features
+}
+
+fn link_error(module: &str, e: impl std::fmt::Display) -> WarpError {
+	WarpError::Link {
+		message: format!("{}: {}", module, e),
+	}
+}
+
+fn reencode_error(e: reencode::Error<Infallible>) -> WarpError {
+	WarpError::Link { message: e.to_string() }
+}
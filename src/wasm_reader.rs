@@ -4,7 +4,7 @@ use crate::util::gc_engine;
 use anyhow::{anyhow, Result};
 use std::cell::RefCell;
 use std::rc::Rc;
-use wasmtime::{Instance, Linker, Module, Store, Val};
+use wasmtime::{Engine, Instance, Linker, Module, Store, Val};
 use wasmtime_wasi::{WasiCtxBuilder, p1};
 
 /// GcObject wraps a WASM GC struct reference with ergonomic field access
@@ -155,6 +155,56 @@ impl GcObject {
 		self.read_string(ptr, len)
 	}
 
+	/// Enum variants and classes in a hierarchy lead with an i32 tag field; they are the wasm
+	/// subtypes among user structs, declaring a supertype or open to subtypes
+	pub fn is_tagged(&self) -> bool {
+		let store = self.store.borrow();
+		let Some(anyref) = self.inner.unwrap_anyref() else { return false };
+		match anyref.unwrap_struct(&*store).and_then(|s| s.ty(&*store)) {
+			Ok(struct_type) => struct_type.supertype().is_some() || !struct_type.finality().is_final(),
+			Err(_) => false,
+		}
+	}
+
+	/// User struct instances, told apart from $Node by the name section,
+	/// else by not leading with the i64 kind
+	pub fn is_user_struct(&self) -> bool {
+		match self.struct_names() {
+			Ok((Some(name), _)) => name != "Node" && name != "String",
+			_ => matches!(self.get_field(FIELD_KIND), Ok(val) if !matches!(val, Val::I64(_))),
+		}
+	}
+
+	/// Type name and field names of a user struct, from the registered name section
+	pub fn struct_names(&self) -> Result<(Option<String>, Vec<Option<String>>)> {
+		use crate::gc_traits::wasm_name_resolver;
+		let store = self.store.borrow();
+		let anyref = self.inner.unwrap_anyref().ok_or_else(|| anyhow!("not a struct"))?;
+		let struct_type = anyref.unwrap_struct(&*store)?.ty(&*store)?;
+		Ok((wasm_name_resolver::type_name(&struct_type)?, wasm_name_resolver::field_names(&struct_type)?))
+	}
+
+	/// Field idx of a user struct as a Node: numbers, $String text, nested Nodes and structs
+	pub fn field_node(&self, idx: usize) -> Result<Node> {
+		use crate::extensions::numbers::Number;
+		let child = match self.get_field(idx)? {
+			Val::I32(n) => return Ok(Node::Number(Number::Int(n as i64))),
+			Val::I64(n) => return Ok(Node::Number(Number::Int(n))),
+			Val::F32(bits) => return Ok(Node::Number(Number::Float(f32::from_bits(bits) as f64))),
+			Val::F64(bits) => return Ok(Node::Number(Number::Float(f64::from_bits(bits)))),
+			val if val.unwrap_anyref().is_none() => return Ok(Node::Empty),
+			val => GcObject::new(val, self.store.clone(), self.instance),
+		};
+		if let Ok((Some(name), _)) = child.struct_names() {
+			if name == "String" {
+				let ptr = child.get_field(0)?.unwrap_i32();
+				let len = child.get_field(1)?.unwrap_i32();
+				return Ok(Node::Text(child.read_string(ptr, len)?));
+			}
+		}
+		Ok(Node::from_gc_object(&child))
+	}
+
 	/// Get the data field as a child GcObject (for Key nodes where data is a node ref)
 	pub fn data_as_node(&self) -> Result<GcObject> {
 		let val = self.data()?;
@@ -289,25 +339,29 @@ pub fn read_bytes_gc(bytes: &[u8]) -> Result<GcObject> {
 /// Load WASM bytes with host function support and return Node
 /// Use this for modules that import host.fetch or host.run
 pub fn read_bytes_with_host(bytes: &[u8]) -> Result<Node> {
-	use crate::host::{HostState, link_host_functions};
-
 	let engine = gc_engine();
-	let mut store: Store<HostState> = Store::new(&engine, HostState::new());
-
 	let module = Module::new(&engine, bytes)?;
+	call_with_host(&engine, &module, "main", &[])
+}
+
+/// Instantiate a compiled module with host functions and call one of its exports
+pub fn call_with_host(engine: &Engine, module: &Module, name: &str, params: &[Val]) -> Result<Node> {
+	use crate::host::{HostState, link_host_functions};
+
+	let mut store: Store<HostState> = Store::new(engine, HostState::new());
 
 	// Create linker with host functions
-	let mut linker = Linker::new(&engine);
-	link_host_functions(&mut linker, &engine)?;
+	let mut linker = Linker::new(engine);
+	link_host_functions(&mut linker, engine)?;
 
-	let instance = linker.instantiate(&mut store, &module)?;
+	let instance = linker.instantiate(&mut store, module)?;
 
-	let main = instance
-		.get_func(&mut store, "main")
-		.ok_or_else(|| anyhow!("No main function"))?;
+	let func = instance
+		.get_func(&mut store, name)
+		.ok_or_else(|| anyhow!("No {} function", name))?;
 
 	let mut results = vec![Val::I32(0)];
-	main.call(&mut store, &[], &mut results)?;
+	func.call(&mut store, params, &mut results)?;
 
 	// Convert result to Node - handle both primitives and GC refs
 	let result = &results[0];
@@ -428,56 +482,69 @@ impl WasiState {
 /// Load WASM bytes with WASI support (for fd_write, puts, etc.)
 pub fn read_bytes_with_wasi(bytes: &[u8]) -> Result<i64> {
 	let engine = gc_engine();
-	let mut store: Store<WasiState> = Store::new(&engine, WasiState::new());
-
 	let module = Module::new(&engine, bytes)?;
+	Ok(call_with_wasi(&engine, &module, "main", &[])?.unwrap_i64())
+}
+
+/// Instantiate a compiled module with WASI and call one of its exports, main falls back to _start
+pub fn call_with_wasi(engine: &Engine, module: &Module, name: &str, params: &[Val]) -> Result<Val> {
+	let mut store: Store<WasiState> = Store::new(engine, WasiState::new());
 
-	let mut linker: Linker<WasiState> = Linker::new(&engine);
+	let mut linker: Linker<WasiState> = Linker::new(engine);
 	p1::add_to_linker_sync(&mut linker, |state: &mut WasiState| &mut state.ctx)?;
 
-	let instance = linker.instantiate(&mut store, &module)?;
+	let instance = linker.instantiate(&mut store, module)?;
 
-	let main = instance
-		.get_func(&mut store, "main")
-		.or_else(|| instance.get_func(&mut store, "_start"))
-		.ok_or_else(|| anyhow!("No main or _start function"))?;
+	let func = instance
+		.get_func(&mut store, name)
+		.or_else(|| if name == "main" { instance.get_func(&mut store, "_start") } else { None })
+		.ok_or_else(|| anyhow!("No {} function", name))?;
 
 	let mut results = vec![Val::I64(0)];
-	main.call(&mut store, &[], &mut results)?;
+	func.call(&mut store, params, &mut results)?;
 
-	Ok(results[0].unwrap_i64())
+	Ok(results[0])
 }
 
 /// Load WASM bytes with FFI support (for native function imports)
 /// Uses inline reading to handle GC references with FFI state
 pub fn read_bytes_with_ffi(bytes: &[u8]) -> Result<Node> {
+	let engine = gc_engine();
+	let module = Module::new(&engine, bytes)?;
+	call_with_ffi(&engine, &module, "main", &[])
+}
+
+/// Instantiate a compiled module with FFI imports linked and call one of its exports
+pub fn call_with_ffi(engine: &Engine, module: &Module, name: &str, params: &[Val]) -> Result<Node> {
 	use crate::ffi::{link_ffi_functions, link_module_libraries, FfiState};
 	use crate::extensions::numbers::Number;
 	use crate::type_kinds::Kind;
 
-	let engine = gc_engine();
-	let mut store: Store<FfiState> = Store::new(&engine, FfiState::new());
-
-	let module = Module::new(&engine, bytes)?;
+	let mut store: Store<FfiState> = Store::new(engine, FfiState::new());
 
 	// Create linker with FFI functions
-	let mut linker: Linker<FfiState> = Linker::new(&engine);
-	link_ffi_functions(&mut linker, &engine)?;
+	let mut linker: Linker<FfiState> = Linker::new(engine);
+	link_ffi_functions(&mut linker, engine)?;
 
 	// Auto-link dynamic libraries discovered from module imports (raylib, SDL2, etc.)
-	link_module_libraries(&mut linker, &engine, &module)?;
+	link_module_libraries(&mut linker, engine, module)?;
 
-	let instance = linker.instantiate(&mut store, &module)?;
+	let instance = linker.instantiate(&mut store, module)?;
 
-	let main = instance
-		.get_func(&mut store, "main")
-		.ok_or_else(|| anyhow!("No main function"))?;
+	let func = instance
+		.get_func(&mut store, name)
+		.ok_or_else(|| anyhow!("No {} function", name))?;
 
 	let mut results = vec![Val::I32(0)];
-	main.call(&mut store, &[], &mut results)?;
+	func.call(&mut store, params, &mut results)?;
 
 	// Read the GC struct fields directly
 	let result_val = results[0];
+	match result_val {
+		Val::I64(n) => return Ok(Node::Number(Number::Int(n))),
+		Val::F64(bits) => return Ok(Node::Number(Number::Float(f64::from_bits(bits)))),
+		_ => {}
+	}
 	if let Some(anyref) = result_val.unwrap_anyref() {
 		if let Ok(structref) = anyref.unwrap_struct(&store) {
 			// Read kind field (i64)
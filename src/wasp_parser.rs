@@ -1,3 +1,4 @@
+use crate::error::{Diagnostic, Severity};
 use crate::extensions::numbers::Number;
 use crate::extensions::strings::StringExtensions;
 use crate::meta::LineInfo;
@@ -15,19 +16,39 @@ use std::fs::read_to_string;
 pub struct ParserOptions {
 	/// XML mode: treat <tag> as XML tags, not C++ generics
 	pub xml_mode: bool,
+	/// YAML mode: read the input as a YAML stream
+	pub yaml_mode: bool,
+	/// Strict mode: unbalanced brackets, duplicate keys, bad escapes and unterminated strings are errors
+	pub strict: bool,
+	/// Plain strings: no "{expr}" or "$name" interpolation, for JSON and other data read verbatim
+	pub plain_strings: bool,
 	// Future: other format-specific options can be added here
 }
 
 
 impl ParserOptions {
 	pub fn xml() -> Self {
-		ParserOptions { xml_mode: true }
+		ParserOptions { xml_mode: true, ..Default::default() }
+	}
+
+	pub fn yaml() -> Self {
+		ParserOptions { yaml_mode: true, ..Default::default() }
+	}
+
+	pub fn strict() -> Self {
+		ParserOptions { strict: true, ..Default::default() }
+	}
+
+	pub fn plain() -> Self {
+		ParserOptions { plain_strings: true, ..Default::default() }
 	}
 }
 
 /// Read and parse a WASP file
 pub fn parse_file(path: &str) -> Node {
 	match read_to_string(path) {
+		Ok(content) if path.ends_with(".yaml") || path.ends_with(".yml") => parse_yaml(&content),
+		Ok(content) if path.ends_with(".json") => WaspParser::parse_with_options(&content, ParserOptions::plain()),
 		Ok(content) => WaspParser::parse(&content),
 		_ => error(&format!("Failed to read {}", path)),
 	}
@@ -44,6 +65,10 @@ pub fn parse_xml(input: &str) -> Node {
 	WaspParser::parse_with_options(input, ParserOptions::xml())
 }
 
+pub fn parse_yaml(input: &str) -> Node {
+	WaspParser::parse_with_options(input, ParserOptions::yaml())
+}
+
 pub struct WaspParser {
 	input: String,
 	chars: Vec<char>,
@@ -54,6 +79,8 @@ pub struct WaspParser {
 	pub current_line: String,
 	base_indent: usize,
 	options: ParserOptions,
+	/// Problems the generous parser repaired or skipped, errors in strict mode
+	diagnostics: Vec<Diagnostic>,
 }
 
 impl WaspParser {
@@ -74,6 +101,7 @@ impl WaspParser {
 			current_line,
 			base_indent: 0,
 			options,
+			diagnostics: Vec::new(),
 		}
 	}
 
@@ -87,8 +115,65 @@ impl WaspParser {
 	}
 
 	pub fn parse_with_options(input: &str, options: ParserOptions) -> Node {
+		let (node, diagnostics) = Self::parse_with_diagnostics(input, options);
+		if options.strict && !diagnostics.is_empty() {
+			let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+			return error(&messages.join("\n"));
+		}
+		node
+	}
+
+	/// Parse and list every syntax problem; warnings unless options.strict
+	pub fn parse_with_diagnostics(input: &str, options: ParserOptions) -> (Node, Vec<Diagnostic>) {
+		if options.yaml_mode {
+			return match crate::yaml::read_yaml(input, options.strict) {
+				Ok(result) => result,
+				Err(e) => {
+					let (line_nr, column) = e.position().map(|p| (p.line_nr, p.column)).unwrap_or((1, 1));
+					let diagnostic = Diagnostic {
+						severity: Severity::Error,
+						message: e.message().to_string(),
+						span: 0..input.len(),
+						line_nr,
+						column,
+					};
+					(error(&e.to_string()), vec![diagnostic])
+				}
+			};
+		}
 		let mut parser = WaspParser::new_with_options(input.to_string(), options);
-		parser.parse_list_with_separators(None, Bracket::None)
+		let node = parser.parse_list_with_separators(None, Bracket::None);
+		(node, parser.diagnostics)
+	}
+
+	/// Parse in strict mode: the node, or all problems found
+	pub fn parse_strict(input: &str) -> Result<Node, Vec<Diagnostic>> {
+		let (node, diagnostics) = Self::parse_with_diagnostics(input, ParserOptions::strict());
+		if diagnostics.is_empty() { Ok(node) } else { Err(diagnostics) }
+	}
+
+	/// Record a problem spanning chars start..end, found at line_nr:column
+	fn report(&mut self, start: usize, end: usize, line_nr: usize, column: usize, message: String) {
+		let byte = |pos: usize| -> usize { self.chars[..pos.min(self.chars.len())].iter().map(|c| c.len_utf8()).sum() };
+		let span = byte(start)..byte(end);
+		let severity = if self.options.strict { Severity::Error } else { Severity::Warning };
+		self.diagnostics.push(Diagnostic { severity, message, span, line_nr, column });
+	}
+
+	/// Char index of a 1-based line and column
+	fn index_of(&self, line_nr: usize, column: usize) -> usize {
+		let mut line = 1;
+		let mut start = 0;
+		for (i, c) in self.chars.iter().enumerate() {
+			if line >= line_nr {
+				break;
+			}
+			if *c == '\n' {
+				line += 1;
+				start = i + 1;
+			}
+		}
+		(start + column.saturating_sub(1)).min(self.chars.len())
 	}
 
 	fn end_of_input(&self) -> bool {
@@ -385,6 +470,8 @@ impl WaspParser {
 			('+', '+') => return Some((Op::Inc, 2)),
 			('-', '-') => return Some((Op::Dec, 2)),
 			('.', '.') => return Some((Op::Range, 2)),
+			('?', '.') if !c3.is_ascii_digit() => return Some((Op::SafeDot, 2)),
+			('?', '?') => return Some((Op::Coalesce, 2)),
 			('&', '&') => { self.set_hint_pos(); norm::and_operator("&&"); return Some((Op::And, 2)); }
 			('|', '|') => { self.set_hint_pos(); norm::or_operator("||"); return Some((Op::Or, 2)); }
 			_ => {}
@@ -446,6 +533,128 @@ impl WaspParser {
 		}
 	}
 
+	/// `for x in` or `for key, value in` ahead; otherwise `for` is a plain word
+	fn peek_for_loop(&self) -> bool {
+		if !self.matches_keyword("for") {
+			return false;
+		}
+		let is_name = |c: char| c.is_alphanumeric() || c == '_';
+		let mut i = 3;
+		loop {
+			while self.peek_char(i) == ' ' {
+				i += 1;
+			}
+			let start = i;
+			while is_name(self.peek_char(i)) {
+				i += 1;
+			}
+			if i == start {
+				return false;
+			}
+			while self.peek_char(i) == ' ' {
+				i += 1;
+			}
+			match self.peek_char(i) {
+				',' => i += 1,
+				'i' => return self.peek_char(i + 1) == 'n' && !is_name(self.peek_char(i + 2)),
+				_ => return false,
+			}
+		}
+	}
+
+	/// `for x in items {body}`, `for i in 1..10 do body` or `for key, value in map {body}`
+	/// Structure: (for (x in items)) do body, like while; several names become a (key, value) list
+	fn parse_for(&mut self) -> Node {
+		self.advance_by(3); // skip "for"
+		let mut names = Vec::new();
+		loop {
+			self.skip_spaces();
+			let mut name = String::new();
+			while self.current_char().is_alphanumeric() || self.current_char() == '_' {
+				name.push(self.current_char());
+				self.advance();
+			}
+			names.push(Node::Symbol(name));
+			self.skip_spaces();
+			if self.current_char() != ',' {
+				break;
+			}
+			self.advance();
+		}
+		self.advance_by(2); // skip "in"
+		self.skip_spaces();
+		let iterable = self.parse_expr(Op::For.binding_power().1);
+		let vars = if names.len() == 1 { names.remove(0) } else { Node::List(names, Bracket::Round, Separator::Colon) };
+		let for_in = |iterable: Node| {
+			let each = Node::Key(Box::new(vars.clone()), Op::In, Box::new(iterable));
+			Node::Key(Box::new(Empty), Op::For, Box::new(each))
+		};
+		self.skip_spaces();
+		if self.current_char() == '{' {
+			let body = self.parse_atom();
+			return Node::Key(Box::new(for_in(iterable)), Op::Do, Box::new(body));
+		}
+		// for x in items {block} reads items {block} as an application
+		if let Node::List(items, Bracket::None, _) = iterable.drop_meta() {
+			if let [items, body] = items.as_slice() {
+				if matches!(body.drop_meta(), Node::List(_, Bracket::Curly, _)) {
+					return Node::Key(Box::new(for_in(items.clone())), Op::Do, Box::new(body.clone()));
+				}
+			}
+		}
+		// for x in items do body: the caller binds do
+		for_in(iterable)
+	}
+
+	/// `match subject {` ahead; `match = 1` or `match(x)` keep match as a plain word
+	fn peek_match(&self) -> bool {
+		if !self.matches_keyword("match") || self.peek_char(5) != ' ' {
+			return false;
+		}
+		let mut i = 5;
+		while self.peek_char(i) == ' ' {
+			i += 1;
+		}
+		let c = self.peek_char(i);
+		c.is_alphanumeric() || matches!(c, '_' | '(' | '[' | '"' | '\'' | '-')
+	}
+
+	/// `match subject { pattern => value ... }`, arms may carry a guard: `x if x > 0 => x`
+	/// Structure: subject match {arms}, each arm a FatArrow key whose pattern may be (pattern if guard)
+	fn parse_match(&mut self) -> Node {
+		self.advance_by(5); // skip "match"
+		self.skip_spaces();
+		let subject = self.parse_expr(Op::Match.binding_power().1);
+		self.skip_spaces();
+		let (subject, arms) = if self.current_char() == '{' {
+			(subject, self.parse_atom())
+		} else {
+			match subject.drop_meta() {
+				// match c {arms} reads c {arms} as an application
+				Node::List(items, Bracket::None, _) if items.len() == 2 && is_curly_block(&items[1]) => {
+					(items[0].clone(), items[1].clone())
+				}
+				// match next() {arms} reads like the definition next() {body}
+				Node::List(items, Bracket::Round, Separator::None) if items.len() == 2 && is_curly_block(&items[1]) => {
+					match items[0].drop_meta() {
+						Node::List(signature, Bracket::Round, Separator::None) if signature.len() == 2 => {
+							let mut call = vec![signature[0].clone()];
+							match signature[1].drop_meta() {
+								Node::List(args, _, _) => call.extend(args.iter().cloned()),
+								Node::Empty => {}
+								arg => call.push(arg.clone()),
+							}
+							(Node::List(call, Bracket::Round, Separator::None), items[1].clone())
+						}
+						_ => return error("match expects a block of arms: match x { pattern => value }"),
+					}
+				}
+				_ => return error("match expects a block of arms: match x { pattern => value }"),
+			}
+		};
+		Node::Key(Box::new(subject), Op::Match, Box::new(match_arms(arms)))
+	}
+
 	/// Peek for suffix operators (unary operators that bind to left operand)
 	fn peek_suffix_operator(&self) -> Option<(Op, usize)> {
 		match (self.current_char(), self.peek_char(1)) {
@@ -453,10 +662,17 @@ impl WaspParser {
 			('-', '-') => Some((Op::Dec, 2)),
 			('²', _) => Some((Op::Square, 1)),
 			('³', _) => Some((Op::Cube, 1)),
+			('?', next) if self.is_optional_mark(next) => Some((Op::Question, 1)),
 			_ => None,
 		}
 	}
 
+	/// `?` right after a name and before a space or the end of the item marks an optional type: left: Tree?
+	fn is_optional_mark(&self, next: char) -> bool {
+		let after_name = self.pos > 0 && matches!(self.chars.get(self.pos - 1), Some(c) if c.is_alphanumeric() || *c == '_');
+		after_name && (next.is_whitespace() || matches!(next, '\0' | ',' | ';' | ')' | ']' | '}'))
+	}
+
 	/// Parse an atomic expression (no infix operators)
 	/// Handles: numbers, strings, brackets, symbols with named blocks
 	fn parse_atom(&mut self) -> Node {
@@ -552,7 +768,7 @@ impl WaspParser {
 			return Node::Key(Box::new(Symbol("global".to_string())), Op::Colon, Box::new(decl));
 		}
 
-		// Handle "class"/"struct"/"type" keyword: class Name { fields }
+		// Handle "class"/"struct"/"type" keyword: class Name { fields }, class Name: Supertype { fields }
 		// But NOT type(x) which is a function call for type introspection
 		if symbol == "class" || symbol == "struct" || (symbol == "type" && self.current_char() != '(') {
 			self.skip_whitespace();
@@ -560,6 +776,25 @@ impl WaspParser {
 				Ok(s) => s,
 				Err(e) => return error(&e),
 			};
+			// class Pair<A, B> { … } takes the type parameters A and B
+			let type_name = if self.current_char() == '<' {
+				let type_params = self.parse_type_args();
+				Node::Key(Box::new(Symbol(type_name)), Op::Colon, Box::new(type_params))
+			} else {
+				Symbol(type_name)
+			};
+			self.skip_whitespace();
+			// class Employee: Person { … } extends Person
+			let name = if self.current_char() == ':' {
+				self.advance();
+				self.skip_whitespace();
+				match self.parse_symbol() {
+					Ok(supertype) => Node::Key(Box::new(type_name), Op::Colon, Box::new(Symbol(supertype))),
+					Err(e) => return error(&e),
+				}
+			} else {
+				type_name
+			};
 			self.skip_whitespace();
 			let body = if self.current_char() == '{' {
 				let block = self.parse_bracketed('{');
@@ -569,11 +804,28 @@ impl WaspParser {
 				Empty
 			};
 			return Node::Type {
-				name: Box::new(Symbol(type_name)),
+				name: Box::new(name),
 				body: Box::new(body),
 			};
 		}
 
+		// Handle "enum" keyword: enum Name { Variant(field: Type) Other }
+		if symbol == "enum" && self.current_char() == ' ' {
+			self.skip_whitespace();
+			let type_name = match self.parse_symbol() {
+				Ok(s) => s,
+				Err(e) => return error(&e),
+			};
+			self.skip_whitespace();
+			if self.current_char() != '{' {
+				return error("enum expects a block of variants: enum Shape { Circle(r: float) Dot }");
+			}
+			return Node::Type {
+				name: Box::new(Symbol(type_name)),
+				body: Box::new(self.parse_variants()),
+			};
+		}
+
 		// Check for IMMEDIATE suffix blocks (no space allowed)
 		// This distinguishes List<int> (generic) from x < y (comparison)
 		let ch = self.current_char();
@@ -585,38 +837,72 @@ impl WaspParser {
 			'<' if !self.options.xml_mode && !self.peek_char(1).is_numeric() => {
 				// Only treat as generic if immediately after symbol (no space)
 				// and NOT followed by a number (that would be comparison: i<9)
-				let generic = self.parse_bracketed('<');
-				Node::Key(Box::new(Symbol(symbol)), Op::Colon, Box::new(generic))
-			}
-			'(' => {
-				// Parse arguments as a proper Node
-				let args_node = self.parse_bracketed('(');
-				self.skip_spaces(); // Only spaces, preserve newlines as statement separators
-
-				if self.current_char() == '{' {
-					// Function with body: name(params) { body }
-					let body = self.parse_bracketed('{');
-					let signature = Node::List(
-						vec![Symbol(symbol), args_node],
-						Bracket::Round,
-						Separator::None,
-					);
-					Node::List(vec![signature, body], Bracket::Round, Separator::None)
+				let generic = self.parse_type_args();
+				let generic = Node::Key(Box::new(Symbol(symbol)), Op::Colon, Box::new(generic));
+				// generic function: first<T>(xs: List<T>)
+				if self.current_char() == '(' {
+					self.parse_call(generic)
 				} else {
-					// Function call: name(params) -> List([symbol, args...])
-					let mut items = vec![Symbol(symbol)];
-					match args_node {
-						Node::List(args, _, _) => items.extend(args),
-						Node::Empty => {}
-						other => items.push(other),
-					}
-					Node::List(items, Bracket::Round, Separator::None)
+					generic
 				}
 			}
+			'(' => self.parse_call(Symbol(symbol)),
 			_ => Node::symbol(&symbol),
 		}
 	}
 
+	/// Parse the arguments after name: name(params) { body } or the call name(args)
+	fn parse_call(&mut self, name: Node) -> Node {
+		// Parse arguments as a proper Node
+		let args_node = self.parse_bracketed('(');
+		self.skip_spaces(); // Only spaces, preserve newlines as statement separators
+
+		if self.current_char() == '{' {
+			// Function with body: name(params) { body }
+			let body = self.parse_bracketed('{');
+			let signature = Node::List(vec![name, args_node], Bracket::Round, Separator::None);
+			Node::List(vec![signature, body], Bracket::Round, Separator::None)
+		} else {
+			// Function call: name(params) -> List([symbol, args...])
+			let mut items = vec![name];
+			match args_node {
+				Node::List(args, _, _) => items.extend(args),
+				Node::Empty => {}
+				other => items.push(other),
+			}
+			Node::List(items, Bracket::Round, Separator::None)
+		}
+	}
+
+	/// Parse type arguments <A, B> as a Less list of names, nested ones like List<Pair<A, B>> as keys
+	fn parse_type_args(&mut self) -> Node {
+		let (start, line_nr, column) = (self.pos, self.line_nr, self.column);
+		self.advance(); // skip '<'
+		let mut args = Vec::new();
+		loop {
+			self.skip_spaces();
+			match self.current_char() {
+				'>' => {
+					self.advance();
+					break;
+				}
+				',' => self.advance(),
+				_ => match self.parse_symbol() {
+					Ok(name) if self.current_char() == '<' => {
+						let inner = self.parse_type_args();
+						args.push(Node::Key(Box::new(Symbol(name)), Op::Colon, Box::new(inner)));
+					}
+					Ok(name) => args.push(Symbol(name)),
+					Err(_) => {
+						self.report(start, start + 1, line_nr, column, "Unclosed '<', expected '>'".to_string());
+						break;
+					}
+				},
+			}
+		}
+		Node::List(args, Bracket::Less, Separator::Colon)
+	}
+
 	/// Helper to advance by N characters
 	fn advance_by(&mut self, n: usize) {
 		for _ in 0..n {
@@ -630,7 +916,11 @@ impl WaspParser {
 		self.skip_spaces();
 
 		// Step 1: Handle prefix operators
-		let mut lhs = if let Some((op, chars)) = self.peek_prefix_operator() {
+		let mut lhs = if self.peek_for_loop() {
+			self.parse_for()
+		} else if self.peek_match() {
+			self.parse_match()
+		} else if let Some((op, chars)) = self.peek_prefix_operator() {
 			// Check if this is really a prefix (not infix like x - y)
 			// Prefix operators should be at start or after another operator
 			self.advance_by(chars);
@@ -878,14 +1168,40 @@ impl WaspParser {
 		let quote = self.current_char();
 		// let is_double_quote = quote == '"';
 		let is_single_quote = quote == '\'';
+		let (start, line_nr, column) = (self.pos, self.line_nr, self.column);
 		self.advance(); // skip opening quote
 
+		// "Hello {name}" and "Hello $name" become "Hello " + name
+		let interpolate = quote == '"' && !self.options.xml_mode && !self.options.plain_strings;
+		let mut parts: Vec<Node> = Vec::new();
 		let mut s = String::new();
 		loop {
 			let ch = self.current_char();
 			if ch == '\0' {
+				self.report(start, self.chars.len(), line_nr, column, format!("Unterminated string starting with {}", quote));
 				return error("Unterminated string");
 			}
+			if interpolate && (ch == '{' || ch == '$') {
+				if let Some(part) = self.parse_interpolation() {
+					if !s.is_empty() {
+						parts.push(Node::text(&s));
+						s.clear();
+					}
+					parts.push(part);
+					continue;
+				}
+			}
+			if ch == quote && !parts.is_empty() {
+				self.advance(); // skip closing quote
+				if !s.is_empty() {
+					parts.push(Node::text(&s));
+				}
+				let mut parts = parts.into_iter();
+				let first = parts.next().unwrap_or(Empty);
+				let first = if matches!(first, Node::Text(_)) { first } else { Node::Key(Box::new(Node::text("")), Op::Add, Box::new(first)) };
+				let joined = parts.fold(first, |joined, part| Node::Key(Box::new(joined), Op::Add, Box::new(part)));
+				return joined.with_meta_data(Interpolation);
+			}
 			if ch == quote {
 				self.advance(); // skip closing quote
 				// Hint for double quotes (only for multi-char strings)
@@ -903,12 +1219,29 @@ impl WaspParser {
 				return Node::text(&s);
 			}
 			if ch == '\\' {
+				let (escape, line_nr, column) = (self.pos, self.line_nr, self.column);
 				self.advance();
 				match self.current_char() {
 					'n' => s.push('\n'),
 					't' => s.push('\t'),
 					'r' => s.push('\r'),
-					c => s.push(c),
+					'b' => s.push('\u{8}'),
+					'f' => s.push('\u{c}'),
+					'0' => s.push('\0'),
+					'u' => match self.parse_unicode_escape() {
+						Some(c) => s.push(c),
+						None => {
+							self.report(escape, self.pos + 1, line_nr, column, "Invalid unicode escape".to_string());
+							s.push('u');
+						}
+					},
+					'\0' => continue, // unterminated, reported above
+					c @ ('\\' | '"' | '\'' | '/' | '{' | '}' | '$') => s.push(c),
+					c if c == quote => s.push(c),
+					c => {
+						self.report(escape, self.pos + 1, line_nr, column, format!("Invalid escape \\{}", c));
+						s.push(c)
+					}
 				}
 				self.advance();
 			} else {
@@ -918,6 +1251,83 @@ impl WaspParser {
 		}
 	}
 
+	/// `{expr}` or `$name` inside a double-quoted string, parser on the `{` or `$`
+	/// Unbalanced or empty braces and `$` without a name stay literal: "costs $5", "{}"
+	fn parse_interpolation(&mut self) -> Option<Node> {
+		let start = self.pos;
+		let end = if self.current_char() == '$' {
+			let first = *self.chars.get(start + 1)?;
+			if !(first.is_alphabetic() || first == '_') {
+				return None;
+			}
+			let mut end = start + 1;
+			while end < self.chars.len() && (self.chars[end].is_alphanumeric() || self.chars[end] == '_') {
+				end += 1;
+			}
+			// a lone "$ref" or "$schema" is a JSON key, not a template
+			if self.prev_char() == '"' && self.chars.get(end) == Some(&'"') {
+				return None;
+			}
+			end
+		} else {
+			let mut depth = 0;
+			let mut end = start;
+			let mut nested_quote = None;
+			loop {
+				let c = *self.chars.get(end)?;
+				match nested_quote {
+					Some(_) if c == '\\' => end += 1,
+					Some(q) if c == q => nested_quote = None,
+					Some(_) => {}
+					// the closing quote ends the string: "a {" + b + "}" stays literal
+					None if c == '"' || c == '\n' => return None,
+					None if c == '\'' => nested_quote = Some(c),
+					None if c == '{' => depth += 1,
+					None if c == '}' => depth -= 1,
+					None => {}
+				}
+				end += 1;
+				if depth == 0 {
+					break;
+				}
+			}
+			end
+		};
+		let source: String = if self.chars[start] == '$' {
+			self.chars[start + 1..end].iter().collect()
+		} else {
+			self.chars[start + 1..end - 1].iter().collect()
+		};
+		if source.trim().is_empty() {
+			return None;
+		}
+		let (part, _) = Self::parse_with_diagnostics(&source, self.options);
+		while self.pos < end {
+			self.advance();
+		}
+		Some(part.drop_meta().clone())
+	}
+
+	/// \\u0041 or \\u{1F600}; on success the parser is left on the last char of the escape
+	fn parse_unicode_escape(&mut self) -> Option<char> {
+		let braced = self.peek_char(1) == '{';
+		let start = if braced { self.pos + 2 } else { self.pos + 1 };
+		let mut end = start;
+		while end < self.chars.len() && self.chars[end].is_ascii_hexdigit() && (braced || end - start < 4) {
+			end += 1;
+		}
+		if (braced && self.chars.get(end) != Some(&'}')) || (!braced && end - start != 4) || end == start {
+			return None;
+		}
+		let hex: String = self.chars[start..end].iter().collect();
+		let c = char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?;
+		let last = if braced { end } else { end - 1 };
+		while self.pos < last {
+			self.advance();
+		}
+		Some(c)
+	}
+
 	fn parse_number(&mut self) -> Node {
 		let mut num_str = String::new();
 		let mut has_dot = false;
@@ -1009,8 +1419,13 @@ impl WaspParser {
 			'<' => ('>', Bracket::Round),
 			_ => panic!("Invalid bracket: {}", open),
 		};
+		let (start, line_nr, column) = (self.pos, self.line_nr, self.column);
 		self.advance(); // skip opening bracket
-		self.parse_list_with_separators(Some(close), bracket_type)
+		let list = self.parse_list_with_separators(Some(close), bracket_type);
+		if self.chars.get(self.pos - 1) != Some(&close) {
+			self.report(start, start + 1, line_nr, column, format!("Unclosed '{}', expected '{}'", open, close));
+		}
+		list
 	}
 
 	/// Parse XML tag: <tag attr="value">content</tag> or <tag />
@@ -1218,6 +1633,14 @@ impl WaspParser {
 
 			if item == Empty {
 				if self.pos == pos_before {
+					let stray = self.current_char();
+					if matches!(stray, ')' | ']' | '}') {
+						let message = match close {
+							Some(c) => format!("Unexpected '{}', expected '{}'", stray, c),
+							None => format!("Unexpected '{}' without opening bracket", stray),
+						};
+						self.report(self.pos, self.pos + 1, self.line_nr, self.column, message);
+					}
 					self.advance();
 				}
 				continue;
@@ -1270,9 +1693,35 @@ impl WaspParser {
 			}
 		}
 
+		if bracket == Bracket::Curly {
+			self.check_duplicate_keys(&items_with_seps);
+		}
+		resolve_interpolations(&mut items_with_seps);
 		// Use recursive grouping
 		self.group_by_separators(items_with_seps, bracket)
 	}
+
+	/// Duplicate keys in comma separated objects like {a: 1, a: 2} or {a=1, a=2}
+	/// Space separated keys may repeat, as in markup: {li:"one" li:"two"}
+	fn check_duplicate_keys(&mut self, items_with_seps: &[(Node, Separator)]) {
+		if !items_with_seps.iter().any(|(_, sep)| *sep == Separator::Colon) {
+			return;
+		}
+		let mut seen: Vec<String> = Vec::new();
+		for (item, _) in items_with_seps {
+			let Node::Key(key, Op::Colon | Op::Assign, _) = item.drop_meta() else { continue };
+			let (Node::Symbol(name) | Node::Text(name)) = key.drop_meta() else { continue };
+			if !seen.contains(name) {
+				seen.push(name.clone());
+				continue;
+			}
+			let Some(info) = item.get_lineinfo() else { continue };
+			let start = self.index_of(info.line_nr, info.column);
+			let quotes = if matches!(key.drop_meta(), Node::Text(_)) { 2 } else { 0 };
+			let end = start + name.chars().count() + quotes;
+			self.report(start, end, info.line_nr, info.column, format!("Duplicate key '{}'", name));
+		}
+	}
 	fn group_by_separators(
 		&self,
 		items_with_seps: Vec<(Node, Separator)>,
@@ -1369,17 +1818,57 @@ impl WaspParser {
 		}
 	}
 
-	/// Transform field definitions: Key(name, op, Symbol) -> Key(name, op, Type)
+	/// Variants of an enum, separated by spaces, commas, newlines or |
+	/// Each becomes a Type whose body lists its fields: Circle(r: float) -> Type(Circle, (r:float))
+	fn parse_variants(&mut self) -> Node {
+		self.advance(); // skip '{'
+		let mut variants = Vec::new();
+		loop {
+			self.skip_whitespace_and_comments();
+			match self.current_char() {
+				',' | '|' | ';' => {
+					self.advance();
+					continue;
+				}
+				'}' => {
+					self.advance();
+					break;
+				}
+				'\0' => return error("unclosed enum, missing }"),
+				_ => {}
+			}
+			let name = match self.parse_symbol() {
+				Ok(s) => s,
+				Err(_) => return error(&format!("Unexpected character '{}' in enum", self.current_char())),
+			};
+			let fields = match self.current_char() {
+				'(' | '{' => match Self::transform_fields_to_types(self.parse_bracketed(self.current_char())) {
+					Empty => Empty,
+					fields if matches!(fields.drop_meta(), Node::List(..)) => fields,
+					field => Node::List(vec![field], Bracket::Round, Separator::None),
+				},
+				_ => Empty,
+			};
+			variants.push(Node::Type {
+				name: Box::new(Symbol(name)),
+				body: Box::new(fields),
+			});
+		}
+		Node::List(variants, Bracket::Curly, Separator::None)
+	}
+
+	/// Transform field definitions: Key(name, :, Symbol) -> Key(name, :, Type)
 	/// Used for class/struct definitions to convert type names to Type nodes
+	/// Methods declared next to the fields keep their bodies as they are
 	fn transform_fields_to_types(node: Node) -> Node {
 		match node {
-			Node::List(items, bracket, sep) => {
+			Node::List(items, bracket, sep) if !is_definition(&items) => {
 				let transformed: Vec<Node> = items.into_iter().map(Self::transform_fields_to_types).collect();
 				Node::List(transformed, bracket, sep)
 			}
-			Node::Key(name, op, value) => {
+			Node::Key(name, Op::Colon, value) => {
 				let type_node = Self::symbol_to_type(*value);
-				Node::Key(name, op, Box::new(type_node))
+				Node::Key(name, Op::Colon, Box::new(type_node))
 			}
 			Node::Meta { node, data } => {
 				Node::Meta { node: Box::new(Self::transform_fields_to_types(*node)), data }
@@ -1388,13 +1877,17 @@ impl WaspParser {
 		}
 	}
 
-	/// Convert a Symbol to a Type node (for type references)
+	/// Convert a Symbol to a Type node (for type references), Tree? to the optional type named Tree?
 	fn symbol_to_type(node: Node) -> Node {
 		match node {
 			Node::Symbol(s) => Node::Type {
 				name: Box::new(Node::Symbol(s)),
 				body: Box::new(Empty),
 			},
+			Node::Key(name, Op::Question, empty) if matches!(empty.drop_meta(), Empty) => match name.drop_meta() {
+				Node::Symbol(s) => Self::symbol_to_type(Node::Symbol(format!("{}?", s))),
+				_ => Node::Key(name, Op::Question, empty),
+			},
 			Node::Meta { node, data } => {
 				Node::Meta { node: Box::new(Self::symbol_to_type(*node)), data }
 			}
@@ -1403,6 +1896,114 @@ impl WaspParser {
 	}
 }
 
+/// Items of a function definition: def area(): w * h
+fn is_definition(items: &[Node]) -> bool {
+	matches!(items.first().map(Node::drop_meta), Some(Symbol(s)) if crate::operators::is_function_keyword(s))
+}
+
+fn is_curly_block(node: &Node) -> bool {
+	matches!(node.drop_meta(), Node::List(_, Bracket::Curly, _))
+}
+
+/// The arms of a match block as one flat list, whichever separators sat between them
+fn match_arms(block: Node) -> Node {
+	fn flatten(node: &Node, arms: &mut Vec<Node>) {
+		match node.drop_meta() {
+			Node::List(items, Bracket::None, _) => items.iter().for_each(|item| flatten(item, arms)),
+			_ => arms.push(node.clone()),
+		}
+	}
+	let mut arms = Vec::new();
+	match block.drop_meta() {
+		Node::List(items, Bracket::Curly, _) => items.iter().for_each(|item| flatten(item, &mut arms)),
+		Node::Empty => {}
+		other => arms.push(other.clone()),
+	}
+	Node::List(arms, Bracket::Curly, Separator::Newline)
+}
+
+/// Marks the concatenation an interpolated string literal parsed to, unlike an explicit `+`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interpolation;
+
+pub fn is_interpolation(node: &Node) -> bool {
+	let mut current = node;
+	while let Node::Meta { node: inner, data } = current {
+		if matches!(data.as_ref(), Node::Data(dada) if dada.downcast_ref::<Interpolation>().is_some()) {
+			return true;
+		}
+		current = inner;
+	}
+	false
+}
+
+/// Data mode: {name: "Bob", greeting: "Hi {name}"} reads "Hi Bob" from sibling keys
+/// Only lists of `key: value` pairs and only the placeholders of string literals are resolved;
+/// code and explicit `+` are left for the runtime, as are placeholders with anything but literal siblings
+fn resolve_interpolations(items_with_seps: &mut [(Node, Separator)]) {
+	if !items_with_seps.iter().all(|(item, _)| matches!(item.drop_meta(), Node::Key(_, Op::Colon, _))) {
+		return;
+	}
+	let mut values: Vec<(String, String)> = Vec::new();
+	for (item, _) in items_with_seps.iter() {
+		if let Node::Key(key, Op::Colon, value) = item.drop_meta() {
+			if let (Symbol(name) | Node::Text(name), Some(text)) = (key.drop_meta(), literal_text(value)) {
+				values.push((name.clone(), text));
+			}
+		}
+	}
+	if values.is_empty() {
+		return;
+	}
+	for (item, _) in items_with_seps.iter_mut() {
+		if let Some(resolved) = resolve_interpolation(item, &mut values) {
+			*item = resolved;
+		}
+	}
+}
+
+fn resolve_interpolation(item: &Node, values: &mut Vec<(String, String)>) -> Option<Node> {
+	match item {
+		Node::Meta { node, data } => {
+			let node = resolve_interpolation(node, values)?;
+			Some(Node::Meta { node: Box::new(node), data: data.clone() })
+		}
+		Node::Key(key, Op::Colon, value) if is_interpolation(value) => {
+			let mut parts = Vec::new();
+			let mut joined = value.drop_meta();
+			while let Node::Key(left, Op::Add, right) = joined {
+				parts.push(right.drop_meta());
+				joined = left.drop_meta();
+			}
+			let Node::Text(first) = joined else { return None };
+			if parts.is_empty() {
+				return None;
+			}
+			let mut text = first.clone();
+			for part in parts.iter().rev() {
+				match part {
+					Symbol(name) => text += &values.iter().rev().find(|(key, _)| key == name)?.1,
+					part => text += &literal_text(part)?,
+				}
+			}
+			if let Symbol(name) | Node::Text(name) = key.drop_meta() {
+				values.push((name.clone(), text.clone()));
+			}
+			Some(Node::Key(key.clone(), Op::Colon, Box::new(Node::text(&text))))
+		}
+		_ => None,
+	}
+}
+
+fn literal_text(node: &Node) -> Option<String> {
+	match node.drop_meta() {
+		Node::Text(_) | Node::Number(_) | Node::Char(_) => Some(node.drop_meta().to_string()),
+		Node::True => Some("true".to_string()),
+		Node::False => Some("false".to_string()),
+		_ => None,
+	}
+}
+
 fn check_constants(s: &str) -> Option<Node> {
 	match s.to_lowercase().as_str() {
 		"⊤" | "true" | "yes" | "✓" | "🗸" | "✔" | "✓️" | "🗹" | "☑" | "✅" | "⊨" => Some(Node::True),
@@ -0,0 +1,948 @@
+//! YAML reading and writing for Node
+//!
+//! Mappings become Curly lists of `key: value` Colon keys and sequences become Square lists,
+//! the same shapes JSON and wasp data parse to. Plain keys are Symbols, quoted keys Text.
+//! An anchored node and its aliases carry the same `Anchor`, so they stay one shared node:
+//! writing puts `&name` on its first occurrence and `*name` on the others. `<<: *base` merges its entries.
+//! A stream with several documents reads as a Bracket::None list with one item per document.
+//! Duplicate keys in a block mapping are reported like the wasp parser's; the later value wins.
+
+use crate::error::{Diagnostic, Severity, WarpError};
+use crate::extensions::numbers::Number;
+use crate::meta::LineInfo;
+use crate::node::{Bracket, Node, Separator};
+use crate::operators::Op;
+use std::collections::{HashMap, HashSet};
+
+/// Name of the `&anchor` a node was read with, kept as its metadata
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anchor(pub String);
+
+/// The anchor shared by a node and its aliases, if any
+pub fn anchor_of(node: &Node) -> Option<&str> {
+	let mut current = node;
+	while let Node::Meta { node: inner, data } = current {
+		if let Node::Data(dada) = data.as_ref() {
+			if let Some(Anchor(name)) = dada.downcast_ref::<Anchor>() {
+				return Some(name);
+			}
+		}
+		current = inner;
+	}
+	None
+}
+
+/// Parse a YAML stream
+pub fn parse_yaml(input: &str) -> Result<Node, WarpError> {
+	read_yaml(input, false).map(|(node, _)| node)
+}
+
+/// Parse a YAML stream and list the duplicate keys found; errors instead of warnings if strict
+pub fn read_yaml(input: &str, strict: bool) -> Result<(Node, Vec<Diagnostic>), WarpError> {
+	let line_starts: Vec<usize> =
+		std::iter::once(0).chain(input.match_indices('\n').map(|(i, _)| i + 1)).collect();
+	let mut documents = Vec::new();
+	let mut diagnostics = Vec::new();
+	for lines in split_documents(input) {
+		let mut parser = YamlParser { lines, pos: 0, anchors: HashMap::new(), line_starts: &line_starts, strict, diagnostics: Vec::new() };
+		documents.push(parser.document()?);
+		diagnostics.append(&mut parser.diagnostics);
+	}
+	let node = match documents.len() {
+		0 => Node::Empty,
+		1 => documents.remove(0),
+		_ => Node::List(documents, Bracket::None, Separator::Newline),
+	};
+	Ok((node, diagnostics))
+}
+
+/// Write a node as block style YAML
+pub fn to_yaml(node: &Node) -> String {
+	let mut anchors = HashSet::new();
+	match node.drop_meta() {
+		Node::List(documents, Bracket::None, Separator::Newline) if !is_mapping(node) && documents.len() > 1 => {
+			documents.iter().map(|document| format!("---\n{}", block(document, 0, &mut anchors))).collect()
+		}
+		_ => block(node, 0, &mut anchors),
+	}
+}
+
+/// One source line: indentation in spaces and the raw rest, comments included
+#[derive(Clone, Debug)]
+struct Line {
+	number: usize,
+	indent: usize,
+	text: String,
+}
+
+impl Line {
+	fn new(number: usize, raw: &str) -> Line {
+		let text = raw.trim_start_matches(' ');
+		Line { number, indent: raw.len() - text.len(), text: text.to_string() }
+	}
+
+	/// The line without its comment
+	fn content(&self) -> &str {
+		strip_comment(&self.text)
+	}
+}
+
+/// Split a stream at `---` and `...` markers, dropping directives
+fn split_documents(input: &str) -> Vec<Vec<Line>> {
+	let mut documents = Vec::new();
+	let mut current: Vec<Line> = Vec::new();
+	let mut explicit = false;
+	for (i, raw) in input.lines().enumerate() {
+		let raw = raw.trim_end_matches('\r');
+		if raw.starts_with('%') && current.is_empty() {
+			continue;
+		}
+		if raw == "---" || raw.starts_with("--- ") || raw.starts_with("---\t") {
+			if explicit || has_content(&current) {
+				documents.push(std::mem::take(&mut current));
+			}
+			current.clear();
+			explicit = true;
+			let rest = raw[3..].trim();
+			if !rest.is_empty() {
+				current.push(Line::new(i + 1, rest));
+			}
+			continue;
+		}
+		if raw == "..." || raw.starts_with("... ") {
+			if explicit || has_content(&current) {
+				documents.push(std::mem::take(&mut current));
+			}
+			explicit = false;
+			continue;
+		}
+		current.push(Line::new(i + 1, raw));
+	}
+	if explicit || has_content(&current) {
+		documents.push(current);
+	}
+	documents
+}
+
+fn has_content(lines: &[Line]) -> bool {
+	lines.iter().any(|line| !line.content().is_empty())
+}
+
+struct YamlParser<'a> {
+	lines: Vec<Line>,
+	pos: usize,
+	anchors: HashMap<String, Node>,
+	/// Byte offset of each line of the whole stream, for diagnostic spans
+	line_starts: &'a [usize],
+	strict: bool,
+	diagnostics: Vec<Diagnostic>,
+}
+
+impl YamlParser<'_> {
+	fn document(&mut self) -> Result<Node, WarpError> {
+		let node = self.block(0)?;
+		self.skip_blank();
+		if let Some(line) = self.lines.get(self.pos) {
+			return Err(error(line, "unexpected content after the document"));
+		}
+		Ok(node)
+	}
+
+	/// Skip empty and comment-only lines
+	fn skip_blank(&mut self) {
+		while self.pos < self.lines.len() && self.lines[self.pos].content().is_empty() {
+			self.pos += 1;
+		}
+	}
+
+	/// The next meaningful line, if it is indented at least min_indent
+	fn next_line(&mut self, min_indent: usize) -> Option<Line> {
+		self.skip_blank();
+		self.lines.get(self.pos).filter(|line| line.indent >= min_indent).cloned()
+	}
+
+	/// A sequence, mapping or scalar starting at the next line
+	fn block(&mut self, min_indent: usize) -> Result<Node, WarpError> {
+		let Some(line) = self.next_line(min_indent) else {
+			return Ok(Node::Empty);
+		};
+		let content = line.content();
+		if is_sequence_entry(content) {
+			return self.sequence(line.indent);
+		}
+		if mapping_colon(content).is_some() {
+			return self.mapping(line.indent);
+		}
+		self.pos += 1;
+		self.value(&line, content, line.indent, false)
+	}
+
+	fn sequence(&mut self, indent: usize) -> Result<Node, WarpError> {
+		let mut items = Vec::new();
+		while let Some(line) = self.next_line(indent) {
+			let content = line.content();
+			if line.indent > indent {
+				return Err(error(&line, "unexpected indentation in sequence"));
+			}
+			if !is_sequence_entry(content) {
+				break;
+			}
+			let rest = content[1..].trim_start();
+			let offset = content.len() - rest.len();
+			if is_sequence_entry(rest) || mapping_colon(rest).is_some() {
+				// `- key: value` and `- - item` open a nested block right after the dash
+				self.lines[self.pos] = Line {
+					number: line.number,
+					indent: indent + offset,
+					text: line.text[offset..].to_string(),
+				};
+				items.push(self.block(indent + offset)?);
+			} else {
+				self.pos += 1;
+				items.push(self.value(&line, rest, indent, false)?);
+			}
+		}
+		Ok(Node::List(items, Bracket::Square, Separator::Colon))
+	}
+
+	fn mapping(&mut self, indent: usize) -> Result<Node, WarpError> {
+		let mut entries: Vec<Node> = Vec::new();
+		let mut merged: Vec<Node> = Vec::new();
+		while let Some(line) = self.next_line(indent) {
+			let content = line.content();
+			if line.indent > indent {
+				return Err(error(&line, "unexpected indentation in mapping"));
+			}
+			let Some(colon) = mapping_colon(content) else {
+				if is_sequence_entry(content) {
+					break;
+				}
+				return Err(error(&line, "expected `key: value`"));
+			};
+			self.pos += 1;
+			let key = self.key(&line, content[..colon].trim())?;
+			let value = self.value(&line, content[colon + 1..].trim(), indent, true)?;
+			if matches!(&key, Node::Symbol(k) if k == "<<") {
+				merged.extend(merge_entries(value));
+				continue;
+			}
+			let name = key_name(&key);
+			let before = entries.len();
+			entries.retain(|entry| !matches!(entry, Node::Key(k, _, _) if key_name(k) == name));
+			if entries.len() < before {
+				self.report(&line, content[..colon].trim_end(), format!("Duplicate key '{}'", name));
+			}
+			entries.push(Node::Key(Box::new(key), Op::Colon, Box::new(value)));
+		}
+		for entry in merged {
+			if let Node::Key(k, _, _) = &entry {
+				let name = key_name(k);
+				if !entries.iter().any(|e| matches!(e, Node::Key(other, _, _) if key_name(other) == name)) {
+					entries.push(entry);
+				}
+			}
+		}
+		Ok(Node::List(entries, Bracket::Curly, Separator::Newline))
+	}
+
+	/// Record a problem with the text starting at the line's indentation
+	fn report(&mut self, line: &Line, text: &str, message: String) {
+		let start = self.line_starts.get(line.number - 1).copied().unwrap_or(0) + line.indent;
+		let severity = if self.strict { Severity::Error } else { Severity::Warning };
+		let column = line.indent + 1;
+		self.diagnostics.push(Diagnostic { severity, message, span: start..start + text.len(), line_nr: line.number, column });
+	}
+
+	fn key(&mut self, line: &Line, text: &str) -> Result<Node, WarpError> {
+		if text.starts_with('"') || text.starts_with('\'') {
+			let chars: Vec<char> = text.chars().collect();
+			let mut pos = 0;
+			return self.flow_value(&chars, &mut pos, line);
+		}
+		if text.is_empty() || text.starts_with('?') {
+			return Err(error(line, "complex mapping keys are not supported"));
+		}
+		Ok(Node::Symbol(text.to_string()))
+	}
+
+	/// The value after `key:` or `- `, continuing on following lines if the rest is empty
+	/// Mapping values may be a sequence at the key's own indentation
+	fn value(&mut self, line: &Line, rest: &str, parent: usize, mapping_value: bool) -> Result<Node, WarpError> {
+		let (anchor, tag, rest) = properties(rest);
+		let node = if rest.is_empty() {
+			match self.next_line(0) {
+				Some(next) if next.indent > parent => self.block(parent + 1)?,
+				Some(next) if mapping_value && next.indent == parent && is_sequence_entry(next.content()) => self.sequence(parent)?,
+				_ => Node::Empty,
+			}
+		} else if rest.starts_with('|') || rest.starts_with('>') {
+			self.block_scalar(rest, parent)
+		} else if let Some(name) = rest.strip_prefix('*') {
+			self.alias(line, name.trim())?
+		} else {
+			self.inline(line, rest, parent)?
+		};
+		let node = apply_tag(node, tag.as_deref(), rest);
+		Ok(self.anchored(node, anchor))
+	}
+
+	/// Mark a node with its anchor and remember it for aliases
+	fn anchored(&mut self, node: Node, anchor: Option<String>) -> Node {
+		let Some(name) = anchor else { return node };
+		let node = node.with_meta_data(Anchor(name.clone()));
+		self.anchors.insert(name, node.clone());
+		node
+	}
+
+	fn alias(&self, line: &Line, name: &str) -> Result<Node, WarpError> {
+		self.anchors.get(name).cloned().ok_or_else(|| error(line, &format!("unknown alias *{}", name)))
+	}
+
+	/// Flow collections, quoted and plain scalars, which may span several lines
+	fn inline(&mut self, line: &Line, rest: &str, parent: usize) -> Result<Node, WarpError> {
+		let mut text = rest.to_string();
+		if text.starts_with(['[', '{', '"', '\'']) {
+			while !is_complete(&text) {
+				let Some(next) = self.lines.get(self.pos) else {
+					return Err(error(line, "unterminated flow value"));
+				};
+				let quoted = text.starts_with(['"', '\'']);
+				let more = if quoted { next.text.trim() } else { next.content().trim() };
+				if more.is_empty() {
+					if quoted {
+						text.push('\n');
+					}
+				} else {
+					if !text.ends_with('\n') {
+						text.push(' ');
+					}
+					text.push_str(more);
+				}
+				self.pos += 1;
+			}
+			let chars: Vec<char> = text.chars().collect();
+			let mut pos = 0;
+			let node = self.flow_value(&chars, &mut pos, line)?;
+			skip_spaces(&chars, &mut pos);
+			if pos < chars.len() {
+				return Err(error(line, "unexpected text after value"));
+			}
+			return Ok(node);
+		}
+		// Plain scalars continue on more indented lines, folded with spaces
+		while let Some(next) = self.next_line(parent + 1) {
+			if is_sequence_entry(next.content()) || mapping_colon(next.content()).is_some() {
+				break;
+			}
+			text.push(' ');
+			text.push_str(next.content().trim());
+			self.pos += 1;
+		}
+		Ok(plain_scalar(&text))
+	}
+
+	/// `|` literal and `>` folded scalars with `-`/`+` chomping and an optional indentation digit
+	fn block_scalar(&mut self, header: &str, parent: usize) -> Node {
+		let literal = header.starts_with('|');
+		let strip = header.contains('-');
+		let keep = header.contains('+');
+		let mut indent = header.chars().find_map(|c| c.to_digit(10)).map(|d| parent + d as usize);
+		let mut lines: Vec<String> = Vec::new();
+		while let Some(line) = self.lines.get(self.pos) {
+			if line.text.trim().is_empty() {
+				lines.push(String::new());
+				self.pos += 1;
+				continue;
+			}
+			let block_indent = *indent.get_or_insert(line.indent);
+			if line.indent < block_indent || line.indent <= parent {
+				break;
+			}
+			lines.push(format!("{}{}", " ".repeat(line.indent - block_indent), line.text));
+			self.pos += 1;
+		}
+		let end = lines.iter().rposition(|l| !l.is_empty()).map(|i| i + 1).unwrap_or(0);
+		let trailing = lines.len() - end;
+		let body = if literal { lines[..end].join("\n") } else { fold_lines(&lines[..end]) };
+		let text = if body.is_empty() || strip {
+			body
+		} else if keep {
+			format!("{}{}", body, "\n".repeat(trailing + 1))
+		} else {
+			format!("{}\n", body)
+		};
+		Node::Text(text)
+	}
+
+	fn flow_value(&mut self, chars: &[char], pos: &mut usize, line: &Line) -> Result<Node, WarpError> {
+		skip_spaces(chars, pos);
+		let mut anchor = None;
+		let mut tag = None;
+		loop {
+			match chars.get(*pos) {
+				Some('&') => anchor = Some(flow_word(chars, pos)[1..].to_string()),
+				Some('!') => tag = Some(flow_word(chars, pos)),
+				_ => break,
+			}
+			skip_spaces(chars, pos);
+		}
+		let start = *pos;
+		let node = match chars.get(*pos) {
+			Some('[') => {
+				*pos += 1;
+				let mut items = Vec::new();
+				loop {
+					skip_spaces(chars, pos);
+					match chars.get(*pos) {
+						Some(']') => {
+							*pos += 1;
+							break;
+						}
+						None => return Err(error(line, "unterminated [")),
+						_ => {}
+					}
+					let quoted = matches!(chars.get(*pos), Some('"' | '\''));
+					let item = self.flow_value(chars, pos, line)?;
+					skip_spaces(chars, pos);
+					if chars.get(*pos) == Some(&':') {
+						// [key: value] is a single pair mapping
+						*pos += 1;
+						let value = self.flow_value(chars, pos, line)?;
+						let key = if quoted { item } else { flow_key(item) };
+						items.push(Node::List(vec![Node::Key(Box::new(key), Op::Colon, Box::new(value))], Bracket::Curly, Separator::Colon));
+						skip_spaces(chars, pos);
+					} else {
+						items.push(item);
+					}
+					flow_separator(chars, pos, ']', line)?;
+				}
+				Node::List(items, Bracket::Square, Separator::Colon)
+			}
+			Some('{') => {
+				*pos += 1;
+				let mut entries = Vec::new();
+				loop {
+					skip_spaces(chars, pos);
+					match chars.get(*pos) {
+						Some('}') => {
+							*pos += 1;
+							break;
+						}
+						None => return Err(error(line, "unterminated {")),
+						_ => {}
+					}
+					let quoted = matches!(chars.get(*pos), Some('"' | '\''));
+					let key = self.flow_value(chars, pos, line)?;
+					let key = if quoted { key } else { flow_key(key) };
+					skip_spaces(chars, pos);
+					let value = if chars.get(*pos) == Some(&':') {
+						*pos += 1;
+						self.flow_value(chars, pos, line)?
+					} else {
+						Node::Empty
+					};
+					entries.push(Node::Key(Box::new(key), Op::Colon, Box::new(value)));
+					skip_spaces(chars, pos);
+					flow_separator(chars, pos, '}', line)?;
+				}
+				Node::List(entries, Bracket::Curly, Separator::Colon)
+			}
+			Some('"') => Node::Text(double_quoted(chars, pos).ok_or_else(|| error(line, "unterminated double quoted string"))?),
+			Some('\'') => Node::Text(single_quoted(chars, pos).ok_or_else(|| error(line, "unterminated single quoted string"))?),
+			Some('*') => {
+				let name = flow_word(chars, pos);
+				self.alias(line, &name[1..])?
+			}
+			_ => {
+				while let Some(&c) = chars.get(*pos) {
+					let ends_key = c == ':' && matches!(chars.get(*pos + 1), None | Some(' ' | ',' | ']' | '}'));
+					if matches!(c, ',' | ']' | '}') || ends_key {
+						break;
+					}
+					*pos += 1;
+				}
+				plain_scalar(chars[start..*pos].iter().collect::<String>().trim())
+			}
+		};
+		let source: String = chars[start..*pos].iter().collect();
+		let node = apply_tag(node, tag.as_deref(), source.trim());
+		Ok(self.anchored(node, anchor))
+	}
+}
+
+fn error(line: &Line, message: &str) -> WarpError {
+	WarpError::Parse {
+		message: format!("yaml: {}", message),
+		position: Some(LineInfo {
+			line_nr: line.number,
+			column: line.indent + 1,
+			line: format!("{}{}", " ".repeat(line.indent), line.text),
+		}),
+	}
+}
+
+fn is_sequence_entry(text: &str) -> bool {
+	text == "-" || text.starts_with("- ") || text.starts_with("-\t")
+}
+
+/// Byte offset of the colon that ends a block mapping key
+fn mapping_colon(text: &str) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let ends_key = |i: usize| bytes[i] == b':' && matches!(bytes.get(i + 1), None | Some(b' ' | b'\t'));
+	match bytes.first()? {
+		b'"' | b'\'' => {
+			let chars: Vec<char> = text.chars().collect();
+			let mut pos = 0;
+			let closed = if bytes[0] == b'"' { double_quoted(&chars, &mut pos) } else { single_quoted(&chars, &mut pos) };
+			closed?;
+			let offset = chars[..pos].iter().map(|c| c.len_utf8()).sum::<usize>();
+			let gap = text[offset..].len() - text[offset..].trim_start().len();
+			let i = offset + gap;
+			(i < bytes.len() && ends_key(i)).then_some(i)
+		}
+		b'[' | b'{' | b'&' | b'*' | b'!' | b'|' | b'>' => None,
+		_ => (0..bytes.len()).find(|&i| ends_key(i)),
+	}
+}
+
+/// Cut a trailing `# comment`, leaving quoted `#` alone
+fn strip_comment(text: &str) -> &str {
+	let mut quote: Option<char> = None;
+	let mut previous = ' ';
+	let mut escaped = false;
+	for (i, c) in text.char_indices() {
+		match quote {
+			Some('"') if escaped => escaped = false,
+			Some('"') if c == '\\' => escaped = true,
+			Some(q) if c == q => quote = None,
+			Some(_) => {}
+			None if c == '#' && previous.is_whitespace() => return text[..i].trim_end(),
+			None if (c == '"' || c == '\'') && matches!(previous, ' ' | '\t' | '[' | '{' | ',') => quote = Some(c),
+			None => {}
+		}
+		previous = c;
+	}
+	text.trim_end()
+}
+
+/// Leading `&anchor` and `!tag` of a value
+fn properties(text: &str) -> (Option<String>, Option<String>, &str) {
+	let mut anchor = None;
+	let mut tag = None;
+	let mut rest = text.trim();
+	loop {
+		let word_end = rest.find([' ', '\t']).unwrap_or(rest.len());
+		if let Some(name) = rest.strip_prefix('&') {
+			anchor = Some(name[..word_end - 1].to_string());
+		} else if rest.starts_with('!') {
+			tag = Some(rest[..word_end].to_string());
+		} else {
+			return (anchor, tag, rest);
+		}
+		rest = rest[word_end..].trim_start();
+	}
+}
+
+fn apply_tag(node: Node, tag: Option<&str>, source: &str) -> Node {
+	match (tag, node) {
+		(Some("!!str"), Node::Text(s)) => Node::Text(s),
+		(Some("!!str"), Node::List(items, bracket, separator)) => Node::List(items, bracket, separator),
+		(Some("!!str"), _) => Node::Text(source.to_string()),
+		(Some("!!int"), Node::Text(s)) => s.trim().parse::<i64>().map(|i| Node::Number(Number::Int(i))).unwrap_or(Node::Text(s)),
+		(Some("!!float"), Node::Text(s)) => s.trim().parse::<f64>().map(|f| Node::Number(Number::Float(f))).unwrap_or(Node::Text(s)),
+		(Some("!!float"), Node::Number(Number::Int(i))) => Node::Number(Number::Float(i as f64)),
+		(Some("!!null"), _) => Node::Empty,
+		(_, node) => node,
+	}
+}
+
+/// Entries merged in by `<<`: one mapping or a sequence of mappings
+fn merge_entries(value: Node) -> Vec<Node> {
+	match value {
+		Node::Meta { node, .. } => merge_entries(*node),
+		Node::List(entries, Bracket::Curly, _) => entries,
+		Node::List(maps, _, _) => maps.into_iter().flat_map(merge_entries).collect(),
+		_ => Vec::new(),
+	}
+}
+
+fn key_name(key: &Node) -> String {
+	match key.drop_meta() {
+		Node::Symbol(s) | Node::Text(s) => s.clone(),
+		other => other.serialize(),
+	}
+}
+
+/// Plain flow keys are Symbols like block keys
+fn flow_key(key: Node) -> Node {
+	match key {
+		Node::Empty => Node::Symbol(String::new()),
+		other => Node::Symbol(key_name(&other)),
+	}
+}
+
+/// Type a plain scalar: null, booleans, numbers, else text
+fn plain_scalar(text: &str) -> Node {
+	match text {
+		"" | "~" | "null" | "Null" | "NULL" => Node::Empty,
+		"true" | "True" | "TRUE" => Node::True,
+		"false" | "False" | "FALSE" => Node::False,
+		".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Node::Number(Number::Inf),
+		"-.inf" | "-.Inf" | "-.INF" => Node::Number(Number::NegInf),
+		".nan" | ".NaN" | ".NAN" => Node::Number(Number::Nan),
+		_ => {
+			let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+			if let Ok(i) = text.parse::<i64>() {
+				return Node::Number(Number::Int(i));
+			}
+			if let Some(hex) = digits.strip_prefix("0x") {
+				if let Ok(i) = i64::from_str_radix(hex, 16) {
+					return Node::Number(Number::Int(if text.starts_with('-') { -i } else { i }));
+				}
+			}
+			if let Some(octal) = digits.strip_prefix("0o") {
+				if let Ok(i) = i64::from_str_radix(octal, 8) {
+					return Node::Number(Number::Int(if text.starts_with('-') { -i } else { i }));
+				}
+			}
+			let numeric = digits.starts_with(|c: char| c.is_ascii_digit()) || (digits.starts_with('.') && digits[1..].starts_with(|c: char| c.is_ascii_digit()));
+			if numeric {
+				if let Ok(f) = text.parse::<f64>() {
+					return Node::Number(Number::Float(f));
+				}
+			}
+			Node::Text(text.to_string())
+		}
+	}
+}
+
+/// Are all quotes closed and brackets balanced?
+fn is_complete(text: &str) -> bool {
+	let chars: Vec<char> = text.chars().collect();
+	let mut depth = 0i32;
+	let mut pos = 0;
+	while pos < chars.len() {
+		match chars[pos] {
+			'"' => {
+				if double_quoted(&chars, &mut pos).is_none() {
+					return false;
+				}
+				continue;
+			}
+			'\'' if pos == 0 || matches!(chars[pos - 1], ' ' | '[' | '{' | ',' | ':') => {
+				if single_quoted(&chars, &mut pos).is_none() {
+					return false;
+				}
+				continue;
+			}
+			'[' | '{' => depth += 1,
+			']' | '}' => depth -= 1,
+			_ => {}
+		}
+		pos += 1;
+	}
+	depth <= 0
+}
+
+fn skip_spaces(chars: &[char], pos: &mut usize) {
+	while matches!(chars.get(*pos), Some(' ' | '\t' | '\n')) {
+		*pos += 1;
+	}
+}
+
+/// An anchor, tag or alias name: up to whitespace or a flow indicator
+fn flow_word(chars: &[char], pos: &mut usize) -> String {
+	let start = *pos;
+	while let Some(&c) = chars.get(*pos) {
+		if c.is_whitespace() || matches!(c, ',' | ']' | '}') {
+			break;
+		}
+		*pos += 1;
+	}
+	chars[start..*pos].iter().collect()
+}
+
+fn flow_separator(chars: &[char], pos: &mut usize, close: char, line: &Line) -> Result<(), WarpError> {
+	match chars.get(*pos) {
+		Some(',') => {
+			*pos += 1;
+			Ok(())
+		}
+		Some(&c) if c == close => Ok(()),
+		_ => Err(error(line, &format!("expected , or {}", close))),
+	}
+}
+
+/// Read a "…" scalar with escapes, leaving pos after the closing quote
+fn double_quoted(chars: &[char], pos: &mut usize) -> Option<String> {
+	let mut out = String::new();
+	let mut i = *pos + 1;
+	while let Some(&c) = chars.get(i) {
+		match c {
+			'"' => {
+				*pos = i + 1;
+				return Some(out);
+			}
+			'\\' => {
+				i += 1;
+				let escaped = *chars.get(i)?;
+				let code = |len: usize| {
+					let hex: String = chars.get(i + 1..i + 1 + len)?.iter().collect();
+					u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+				};
+				match escaped {
+					'n' => out.push('\n'),
+					't' | '\t' => out.push('\t'),
+					'r' => out.push('\r'),
+					'0' => out.push('\0'),
+					'a' => out.push('\u{7}'),
+					'b' => out.push('\u{8}'),
+					'e' => out.push('\u{1b}'),
+					'f' => out.push('\u{c}'),
+					'v' => out.push('\u{b}'),
+					'N' => out.push('\u{85}'),
+					'_' => out.push('\u{a0}'),
+					' ' => out.push(' '),
+					'x' | 'u' | 'U' => {
+						let len = match escaped {
+							'x' => 2,
+							'u' => 4,
+							_ => 8,
+						};
+						out.push(code(len)?);
+						i += len;
+					}
+					other => out.push(other),
+				}
+			}
+			_ => out.push(c),
+		}
+		i += 1;
+	}
+	None
+}
+
+/// Read a '…' scalar where '' stands for one quote
+fn single_quoted(chars: &[char], pos: &mut usize) -> Option<String> {
+	let mut out = String::new();
+	let mut i = *pos + 1;
+	while let Some(&c) = chars.get(i) {
+		if c == '\'' {
+			if chars.get(i + 1) == Some(&'\'') {
+				out.push('\'');
+				i += 2;
+				continue;
+			}
+			*pos = i + 1;
+			return Some(out);
+		}
+		out.push(c);
+		i += 1;
+	}
+	None
+}
+
+/// Fold `>` scalar lines: single breaks become spaces, empty and more indented lines keep theirs
+fn fold_lines(lines: &[String]) -> String {
+	let mut out = String::new();
+	for (i, line) in lines.iter().enumerate() {
+		if i == 0 {
+			out.push_str(line);
+			continue;
+		}
+		let previous = &lines[i - 1];
+		if line.is_empty() {
+			out.push('\n');
+		} else if previous.is_empty() {
+			out.push_str(line);
+		} else if line.starts_with(' ') || previous.starts_with(' ') {
+			out.push('\n');
+			out.push_str(line);
+		} else {
+			out.push(' ');
+			out.push_str(line);
+		}
+	}
+	out
+}
+
+// ==================== Writing ====================
+
+/// Key/value pairs of a mapping-like node
+fn pairs(node: &Node) -> Option<Vec<(&Node, &Node)>> {
+	match node.drop_meta() {
+		Node::Key(k, _, v) => Some(vec![(k.as_ref(), v.as_ref())]),
+		Node::List(items, Bracket::Curly | Bracket::None, _) if is_mapping(node) => Some(
+			items
+				.iter()
+				.filter_map(|item| match item.drop_meta() {
+					Node::Key(k, _, v) => Some((k.as_ref(), v.as_ref())),
+					_ => None,
+				})
+				.collect(),
+		),
+		_ => None,
+	}
+}
+
+/// A Curly list, or a list of keys only
+fn is_mapping(node: &Node) -> bool {
+	match node.drop_meta() {
+		Node::List(items, Bracket::Curly, _) if items.is_empty() => true,
+		Node::List(items, Bracket::Curly | Bracket::None, _) => !items.is_empty() && items.iter().all(|i| matches!(i.drop_meta(), Node::Key(..))),
+		Node::Key(..) => true,
+		_ => false,
+	}
+}
+
+fn sequence_items(node: &Node) -> Option<&Vec<Node>> {
+	match node.drop_meta() {
+		Node::List(items, _, _) if !is_mapping(node) => Some(items),
+		_ => None,
+	}
+}
+
+/// Lines for node at the given indentation, each ending in \n
+/// anchors holds the anchors written so far, later nodes with one become aliases
+fn block(node: &Node, indent: usize, anchors: &mut HashSet<String>) -> String {
+	let pad = " ".repeat(indent);
+	if let Some(pairs) = pairs(node) {
+		if pairs.is_empty() {
+			return format!("{}{{}}\n", pad);
+		}
+		let mut out = String::new();
+		for (key, value) in pairs {
+			out.push_str(&format!("{}{}:", pad, scalar(key)));
+			out.push_str(&nested(value, indent, anchors));
+		}
+		return out;
+	}
+	if let Some(items) = sequence_items(node) {
+		if items.is_empty() {
+			return format!("{}[]\n", pad);
+		}
+		let mut out = String::new();
+		for item in items {
+			if is_collection(item) && anchor_of(item).is_none() {
+				// The first line of the nested block goes right after the dash
+				let inner = block(item, indent + 2, anchors);
+				out.push_str(&format!("{}- {}", pad, &inner[indent + 2..]));
+			} else {
+				out.push_str(&format!("{}-", pad));
+				out.push_str(&nested(item, indent, anchors));
+			}
+		}
+		return out;
+	}
+	match literal_block(node, indent + 2) {
+		Some(text) => format!("{}{}", pad, text.trim_start()),
+		None => format!("{}{}\n", pad, scalar(node)),
+	}
+}
+
+/// What follows `key:` or `-`: a nested block on the next lines or ` scalar`, after its ` &anchor`
+/// or just ` *alias` if that anchor was written before
+fn nested(value: &Node, indent: usize, anchors: &mut HashSet<String>) -> String {
+	if let Some(name) = anchor_of(value) {
+		if !anchors.insert(name.to_string()) {
+			return format!(" *{}\n", name);
+		}
+		return format!(" &{}{}", name, nested_value(value, indent, anchors));
+	}
+	nested_value(value, indent, anchors)
+}
+
+fn nested_value(value: &Node, indent: usize, anchors: &mut HashSet<String>) -> String {
+	if is_collection(value) {
+		return format!("\n{}", block(value, indent + 2, anchors));
+	}
+	match literal_block(value, indent + 2) {
+		Some(text) => text,
+		None => format!(" {}\n", scalar(value)),
+	}
+}
+
+/// Non-empty mappings and sequences are written as blocks, empty ones inline
+fn is_collection(node: &Node) -> bool {
+	match node.drop_meta() {
+		Node::List(items, _, _) => !items.is_empty(),
+		Node::Key(..) => true,
+		_ => false,
+	}
+}
+
+/// ` |` block for multi-line text, None if it has to be quoted
+fn literal_block(node: &Node, indent: usize) -> Option<String> {
+	let Node::Text(s) = node.drop_meta() else { return None };
+	if !s.contains('\n') || s.starts_with([' ', '\n']) || s.contains(['\r', '\t']) || s.chars().any(|c| c.is_control() && c != '\n') {
+		return None;
+	}
+	let body = s.trim_end_matches('\n');
+	let chomp = match s.len() - body.len() {
+		0 => "-",
+		1 => "",
+		_ => "+",
+	};
+	let pad = " ".repeat(indent);
+	let mut out = format!(" |{}\n", chomp);
+	for line in s.strip_suffix('\n').unwrap_or(s).split('\n') {
+		if line.is_empty() {
+			out.push('\n');
+		} else {
+			out.push_str(&format!("{}{}\n", pad, line));
+		}
+	}
+	Some(out)
+}
+
+fn scalar(node: &Node) -> String {
+	match node.drop_meta() {
+		Node::Empty => "null".to_string(),
+		Node::True => "true".to_string(),
+		Node::False => "false".to_string(),
+		Node::Number(Number::Int(i)) => i.to_string(),
+		Node::Number(Number::Float(f)) if f.is_nan() => ".nan".to_string(),
+		Node::Number(Number::Float(f)) if f.is_infinite() => if *f > 0.0 { ".inf" } else { "-.inf" }.to_string(),
+		Node::Number(Number::Float(f)) => {
+			let s = f.to_string();
+			if s.contains(['.', 'e', 'E']) { s } else { format!("{}.0", s) }
+		}
+		Node::Number(Number::Inf) => ".inf".to_string(),
+		Node::Number(Number::NegInf) => "-.inf".to_string(),
+		Node::Number(Number::Nan) => ".nan".to_string(),
+		Node::Text(s) | Node::Symbol(s) => quote(s),
+		Node::Char(c) => quote(&c.to_string()),
+		Node::List(_, _, _) if is_mapping(node) => "{}".to_string(),
+		Node::List(_, _, _) => "[]".to_string(),
+		other => quote(&other.serialize()),
+	}
+}
+
+/// Plain if it reads back as the same text, double quoted otherwise
+fn quote(s: &str) -> String {
+	let plain = !s.is_empty()
+		&& matches!(plain_scalar(s), Node::Text(_))
+		&& !s.starts_with(|c: char| c.is_whitespace() || "-?:,[]{}#&*!|>'\"%@`".contains(c))
+		&& !s.ends_with(char::is_whitespace)
+		&& !s.contains(": ")
+		&& !s.contains(" #")
+		&& !s.ends_with(':')
+		&& !s.chars().any(|c| c.is_control());
+	if plain {
+		return s.to_string();
+	}
+	let mut out = String::from("\"");
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\t' => out.push_str("\\t"),
+			'\r' => out.push_str("\\r"),
+			c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
@@ -0,0 +1,44 @@
+// check statements inside wasp files, run by warp test
+use warp::checks::run_checks;
+use warp::eq;
+
+#[test]
+fn test_checks_pass_and_fail() {
+	let code = "square(x) := x * x\ncheck square(3) == 9\ncheck square(2) == 5\ncheck 3 > 2";
+	let results = run_checks(code, "squares.wasp");
+	eq!(results.len(), 3);
+	eq!(results[0].passed, true);
+	eq!(results[1].passed, false);
+	eq!(results[1].line_nr, 3);
+	eq!(results[1].left.clone().unwrap(), 4);
+	eq!(results[1].right.clone().unwrap(), 5);
+	eq!(results[2].passed, true);
+}
+
+#[test]
+fn test_checks_see_earlier_statements_only() {
+	let results = run_checks("x = 1\ncheck x == 1\nx = 2\ncheck x == 2", "order.wasp");
+	eq!(results.iter().all(|r| r.passed), true);
+	// a user function named check is not an assertion
+	let results = run_checks("check(n, i) := n + i\ncheck(1, 2)", "fn.wasp");
+	eq!(results.len(), 0);
+}
+
+#[test]
+fn test_checks_json_report() {
+	let report = warp::checks::TestReport { results: run_checks("check 1 + 1 == 3", "math.wasp") };
+	eq!(report.success(), false);
+	let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+	eq!(json["failed"], 1);
+	eq!(json["checks"][0]["left"], "2");
+	eq!(json["checks"][0]["file"], "math.wasp");
+}
+
+#[test]
+fn test_checks_of_polymorphism_sample() {
+	let code = std::fs::read_to_string("samples/polymorphism.wasp").unwrap();
+	let results = run_checks(&code, "polymorphism.wasp");
+	eq!(results.len(), 2);
+	assert!(results.iter().all(|r| r.passed), "{:?}", results);
+	eq!(results[1].left.clone().unwrap(), 6);
+}
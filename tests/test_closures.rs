@@ -0,0 +1,42 @@
+// first-class functions: closures capturing outer variables, called through call_ref
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{is, Op};
+
+#[test]
+fn test_lambda_parses_as_fat_arrow() {
+	let node = parse("x => x * 2");
+	assert!(matches!(node.drop_meta(), Node::Key(_, Op::FatArrow, _)));
+	let node = parse("add = (a, b) => a + b");
+	let Node::Key(_, Op::Assign, lambda) = node.drop_meta() else { panic!("expected assignment: {:?}", node) };
+	assert!(matches!(lambda.drop_meta(), Node::Key(_, Op::FatArrow, _)));
+}
+
+#[test]
+fn test_closure_call() {
+	is!("double = x => x * 2; double(21)", 42);
+	is!("double = x => x * 2; double 21", 42);
+	is!("add = (a, b) => a + b; add(3, 4)", 7);
+	is!("five = () => 5; five()", 5);
+}
+
+#[test]
+fn test_closure_captures() {
+	is!("n = 10; add_n = x => x + n; add_n(5)", 15);
+	is!("n = 10; add_n = x => x + n; n = 20; add_n(5)", 15); // captured when created
+	is!("greeting = 'hi '; greet = name => greeting + name; greet('bob')", "hi bob");
+	is!("make_adder = n => x => x + n; add5 = make_adder(5); add5(3)", 8);
+}
+
+#[test]
+fn test_closures_in_lists_and_maps() {
+	is!("fs = [x => x + 1, x => x * 10]; sum = 0; for f in fs {sum += f(2)}; sum", 23);
+	is!("ops = {inc: (x => x + 1), dbl: (x => x * 2)}; sum = 0; for name, f in ops {sum += f(10)}; sum", 31);
+}
+
+#[test]
+fn test_closures_passed_to_functions() {
+	is!("apply(f, x) = f(x); apply(x => x * 3, 4)", 12);
+	is!("def twice(f, x){ f(f(x)) }; n = 5; twice(x => x + n, 1)", 11);
+	is!("grows := it * 2; apply(f, x) = f(x); apply(grows, 3)", 6);
+}
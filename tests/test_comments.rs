@@ -1,6 +1,8 @@
 // use warp::wasp_parser::WaspParser::parse;
 use warp::Node;
 use warp::wasp_parser::{parse, WaspParser};
+use warp::formatter::format_with_style;
+use warp::normalize::Style;
 use warp::{eq, is, put};
 
 #[test]
@@ -65,6 +67,12 @@ fn test_comment_metadata() {
 	assert!(node["comment"].to_string().contains("Important config"));
 }
 
+#[test]
+fn test_comments_between_and_after_items() {
+	let code = "a = 1 // why\n// about b\nb = 'not // a comment'\n";
+	eq!(format_with_style(code, &Style::default()).unwrap(), code);
+}
+
 #[test]
 fn test_comments_in_html_structure() {
 	let wasp = r#"
@@ -0,0 +1,49 @@
+// Compile once, run and call many times
+use warp::{compile, eq, float, int, WarpError};
+
+#[test]
+fn test_compile_run() {
+	let module = compile("fib(n) = n < 2 ? n : fib(n - 1) + fib(n - 2); fib(10)").expect("should compile");
+	eq!(&module.bytes()[0..4], &[0x00, 0x61, 0x73, 0x6d]);
+	eq!(module.functions(), &["fib".to_string()]);
+	eq!(module.run().unwrap(), 55);
+	eq!(module.run().unwrap(), 55);
+}
+
+#[test]
+fn test_compile_call() {
+	let module = compile("fib(n) = n < 2 ? n : fib(n - 1) + fib(n - 2); fib(1)").expect("should compile");
+	for (n, expected) in [(0i64, 0i64), (1, 1), (10, 55), (20, 6765)] {
+		eq!(module.call("fib", &[int(n)]).unwrap(), expected);
+	}
+	assert!(matches!(module.call("nope", &[]), Err(WarpError::Link { .. })));
+}
+
+#[test]
+fn test_compile_wat() {
+	let module = compile("square(x) = x*x; square(3)").expect("should compile");
+	let wat = module.wat();
+	assert!(wat.starts_with("(module"), "{}", wat);
+	assert!(wat.contains("\"square\""), "{}", wat);
+}
+
+#[test]
+fn test_compile_types() {
+	let module = compile("class Point{x:i64 y:i64}; 1").expect("should compile");
+	assert!(module.types().get_by_name("Point").is_some());
+}
+
+#[test]
+fn test_compile_call_float() {
+	let module = compile("half(x: float) = x / 2; half(1.0)").expect("should compile");
+	eq!(module.call("half", &[float(3.0)]).unwrap(), 1.5);
+	eq!(module.call("half", &[int(5)]).unwrap(), 2.5);
+	assert!(matches!(module.call("half", &[]), Err(WarpError::Type { .. })));
+}
+
+#[test]
+fn test_compile_call_with_imports() {
+	let module = compile("twice(n) = n * 2\nputs 'ok'\ntwice(2)").expect("should compile");
+	eq!(module.run().unwrap(), 4);
+	eq!(module.call("twice", &[int(21)]).unwrap(), 42);
+}
@@ -0,0 +1,53 @@
+// Format conversion behind warp convert
+use warp::convert::{convert, read, write, Format};
+use warp::eq;
+
+#[test]
+fn test_convert_detects_formats() {
+	eq!(Format::from_path("fixtures/a.json"), Some(Format::Json));
+	eq!(Format::from_path("page.html"), Some(Format::Xml));
+	eq!(Format::from_path("config.yml"), Some(Format::Yaml));
+	eq!(Format::from_path("README"), None);
+	eq!(Format::detect("  {\"a\": 1}"), Format::Json);
+	eq!(Format::detect("<div>hi</div>"), Format::Xml);
+	eq!(Format::detect("---\na: 1"), Format::Yaml);
+	eq!(Format::detect("a: 1 b: 2"), Format::Wasp);
+}
+
+#[test]
+fn test_convert_json_to_yaml_and_back() {
+	let yaml = convert(r#"{"name": "warp", "tags": ["fast", "small"]}"#, Format::Json, Format::Yaml).unwrap();
+	eq!(yaml.output, "name: warp\ntags:\n  - fast\n  - small\n");
+	eq!(yaml.lost.len(), 0);
+	let json = convert(&yaml.output, Format::Yaml, Format::Json).unwrap();
+	let node = read(&json.output, Format::Json).unwrap();
+	eq!(node["tags"][1], "small");
+}
+
+#[test]
+fn test_convert_reports_lost_comments() {
+	let code = "// config\nport: 8080 // default";
+	let json = convert(code, Format::Wasp, Format::Json).unwrap();
+	eq!(json.lost.len(), 2);
+	assert!(json.lost[0].contains("config"), "{:?}", json.lost);
+	assert!(!json.output.contains("config"));
+	let wasp = convert(code, Format::Wasp, Format::Wasp).unwrap();
+	eq!(wasp.lost.len(), 0);
+	assert!(wasp.output.contains("// default"), "{}", wasp.output);
+}
+
+#[test]
+fn test_convert_refuses_broken_input() {
+	assert!(convert("a: [1, 2", Format::Yaml, Format::Json).is_err());
+}
+
+#[test]
+fn test_read_json_is_plain_json() {
+	let node = read(r#"{"zeta": "Hi {name}", "alpha": ["$ref", 1.5, null, true]}"#, Format::Json).unwrap();
+	eq!(node["zeta"], "Hi {name}");
+	eq!(node["alpha"][0], "$ref");
+	eq!(node["alpha"][1], 1.5);
+	let yaml = write(&node, Format::Yaml).unwrap();
+	assert!(yaml.starts_with("zeta:"), "{}", yaml);
+	assert!(read("{a: 1}", Format::Json).is_err());
+}
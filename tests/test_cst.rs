@@ -0,0 +1,51 @@
+// Lossless syntax tree and in-place edits
+use warp::cst::Cst;
+use warp::{eq, Node, WarpError};
+
+const CONFIG: &str = "# service config
+server: {
+  host: \"localhost\"  // dev box
+  port: 8080
+}
+name = 'warp'
+";
+
+#[test]
+fn test_cst_roundtrip() {
+	eq!(Cst::parse(CONFIG).to_string(), CONFIG);
+	let broken = "a: [1, (2}\n} ä: 'x\\'y' /* c */ // d\n\t#e\r\n{ \"open";
+	eq!(Cst::parse(broken).to_string(), broken);
+}
+
+#[test]
+fn test_cst_set_keeps_other_lines() {
+	let mut cst = Cst::parse(CONFIG);
+	eq!(cst.raw("server.port"), Some("8080"));
+	cst.set("server.port", &Node::int(9090)).unwrap();
+	eq!(cst.to_string(), CONFIG.replace("8080", "9090"));
+	cst.set("name", &Node::text("fast")).unwrap();
+	eq!(cst.to_string(), CONFIG.replace("8080", "9090").replace("'warp'", "'fast'"));
+	eq!(cst.get("server.port").unwrap(), 9090);
+}
+
+#[test]
+fn test_cst_insert_keys() {
+	let mut cst = Cst::parse(CONFIG);
+	cst.set("server.debug", &Node::True).unwrap();
+	cst.set("version", &Node::int(2)).unwrap();
+	let expected = CONFIG.replace("port: 8080\n", "port: 8080\n  debug: true\n").replace("'warp'\n", "'warp'\nversion = 2\n");
+	eq!(cst.to_string(), expected);
+	let mut flat = Cst::parse("point: {x: 1, y: 2}");
+	flat.set("point.z", &Node::int(3)).unwrap();
+	eq!(flat.to_string(), "point: {x: 1, y: 2, z: 3}");
+}
+
+#[test]
+fn test_cst_indented_blocks() {
+	let mut cst = Cst::parse("server:\n  port: 80\ndebug: false\n");
+	eq!(cst.raw("debug"), Some("false"));
+	cst.set("server.port", &Node::int(81)).unwrap();
+	cst.set("server.host", &Node::text("a")).unwrap();
+	eq!(cst.to_string(), "server:\n  port: 81\n  host: \"a\"\ndebug: false\n");
+	assert!(matches!(cst.set("debug.level", &Node::int(1)), Err(WarpError::Parse { .. })));
+}
@@ -0,0 +1,74 @@
+// multiple dispatch: user functions defined for several parameter types, chosen statically or at runtime
+use warp::{compile, is, try_eval, WarpError};
+
+const COMBINE: &str = "combine(a: float, b: float) = a + b\ncombine(a: int, b: int) = a * b";
+const SHAPES: &str = "class Sq {\ns:i64\n}\nclass Tri {\nb:i64\nh:i64\n}\narea(x: Sq) = x.s * x.s\narea(x: Tri) = x.b * x.h";
+
+#[test]
+fn test_overloads_become_variants() {
+	let module = compile(&format!("{}; 1", COMBINE)).expect("should compile");
+	let wat = module.wat();
+	assert!(wat.contains("combine(float,float)"), "{}", wat);
+	assert!(wat.contains("combine(int,int)"), "{}", wat);
+}
+
+#[test]
+fn test_static_dispatch() {
+	is!(&format!("{}; combine(2, 3)", COMBINE), 6);
+	is!(&format!("{}; combine(1.5, 2.25)", COMBINE), 3.75);
+	is!(&format!("{}; combine(1.5, 2.25) == 3.75", COMBINE), 1);
+	// an int is taken for a float when no variant takes ints
+	is!("half(x: float) = x / 2\nhalf(s: text) = 0; half(3)", 1.5);
+}
+
+#[test]
+fn test_dispatch_on_classes() {
+	is!(&format!("{}; area(Sq{{s:3}})", SHAPES), 9);
+	is!(&format!("{}; area(Tri{{b:4 h:3}})", SHAPES), 12);
+	// instances in variables are told apart at runtime
+	is!(&format!("{}; t = Tri{{b:4 h:3}}; area(t)", SHAPES), 12);
+}
+
+#[test]
+fn test_subclass_dispatch() {
+	let staff = "class Person {\nid:i64\n}\nclass Employee: Person {\nsalary:i64\n}\ntitle(p: Person) = 1\ntitle(e: Employee) = 2";
+	is!(&format!("{}; title(Employee{{id:1 salary:9}})", staff), 2);
+	is!(&format!("{}; title(Person{{id:1}})", staff), 1);
+	is!(&format!("{}; p = Employee{{id:1 salary:9}}; title(p)", staff), 2);
+}
+
+#[test]
+fn test_runtime_dispatch() {
+	let twice = format!("{}\ntwice(x: any) = combine(x, x)", COMBINE);
+	is!(&format!("{}; twice(3)", twice), 9);
+	let module = compile(&format!("{}; 1", twice)).expect("should compile");
+	assert!(module.wat().contains("\"combine\""), "{}", module.wat());
+}
+
+#[test]
+fn test_ambiguous_call() {
+	match try_eval("f(a: int, b) = 1\nf(a, b: int) = 2; f(1, 2)") {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("call of f is ambiguous"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+	// a more specific variant is no ambiguity
+	is!("f(a: int, b: int) = 1\nf(a, b: int) = 2; f(1, 2)", 1);
+}
+
+#[test]
+fn test_no_matching_variant() {
+	match try_eval(&format!("{}; combine('a', 'b')", COMBINE)) {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("no combine takes (text, text)"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+}
+
+#[test]
+fn test_with_definitions() {
+	// samples/polymorphism.wasp: parameters named by their type, or by position
+	let combine = "combine float with float = float#1 + float#2\ncombine int with int = $0 * $1";
+	is!(&format!("{}\ncombine(2, 3)", combine), 6);
+	is!(&format!("{}\ncombine(1.5, 2.25)", combine), 3.75);
+	// each type counts from 1
+	is!("second int with int = int#2\nsecond(4, 7)", 7);
+}
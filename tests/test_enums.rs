@@ -0,0 +1,84 @@
+// enums: variants as wasm GC subtypes of their enum, construction, matching and exhaustiveness
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{compile, int, is, try_eval, WarpError};
+
+#[test]
+fn test_enum_parses_variants() {
+	let node = parse("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }");
+	let Node::Type { name, body } = node.drop_meta() else { panic!("expected type: {:?}", node) };
+	assert_eq!(name.drop_meta(), &Node::Symbol("Shape".to_string()));
+	let Node::List(variants, _, _) = body.drop_meta() else { panic!("expected variants: {:?}", body) };
+	assert_eq!(variants.len(), 3);
+	assert!(matches!(variants[1].drop_meta(), Node::Type { body, .. } if matches!(body.drop_meta(), Node::List(fields, _, _) if fields.len() == 2)));
+	assert!(matches!(variants[2].drop_meta(), Node::Type { body, .. } if matches!(body.drop_meta(), Node::Empty)));
+	// separators between variants are optional
+	assert_eq!(parse("enum Color { Red, Green | Blue }"), parse("enum Color { Red Green Blue }"));
+}
+
+#[test]
+fn test_enum_registers_subtypes() {
+	let module = compile("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; 1").expect("should compile");
+	let shape = module.types().get_by_name("Shape").expect("enum registered");
+	assert_eq!(shape.variants, ["Circle", "Rect", "Dot"]);
+	let rect = module.types().get_by_name("Rect").expect("variant registered");
+	assert_eq!(rect.supertype.as_deref(), Some("Shape"));
+	assert_eq!(rect.fields.len(), 2);
+	let wat = module.wat();
+	assert!(wat.contains("(sub final"), "{}", wat);
+}
+
+#[test]
+fn test_enum_construction() {
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Rect(2, 3); match s { Circle(r) => 3 * r * r, Rect(w, h) => w * h, Dot => 0 }", 6);
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Circle(r: 2); match s { Circle(x) => 3 * x * x, _ => 0 }", 12);
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Rect{w:4 h:5}; match s { Rect{w: a, h: b} => a + b, _ => 0 }", 9);
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Dot; match s { Circle(r) => r, Rect(w, h) => w, Dot => 7 }", 7);
+}
+
+#[test]
+fn test_enum_match() {
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Circle(5); match s { Circle(r) if r > 3 => 'big', Circle(r) => 'small', Rect(w, h) => 'rect', Dot => 'dot' }", "big");
+	// the enum matches any of its variants
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Dot; match s { Shape => 'shape', _ => 'other' }", "shape");
+	is!("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; x = 3; match x { Shape => 'shape', _ => 'other' }", "other");
+}
+
+#[test]
+fn test_enum_match_exhaustive() {
+	match try_eval("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Dot; match s { Circle(r) => r, Dot => 0 }") {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("misses Rect"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+	// a guarded arm covers nothing, a catch-all everything
+	assert!(try_eval("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Dot; match s { Circle(r) if r > 1 => r, Rect(w, h) => w, Dot => 0 }").is_err());
+	assert!(try_eval("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; s = Dot; match s { Circle(r) => r, _ => 0 }").is_ok());
+}
+
+#[test]
+fn test_enum_variant_arity() {
+	match try_eval("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }\ns = Rect(1, 2, 3)") {
+		Err(WarpError::Type { message, position }) => {
+			assert!(message.contains("Rect takes 2 fields, got 3"), "{}", message);
+			assert_eq!(position.map(|p| p.line_nr), Some(2));
+		}
+		other => panic!("expected a type error: {:?}", other),
+	}
+}
+
+#[test]
+fn test_enum_variant_roundtrip() {
+	let module = compile("enum Shape { Circle(r: i64) Rect(w: i64, h: i64) Dot }; 1").expect("should compile");
+	let rect = module.call("new_Rect", &[int(2), int(3)]).expect("constructor call");
+	assert_eq!(parse("Rect{w:2 h:3}"), rect);
+	let circle = module.call("new_Circle", &[int(4)]).expect("constructor call");
+	assert_eq!(parse("Circle{r:4}"), circle);
+}
+
+#[test]
+fn test_plain_struct_with_int_field_roundtrip() {
+	// an untagged struct leading with an i32 field is not a variant
+	let module = compile("class C{n:int}; 1").expect("should compile");
+	let c = module.call("new_C", &[int(5)]).expect("constructor call");
+	assert_eq!(parse("C{n:5}"), c);
+}
@@ -0,0 +1,53 @@
+// Structured errors from try_eval
+use warp::wasm_emitter::try_eval;
+use warp::{eq, WarpError};
+
+#[test]
+fn test_try_eval_ok() {
+	let result = try_eval("1+2").expect("1+2 should evaluate");
+	eq!(result, 3);
+}
+
+#[test]
+fn test_try_eval_parse_error() {
+	match try_eval("x='unterminated") {
+		Err(WarpError::Parse { message, .. }) => assert!(message.contains("Unterminated"), "{}", message),
+		other => panic!("expected parse error, got {:?}", other),
+	}
+}
+
+#[test]
+fn test_try_eval_type_error_position() {
+	match try_eval("x=1\nx='ok'") {
+		Err(err @ WarpError::Type { .. }) => {
+			assert!(err.message().contains("type mismatch"), "{}", err);
+			let position = err.position().expect("type error should carry a position");
+			eq!(position.line_nr, 2);
+		}
+		other => panic!("expected type error, got {:?}", other),
+	}
+}
+
+#[test]
+fn test_try_eval_compile_error() {
+	match try_eval("not_defined_anywhere + 1") {
+		Err(WarpError::Compile { .. }) | Err(WarpError::Validation { .. }) => {}
+		other => panic!("expected compile error, got {:?}", other),
+	}
+}
+
+#[test]
+fn test_compile_error_position() {
+	// the statement being emitted, not the start of the program
+	match try_eval("x = 1\ny = not_defined_anywhere + 1") {
+		Err(err @ WarpError::Compile { .. }) => eq!(err.position().expect("compile error should carry a position").line_nr, 2),
+		other => panic!("expected compile error, got {:?}", other),
+	}
+}
+
+#[test]
+fn test_eval_falls_back_to_parsed_node() {
+	use warp::wasm_emitter::eval;
+	use warp::wasp_parser::parse;
+	eq!(eval("x='unterminated"), parse("x='unterminated"));
+}
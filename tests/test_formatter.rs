@@ -0,0 +1,94 @@
+// Canonical formatting driven by normalize::Style
+use warp::formatter::{format_source, format_with_style};
+use warp::normalize::{ConditionalStyle, FunctionStyle, LogicalStyle, QuoteStyle, Style};
+use warp::WarpError;
+
+fn fmt(code: &str) -> String {
+	format_with_style(code, &Style::default()).expect("code should parse")
+}
+
+#[test]
+fn test_fmt_default_style() {
+	assert_eq!(fmt("fn square(x) = x*x"), "square(x) := x * x\n");
+	assert_eq!(fmt("let x = 5"), "x := 5\n");
+	assert_eq!(fmt("x=\"hi\""), "x = 'hi'\n");
+	assert_eq!(fmt("a && b || c**2"), "a and b or c ^ 2\n");
+	assert_eq!(fmt("max = a > b ? a : b"), "max = if a > b then a else b\n");
+	assert_eq!(fmt("int('42')"), "'42' as int\n");
+	assert_eq!(fmt("def inc(x) {x + 1}"), "inc(x) := {x + 1}\n");
+}
+
+#[test]
+fn test_fmt_for_loops() {
+	assert_eq!(fmt("for x in xs {sum+=x}"), "for x in xs {sum += x}\n");
+	assert_eq!(fmt("for k,v in m {k}"), "for k, v in m {k}\n");
+}
+
+#[test]
+fn test_fmt_match() {
+	assert_eq!(fmt("match x {1 => \"a\", n if n > 2 => n, _ => 0}"), "match x {\n\t1 => 'a'\n\tn if n > 2 => n\n\t_ => 0\n}\n");
+}
+
+#[test]
+fn test_fmt_enum() {
+	assert_eq!(fmt("enum Shape { Circle(r:i64), Rect(w:i64, h:i64), Dot }"), "enum Shape{Circle(r: i64) Rect(w: i64, h: i64) Dot}\n");
+}
+
+#[test]
+fn test_fmt_subclass() {
+	assert_eq!(fmt("class Employee : Person { salary:i64 level:i64 }"), "class Employee: Person{salary: i64 level: i64}\n");
+}
+
+#[test]
+fn test_fmt_optionals() {
+	assert_eq!(fmt("x = t?.left??t.right"), "x = t?.left ?? t.right\n");
+	assert_eq!(fmt("left:Tree?"), "left: Tree?\n");
+}
+
+#[test]
+fn test_fmt_keeps_comments_and_blank_lines() {
+	let code = "// doc\nx = 1 // trailing\n\ny = 2\n";
+	assert_eq!(fmt(code), code);
+	assert_eq!(fmt("x=1\n// about y\ny=2"), "x = 1\n// about y\ny = 2\n");
+}
+
+#[test]
+fn test_fmt_keeps_comments_in_blocks() {
+	let code = "f(x) := {\n\t// double it\n\ty = x * 2 // why\n\treturn y\n\t// done\n}\n// the end\n";
+	assert_eq!(fmt(code), code);
+}
+
+#[test]
+fn test_fmt_keeps_float_literals() {
+	assert_eq!(fmt("x = 3.141592653589793"), "x = 3.141592653589793\n");
+	assert_eq!(fmt("y = 2.0"), "y = 2.0\n");
+}
+
+#[test]
+fn test_fmt_is_idempotent() {
+	let code = "fact(n) := {\n\tif n <= 1 {return 1}\n\treturn n * fact(n - 1)\n}\nfact(5)\n";
+	assert_eq!(fmt(code), code);
+	assert_eq!(fmt(&fmt("sign = x < 0 ? 'neg' : x > 0 ? 'pos' : 'zero'")), fmt("sign = x < 0 ? 'neg' : x > 0 ? 'pos' : 'zero'"));
+}
+
+#[test]
+fn test_fmt_custom_style() {
+	let style = Style {
+		function_def: FunctionStyle::Def,
+		logical: LogicalStyle::Symbols,
+		quotes: QuoteStyle::Double,
+		conditional: ConditionalStyle::Ternary,
+		..Style::default()
+	};
+	let code = "square(x) := x * x\nok = a and not b\ns = 'hi'\nm = if a > b then a else b";
+	let expected = "def square(x): x * x\nok = a && !b\ns = \"hi\"\nm = a > b ? a : b\n";
+	assert_eq!(format_with_style(code, &style).unwrap(), expected);
+}
+
+#[test]
+fn test_fmt_refuses_parse_errors() {
+	match format_source("x='unterminated") {
+		Err(WarpError::Parse { .. }) => {}
+		other => panic!("expected parse error, got {:?}", other),
+	}
+}
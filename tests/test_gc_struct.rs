@@ -73,6 +73,10 @@ fn person_type_def() -> TypeDef {
 			FieldDef { name: "age".to_string(), type_name: "i64".to_string() },
 		],
 		wasm_type_idx: None,
+		supertype: None,
+		variants: vec![],
+		subtypes: vec![],
+		type_params: vec![],
 	}
 }
 
@@ -86,6 +90,10 @@ fn point_type_def() -> TypeDef {
 			FieldDef { name: "y".to_string(), type_name: "i64".to_string() },
 		],
 		wasm_type_idx: None,
+		supertype: None,
+		variants: vec![],
+		subtypes: vec![],
+		type_params: vec![],
 	}
 }
 
@@ -0,0 +1,64 @@
+// generics: classes and functions with type parameters, checked by the analyzer and erased to nodes
+use warp::analyzer::collect_all_types;
+use warp::{is, parse, try_eval, TypeRegistry, WarpError};
+
+const PAIR: &str = "class Pair<A, B> {\nfirst: A\nsecond: B\n}";
+const FIRST: &str = "def first<T>(xs: List<T>) -> T: xs#1";
+const CELL: &str = "class Cell<A> {\nitem: A\n}\ndef unbox<T>(c: Cell<T>) -> T: c.item";
+
+fn type_error(code: &str) -> String {
+	match try_eval(code) {
+		Err(WarpError::Type { message, .. }) => message,
+		other => panic!("expected a type error for {}: {:?}", code, other),
+	}
+}
+
+#[test]
+fn test_generic_class_declares_type_params() {
+	let mut registry = TypeRegistry::new();
+	collect_all_types(&mut registry, &parse(PAIR));
+	let pair = registry.get_by_name("Pair").expect("Pair should be registered");
+	assert_eq!(pair.type_params, vec!["A", "B"]);
+	let field_types: Vec<&str> = pair.fields.iter().map(|field| field.type_name.as_str()).collect();
+	assert_eq!(field_types, vec!["A", "B"]);
+}
+
+#[test]
+fn test_generic_class() {
+	is!(&format!("{}; p = Pair{{first: 1 second: 'two'}}; p.first", PAIR), 1);
+	is!(&format!("{}; p = Pair{{first: 1 second: 'two'}}; p.second", PAIR), "two");
+	is!(&format!("{}; p = Pair{{first: 1.5 second: 2}}; p.first", PAIR), 1.5);
+}
+
+#[test]
+fn test_generic_function() {
+	is!(&format!("{}\nfirst([7, 8, 9])", FIRST), 7);
+	is!(&format!("{}\nfirst(['ab', 'cd'])", FIRST), "ab");
+	is!("pick<T>(a: T, b: T) = b; pick(1, 2)", 2);
+}
+
+#[test]
+fn test_generic_results_unboxed_by_caller() {
+	is!(&format!("{}\nfirst([7, 8, 9]) + 1", FIRST), 8);
+	is!(&format!("{}\nfirst([1.5, 2.5]) * 2", FIRST), 3.0);
+	is!(&format!("{}\nunbox(Cell{{item: 5}}) + 1", CELL), 6);
+	is!(&format!("{}\nunbox(Cell{{item: 2.5}}) * 2", CELL), 5.0);
+}
+
+#[test]
+fn test_type_param_takes_one_type() {
+	let message = type_error("pick<T>(a: T, b: T) = a; pick(1, 'x')");
+	assert!(message.contains("pick<T> takes one type for T, got int and text"), "{}", message);
+	let message = type_error(&format!("{}\nfirst([1, 'x'])", FIRST));
+	assert!(message.contains("first<T> takes one type for T, got int and text"), "{}", message);
+	let message = type_error("class Same<T> {\na: T\nb: T\n}\nSame{a: 1 b: 'x'}");
+	assert!(message.contains("Same<T> takes one type for T, got int and text"), "{}", message);
+}
+
+#[test]
+fn test_type_argument_count() {
+	let message = type_error(&format!("{}\nclass Holder {{\np: Pair<int>\n}}\n1", PAIR));
+	assert!(message.contains("Pair takes 2 type parameters, got 1"), "{}", message);
+	let message = type_error(&format!("{}\nshow(p: Pair<int, text, int>) = 1\n1", PAIR));
+	assert!(message.contains("Pair takes 2 type parameters, got 3"), "{}", message);
+}
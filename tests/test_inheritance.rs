@@ -0,0 +1,72 @@
+// class inheritance: subclasses as wasm GC subtypes, inherited fields and methods, polymorphic matches
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{compile, int, is, try_eval, Op, WarpError};
+
+const STAFF: &str = "class Person {\nid:i64\ndef describe(): id\ndef badge(): id * 10\n}\nclass Employee: Person {\nsalary:i64\ndef describe(): salary\n}";
+
+#[test]
+fn test_parse_subclass() {
+	let node = parse("class Employee: Person { salary: i64 }");
+	let Node::Type { name, .. } = node.drop_meta() else { panic!("expected type: {:?}", node) };
+	let Node::Key(name, Op::Colon, supertype) = name.drop_meta() else { panic!("expected supertype: {:?}", name) };
+	assert_eq!(name.drop_meta(), &Node::Symbol("Employee".to_string()));
+	assert_eq!(supertype.drop_meta(), &Node::Symbol("Person".to_string()));
+}
+
+#[test]
+fn test_subclass_inherits_fields() {
+	let module = compile(&format!("{}; 1", STAFF)).expect("should compile");
+	let types = module.types();
+	let employee = types.get_by_name("Employee").expect("subclass registered");
+	assert_eq!(employee.supertype.as_deref(), Some("Person"));
+	let fields: Vec<&str> = employee.fields.iter().map(|f| f.name.as_str()).collect();
+	assert_eq!(fields, ["id", "salary"]);
+	assert_eq!(types.get_by_name("Person").unwrap().subtypes, ["Employee"]);
+	assert!(types.is_subtype("Employee", "Person"));
+	assert!(!types.is_subtype("Person", "Employee"));
+	let wat = module.wat();
+	assert!(wat.contains("(sub final $Person"), "{}", wat);
+}
+
+#[test]
+fn test_subclass_roundtrip() {
+	let module = compile(&format!("{}; 1", STAFF)).expect("should compile");
+	let employee = module.call("new_Employee", &[int(1), int(500)]).expect("constructor call");
+	assert_eq!(parse("Employee{id:1 salary:500}"), employee);
+	let person = module.call("new_Person", &[int(2)]).expect("constructor call");
+	assert_eq!(parse("Person{id:2}"), person);
+}
+
+#[test]
+fn test_inherited_methods() {
+	is!(&format!("{}; e = Employee{{id:3 salary:500}}; e.badge()", STAFF), 30);
+	// overrides win over the methods they replace
+	is!(&format!("{}; e = Employee{{id:3 salary:500}}; e.describe()", STAFF), 500);
+	is!(&format!("{}; p = Person{{id:3}}; p.describe()", STAFF), 3);
+}
+
+#[test]
+fn test_match_subclass() {
+	is!(&format!("{}; e = Employee{{id:3 salary:500}}; match e {{ Person => 'person', _ => 'other' }}", STAFF), "person");
+	is!(&format!("{}; e = Employee{{id:3 salary:500}}; match e {{ Employee => 'employee', Person => 'person' }}", STAFF), "employee");
+	is!(&format!("{}; p = Person{{id:3}}; match p {{ Employee => 'employee', Person => 'person' }}", STAFF), "person");
+}
+
+#[test]
+fn test_field_takes_subclass() {
+	let team = format!("{}\nclass Pet {{\nid:i64\n}}\nclass Team {{\nlead:Person\n}}", STAFF);
+	is!(&format!("{}; t = Team{{lead: Employee{{id:3 salary:500}}}}; t.lead.describe()", team), 500);
+	match try_eval(&format!("{}; Team{{lead: Pet{{id:1}}}}", team)) {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("Team.lead takes a Person, got a Pet"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+}
+
+#[test]
+fn test_unknown_supertype() {
+	match try_eval("class Employee: Person {\nsalary:i64\n}; 1") {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("Employee extends Person"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+}
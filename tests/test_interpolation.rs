@@ -0,0 +1,70 @@
+// String interpolation: "Hello {name}" and "Hello $name"
+use warp::cst::Cst;
+use warp::formatter::format_node;
+use warp::normalize::Style;
+use warp::wasp_parser::ParserOptions;
+use warp::{eq, is, Node, Op, WaspParser};
+
+#[test]
+fn test_interpolation_parses_to_concatenation() {
+	let node = WaspParser::parse("\"Hello {name}!\"");
+	let Node::Key(left, Op::Add, right) = node.drop_meta() else { panic!("not a concatenation: {:?}", node) };
+	eq!(right.drop_meta(), &Node::text("!"));
+	eq!(left.drop_meta(), &Node::Key(Box::new(Node::text("Hello ")), Op::Add, Box::new(Node::Symbol("name".into()))));
+	let dollar = WaspParser::parse("\"hi $name\"");
+	assert!(matches!(dollar.drop_meta(), Node::Key(_, Op::Add, _)), "{:?}", dollar);
+}
+
+#[test]
+fn test_interpolation_literals() {
+	eq!(WaspParser::parse("\"costs $5\""), "costs $5");
+	eq!(WaspParser::parse("\"{} and { open\""), "{} and { open");
+	eq!(WaspParser::parse("\"\\{name\\} \\$name\""), "{name} $name");
+	eq!(WaspParser::parse("'{name}'"), "{name}");
+	eq!(WaspParser::parse("{\"$ref\": 1}")["$ref"], 1);
+}
+
+#[test]
+fn test_interpolation_in_data() {
+	let node = WaspParser::parse("{name: \"Bob\", age: 41, greeting: \"Hi {name} ($age)\"}");
+	eq!(node["greeting"], "Hi Bob (41)");
+	let node = WaspParser::parse("user: \"ann\"\npath: \"/home/$user\"\n");
+	eq!(node["path"], "/home/ann");
+}
+
+#[test]
+fn test_interpolation_resolves_only_placeholders_in_data() {
+	// explicit + is an expression for the runtime
+	let node = WaspParser::parse("{name: \"Bob\", greeting: \"Hi \" + name}");
+	assert!(matches!(node["greeting"].drop_meta(), Node::Key(_, Op::Add, _)), "{:?}", node["greeting"]);
+	// code may reassign names, so its strings are built at runtime
+	let node = WaspParser::parse("{name: \"Bob\"; name = \"Ann\"; greeting: \"Hi {name}\"}");
+	assert!(matches!(node["greeting"].drop_meta(), Node::Key(_, Op::Add, _)), "{:?}", node["greeting"]);
+}
+
+#[test]
+fn test_interpolation_runtime() {
+	is!("name='Bob';age=41;\"Hello {name}, you are {age+1}\"", "Hello Bob, you are 42");
+	is!("x=2.5;\"x: $x\"", "x: 2.5");
+	is!("\"{-7}°, {1.0} or {0.125}\"", "-7°, 1 or 0.125");
+	is!("'ab' + 2", "ab2");
+	is!("\"{'é'}!\"", "é!");
+	is!("s='x:';s+=1;s+='b'", "x:1b");
+}
+
+#[test]
+fn test_plain_strings_keep_braces() {
+	let node = WaspParser::parse_with_options("{\"greeting\": \"Hi {name}\", \"price\": \"$5 or $x\"}", ParserOptions::plain());
+	eq!(node["greeting"], "Hi {name}");
+	eq!(node["price"], "$5 or $x");
+}
+
+#[test]
+fn test_serialized_text_stays_literal() {
+	let text = Node::text("Hi {name}, $x");
+	let formatted = format_node(&text, &Style::default());
+	eq!(WaspParser::parse(&formatted), "Hi {name}, $x");
+	let mut cst = Cst::parse("greeting: \"hello\"\n");
+	cst.set("greeting", &text).unwrap();
+	eq!(cst.get("greeting").unwrap(), "Hi {name}, $x");
+}
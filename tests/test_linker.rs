@@ -0,0 +1,124 @@
+// Linking several wasm modules into one
+use warp::gc_engine;
+use warp::wasm_linker::link_modules;
+use warp::{compile, eq, int, parse, CompiledModule, WarpError};
+use wasmparser::Payload;
+use wasmtime::{Linker, Module, Store};
+
+fn run_main(bytes: &[u8]) -> i64 {
+	let engine = gc_engine();
+	let module = Module::new(&engine, bytes).expect("merged module should load");
+	let mut store = Store::new(&engine, ());
+	let instance = Linker::new(&engine).instantiate(&mut store, &module).expect("no imports left");
+	let main = instance.get_typed_func::<(), i64>(&mut store, "main").unwrap();
+	main.call(&mut store, ()).unwrap()
+}
+
+#[test]
+fn test_link_resolves_imports() {
+	let lib = wat::parse_str(
+		r#"(module
+			(global $offset i64 (i64.const 1))
+			(func (export "double") (param i64) (result i64) (i64.add (i64.mul (local.get 0) (i64.const 2)) (global.get $offset)))
+			(func (export "main") (result i64) (i64.const -1)))"#,
+	)
+	.unwrap();
+	let app = wat::parse_str(
+		r#"(module
+			(import "lib" "double" (func $double (param i64) (result i64)))
+			(func $helper (result i64) (i64.const 20))
+			(func (export "main") (result i64) (call $double (call $helper))))"#,
+	)
+	.unwrap();
+	let merged = link_modules(&[("app", &app[..]), ("lib", &lib[..])]).expect("should link");
+	eq!(run_main(&merged), 41);
+}
+
+#[test]
+fn test_link_gc_types_and_data() {
+	let lib = wat::parse_str(
+		r#"(module
+			(type $point (struct (field $x i64) (field $y i64)))
+			(memory (export "memory") 1)
+			(data (i32.const 0) "\07")
+			(func (export "make") (result (ref $point)) (struct.new $point (i64.const 3) (i64.load8_u (i32.const 0)))))"#,
+	)
+	.unwrap();
+	let app = wat::parse_str(
+		r#"(module
+			(type $pair (array i64))
+			(type $point (struct (field $x i64) (field $y i64)))
+			(import "lib" "make" (func $make (result (ref $point))))
+			(func (export "main") (result i64)
+				(local $p (ref $point))
+				(local.set $p (call $make))
+				(i64.add (struct.get $point 0 (local.get $p)) (struct.get $point 1 (local.get $p)))))"#,
+	)
+	.unwrap();
+	let merged = link_modules(&[("app", &app[..]), ("lib", &lib[..])]).expect("should link");
+	eq!(run_main(&merged), 10);
+}
+
+#[test]
+fn test_link_keeps_unresolved_imports() {
+	let app = wat::parse_str(r#"(module (import "env" "log" (func (param i64))) (func (export "main") (result i64) (i64.const 1)))"#).unwrap();
+	let merged = link_modules(&[("app", &app[..])]).unwrap();
+	let imports = wasmparser::Parser::new(0)
+		.parse_all(&merged)
+		.filter_map(|p| match p.unwrap() {
+			wasmparser::Payload::ImportSection(reader) => Some(reader.count()),
+			_ => None,
+		})
+		.sum::<u32>();
+	eq!(imports, 1);
+
+	let broken = link_modules(&[("app", &[0u8, 1, 2][..])]);
+	assert!(matches!(broken, Err(WarpError::Link { .. })));
+}
+
+#[test]
+fn test_link_emitted_modules() {
+	// both modules bring their own memory, GC types and node runtime, and both export main and memory
+	let app = compile("'hello'").expect("should compile");
+	let lib = compile("class Point{x:i64 y:i64}\nsquare(x: int) = x * x; 1").expect("should compile");
+	let merged = link_modules(&[("app", app.bytes()), ("lib", lib.bytes())]).expect("should link");
+
+	let (mut memories, mut exports, mut named) = (0, Vec::new(), false);
+	for payload in wasmparser::Parser::new(0).parse_all(&merged) {
+		match payload.unwrap() {
+			Payload::MemorySection(reader) => memories += reader.count(),
+			Payload::ExportSection(reader) => exports.extend(reader.into_iter().map(|e| e.unwrap().name.to_string())),
+			Payload::CustomSection(section) => named |= section.name() == "name",
+			_ => {}
+		}
+	}
+	eq!(memories, 2);
+	// the first module keeps the plain names, the second one's clashing exports are namespaced
+	eq!(exports.iter().filter(|name| *name == "main").count(), 1);
+	eq!(exports.iter().filter(|name| *name == "memory").count(), 1);
+	assert!(exports.iter().any(|name| name == "lib.main"));
+	assert!(exports.iter().any(|name| name == "lib.memory"));
+	assert!(named, "name section should survive linking");
+
+	let module = CompiledModule::from_bytes(merged).expect("merged module should load");
+	eq!(module.run().unwrap(), "hello");
+	eq!(module.call("square", &[int(3)]).unwrap(), 9);
+	// lib's struct type is renumbered, its names with it
+	eq!(module.call("new_Point", &[int(1), int(2)]).unwrap(), parse("Point{x:1 y:2}"));
+}
+
+#[test]
+fn test_link_checks_import_types() {
+	let lib = wat::parse_str(r#"(module (func (export "double") (param i32) (result i32) (i32.add (local.get 0) (local.get 0))))"#).unwrap();
+	let app = wat::parse_str(
+		r#"(module
+			(import "lib" "double" (func $double (param i64) (result i64)))
+			(func (export "main") (result i64) (call $double (i64.const 2))))"#,
+	)
+	.unwrap();
+	let linked = link_modules(&[("app", &app[..]), ("lib", &lib[..])]);
+	match linked {
+		Err(WarpError::Link { message }) => assert!(message.contains("lib.double"), "{}", message),
+		other => panic!("expected a link error, got {:?}", other.map(|bytes| bytes.len())),
+	}
+}
@@ -0,0 +1,48 @@
+// List functions compiled to wasm loops: map, filter, reduce, sort, any, all, zip, enumerate
+use warp::is;
+
+#[test]
+fn test_map() {
+	is!("xs=[1, 2, 3];ys=map(xs, it * 2);ys#3", 6);
+	is!("xs=[1, 2, 3];ys=xs.map(x => x * x);s=0;for y in ys {s+=y};s", 14);
+	is!("n=10;ys=map([1, 2], it + n);ys#2", 12);
+	is!("double := it * 2;ys=map([1, 2, 3], double);ys#3", 6);
+	is!("ys=map('abc', it);ys.count", 3);
+}
+
+#[test]
+fn test_filter() {
+	is!("xs=[1, 2, 3, 4, 5, 6];evens=xs.filter(it % 2 == 0);evens.count", 3);
+	is!("limit=2;big=filter([1, 2, 3, 4], x => x > limit);big#1", 3);
+	is!("none=filter([1, 2], it > 5);n=0;for x in none {n+=1};n", 0);
+}
+
+#[test]
+fn test_reduce() {
+	is!("reduce([1, 2, 3, 4], (a, b) => a + b)", 10);
+	is!("reduce([1, 2, 3], (a, b) => a * b, 10)", 60);
+	is!("xs=[5, 6];total=xs.reduce((a, b) => a + b);total + 1", 12);
+}
+
+#[test]
+fn test_sort() {
+	is!("s=0;for x in sort([3, 1, 2]) {s = s * 10 + x};s", 123);
+	is!("s=0;for x in sort([3, 1, 2], (a, b) => b - a) {s = s * 10 + x};s", 321);
+	is!("xs=['pear', 'fig', 'apple'];ys=xs.sort;ys.join(',')", "apple,fig,pear");
+}
+
+#[test]
+fn test_any_all() {
+	is!("any([1, 5, 3], it > 4)", true);
+	is!("all([1, 5, 3], it > 0)", true);
+	is!("xs=[1, 2];xs.all(x => x > 1)", false);
+	is!("any([0, 0])", false);
+	is!("if any([1, 2], it == 2) {7} else {8}", 7);
+}
+
+#[test]
+fn test_zip_enumerate() {
+	is!("s=0;for a, b in zip([1, 2, 3], [10, 20, 30]) {s+=a * b};s", 140);
+	is!("pairs=zip([1, 2, 3], [4, 5]);pairs.count", 2);
+	is!("s=0;for i, x in enumerate([5, 6, 7]) {s+=i * x};s", 20);
+}
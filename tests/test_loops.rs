@@ -0,0 +1,63 @@
+// for loops over ranges, lists, maps and text, with break and continue
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{is, Op};
+
+#[test]
+fn test_for_parses_like_while() {
+	let node = parse("for x in items {x}");
+	let Node::Key(left, Op::Do, _) = node.drop_meta() else { panic!("expected do: {:?}", node) };
+	let Node::Key(_, Op::For, each) = left.drop_meta() else { panic!("expected for: {:?}", left) };
+	assert!(matches!(each.drop_meta(), Node::Key(_, Op::In, _)));
+	// `for` without `in` stays a word
+	assert!(!matches!(parse("for = 1").drop_meta(), Node::Key(_, Op::For, _)));
+}
+
+#[test]
+fn test_for_range() {
+	is!("sum=0;for i in 1..5 {sum+=i};sum", 10);
+	is!("sum=0;for i in 1 to 4 {sum+=i};sum", 10);
+	is!("sum=0;for i in 3..3 {sum+=i};sum", 0);
+	is!("n=3;sum=0;for i in 0..n do sum+=i;sum", 3);
+	is!("sum=0;for k, i in 5..8 {sum+=k};sum", 3);
+}
+
+#[test]
+fn test_for_list() {
+	is!("sum=0;for x in [1, 2, 3] {sum+=x};sum", 6);
+	is!("xs=[4, 5, 6];sum=0;for x in xs {sum+=x*x};sum", 77);
+	is!("s='';for w in ['a', 'bc', 'd'] {s+=w};s", "abcd");
+	is!("n=0;for x in [] {n+=1};n", 0);
+	is!("sum=0;for i, x in [7, 8, 9] {sum+=i};sum", 3);
+}
+
+#[test]
+fn test_for_map() {
+	is!("m={a:1, b:2, c:3};sum=0;for key, value in m {sum+=value};sum", 6);
+	is!("m={a:1, b:2};s='';for key, value in m {s+=key};s", "ab");
+}
+
+#[test]
+fn test_for_text() {
+	is!("n=0;for c in 'größe' {n+=1};n", 5);
+	is!("last=0;for c in 'abc' {last=c};last", 99);
+	is!("n=0;for c in 'hello' {if c == 'l' {n+=1}};n", 2);
+}
+
+#[test]
+fn test_break_continue() {
+	is!("sum=0;for i in 1..10 {if i == 5 {break};sum+=i};sum", 10);
+	is!("sum=0;for i in 1..6 {if i % 2 == 0 {continue};sum+=i};sum", 9);
+	is!("n=0;for y in 0..3 {for x in 0..3 {if x == y {continue};n+=1}};n", 6);
+	is!("i=0;while i < 100 {i+=1;if i == 7 {break}};i", 7);
+}
+
+#[test]
+fn test_nested_loops_reusing_names() {
+	is!("n=0;for x in [1,2] {for x in [3,4,5] {n+=1}};n", 6);
+}
+
+#[test]
+fn test_for_in_function() {
+	is!("def total(n){s=0;for i in 1 to n {s+=i};s};total(4)", 10);
+}
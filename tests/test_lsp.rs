@@ -0,0 +1,113 @@
+// Scripted JSON-RPC sessions against the language server
+use serde_json::{json, Value};
+use std::io::Cursor;
+use warp::lsp::{read_message, serve, write_message, LspServer};
+
+const URI: &str = "file:///test.wasp";
+
+fn open(server: &mut LspServer, text: &str) -> Value {
+	let replies = server.handle(&json!({
+		"jsonrpc": "2.0",
+		"method": "textDocument/didOpen",
+		"params": {"textDocument": {"uri": URI, "languageId": "wasp", "version": 1, "text": text}}
+	}));
+	replies[0].clone()
+}
+
+fn request(server: &mut LspServer, method: &str, line: u32, character: u32) -> Value {
+	let replies = server.handle(&json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": method,
+		"params": {"textDocument": {"uri": URI}, "position": {"line": line, "character": character}}
+	}));
+	replies[0]["result"].clone()
+}
+
+#[test]
+fn test_lsp_diagnostics() {
+	let mut server = LspServer::new();
+	let clean = open(&mut server, "x=1\nx+2");
+	assert_eq!(clean["method"], "textDocument/publishDiagnostics");
+	assert_eq!(clean["params"]["diagnostics"], json!([]));
+
+	let mismatch = open(&mut server, "x=1\nx='ok'");
+	let diagnostics = mismatch["params"]["diagnostics"].as_array().unwrap();
+	assert_eq!(diagnostics.len(), 1);
+	assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+	assert!(diagnostics[0]["message"].as_str().unwrap().contains("type mismatch"));
+
+	let unterminated = open(&mut server, "x='oops");
+	let diagnostics = unterminated["params"]["diagnostics"].as_array().unwrap();
+	assert!(diagnostics[0]["message"].as_str().unwrap().contains("Unterminated"));
+}
+
+#[test]
+fn test_lsp_hover_and_definition() {
+	let mut server = LspServer::new();
+	open(&mut server, "square(n) = n*n\nx=2.5\nsquare(3)");
+
+	let hover = request(&mut server, "textDocument/hover", 2, 2);
+	assert!(hover["contents"]["value"].as_str().unwrap().contains("square(n)"), "{}", hover);
+
+	let hover = request(&mut server, "textDocument/hover", 1, 0);
+	assert!(hover["contents"]["value"].as_str().unwrap().contains("x: float"), "{}", hover);
+
+	let definition = request(&mut server, "textDocument/definition", 2, 2);
+	assert_eq!(definition["uri"], URI);
+	assert_eq!(definition["range"]["start"]["line"], 0);
+}
+
+#[test]
+fn test_lsp_completion() {
+	let mut server = LspServer::new();
+	open(&mut server, "square(n) = n*n\ncounter=0");
+	let items = request(&mut server, "textDocument/completion", 1, 0);
+	let labels: Vec<&str> = items.as_array().unwrap().iter().map(|i| i["label"].as_str().unwrap()).collect();
+	assert!(labels.contains(&"square"));
+	assert!(labels.contains(&"counter"));
+	assert!(labels.contains(&"new_int")); // builtin from the FunctionRegistry
+	assert!(labels.contains(&"while"));
+}
+
+#[test]
+fn test_lsp_stdio_session() {
+	let mut input = Vec::new();
+	write_message(&mut input, &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})).unwrap();
+	write_message(&mut input, &json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"})).unwrap();
+	write_message(&mut input, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+
+	let mut output = Vec::new();
+	serve(&mut Cursor::new(input), &mut output).unwrap();
+
+	let mut reader = Cursor::new(output);
+	let initialized = read_message(&mut reader).unwrap();
+	assert_eq!(initialized["id"], 1);
+	assert_eq!(initialized["result"]["capabilities"]["hoverProvider"], true);
+	let shutdown = read_message(&mut reader).unwrap();
+	assert_eq!(shutdown["id"], 2);
+	assert!(read_message(&mut reader).is_none());
+}
+
+#[test]
+fn test_lsp_skips_malformed_messages() {
+	let mut input = b"Content-Length: 7\r\n\r\n{oops!}Content-Length: x\r\n\r\n".to_vec();
+	write_message(&mut input, &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})).unwrap();
+	write_message(&mut input, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+
+	let mut output = Vec::new();
+	serve(&mut Cursor::new(input), &mut output).unwrap();
+
+	let initialized = read_message(&mut Cursor::new(output)).unwrap();
+	assert_eq!(initialized["id"], 1);
+}
+
+#[test]
+fn test_lsp_columns_in_utf16() {
+	let mut server = LspServer::new();
+	open(&mut server, "f(n) = n // 😀\nx = '😀😀' + f(3)");
+	// each 😀 is two UTF-16 code units, so f sits at character 13
+	let definition = request(&mut server, "textDocument/definition", 1, 13);
+	assert_eq!(definition["range"]["start"]["line"], 0);
+	assert_eq!(definition["range"]["end"]["character"], 14);
+}
@@ -0,0 +1,64 @@
+// match expressions: literal, range, kind and destructuring patterns with guards and wildcards
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{is, Op};
+
+#[test]
+fn test_match_parses_arms() {
+	let node = parse("match c {\n'a' => 1\n_ if c > 'x' => 2\n_ => 3\n}");
+	let Node::Key(subject, Op::Match, arms) = node.drop_meta() else { panic!("expected match: {:?}", node) };
+	assert_eq!(subject.drop_meta(), &Node::Symbol("c".to_string()));
+	let Node::List(arms, _, _) = arms.drop_meta() else { panic!("expected arms: {:?}", arms) };
+	assert_eq!(arms.len(), 3);
+	assert!(matches!(arms[1].drop_meta(), Node::Key(guarded, Op::FatArrow, _) if matches!(guarded.drop_meta(), Node::Key(_, Op::If, _))));
+	// match next() {…} is no definition of next
+	assert!(matches!(parse("match next() {_ => 1}").drop_meta(), Node::Key(_, Op::Match, _)));
+	// `match` without a subject stays a word
+	assert!(!matches!(parse("match = 1").drop_meta(), Node::Key(_, Op::Match, _)));
+}
+
+#[test]
+fn test_match_literals() {
+	is!("x=3;match x { 1 => 'one', 2 => 'two', 3 => 'three', _ => 'many' }", "three");
+	is!("x=9;match x { 1 => 10, 2 => 20, _ => 30 }", 30);
+	is!("x=2;y = match x { 1 => 10, 2 => 20, _ => 30 };y + 1", 21);
+	is!("match 'b' { 'a' => 1, 'b' => 2, _ => 3 }", 2);
+	is!("s='pear';match s { 'apple' => 1, 'pear' => 2, _ => 0 }", 2);
+	is!("match true { false => 'no', true => 'yes' }", "yes");
+}
+
+#[test]
+fn test_match_ranges() {
+	is!("n=15;match n { 0..10 => 'small', 10..100 => 'medium', _ => 'large' }", "medium");
+	is!("n=10;match n { 1 to 10 => 'up to ten', _ => 'more' }", "up to ten");
+	is!("c='q';match c { 'a' to 'z' => 'lower', 'A' to 'Z' => 'upper', _ => 'other' }", "lower");
+}
+
+#[test]
+fn test_match_kinds() {
+	is!("x='hi';match x { Int => 1, Text => 2, _ => 3 }", 2);
+	is!("match 2.5 { Int => 1, Float => 2, _ => 3 }", 2);
+	is!("xs=[1, 2];match xs { Key => 1, List => 2, _ => 3 }", 2);
+}
+
+#[test]
+fn test_match_guards() {
+	is!("x=-5;match x { 0 => 'zero', n if n < 0 => 'negative', _ => 'positive' }", "negative");
+	is!("x=7;match x { n if n % 2 == 0 => n / 2, n => n * 3 + 1 }", 22);
+	is!("limit=3;x=5;match x { _ if x > limit => 'over', _ => 'under' }", "over");
+}
+
+#[test]
+fn test_match_destructuring() {
+	is!("s=0;for p in zip([1, 2], [10, 20]) { s += match p { a: b => a * b } };s", 50);
+	is!("s=0;for p in enumerate([5, 6]) { s += match p { 0: x => x, i: x => i * x } };s", 11);
+	is!("xs=[1, 2];match xs { [a] => a, [a, b] => a + b, _ => 0 }", 3);
+	is!("xs=[[1, 2], 3];match xs { [[a, b], c] => a * b * c, _ => 0 }", 6);
+}
+
+#[test]
+fn test_match_user_types() {
+	is!("class Person{name:String age:i64}; p = Person{name:'Alice' age:30}; match p { Person{age: a} if a < 18 => 'minor', Person{name: n} => n, _ => 'nobody' }", "Alice");
+	is!("class Person{name:String age:i64}; p = Person{name:'Bob' age:12}; match p { Person{age: a} if a < 18 => 'minor', _ => 'adult' }", "minor");
+	is!("class Person{name:String age:i64}; class Pet{name:String}; p = Pet{name:'Rex'}; match p { Person => 1, Pet => 2, _ => 3 }", 2);
+}
@@ -0,0 +1,72 @@
+// methods and constructors declared in class bodies
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{compile, is};
+
+const RECT: &str = "class Rect {\nw:i64\nh:i64\ndef area(): w * h\ndef scaled(k): Rect{w: w * k h: h * k}\ndef wider(other): w > other.w\n}";
+
+#[test]
+fn test_parse_class_methods() {
+	let node = parse(RECT);
+	let Node::Type { body, .. } = node.drop_meta() else { panic!("expected type: {:?}", node) };
+	let Node::List(members, _, _) = body.drop_meta() else { panic!("expected members: {:?}", body) };
+	assert_eq!(members.len(), 5);
+	// methods are no fields
+	let module = compile(&format!("{}; 1", RECT)).expect("should compile");
+	let rect = module.types().get_by_name("Rect").expect("type registered");
+	assert_eq!(rect.fields.len(), 2);
+}
+
+#[test]
+fn test_method_call() {
+	is!(&format!("{}; r = Rect{{w:2 h:3}}; r.area()", RECT), 6);
+	is!(&format!("{}; r = Rect{{w:2 h:3}}; r.area() + 1", RECT), 7);
+	is!(&format!("{}; r = Rect{{w:2 h:3}}; r.area", RECT), 6);
+}
+
+#[test]
+fn test_method_args() {
+	is!(&format!("{}; r = Rect{{w:2 h:3}}; r.scaled(2).area()", RECT), 24);
+	is!(&format!("{}; r = Rect{{w:2 h:3}}; r.scaled(3).w", RECT), 6);
+	is!(&format!("{}; r = Rect{{w:2 h:3}}; r.wider(Rect{{w:1 h:9}})", RECT), 1);
+}
+
+#[test]
+fn test_self() {
+	is!("class Point {\nx:i64\ny:i64\ndef sum(): self.x + y\n}; p = Point{x:3 y:4}; p.sum()", 7);
+	// parameters shadow fields
+	is!("class Point {\nx:i64\ny:i64\ndef plus(x): x + y\n}; p = Point{x:3 y:4}; p.plus(10)", 14);
+}
+
+#[test]
+fn test_constructor() {
+	let square = "class Square {\nside:i64\ndef init(side = 1) {\nif side < 0 { side = 0 }\n}\ndef area(): side * side\n}";
+	is!(&format!("{}; Square(3).area()", square), 9);
+	is!(&format!("{}; Square().side", square), 1);
+	is!(&format!("{}; Square(-5).side", square), 0);
+}
+
+#[test]
+fn test_constructor_validation() {
+	let positive = "class Positive {\nn:i64\ndef init(n) {\nif n < 0 { return null }\n}\n}";
+	is!(&format!("{}; Positive(2)?.n ?? 0", positive), 2);
+	is!(&format!("{}; Positive(-2)?.n ?? 0", positive), 0);
+}
+
+#[test]
+fn test_methods_of_several_types() {
+	let shapes = "class Sq {\ns:i64\ndef area(): s * s\n}\nclass Tri {\nb:i64\nh:i64\ndef area(): b * h\n}";
+	is!(&format!("{}; x = Sq{{s:3}}; x.area()", shapes), 9);
+	is!(&format!("{}; x = Tri{{b:4 h:3}}; x.area()", shapes), 12);
+	let module = compile(&format!("{}; 1", shapes)).expect("should compile");
+	assert!(module.wat().contains("Tri.area"), "{}", module.wat());
+}
+
+#[test]
+fn test_methods_only_on_their_class() {
+	let bag = "class Bag {\nn:i64\ndef count(): n * 100\ndef split(sep): n\n}";
+	is!(&format!("{}; b = Bag{{n:2}}; b.count()", bag), 200);
+	is!(&format!("{}; xs = [1, 2, 3]; xs.count()", bag), 3);
+	is!(&format!("{}; s = 'a,b'; s.split(',').join('+')", bag), "a+b");
+	is!(&format!("{}; take(b: Bag) = b.count(); take(Bag{{n:3}})", bag), 300);
+}
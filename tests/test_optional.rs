@@ -0,0 +1,72 @@
+// optional field types T?, null-safe access ?. and the fallback ??
+use warp::analyzer::check_types;
+use warp::node::Node;
+use warp::wasp_parser::parse;
+use warp::{compile, is, try_eval, Op, WarpError};
+
+const TREE: &str = "class Tree{value:i64 left:Tree? right:Tree?}";
+
+#[test]
+fn test_parse_optional_type() {
+	let node = parse("left: Tree?");
+	let Node::Key(_, Op::Colon, ty) = node.drop_meta() else { panic!("expected key: {:?}", node) };
+	assert!(matches!(ty.drop_meta(), Node::Key(_, Op::Question, empty) if **empty == Node::Empty));
+	// ternaries keep working
+	assert!(matches!(parse("a ? b : c").drop_meta(), Node::Key(_, Op::Question, branches) if matches!(branches.drop_meta(), Node::Key(_, Op::Colon, _))));
+	assert!(matches!(parse("a ?? b").drop_meta(), Node::Key(_, Op::Coalesce, _)));
+	assert!(matches!(parse("a?.b").drop_meta(), Node::Key(_, Op::SafeDot, _)));
+	assert!(matches!(parse("(1<2)?10:255").drop_meta(), Node::Key(_, Op::Question, _)));
+}
+
+#[test]
+fn test_optional_fields() {
+	let module = compile(&format!("{}; 1", TREE)).expect("should compile");
+	let tree = module.types().get_by_name("Tree").expect("type registered");
+	let value = &tree.fields[0];
+	let left = &tree.fields[1];
+	assert!(!value.is_optional());
+	assert!(left.is_optional());
+	assert_eq!(left.base_type(), "Tree");
+	// a type holding itself is its own recursion group
+	let wat = module.wat();
+	assert!(wat.contains("(rec"), "{}", wat);
+}
+
+#[test]
+fn test_field_access() {
+	is!(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.value", TREE), 1);
+	is!(&format!("{}; t = Tree{{value:1 left:Tree{{value:2 left:null right:null}} right:null}}; t.value + 1", TREE), 2);
+}
+
+#[test]
+fn test_safe_dot() {
+	is!(&format!("{}; t = Tree{{value:1 left:Tree{{value:2 left:null right:null}} right:null}}; t.left?.value", TREE), 2);
+	is!(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.left?.value ?? 0", TREE), 0);
+	is!(&format!("{}; t = Tree{{value:1 left:Tree{{value:2 left:null right:null}} right:null}}; t.left?.left?.value ?? 3", TREE), 3);
+}
+
+#[test]
+fn test_coalesce() {
+	is!(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.right ?? 7", TREE), 7);
+	is!(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.left?.value ?? t.right?.value ?? 4", TREE), 4);
+}
+
+#[test]
+fn test_unchecked_optional_access() {
+	match try_eval(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.left.value", TREE)) {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("t.left may be null"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+	// checked first, the access is fine
+	assert!(check_types(&parse(&format!("{}; t = Tree{{value:1 left:null right:null}}; if t.left {{ t.left.value }}", TREE))).is_ok());
+	assert!(check_types(&parse(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.left != null and t.left.value > 1", TREE))).is_ok());
+	assert!(check_types(&parse(&format!("{}; t = Tree{{value:1 left:null right:null}}; t.right != null ? t.left.value : 0", TREE))).is_err());
+}
+
+#[test]
+fn test_null_for_required_field() {
+	match try_eval(&format!("{}; Tree{{value:null left:null right:null}}", TREE)) {
+		Err(WarpError::Type { message, .. }) => assert!(message.contains("Tree.value is not optional"), "{}", message),
+		other => panic!("expected a type error: {:?}", other),
+	}
+}
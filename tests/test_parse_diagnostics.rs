@@ -0,0 +1,56 @@
+// Syntax diagnostics from strict and generous parsing
+use warp::error::Severity;
+use warp::wasp_parser::ParserOptions;
+use warp::{eq, Node, WaspParser};
+
+#[test]
+fn test_strict_unbalanced_brackets() {
+	let diagnostics = WaspParser::parse_strict("a = [1, 2\nb = 3)").unwrap_err();
+	eq!(diagnostics.len(), 2);
+	assert!(diagnostics[0].message.contains("Unexpected ')'"), "{:?}", diagnostics);
+	eq!(diagnostics[0].line_nr, 2);
+	eq!(diagnostics[0].column, 6);
+	eq!(diagnostics[0].span, 15..16);
+	assert!(diagnostics[1].message.contains("Unclosed '['"), "{:?}", diagnostics);
+	eq!(diagnostics[1].span, 4..5);
+	eq!(diagnostics[1].severity, Severity::Error);
+}
+
+#[test]
+fn test_strict_strings() {
+	let diagnostics = WaspParser::parse_strict("s = \"a\\qb\"\nt = 'open").unwrap_err();
+	eq!(diagnostics.len(), 2);
+	assert!(diagnostics[0].message.contains("Invalid escape \\q"), "{:?}", diagnostics);
+	eq!(diagnostics[0].span, 6..8);
+	assert!(diagnostics[1].message.contains("Unterminated string"), "{:?}", diagnostics);
+	eq!(diagnostics[1].line_nr, 2);
+	eq!(diagnostics[1].span, 15..20);
+	eq!(WaspParser::parse_strict("\"\\u0041\\u{1F600}\\/\"").unwrap(), "A😀/");
+}
+
+#[test]
+fn test_strict_duplicate_keys() {
+	let diagnostics = WaspParser::parse_strict("{a: 1, b: 2, a: 3}").unwrap_err();
+	eq!(diagnostics.len(), 1);
+	eq!(diagnostics[0].message, "Duplicate key 'a'");
+	eq!(diagnostics[0].span, 13..14);
+	// markup may repeat keys
+	assert!(WaspParser::parse_strict("{li:\"one\" li:\"two\"}").is_ok());
+}
+
+#[test]
+fn test_spans_count_bytes() {
+	let diagnostics = WaspParser::parse_strict("ä = (ö").unwrap_err();
+	eq!(diagnostics[0].column, 5);
+	eq!(diagnostics[0].span, 5..6);
+}
+
+#[test]
+fn test_generous_mode_warns() {
+	let code = "{\"x\": 1, distict=\"Metro\", distict=\"Metro\"}]";
+	let (node, diagnostics) = WaspParser::parse_with_diagnostics(code, ParserOptions::default());
+	eq!(node["x"], 1);
+	eq!(diagnostics.len(), 2);
+	assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+	assert!(matches!(WaspParser::parse_with_options(code, ParserOptions::strict()), Node::Error(_)));
+}
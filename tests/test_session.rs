@@ -0,0 +1,59 @@
+// REPL session keeps definitions between entries
+use warp::{eq, Kind, Node, Session};
+
+#[test]
+fn test_session_keeps_globals_and_functions() {
+	let mut session = Session::new();
+	eq!(session.eval("x=3").unwrap(), 3);
+	eq!(session.eval("x+1").unwrap(), 4);
+	eq!(session.eval("double(n) := n * 2").is_ok(), true);
+	eq!(session.eval("double(x)").unwrap(), 6);
+	eq!(session.eval("x = x + 1").unwrap(), 4);
+	eq!(session.eval("x").unwrap(), 4);
+}
+
+#[test]
+fn test_session_blank_and_comment_lines() {
+	let mut session = Session::new();
+	session.eval("x=3").unwrap();
+	eq!(session.eval("").unwrap(), Node::Empty);
+	eq!(session.eval("// just a note").unwrap(), Node::Empty);
+	eq!(session.eval("x").unwrap(), 3);
+}
+
+#[test]
+fn test_session_meta_commands() {
+	let mut session = Session::new();
+	session.eval("x=3").unwrap();
+	eq!(session.kind("x"), Kind::Int);
+	eq!(session.kind("1.5"), Kind::Float);
+	eq!(session.ast("a:1")["a"], 1);
+	assert!(session.wat("x * 2").unwrap().starts_with("(module"));
+}
+
+#[test]
+fn test_session_save_and_load() {
+	let path = std::env::temp_dir().join("warp_test_session.wasp");
+	let path = path.to_str().unwrap();
+	let mut session = Session::new();
+	session.eval("x=3").unwrap();
+	session.eval("x * 7").unwrap();
+	session.save(path).unwrap();
+	eq!(std::fs::read_to_string(path).unwrap(), "x = 3\n");
+
+	let mut restored = Session::new();
+	restored.load(path).unwrap();
+	eq!(restored.eval("x * 7").unwrap(), 21);
+	std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_session_keeps_values_not_entries() {
+	let mut session = Session::new();
+	eq!(session.eval("x = 6 * 7; y = x; x + 1").unwrap(), 43);
+	eq!(session.source(), "x = 42\ny = 42\n");
+	eq!(session.eval("x += 1").unwrap(), 43);
+	eq!(session.eval("name = 'warp'; shout(s) := s + '!'").is_ok(), true);
+	eq!(session.eval("shout(name)").unwrap(), "warp!");
+	assert!(session.source().ends_with("x = 43\ny = 42\nname = 'warp'\n"), "{}", session.source());
+}
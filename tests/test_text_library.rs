@@ -0,0 +1,48 @@
+// Text library compiled to wasm: split, join, replace, trim, case, search and slicing
+use warp::is;
+
+#[test]
+fn test_split_join() {
+	is!("s='a,b,,c';s.split(',').join('+')", "a+b++c");
+	is!("join(split('größe', ''), '.')", "g.r.ö.ß.e");
+	is!("s='one  two';parts=split(s, ' ');parts.count", 3);
+	is!("s='';s.split(',').join('+')", "");
+}
+
+#[test]
+fn test_replace() {
+	is!("s='a-b-c';s.replace('-', '--')", "a--b--c");
+	is!("replace('ööö', 'ö', 'o')", "ooo");
+	is!("s='abc';s.replace('', 'x')", "abc");
+}
+
+#[test]
+fn test_trim() {
+	is!("s='  hi there \t\n';s.trim", "hi there");
+	is!("s='   ';s.trim()", "");
+}
+
+#[test]
+fn test_case() {
+	is!("s='Größe';s.upper", "GRÖßE");
+	is!("s='ΑΒΓ Ünïcode';s.lower()", "αβγ ünïcode");
+	is!("lower('Привет')", "привет");
+}
+
+#[test]
+fn test_search() {
+	is!("s='größer';s.index_of('er')", 4);
+	is!("s='abc';s.index_of('x')", -1);
+	is!("s='abc';s.index_of('b') + 1", 2);
+	is!("s='größer';s.contains('ß')", 1);
+	is!("s='abc';s.contains('x')", 0);
+	is!("s='abc';s.starts_with('ab')", 1);
+	is!("s='abc';ends_with(s, 'ab')", 0);
+}
+
+#[test]
+fn test_slice() {
+	is!("s='größer';s.slice(-3)", "ßer");
+	is!("s='hello';slice(s, 1, -1)", "ell");
+	is!("s='hello';s.slice(-10, 2)", "he");
+}
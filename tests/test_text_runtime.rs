@@ -0,0 +1,40 @@
+// Text built at runtime inside the module: allocator, slicing, repetition and number parsing
+use warp::wasm_emitter::eval;
+use warp::{eq, is};
+
+#[test]
+fn test_substring() {
+	is!("s='hello';substring(s, 1, 3)", "el");
+	is!("s='hello';s.substring(2)", "llo");
+	is!("s='größer';s.substring(2, 4)", "öß");
+	is!("s='abc';s.substring(2, 1)", "");
+}
+
+#[test]
+fn test_repeat() {
+	is!("s='ab';s.repeat(3)", "ababab");
+	is!("s='ab';repeat(s, 0)", "");
+	// grows memory beyond the initial page
+	let long = eval("s='abcdefgh';s.repeat(10000)");
+	eq!(long.to_string().len(), 80000);
+}
+
+#[test]
+fn test_number_text_conversions() {
+	is!("x=7;str(x)", "7");
+	is!("x=-2.5;str(x)", "-2.5");
+	is!("s='42';int(s)", 42);
+	is!("s=' -1.5e3';float(s)", -1500.0);
+	is!("s='2.5';number(s)", 2.5);
+	is!("s='12';number(s)", 12);
+}
+
+#[test]
+fn test_float_text_exponent() {
+	is!("x=100000000000000000000.0;str(x)", "1e20");
+	is!("x=123456789012345680000.0;str(x)", "1.234568e20");
+	is!("x=0.0000001;str(x)", "1e-7");
+	is!("x=-0.00000025;str(x)", "-2.5e-7");
+	is!("x=0.0001;str(x)", "0.0001");
+	is!("x=999999999999999.0;str(x)", "999999999999999");
+}
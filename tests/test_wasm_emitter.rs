@@ -1,5 +1,5 @@
 use warp::Node::*;
-use warp::{Bracket, Node, Op};
+use warp::{Bracket, Node, Op, WarpError};
 use warp::run::wasmtime_runner::run;
 use warp::wasm_emitter::{eval, WasmGcEmitter};
 use warp::StringExtensions;
@@ -159,3 +159,40 @@ fn test_emit_for_node_tree_shaking() {
 		bytes_full.len()
 	);
 }
+
+#[test]
+fn test_compile_bytes_honors_config() {
+	use warp::wasm_emitter::{compile_bytes, EmitterConfig};
+
+	let slim = compile_bytes("1+2", EmitterConfig::builder().tree_shaking(true).build()).unwrap();
+	let full = compile_bytes("1+2", EmitterConfig::builder().tree_shaking(false).build()).unwrap();
+	eq!(&slim[0..4], &[0x00, 0x61, 0x73, 0x6d]);
+	assert!(slim.len() < full.len(), "tree-shaking should shrink the module: {} vs {}", slim.len(), full.len());
+
+	let no_globals = compile_bytes("1+2", EmitterConfig::builder().tree_shaking(true).kind_globals(false).build()).unwrap();
+	assert!(no_globals.len() < slim.len());
+}
+
+#[test]
+fn test_compile_bytes_returns_errors() {
+	use warp::wasm_emitter::{compile_bytes, EmitterConfig};
+
+	assert!(compile_bytes("x = \"open", EmitterConfig::default()).is_err());
+	let undefined = compile_bytes("x = 1\ny = undefined_name + x", EmitterConfig::default());
+	assert!(matches!(undefined, Err(WarpError::Compile { .. } | WarpError::Type { .. })), "{:?}", undefined);
+}
+
+#[test]
+fn test_compile_command_reports_errors() {
+	let dir = std::env::temp_dir();
+	let input = dir.join("warp_test_compile_undefined.wasp");
+	std::fs::write(&input, "x = 1\ny = undefined_name + x\n").unwrap();
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_warp"))
+		.args(["compile", input.to_str().unwrap(), "-o", dir.join("warp_test_compile_undefined.wasm").to_str().unwrap()])
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(!output.status.success());
+	assert!(stderr.starts_with("Error:"), "{}", stderr);
+	assert!(!stderr.contains("panicked"), "{}", stderr);
+}
@@ -0,0 +1,107 @@
+// YAML reading and writing
+use warp::wasp_parser::{parse_yaml, ParserOptions};
+use warp::yaml::anchor_of;
+use warp::{eq, Node, WarpError, WaspParser};
+
+#[test]
+fn test_yaml_block_collections() {
+	let yaml = "
+# service config
+name: warp
+port: 8080
+ratio: 0.5
+debug: false
+empty: ~
+tags:
+- fast
+- small
+servers:
+  - host: a.example
+    port: 80
+  - host: b.example
+";
+	let node = Node::from_yaml(yaml).unwrap();
+	eq!(node["name"], "warp");
+	eq!(node["port"], 8080);
+	eq!(node["ratio"], 0.5);
+	eq!(node["debug"], Node::False);
+	eq!(node["empty"], Node::Empty);
+	eq!(node["tags"][1], "small");
+	eq!(node["servers"][0]["port"], 80);
+	eq!(node["servers"][1]["host"], "b.example");
+}
+
+#[test]
+fn test_yaml_flow_and_quoted() {
+	let node = Node::from_yaml("point: {x: 1, y: [2, 3]}\nsay: \"hi\\tyou # not a comment\"\nit: 'it''s'").unwrap();
+	eq!(node["point"]["x"], 1);
+	eq!(node["point"]["y"][1], 3);
+	eq!(node["say"], "hi\tyou # not a comment");
+	eq!(node["it"], "it's");
+}
+
+#[test]
+fn test_yaml_multiline_scalars() {
+	let yaml = "literal: |\n  line one\n  line two\nfolded: >-\n  joined\n  words\n\n  new paragraph\nplain: first\n  second\n";
+	let node = Node::from_yaml(yaml).unwrap();
+	eq!(node["literal"], "line one\nline two\n");
+	eq!(node["folded"], "joined words\nnew paragraph");
+	eq!(node["plain"], "first second");
+}
+
+#[test]
+fn test_yaml_anchors_and_aliases() {
+	let yaml = "base: &base\n  retries: 3\n  timeout: 10\nprod:\n  <<: *base\n  timeout: 60\nsame: *base\n";
+	let node = Node::from_yaml(yaml).unwrap();
+	eq!(node["prod"]["retries"], 3);
+	eq!(node["prod"]["timeout"], 60);
+	eq!(node["same"]["timeout"], 10);
+	assert!(matches!(Node::from_yaml("a: *missing"), Err(WarpError::Parse { .. })));
+}
+
+#[test]
+fn test_yaml_aliases_share_the_anchored_node() {
+	let yaml = "base: &base\n  retries: 3\nsame: *base\nlist:\n  - &one 1\n  - *one\n";
+	let node = Node::from_yaml(yaml).unwrap();
+	eq!(anchor_of(&node["base"]), Some("base"));
+	eq!(anchor_of(&node["same"]), Some("base"));
+	eq!(anchor_of(&node["list"][1]), Some("one"));
+	eq!(node.to_yaml(), yaml);
+}
+
+#[test]
+fn test_yaml_duplicate_keys() {
+	let yaml = "name: a\nport: 1\nname: b\n";
+	let (node, diagnostics) = WaspParser::parse_with_diagnostics(yaml, ParserOptions::yaml());
+	eq!(node["name"], "b");
+	eq!(diagnostics.len(), 1);
+	eq!(diagnostics[0].message, "Duplicate key 'name'");
+	eq!(diagnostics[0].line_nr, 3);
+	eq!(&yaml[diagnostics[0].span.clone()], "name");
+	let strict = WaspParser::parse_with_options(yaml, ParserOptions { yaml_mode: true, ..ParserOptions::strict() });
+	assert!(strict.to_string().contains("Duplicate key 'name'"), "{}", strict);
+}
+
+#[test]
+fn test_yaml_documents() {
+	let node = Node::from_yaml("---\na: 1\n---\nb: 2\n...\n").unwrap();
+	eq!(node.length(), 2);
+	eq!(node[0]["a"], 1);
+	eq!(node[1]["b"], 2);
+	eq!(node.to_yaml(), "---\na: 1\n---\nb: 2\n");
+}
+
+#[test]
+fn test_yaml_roundtrip() {
+	let yaml = "name: warp\nversion: 1.0\nquoted: \"123\"\nlist:\n  - 1\n  - two\n  - nested: true\n    deeper:\n      - x\nnote: |\n  multi\n  line\nnothing: null\n";
+	let node = Node::from_yaml(yaml).unwrap();
+	eq!(node.to_yaml(), yaml);
+	eq!(Node::from_yaml(&node.to_yaml()).unwrap(), node);
+}
+
+#[test]
+fn test_yaml_parser_option() {
+	let node = WaspParser::parse_with_options("a: [1, 2]", ParserOptions::yaml());
+	eq!(node["a"][1], 2);
+	eq!(parse_yaml("k: v")["k"], "v");
+}